@@ -367,6 +367,7 @@ impl USB {
     pub fn init(
         self,
         usb: super::pac::USB,
+        oscctrl: super::pac::OSCCTRL,
         clocks: &mut GenericClockController,
         mclk: &mut MCLK,
         port: &mut Port,
@@ -375,13 +376,19 @@ impl USB {
         let usb_gclk = clocks.get_gclk(GEN_A::GCLK2).unwrap();
         let usb_clock = &clocks.usb(&usb_gclk).unwrap();
 
-        UsbBusAllocator::new(UsbBus::new(
+        let mut usb_bus = UsbBus::new(
             usb_clock,
             mclk,
             self.dm.into_function(port),
             self.dp.into_function(port),
             usb,
-        ))
+        );
+        // Without a crystal, the 48 MHz USB clock is recovered from the host
+        usb_bus.set_clock_recovery(hal::usb::ClockRecovery::new(
+            oscctrl,
+            hal::usb::DfllFallback::OpenLoop,
+        ));
+        UsbBusAllocator::new(usb_bus)
     }
 }
 
@@ -49,6 +49,7 @@ fn main() -> ! {
     let bus_allocator = unsafe {
         USB_ALLOCATOR = Some(pins.usb.init(
             peripherals.USB,
+            peripherals.OSCCTRL,
             &mut clocks,
             &mut peripherals.MCLK,
             &mut pins.port,
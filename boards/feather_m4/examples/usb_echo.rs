@@ -36,6 +36,7 @@ fn main() -> ! {
             pins.usb_dm,
             pins.usb_dp,
             peripherals.USB,
+            peripherals.OSCCTRL,
             &mut clocks,
             &mut peripherals.MCLK,
             &mut pins.port,
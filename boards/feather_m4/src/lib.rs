@@ -178,6 +178,7 @@ pub fn usb_allocator(
     dm: gpio::Pa24<Input<Floating>>,
     dp: gpio::Pa25<Input<Floating>>,
     usb: pac::USB,
+    oscctrl: pac::OSCCTRL,
     clocks: &mut GenericClockController,
     mclk: &mut pac::MCLK,
     port: &mut Port,
@@ -189,11 +190,17 @@ pub fn usb_allocator(
     let usb_gclk = clocks.get_gclk(GEN_A::GCLK2).unwrap();
     let usb_clock = &clocks.usb(&usb_gclk).unwrap();
 
-    UsbBusAllocator::new(UsbBus::new(
+    let mut usb_bus = UsbBus::new(
         usb_clock,
         mclk,
         dm.into_function_h(port),
         dp.into_function_h(port),
         usb,
-    ))
+    );
+    // Without a crystal, the 48 MHz USB clock is recovered from the host
+    usb_bus.set_clock_recovery(hal::usb::ClockRecovery::new(
+        oscctrl,
+        hal::usb::DfllFallback::OpenLoop,
+    ));
+    UsbBusAllocator::new(usb_bus)
 }
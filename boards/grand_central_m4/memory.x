@@ -2,6 +2,7 @@ MEMORY
 {
   FLASH (rx) : ORIGIN = 0x00000000+0x4000, LENGTH = 0x100000-0x4000 /* First 16KB used by bootloader */
   RAM (rwx) : ORIGIN = 0x20000000, LENGTH = 0x40000
+  BKUPRAM (rw) : ORIGIN = 0x47000000, LENGTH = 8K
 }
 
 /* This is where the call stack will be allocated. */
@@ -30,4 +31,13 @@ MEMORY
    } INSERT AFTER .bss;
 */
 
-
+/* Backup RAM, retained in backup mode. Variables are placed in it with the
+   `backup_ram!` macro of the HAL, and are not initialized at startup. */
+SECTIONS
+{
+  .bkupram (NOLOAD) : ALIGN(4)
+  {
+    *(.bkupram .bkupram.*);
+  } > BKUPRAM
+}
+INSERT AFTER .uninit;
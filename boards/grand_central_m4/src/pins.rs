@@ -1,12 +1,13 @@
 //! Grand Central M4 Express Pins
 
 use super::{
-    hal, pac::MCLK, pac::SERCOM0, pac::SERCOM1, pac::SERCOM4, pac::SERCOM5, pac::SERCOM6,
+    hal, pac::MCLK, pac::PCC, pac::SERCOM0, pac::SERCOM1, pac::SERCOM4, pac::SERCOM5, pac::SERCOM6,
     pac::SERCOM7, target_device,
 };
 
 use hal::define_pins;
 use hal::gpio::{self, *};
+use hal::pcc;
 use hal::sercom::{
     I2CMaster6, PadPin, SPIMaster7, Sercom0Pad0, Sercom0Pad1, Sercom1Pad0, Sercom1Pad1,
     Sercom4Pad0, Sercom4Pad1, Sercom5Pad0, Sercom5Pad1, Sercom6Pad0, Sercom6Pad1, UART0, UART1,
@@ -480,6 +481,21 @@ impl Pins {
             neopixel: self.neopixel,
         };
 
+        let camera = Camera {
+            den1: self.d26,
+            den2: self.d27,
+            clk: self.d28,
+            xclk: self.d29,
+            d0: self.d37,
+            d1: self.d36,
+            d2: self.d35,
+            d3: self.d34,
+            d4: self.d33,
+            d5: self.d32,
+            d6: self.d31,
+            d7: self.d30,
+        };
+
         Sets {
             port: self.port,
             analog,
@@ -495,6 +511,7 @@ impl Pins {
             tx_led: self.tx_led,
             rx_led: self.rx_led,
             neopixel,
+            camera,
         }
     }
 }
@@ -522,6 +539,9 @@ pub struct Sets {
     /// Neopixel
     pub neopixel: Neopixel,
 
+    /// Parallel capture (camera) pins
+    pub camera: Camera,
+
     /// USB pins
     pub usb: USB,
 
@@ -622,6 +642,7 @@ impl USB {
     pub fn init(
         self,
         usb: super::pac::USB,
+        oscctrl: super::pac::OSCCTRL,
         clocks: &mut GenericClockController,
         mclk: &mut MCLK,
         port: &mut Port,
@@ -630,13 +651,19 @@ impl USB {
         let usb_gclk = clocks.get_gclk(GEN_A::GCLK2).unwrap();
         let usb_clock = &clocks.usb(&usb_gclk).unwrap();
 
-        UsbBusAllocator::new(UsbBus::new(
+        let mut usb_bus = UsbBus::new(
             usb_clock,
             mclk,
             self.dm.into_function(port),
             self.dp.into_function(port),
             usb,
-        ))
+        );
+        // Without a crystal, the 48 MHz USB clock is recovered from the host
+        usb_bus.set_clock_recovery(hal::usb::ClockRecovery::new(
+            oscctrl,
+            hal::usb::DfllFallback::OpenLoop,
+        ));
+        UsbBusAllocator::new(usb_bus)
     }
 }
 
@@ -785,6 +812,55 @@ pub struct QSPIFlash {
     pub data3: Pa11<Input<Floating>>,
 }
 
+/// Parallel capture pins of an 8-bit camera, like the OV7670
+pub struct Camera {
+    /// VSYNC
+    pub den1: Pa12<Input<Floating>>,
+    /// HREF
+    pub den2: Pa13<Input<Floating>>,
+    /// PCLK
+    pub clk: Pa14<Input<Floating>>,
+    /// Clock output to the camera, not driven by the PCC
+    pub xclk: Pb19<Input<Floating>>,
+    pub d0: Pa16<Input<Floating>>,
+    pub d1: Pa17<Input<Floating>>,
+    pub d2: Pa18<Input<Floating>>,
+    pub d3: Pa19<Input<Floating>>,
+    pub d4: Pa20<Input<Floating>>,
+    pub d5: Pa21<Input<Floating>>,
+    pub d6: Pa22<Input<Floating>>,
+    pub d7: Pa23<Input<Floating>>,
+}
+
+impl Camera {
+    /// Configure the PCC to capture 8-bit data. The XCLK pin is returned,
+    /// to be driven by a clock generator output or a timer.
+    pub fn init(
+        self,
+        mclk: &mut MCLK,
+        port: &mut Port,
+        pcc: PCC,
+        config: pcc::PccConfig,
+    ) -> (pcc::Pcc<pcc::Data8>, Pb19<Input<Floating>>) {
+        let pins = pcc::PccPins {
+            den1: self.den1.into_function_k(port).into(),
+            den2: self.den2.into_function_k(port).into(),
+            clk: self.clk.into_function_k(port).into(),
+            data: pcc::Data8 {
+                d0: self.d0.into_function_k(port).into(),
+                d1: self.d1.into_function_k(port).into(),
+                d2: self.d2.into_function_k(port).into(),
+                d3: self.d3.into_function_k(port).into(),
+                d4: self.d4.into_function_k(port).into(),
+                d5: self.d5.into_function_k(port).into(),
+                d6: self.d6.into_function_k(port).into(),
+                d7: self.d7.into_function_k(port).into(),
+            },
+        };
+        (pcc::Pcc::new(mclk, pcc, pins, config), self.xclk)
+    }
+}
+
 /// Neopixel pins
 pub struct Neopixel {
     pub neopixel: Pc24<Input<Floating>>,
@@ -68,6 +68,7 @@ fn main() -> ! {
     let bus_allocator = unsafe {
         USB_ALLOCATOR = Some(pins.usb.usb_allocator(
             peripherals.USB,
+            peripherals.OSCCTRL,
             &mut clocks,
             &mut peripherals.MCLK,
             &mut pins.port,
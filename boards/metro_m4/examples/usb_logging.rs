@@ -39,6 +39,7 @@ fn main() -> ! {
             pins.usb_dm,
             pins.usb_dp,
             peripherals.USB,
+            peripherals.OSCCTRL,
             &mut clocks,
             &mut peripherals.MCLK,
             &mut pins.port,
@@ -3,6 +3,17 @@ MEMORY
   /* Leave 16k for the default bootloader on the Metro M4 */
   FLASH (rx) : ORIGIN = 0x00000000 + 16K, LENGTH = 512K - 16K
   RAM (xrw)  : ORIGIN = 0x20000000, LENGTH = 192K
+  BKUPRAM (rw) : ORIGIN = 0x47000000, LENGTH = 8K
 }
 _stack_start = ORIGIN(RAM) + LENGTH(RAM);
 
+/* Backup RAM, retained in backup mode. Variables are placed in it with the
+   `backup_ram!` macro of the HAL, and are not initialized at startup. */
+SECTIONS
+{
+  .bkupram (NOLOAD) : ALIGN(4)
+  {
+    *(.bkupram .bkupram.*);
+  } > BKUPRAM
+}
+INSERT AFTER .uninit;
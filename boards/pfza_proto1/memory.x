@@ -3,6 +3,7 @@ MEMORY
   /* NOTE 1 K = 1 KiBi = 1024 bytes */
   FLASH : ORIGIN = 0x00000000, LENGTH = 1M
   RAM : ORIGIN = 0x20000000, LENGTH = 256k
+  BKUPRAM : ORIGIN = 0x47000000, LENGTH = 8K
 }
 
 /* This is where the call stack will be allocated. */
@@ -31,3 +32,13 @@ MEMORY
    } INSERT AFTER .bss;
 */
 
+/* Backup RAM, retained in backup mode. Variables are placed in it with the
+   `backup_ram!` macro of the HAL, and are not initialized at startup. */
+SECTIONS
+{
+  .bkupram (NOLOAD) : ALIGN(4)
+  {
+    *(.bkupram .bkupram.*);
+  } > BKUPRAM
+}
+INSERT AFTER .uninit;
@@ -30,6 +30,7 @@ fn main() -> ! {
 
     let usb_bus = pins.usb.init(
         peripherals.USB,
+        peripherals.OSCCTRL,
         &mut clocks,
         &mut peripherals.MCLK,
         &mut pins.port,
@@ -3,6 +3,31 @@ MEMORY
   /* Leave 16k for the default bootloader on the PyGamer */
   FLASH (rx) : ORIGIN = 0x00000000 + 16K, LENGTH = 512K - 16K
   RAM (xrw)  : ORIGIN = 0x20000000, LENGTH = 192K
+  /* QSPI flash, memory-mapped in XIP mode */
+  QSPI (rx)  : ORIGIN = 0x04000000, LENGTH = 8M
+  BKUPRAM (rw) : ORIGIN = 0x47000000, LENGTH = 8K
 }
 _stack_start = ORIGIN(RAM) + LENGTH(RAM);
 
+/* Code and data placed in the QSPI flash with #[link_section = ".qspi"],
+   read or executed once the QSPI is in XIP mode. Remove it from the internal
+   flash image with `objcopy -R .qspi`, and program it into the QSPI flash. */
+SECTIONS
+{
+  .qspi :
+  {
+    *(.qspi .qspi.*);
+  } > QSPI
+}
+INSERT AFTER .rodata;
+
+/* Backup RAM, retained in backup mode. Variables are placed in it with the
+   `backup_ram!` macro of the HAL, and are not initialized at startup. */
+SECTIONS
+{
+  .bkupram (NOLOAD) : ALIGN(4)
+  {
+    *(.bkupram .bkupram.*);
+  } > BKUPRAM
+}
+INSERT AFTER .uninit;
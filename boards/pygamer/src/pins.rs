@@ -492,6 +492,7 @@ impl USB {
     pub fn init(
         self,
         usb: pac::USB,
+        oscctrl: pac::OSCCTRL,
         clocks: &mut GenericClockController,
         mclk: &mut MCLK,
         port: &mut Port,
@@ -500,13 +501,19 @@ impl USB {
         let usb_gclk = clocks.get_gclk(GEN_A::GCLK2).unwrap();
         let usb_clock = &clocks.usb(&usb_gclk).unwrap();
 
-        UsbBusAllocator::new(UsbBus::new(
+        let mut usb_bus = UsbBus::new(
             usb_clock,
             mclk,
             self.dm.into_function(port),
             self.dp.into_function(port),
             usb,
-        ))
+        );
+        // Without a crystal, the 48 MHz USB clock is recovered from the host
+        usb_bus.set_clock_recovery(hal::usb::ClockRecovery::new(
+            oscctrl,
+            hal::usb::DfllFallback::OpenLoop,
+        ));
+        UsbBusAllocator::new(usb_bus)
     }
 }
 
@@ -579,6 +586,16 @@ impl QSPIFlash {
             mclk, port, qspi, self.sck, self.cs, self.data0, self.data1, self.data2, self.data3,
         )
     }
+
+    /// Initialize the QSPI and the flash, discovered from its SFDP tables
+    pub fn init_flash(
+        self,
+        mclk: &mut MCLK,
+        port: &mut Port,
+        qspi: QSPI,
+    ) -> Result<qspi::SerialFlash, qspi::Error> {
+        qspi::SerialFlash::new(self.init(mclk, port, qspi))
+    }
 }
 
 /// Button pins
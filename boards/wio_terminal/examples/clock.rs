@@ -88,6 +88,7 @@ fn main() -> ! {
     let bus_allocator = unsafe {
         USB_ALLOCATOR = Some(sets.usb.usb_allocator(
             peripherals.USB,
+            peripherals.OSCCTRL,
             &mut clocks,
             &mut peripherals.MCLK,
             &mut sets.port,
@@ -39,6 +39,16 @@ impl QSPIFlash {
             mclk, port, qspi, self.sck, self.cs, self.d0, self.d1, self.d2, self.d3,
         )
     }
+
+    /// Initialize the QSPI and the flash, discovered from its SFDP tables
+    pub fn init_flash(
+        self,
+        mclk: &mut MCLK,
+        port: &mut Port,
+        qspi: QSPI,
+    ) -> Result<qspi::SerialFlash, qspi::Error> {
+        qspi::SerialFlash::new(self.init(mclk, port, qspi))
+    }
 }
 
 /// SD Card pins (uses `SERCOM6`)
@@ -277,6 +277,23 @@ impl<const ID: u8> Channel<Busy, ID> {
         dmac.busych.read().bits() & (1 << ID) == 0 && dmac.pendch.read().bits() & (1 << ID) == 0
     }
 
+    /// Returns whether the transfer complete flag is set, and clears it.
+    ///
+    /// The flag is set at the end of every block transfer whose descriptor
+    /// has an interrupt block action, so it signals the completed blocks of
+    /// a linked list.
+    #[inline]
+    pub(crate) fn take_xfer_complete(&mut self, dmac: &mut DMAC) -> bool {
+        let complete = core::cell::Cell::new(false);
+        self.with_chid(dmac, |d| {
+            if d.chintflag.read().tcmpl().bit_is_set() {
+                d.chintflag.write(|w| w.tcmpl().set_bit());
+                complete.set(true);
+            }
+        });
+        complete.get()
+    }
+
     /// Wait for the channel to clear its busy status, then release the channel.
     ///
     /// # Return
@@ -8,9 +8,10 @@
 //! functions, including memory-to-memory,
 //! memory-to-peripheral, peripheral-to-memory,
 //! and peripheral-to-peripheral transfers.
-//! One-shot and circular transfers are supported. More complex
-//! transfer configurations, including multi-buffer
-//! (linked-list descriptor) transfers, are not currently supported.
+//! One-shot and circular transfers are supported, as well as
+//! peripheral-to-memory transfers into a ring of buffers linked by
+//! descriptors (see [`ring`]). Other linked-list descriptor transfers are
+//! not currently supported.
 //!
 //! Transfers are supported for `i8`, `u8`, `i16`, `u16`, `i32`, `u32` and `f32`
 //! beat sizes.
@@ -130,11 +131,13 @@
 
 pub mod channel;
 pub mod dma_controller;
+pub mod ring;
 pub mod transfer;
 
 #[cfg(feature = "min-samd51g")]
 pub use dma_controller::{BurstLength, FifoThreshold};
 pub use dma_controller::{DmaController, PriorityLevel, TriggerAction, TriggerSource};
+pub use ring::{Block, RingTransfer};
 pub use static_assertions::const_assert;
 pub use transfer::{Beat, Buffer, BufferPair, Transfer, TransferConfiguration};
 
@@ -161,9 +164,11 @@ pub const NUM_CHANNELS: usize = MAX_CHANNELS / 2;
 
 // ----- DMAC SRAM registers ----- //
 /// Descriptor representing a SRAM register. Datasheet section 19.8.2
+///
+/// Linked transfers need descriptors in RAM for the blocks after the first,
+/// which can be initialized with [`DEFAULT_DESCRIPTOR`].
 #[derive(Clone, Copy)]
 #[repr(C, align(16))]
-#[doc(hidden)]
 pub struct DmacDescriptor {
     btctrl: u16,
     btcnt: u16,
@@ -172,7 +177,7 @@ pub struct DmacDescriptor {
     descaddr: u32,
 }
 
-#[doc(hidden)]
+/// Empty descriptor
 pub const DEFAULT_DESCRIPTOR: DmacDescriptor = DmacDescriptor {
     btctrl: 0,
     btcnt: 0,
@@ -0,0 +1,334 @@
+//! # Ring transfers
+//!
+//! A ring transfer moves data from a fixed source, usually a peripheral
+//! register, into a ring of equal-sized buffers. Every buffer is filled by
+//! its own block transfer, whose descriptor is linked to the descriptor of
+//! the next buffer, and the descriptor of the last buffer is linked back to
+//! the first one. The DMAC therefore keeps filling the buffers in turn,
+//! without CPU intervention, until the transfer is stopped.
+//!
+//! The descriptor of the first buffer is the one of the channel. The
+//! descriptors of the other buffers are stored in a `'static` array provided
+//! by the user, with one entry per buffer after the first:
+//!
+//! ```ignore
+//! static mut BUFFERS: [[u32; 512]; 3] = [[0; 512]; 3];
+//! static mut DESCRIPTORS: [DmacDescriptor; 2] = [DEFAULT_DESCRIPTOR; 2];
+//!
+//! let mut ring = RingTransfer::start(
+//!     &mut dmac,
+//!     chan0,
+//!     source,
+//!     unsafe { &mut BUFFERS },
+//!     unsafe { &mut DESCRIPTORS },
+//!     (),
+//!     TriggerSource::PCC_RX,
+//!     TriggerAction::BURST,
+//! );
+//! loop {
+//!     if let Some(block) = ring.next_block(&mut dmac) {
+//!         if block.missed > 0 {
+//!             // The CPU did not keep up with the DMAC
+//!         }
+//!         process(ring.buffer(block.index));
+//!     }
+//! }
+//! ```
+//!
+//! A buffer is only stable until the DMAC comes back to it, one turn of the
+//! ring later: use enough buffers to process each of them in time.
+//!
+//! Completed blocks are found from the write-back descriptor of the channel,
+//! whose next descriptor address tells which buffer the DMAC is filling. The
+//! blocks completed between two calls to
+//! [`next_block`](RingTransfer::next_block) are therefore not lost track of,
+//! only the last one is returned, along with the number of missed ones.
+
+use core::sync::atomic;
+
+use super::{
+    channel::{Busy, Channel, Ready},
+    dma_controller::{DmaController, TriggerAction, TriggerSource},
+    transfer::Beat,
+    DmacDescriptor, DESCRIPTOR_SECTION, WRITEBACK,
+};
+
+/// BTCTRL: valid descriptor
+const BTCTRL_VALID: u16 = 1 << 0;
+/// BTCTRL: set the transfer complete flag at the end of the block
+const BTCTRL_BLOCKACT_INT: u16 = 1 << 3;
+/// BTCTRL: increment the destination address
+const BTCTRL_DSTINC: u16 = 1 << 11;
+
+impl DmacDescriptor {
+    /// Descriptor of a block transfer from the fixed `source` to the whole of
+    /// `destination`, followed by the block transfer of `next`
+    fn block<B: Beat>(
+        source: *const B,
+        destination: &mut [B],
+        next: *const DmacDescriptor,
+    ) -> Self {
+        DmacDescriptor {
+            btctrl: BTCTRL_VALID
+                | BTCTRL_BLOCKACT_INT
+                | ((B::BEATSIZE as u16) << 8)
+                | BTCTRL_DSTINC,
+            btcnt: destination.len() as u16,
+            srcaddr: source as u32,
+            // Address of the last beat, plus one
+            dstaddr: destination.as_mut_ptr_range().end as u32,
+            descaddr: next as u32,
+        }
+    }
+}
+
+/// Block completed by a [`RingTransfer`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Block {
+    /// Index of the filled buffer
+    pub index: usize,
+    /// Number of blocks completed before this one since the last returned
+    /// block, modulo the number of buffers. Their buffers may already have
+    /// been overwritten.
+    pub missed: usize,
+}
+
+/// Last block completed, when the DMAC is filling the buffer before buffer
+/// `next_desc` of a ring of `count` buffers, and the block before buffer
+/// `next` was the last returned
+fn completed_block(next_desc: usize, next: usize, count: usize) -> Option<Block> {
+    // The buffer being filled is the one before `next_desc`, so the last
+    // completed one is two buffers before
+    let index = (next_desc + 2 * count - 2) % count;
+    if (index + 1) % count == next {
+        return None;
+    }
+    Some(Block {
+        index,
+        missed: (index + count - next) % count,
+    })
+}
+
+/// DMA transfer from a fixed source into a ring of `N`-beat buffers,
+/// owning the resources until it is stopped
+pub struct RingTransfer<B: Beat + 'static, P, const N: usize, const ID: u8> {
+    chan: Channel<Busy, ID>,
+    buffers: &'static mut [[B; N]],
+    descriptors: &'static mut [DmacDescriptor],
+    payload: P,
+    next: usize,
+}
+
+impl<B: Beat + 'static, P, const N: usize, const ID: u8> RingTransfer<B, P, N, ID> {
+    /// Link one block transfer per buffer of `buffers` into a ring, and start
+    /// it with the given trigger.
+    ///
+    /// `payload` is owned by the transfer until it is stopped, like the
+    /// payload of a [`Transfer`](super::transfer::Transfer).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffers` has less than two buffers, if `N` is 0 or more than 65535 beats, or
+    /// if `descriptors` has less than one entry per buffer after the first.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start(
+        dmac: &mut DmaController,
+        chan: Channel<Ready, ID>,
+        source: &'static mut B,
+        buffers: &'static mut [[B; N]],
+        descriptors: &'static mut [DmacDescriptor],
+        payload: P,
+        trig_src: TriggerSource,
+        trig_act: TriggerAction,
+    ) -> Self {
+        assert!(buffers.len() >= 2, "A ring transfer needs two buffers");
+        assert!(
+            N > 0 && N <= u16::MAX as usize,
+            "Block transfers are limited to 65535 beats"
+        );
+        assert!(
+            descriptors.len() >= buffers.len() - 1,
+            "A ring transfer needs a descriptor per buffer after the first"
+        );
+
+        let source = source as *const B;
+        let count = buffers.len();
+        // SAFETY: This is safe as long as we ONLY write to the descriptor
+        // belonging to OUR channel. The descriptors are 'static, so they
+        // outlive the transfer.
+        unsafe {
+            let first = (core::ptr::addr_of_mut!(DESCRIPTOR_SECTION) as *mut DmacDescriptor)
+                .add(ID as usize);
+            let others = descriptors.as_mut_ptr();
+            for (index, buffer) in buffers.iter_mut().enumerate() {
+                let next = if index + 1 == count {
+                    first
+                } else {
+                    others.add(index)
+                };
+                let descriptor = DmacDescriptor::block(source, buffer, next);
+                if index == 0 {
+                    *first = descriptor;
+                    // Until the DMAC fetches the first descriptor, the
+                    // write-back descriptor is left from a previous transfer
+                    *(core::ptr::addr_of_mut!(WRITEBACK) as *mut DmacDescriptor).add(ID as usize) =
+                        descriptor;
+                } else {
+                    *others.add(index - 1) = descriptor;
+                }
+            }
+        }
+
+        // Memory barrier to prevent the compiler/CPU from re-ordering read/write
+        // operations beyond this fence.
+        // (see https://docs.rust-embedded.org/embedonomicon/dma.html#compiler-misoptimizations)
+        atomic::fence(atomic::Ordering::Release); //  ▲
+
+        // SAFETY: This is safe because we only borrow dmac once
+        let chan = chan.start(unsafe { dmac.dmac_mut() }, trig_src, trig_act);
+
+        Self {
+            chan,
+            buffers,
+            descriptors,
+            payload,
+            next: 0,
+        }
+    }
+
+    /// Returns the last block completed since the last call, if any.
+    ///
+    /// The blocks completed before it since the last call are counted in
+    /// [`Block::missed`]. A whole turn of the ring between two calls goes
+    /// unnoticed.
+    pub fn next_block(&mut self, dmac: &mut DmaController) -> Option<Block> {
+        // SAFETY: This is safe because we only borrow dmac once.
+        let dmac = unsafe { dmac.dmac_mut() };
+        // The flag is only cleared for interrupt users, the write-back
+        // descriptor tells which blocks are complete
+        self.chan.take_xfer_complete(dmac);
+
+        // SAFETY: The write-back descriptor of OUR channel is only written by
+        // the DMAC, so it is only read here.
+        let descaddr = unsafe {
+            let writeback =
+                (core::ptr::addr_of!(WRITEBACK) as *const DmacDescriptor).add(ID as usize);
+            core::ptr::read_volatile(core::ptr::addr_of!((*writeback).descaddr))
+        };
+        let block = completed_block(self.buffer_index(descaddr)?, self.next, self.buffers.len())?;
+        atomic::fence(atomic::Ordering::Acquire); // ▼
+
+        self.next = (block.index + 1) % self.buffers.len();
+        Some(block)
+    }
+
+    /// Index of the buffer filled by the descriptor at `address`
+    fn buffer_index(&self, address: u32) -> Option<usize> {
+        // SAFETY: Only the address of the descriptor is taken
+        let first = unsafe {
+            (core::ptr::addr_of!(DESCRIPTOR_SECTION) as *const DmacDescriptor).add(ID as usize)
+        };
+        if address == first as u32 {
+            return Some(0);
+        }
+        let offset = address.checked_sub(self.descriptors.as_ptr() as u32)? as usize;
+        let index = offset / core::mem::size_of::<DmacDescriptor>() + 1;
+        if index < self.buffers.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// Buffer `index` of the ring
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of range.
+    pub fn buffer(&self, index: usize) -> &[B; N] {
+        &self.buffers[index]
+    }
+
+    /// Number of buffers in the ring
+    pub fn buffer_count(&self) -> usize {
+        self.buffers.len()
+    }
+
+    /// Payload owned by the transfer
+    pub fn payload(&self) -> &P {
+        &self.payload
+    }
+
+    /// Immediately stop the transfer and release all owned resources
+    #[allow(clippy::type_complexity)]
+    pub fn stop(
+        self,
+        dmac: &mut DmaController,
+    ) -> (
+        Channel<Ready, ID>,
+        &'static mut [[B; N]],
+        &'static mut [DmacDescriptor],
+        P,
+    ) {
+        // SAFETY: This is safe because we only borrow dmac once.
+        let dmac = unsafe { dmac.dmac_mut() };
+        let chan = self.chan.stop(dmac);
+
+        // Memory barrier to prevent the compiler/CPU from re-ordering read/write
+        // operations beyond this fence.
+        // (see https://docs.rust-embedded.org/embedonomicon/dma.html#compiler-misoptimizations)
+        atomic::fence(atomic::Ordering::Acquire); // ▼
+
+        (chan, self.buffers, self.descriptors, self.payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_block() {
+        // Filling buffer 0
+        assert_eq!(completed_block(1, 0, 3), None);
+        // Filling buffer 1
+        assert_eq!(
+            completed_block(2, 0, 3),
+            Some(Block {
+                index: 0,
+                missed: 0
+            })
+        );
+        assert_eq!(completed_block(2, 1, 3), None);
+    }
+
+    #[test]
+    fn missed_blocks() {
+        // Filling buffer 2 after buffer 0 was returned
+        assert_eq!(
+            completed_block(0, 1, 3),
+            Some(Block {
+                index: 1,
+                missed: 0
+            })
+        );
+        // Filling buffer 0 after buffer 0 was returned
+        assert_eq!(
+            completed_block(1, 1, 3),
+            Some(Block {
+                index: 2,
+                missed: 1
+            })
+        );
+        // Filling buffer 3 after buffer 0 was returned
+        assert_eq!(
+            completed_block(0, 1, 4),
+            Some(Block {
+                index: 2,
+                missed: 1
+            })
+        );
+        // A whole turn goes unnoticed
+        assert_eq!(completed_block(2, 1, 4), None);
+    }
+}
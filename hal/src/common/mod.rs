@@ -18,6 +18,9 @@ pub mod timer_traits;
 #[cfg(all(feature = "unproven", feature = "dma"))]
 pub mod dmac;
 
+#[cfg(all(feature = "usb", any(feature = "samd21", feature = "min-samd51g")))]
+pub(crate) mod usb_host;
+
 #[cfg(any(feature = "samd11", feature = "samd21"))]
 mod thumbv6m;
 #[cfg(any(feature = "samd11", feature = "samd21"))]
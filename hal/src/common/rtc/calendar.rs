@@ -0,0 +1,339 @@
+//! Calendar arithmetic and conversions of the RTC clock registers
+//!
+//! The RTC counts years as a 6-bit offset from a reference year, and treats
+//! every year whose offset is a multiple of 4 as a leap year. The helpers
+//! here convert between [`CalendarDatetime`], the register layout and UNIX
+//! timestamps without depending on the hardware.
+
+/// Seconds since the UNIX epoch of 2000-01-01T00:00:00
+#[cfg(test)]
+const Y2K: i64 = 946_684_800;
+
+/// Day of the week
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+/// Returns `true` if `year` is a leap year in the Gregorian calendar
+pub fn is_leap_year(year: u16) -> bool {
+    match (year % 4, year % 100, year % 400) {
+        (0, 0, 0) => true,
+        (0, 0, _) => false,
+        (0, _, _) => true,
+        _ => false,
+    }
+}
+
+/// Number of days in `month` (1 to 12) of `year`
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns `true` if the RTC can count from `reference_year`: the reference
+/// year must be a leap year, and so must every year of the 64 year range
+/// whose offset is a multiple of 4.
+pub fn is_valid_reference_year(reference_year: u16) -> bool {
+    reference_year <= u16::MAX - 63
+        && (0..64)
+            .step_by(4)
+            .all(|offset| is_leap_year(reference_year + offset))
+}
+
+/// Days from 1970-01-01 to the given date
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date from the number of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Calendar date and time, with the full year.
+///
+/// Unlike [`Datetime`](super::Datetime), which holds the raw register
+/// fields, the year is not relative to the reference year, and hours are
+/// always in 24-hour format; the RTC 12-hour mode only changes the register
+/// representation.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct CalendarDatetime {
+    pub seconds: u8,
+    pub minutes: u8,
+    pub hours: u8,
+    pub day: u8,
+    pub month: u8,
+    pub year: u16,
+}
+
+impl CalendarDatetime {
+    /// Returns `true` if every field is in range, taking leap years into
+    /// account
+    pub fn is_valid(&self) -> bool {
+        self.seconds < 60
+            && self.minutes < 60
+            && self.hours < 24
+            && (1..=12).contains(&self.month)
+            && self.day >= 1
+            && self.day <= days_in_month(self.year, self.month)
+    }
+
+    /// Day of the week
+    pub fn weekday(&self) -> Weekday {
+        // 1970-01-01 was a Thursday
+        match days_from_civil(self.year as i64, self.month, self.day).rem_euclid(7) {
+            0 => Weekday::Thursday,
+            1 => Weekday::Friday,
+            2 => Weekday::Saturday,
+            3 => Weekday::Sunday,
+            4 => Weekday::Monday,
+            5 => Weekday::Tuesday,
+            _ => Weekday::Wednesday,
+        }
+    }
+
+    /// Day of the year, from 1 to 366
+    pub fn day_of_year(&self) -> u16 {
+        let year = self.year as i64;
+        (days_from_civil(year, self.month, self.day) - days_from_civil(year, 1, 1) + 1) as u16
+    }
+
+    /// Seconds since the UNIX epoch, ignoring leap seconds
+    pub fn to_unix(&self) -> i64 {
+        days_from_civil(self.year as i64, self.month, self.day) * 86_400
+            + self.hours as i64 * 3_600
+            + self.minutes as i64 * 60
+            + self.seconds as i64
+    }
+
+    /// Datetime from seconds since the UNIX epoch. Returns `None` if the
+    /// year does not fit in a `u16`.
+    pub fn from_unix(timestamp: i64) -> Option<Self> {
+        let (year, month, day) = civil_from_days(timestamp.div_euclid(86_400));
+        let seconds = timestamp.rem_euclid(86_400);
+        if !(0..=u16::MAX as i64).contains(&year) {
+            return None;
+        }
+        Some(Self {
+            seconds: (seconds % 60) as u8,
+            minutes: (seconds / 60 % 60) as u8,
+            hours: (seconds / 3_600) as u8,
+            day,
+            month,
+            year: year as u16,
+        })
+    }
+
+    /// Encode into the layout of the CLOCK, ALARM and TIMESTAMP registers.
+    /// Returns `None` if the datetime is invalid, or out of the 64 year
+    /// range of `reference_year`.
+    pub(super) fn to_register(self, reference_year: u16, twelve_hour: bool) -> Option<u32> {
+        let offset = self.year.checked_sub(reference_year)?;
+        if !self.is_valid() || offset > 63 {
+            return None;
+        }
+        Some(
+            self.seconds as u32
+                | (self.minutes as u32) << 6
+                | (encode_hour(self.hours, twelve_hour) as u32) << 12
+                | (self.day as u32) << 17
+                | (self.month as u32) << 22
+                | (offset as u32) << 26,
+        )
+    }
+
+    /// Decode the layout of the CLOCK, ALARM and TIMESTAMP registers
+    pub(super) fn from_register(bits: u32, reference_year: u16, twelve_hour: bool) -> Self {
+        Self {
+            seconds: (bits & 0x3f) as u8,
+            minutes: (bits >> 6 & 0x3f) as u8,
+            hours: decode_hour((bits >> 12 & 0x1f) as u8, twelve_hour),
+            day: (bits >> 17 & 0x1f) as u8,
+            month: (bits >> 22 & 0xf) as u8,
+            year: reference_year + (bits >> 26) as u16,
+        }
+    }
+}
+
+/// PM flag of the HOUR field in 12-hour mode
+const PM: u8 = 0x10;
+
+/// Encode a 24-hour value into the HOUR field
+fn encode_hour(hours: u8, twelve_hour: bool) -> u8 {
+    if !twelve_hour {
+        return hours;
+    }
+    let pm = if hours >= 12 { PM } else { 0 };
+    match hours % 12 {
+        0 => 12 | pm,
+        hours => hours | pm,
+    }
+}
+
+/// Decode the HOUR field into a 24-hour value
+fn decode_hour(field: u8, twelve_hour: bool) -> u8 {
+    if !twelve_hour {
+        return field;
+    }
+    let hours = (field & !PM) % 12;
+    if field & PM != 0 {
+        hours + 12
+    } else {
+        hours
+    }
+}
+
+/// Convert a frequency correction into the FREQCORR sign and value. Returns
+/// `None` if the correction is out of range.
+///
+/// One step adds or skips one prescaler count every 1024 * 976 cycles, a
+/// correction of 1.0006 ppm.
+pub(super) fn frequency_correction(ppm: i16) -> Option<(bool, u8)> {
+    let value = ((ppm as i32).abs() * 1024 * 976 + 500_000) / 1_000_000;
+    if value > 127 {
+        None
+    } else {
+        Some((ppm < 0, value as u8))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(
+        year: u16,
+        month: u8,
+        day: u8,
+        hours: u8,
+        minutes: u8,
+        seconds: u8,
+    ) -> CalendarDatetime {
+        CalendarDatetime {
+            seconds,
+            minutes,
+            hours,
+            day,
+            month,
+            year,
+        }
+    }
+
+    #[test]
+    fn leap_years() {
+        assert!(is_leap_year(2000));
+        assert!(is_leap_year(2024));
+        assert!(!is_leap_year(2023));
+        assert!(!is_leap_year(2100));
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2100, 2), 28);
+        assert_eq!(days_in_month(2023, 4), 30);
+    }
+
+    #[test]
+    fn reference_years() {
+        assert!(is_valid_reference_year(2000));
+        assert!(is_valid_reference_year(2036));
+        assert!(!is_valid_reference_year(2001));
+        // 2100 is not a leap year
+        assert!(!is_valid_reference_year(2040));
+        assert!(!is_valid_reference_year(u16::MAX - 3));
+    }
+
+    #[test]
+    fn validation() {
+        assert!(datetime(2024, 2, 29, 23, 59, 59).is_valid());
+        assert!(!datetime(2023, 2, 29, 0, 0, 0).is_valid());
+        assert!(!datetime(2024, 13, 1, 0, 0, 0).is_valid());
+        assert!(!datetime(2024, 1, 0, 0, 0, 0).is_valid());
+        assert!(!datetime(2024, 1, 1, 24, 0, 0).is_valid());
+    }
+
+    #[test]
+    fn weekdays() {
+        assert_eq!(datetime(2000, 1, 1, 0, 0, 0).weekday(), Weekday::Saturday);
+        assert_eq!(datetime(2024, 2, 29, 0, 0, 0).weekday(), Weekday::Thursday);
+        assert_eq!(
+            datetime(1969, 12, 31, 0, 0, 0).weekday(),
+            Weekday::Wednesday
+        );
+        assert_eq!(datetime(2024, 12, 31, 0, 0, 0).day_of_year(), 366);
+    }
+
+    #[test]
+    fn unix_round_trip() {
+        let y2k = datetime(2000, 1, 1, 0, 0, 0);
+        assert_eq!(y2k.to_unix(), Y2K);
+        assert_eq!(CalendarDatetime::from_unix(Y2K), Some(y2k));
+
+        let leap = datetime(2024, 2, 29, 13, 37, 42);
+        assert_eq!(leap.to_unix(), 1_709_213_862);
+        assert_eq!(CalendarDatetime::from_unix(leap.to_unix()), Some(leap));
+
+        let before = datetime(1969, 12, 31, 23, 59, 59);
+        assert_eq!(before.to_unix(), -1);
+        assert_eq!(CalendarDatetime::from_unix(-1), Some(before));
+    }
+
+    #[test]
+    fn register_layout() {
+        let time = datetime(2021, 7, 14, 18, 30, 5);
+        let bits = time.to_register(2000, false).unwrap();
+        assert_eq!(bits, 5 | 30 << 6 | 18 << 12 | 14 << 17 | 7 << 22 | 21 << 26);
+        assert_eq!(CalendarDatetime::from_register(bits, 2000, false), time);
+
+        let bits = time.to_register(2000, true).unwrap();
+        assert_eq!(bits >> 12 & 0x1f, (6 | PM) as u32);
+        assert_eq!(CalendarDatetime::from_register(bits, 2000, true), time);
+
+        assert_eq!(time.to_register(2024, false), None);
+        assert_eq!(time.to_register(1956, false), None);
+    }
+
+    #[test]
+    fn twelve_hour_encoding() {
+        assert_eq!(encode_hour(0, true), 12);
+        assert_eq!(encode_hour(11, true), 11);
+        assert_eq!(encode_hour(12, true), 12 | PM);
+        assert_eq!(encode_hour(23, true), 11 | PM);
+        for hours in 0..24 {
+            assert_eq!(decode_hour(encode_hour(hours, true), true), hours);
+        }
+    }
+
+    #[test]
+    fn frequency_corrections() {
+        assert_eq!(frequency_correction(0), Some((false, 0)));
+        assert_eq!(frequency_correction(10), Some((false, 10)));
+        assert_eq!(frequency_correction(-127), Some((true, 127)));
+        assert_eq!(frequency_correction(128), None);
+    }
+}
@@ -20,7 +20,82 @@ use crate::target_device::{
     rtc::mode2::CTRL as MODE2_CTRLA, PM,
 };
 
+mod calendar;
+pub use calendar::{
+    days_in_month, is_leap_year, is_valid_reference_year, CalendarDatetime, Weekday,
+};
+
+mod monotonic;
+pub use monotonic::*;
+
+#[cfg(feature = "min-samd51g")]
+mod tamper;
+#[cfg(feature = "min-samd51g")]
+pub use tamper::*;
+
+/// Errors of the clock/calendar mode
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// The datetime is invalid, or out of the 64 year range of the
+    /// reference year
+    InvalidDatetime,
+    /// The reference year is not usable, see [`is_valid_reference_year`]
+    InvalidReferenceYear,
+    /// The frequency correction is out of range
+    InvalidCorrection,
+}
+
+/// Clock/calendar mode configuration
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct CalendarConfig {
+    /// Year represented by a year offset of 0
+    pub reference_year: u16,
+    /// Store the hours in 12-hour format in the registers.
+    /// [`CalendarDatetime`] always uses the 24-hour format.
+    pub twelve_hour: bool,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        Self {
+            reference_year: 2000,
+            twelve_hour: false,
+        }
+    }
+}
+
+/// RTC alarm
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Alarm {
+    Alarm0,
+    #[cfg(feature = "min-samd51g")]
+    Alarm1,
+}
+
+/// Fields of the datetime compared by an alarm
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AlarmMask {
+    /// The alarm is disabled
+    Off = 0,
+    /// Match the seconds: the alarm fires every minute
+    Seconds,
+    /// Match the minutes and seconds: the alarm fires every hour
+    Minutes,
+    /// Match the time of day: the alarm fires every day
+    Hours,
+    /// Match the day of the month and the time: the alarm fires every month
+    Days,
+    /// Match everything but the year: the alarm fires every year
+    Months,
+    /// Match the full datetime: the alarm fires once
+    Years,
+}
+
 /// Datetime represents an RTC clock/calendar value.
+///
+/// The fields are the raw register values: `year` is the offset from the
+/// reference year, and `hours` follow the 12-hour format if it is selected.
+/// See [`CalendarDatetime`] for a value with the full year.
 #[derive(Debug, Clone, Copy)]
 pub struct Datetime {
     pub seconds: u8,
@@ -50,6 +125,8 @@ impl From<ClockR> for Datetime {
 pub struct Rtc {
     rtc: RTC,
     rtc_clock_freq: Hertz,
+    reference_year: u16,
+    twelve_hour: bool,
 }
 
 impl Rtc {
@@ -61,6 +138,8 @@ impl Rtc {
         let new_rtc = Self {
             rtc,
             rtc_clock_freq,
+            reference_year: 2000,
+            twelve_hour: false,
         };
 
         new_rtc.reset();
@@ -109,6 +188,16 @@ impl Rtc {
         self.sync();
     }
 
+    /// Runs `f` with the RTC disabled, to modify enable-protected registers
+    #[cfg(feature = "min-samd51g")]
+    fn while_disabled<F: FnOnce(&Self)>(&mut self, f: F) {
+        let enabled = self.mode0_ctrla().read().enable().bit_is_set();
+        self.enable(false);
+        f(self);
+        self.sync();
+        self.enable(enabled);
+    }
+
     #[inline]
     fn enable(&self, enable: bool) {
         if enable {
@@ -121,15 +210,31 @@ impl Rtc {
 
     // --- clock functions
 
+    /// Configures the peripheral for clock/calendar mode, counting from the
+    /// year 2000 in 24-hour format. Requires the source clock to be running
+    /// at 1024 Hz.
+    pub fn clock_mode(&mut self) {
+        self.configure_calendar(CalendarConfig::default());
+    }
+
     /// Configures the peripheral for clock/calendar mode. Requires the source
     /// clock to be running at 1024 Hz.
-    pub fn clock_mode(&mut self) {
+    pub fn calendar_mode(&mut self, config: CalendarConfig) -> Result<(), Error> {
+        if !is_valid_reference_year(config.reference_year) {
+            return Err(Error::InvalidReferenceYear);
+        }
+        self.configure_calendar(config);
+        Ok(())
+    }
+
+    fn configure_calendar(&mut self, config: CalendarConfig) {
         // The max divisor is 1024, so to get 1 Hz, we need a 1024 Hz source.
         assert_eq!(self.rtc_clock_freq.0, 1024_u32, "RTC clk not 1024 Hz!");
 
+        self.enable(false);
         self.mode2_ctrla().modify(|_, w| {
             w.mode().clock() // enable mode2 (clock)
-            .clkrep().clear_bit()
+            .clkrep().bit(config.twelve_hour)
             .matchclr().clear_bit()
             .prescaler().div1024() // 1.024 kHz / 1024 = 1Hz
         });
@@ -145,17 +250,19 @@ impl Rtc {
             self.sync();
         }
 
+        self.reference_year = config.reference_year;
+        self.twelve_hour = config.twelve_hour;
         self.enable(true);
     }
 
+    /// Returns the year represented by a year offset of 0
+    pub fn reference_year(&self) -> u16 {
+        self.reference_year
+    }
+
     /// Returns the current clock/calendar value.
     pub fn current_time(&self) -> Datetime {
-        // synchronize this read on SAMD11/21. SAMx5x is automatically synchronized
-        #[cfg(any(feature = "samd11", feature = "samd21"))]
-        {
-            self.mode2().readreq.modify(|_, w| w.rcont().set_bit());
-            self.sync();
-        }
+        self.sync_clock_read();
         self.mode2().clock.read().into()
     }
 
@@ -175,7 +282,119 @@ impl Rtc {
                 .year()
                 .bits(time.year)
         });
+    }
+
+    /// Returns the current date and time, with the full year.
+    pub fn current_datetime(&self) -> CalendarDatetime {
+        self.sync_clock_read();
+        let bits = self.mode2().clock.read().bits();
+        CalendarDatetime::from_register(bits, self.reference_year, self.twelve_hour)
+    }
+
+    /// Updates the current date and time. Fails if `time` is invalid, or
+    /// out of the 64 year range of the reference year.
+    pub fn set_datetime(&mut self, time: CalendarDatetime) -> Result<(), Error> {
+        let bits = time
+            .to_register(self.reference_year, self.twelve_hour)
+            .ok_or(Error::InvalidDatetime)?;
+        self.mode2().clock.write(|w| unsafe { w.bits(bits) });
+        self.sync();
+        Ok(())
+    }
+
+    #[inline]
+    fn sync_clock_read(&self) {
+        // synchronize this read on SAMD11/21. SAMx5x is automatically synchronized
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        {
+            self.mode2().readreq.modify(|_, w| w.rcont().set_bit());
+            self.sync();
+        }
+    }
+
+    /// Sets `alarm` to fire when the fields of `time` selected by `mask`
+    /// match the current time.
+    pub fn set_alarm(
+        &mut self,
+        alarm: Alarm,
+        time: CalendarDatetime,
+        mask: AlarmMask,
+    ) -> Result<(), Error> {
+        let bits = time
+            .to_register(self.reference_year, self.twelve_hour)
+            .ok_or(Error::InvalidDatetime)?;
+        match alarm {
+            Alarm::Alarm0 => {
+                self.mode2().alarm0.write(|w| unsafe { w.bits(bits) });
+                self.sync();
+                self.mode2()
+                    .mask0
+                    .write(|w| unsafe { w.sel().bits(mask as u8) });
+            }
+            #[cfg(feature = "min-samd51g")]
+            Alarm::Alarm1 => {
+                self.mode2().alarm1.write(|w| unsafe { w.bits(bits) });
+                self.sync();
+                self.mode2()
+                    .mask1
+                    .write(|w| unsafe { w.sel().bits(mask as u8) });
+            }
+        }
+        self.sync();
+        Ok(())
+    }
+
+    /// Enables the interrupt of `alarm`.
+    pub fn enable_alarm_interrupt(&mut self, alarm: Alarm) {
+        match alarm {
+            Alarm::Alarm0 => self.mode2().intenset.write(|w| w.alarm0().set_bit()),
+            #[cfg(feature = "min-samd51g")]
+            Alarm::Alarm1 => self.mode2().intenset.write(|w| w.alarm1().set_bit()),
+        }
+    }
+
+    /// Disables the interrupt of `alarm`.
+    pub fn disable_alarm_interrupt(&mut self, alarm: Alarm) {
+        match alarm {
+            Alarm::Alarm0 => self.mode2().intenclr.write(|w| w.alarm0().set_bit()),
+            #[cfg(feature = "min-samd51g")]
+            Alarm::Alarm1 => self.mode2().intenclr.write(|w| w.alarm1().set_bit()),
+        }
+    }
+
+    /// Returns `true` if `alarm` has fired since its flag was last cleared.
+    pub fn alarm_triggered(&self, alarm: Alarm) -> bool {
+        let intflag = self.mode2().intflag.read();
+        match alarm {
+            Alarm::Alarm0 => intflag.alarm0().bit_is_set(),
+            #[cfg(feature = "min-samd51g")]
+            Alarm::Alarm1 => intflag.alarm1().bit_is_set(),
+        }
+    }
+
+    /// Clears the flag of `alarm`.
+    pub fn clear_alarm(&mut self, alarm: Alarm) {
+        match alarm {
+            Alarm::Alarm0 => self.mode2().intflag.write(|w| w.alarm0().set_bit()),
+            #[cfg(feature = "min-samd51g")]
+            Alarm::Alarm1 => self.mode2().intflag.write(|w| w.alarm1().set_bit()),
+        }
+    }
+
+    /// Sets the frequency correction, in ppm, from -127 to 127, with a
+    /// resolution of 1.0006 ppm. Positive values slow the RTC down, to
+    /// compensate for a source clock running fast.
+    ///
+    /// The correction requires a prescaler of at least 2, which the
+    /// clock/calendar mode always uses.
+    pub fn set_frequency_correction(&mut self, ppm: i16) -> Result<(), Error> {
+        let (negative, value) =
+            calendar::frequency_correction(ppm).ok_or(Error::InvalidCorrection)?;
+        self.mode2()
+            .freqcorr
+            .write(|w| unsafe { w.sign().bit(negative).value().bits(value) });
         self.sync();
+        Ok(())
     }
 }
 
@@ -0,0 +1,309 @@
+//! RTC-based monotonic timer
+//!
+//! [`RtcMonotonic`] runs the RTC as a free-running 32-bit ([`Count32`]) or
+//! 16-bit ([`Count16`]) counter, and extends it to 64 bits by counting the
+//! counter overflows. Since the RTC keeps running in standby mode, it can
+//! serve as the timebase of low-power applications.
+//!
+//! The overflow interrupt must be serviced by calling
+//! [`RtcMonotonic::on_interrupt`] from the RTC interrupt handler, at least
+//! once per counter period. With the `rtic` feature, `RtcMonotonic`
+//! implements `rtic_monotonic::Monotonic`, and RTIC takes care of it. With
+//! the `embedded-time` feature, it implements `embedded_time::Clock`.
+//!
+//! The tick frequency `FREQ` is a const generic parameter. It must be the RTC
+//! source clock frequency divided by a power of two up to 1024.
+
+use super::PM;
+use crate::target_device::RTC;
+use crate::time::Hertz;
+use crate::typelevel::Sealed;
+use core::marker::PhantomData;
+use cortex_m::interrupt;
+
+#[cfg(feature = "embedded-time")]
+use embedded_time::{clock, fraction::Fraction, Instant};
+#[cfg(feature = "rtic")]
+use rtic_monotonic::Monotonic;
+
+/// Minimum distance of a compare value from the current count, in ticks,
+/// to cover the register synchronization delay
+const MIN_COMPARE_DELTA: u64 = 3;
+
+fn sync(rtc: &RTC) {
+    #[cfg(feature = "min-samd51g")]
+    while rtc.mode0().syncbusy.read().bits() != 0 {}
+    #[cfg(any(feature = "samd11", feature = "samd21"))]
+    while rtc.mode0().status.read().syncbusy().bit_is_set() {}
+}
+
+/// Disables the RTC and performs a software reset
+fn software_reset(rtc: &RTC) {
+    #[cfg(feature = "min-samd51g")]
+    let ctrla = &rtc.mode0().ctrla;
+    #[cfg(any(feature = "samd11", feature = "samd21"))]
+    let ctrla = &rtc.mode0().ctrl;
+    ctrla.modify(|_, w| w.enable().clear_bit());
+    sync(rtc);
+    ctrla.modify(|_, w| w.swrst().set_bit());
+    sync(rtc);
+}
+
+/// Counter mode of the RTC
+pub trait MonotonicMode: Sealed {
+    /// Width of the counter
+    const BITS: u32;
+
+    #[doc(hidden)]
+    fn configure(rtc: &RTC, prescaler: u8);
+    #[doc(hidden)]
+    fn count(rtc: &RTC) -> u32;
+    #[doc(hidden)]
+    fn set_count(rtc: &RTC, count: u32);
+    #[doc(hidden)]
+    fn set_compare(rtc: &RTC, compare: u32);
+}
+
+/// Mode 0: 32-bit counter
+pub enum Count32 {}
+
+impl Sealed for Count32 {}
+
+impl MonotonicMode for Count32 {
+    const BITS: u32 = 32;
+
+    fn configure(rtc: &RTC, prescaler: u8) {
+        #[cfg(feature = "min-samd51g")]
+        rtc.mode0().ctrla.write(|w| unsafe {
+            w.mode().count32();
+            w.matchclr().clear_bit();
+            w.countsync().set_bit();
+            w.prescaler().bits(prescaler)
+        });
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        rtc.mode0().ctrl.write(|w| unsafe {
+            w.mode().count32();
+            w.matchclr().clear_bit();
+            w.prescaler().bits(prescaler)
+        });
+    }
+
+    fn count(rtc: &RTC) -> u32 {
+        #[cfg(feature = "min-samd51g")]
+        while rtc.mode0().syncbusy.read().count().bit_is_set() {}
+        rtc.mode0().count.read().bits()
+    }
+
+    fn set_count(rtc: &RTC, count: u32) {
+        rtc.mode0().count.write(|w| unsafe { w.bits(count) });
+    }
+
+    fn set_compare(rtc: &RTC, compare: u32) {
+        rtc.mode0().comp[0].write(|w| unsafe { w.bits(compare) });
+    }
+}
+
+/// Mode 1: 16-bit counter
+pub enum Count16 {}
+
+impl Sealed for Count16 {}
+
+impl MonotonicMode for Count16 {
+    const BITS: u32 = 16;
+
+    fn configure(rtc: &RTC, prescaler: u8) {
+        #[cfg(feature = "min-samd51g")]
+        rtc.mode1().ctrla.write(|w| unsafe {
+            w.mode().count16();
+            w.countsync().set_bit();
+            w.prescaler().bits(prescaler)
+        });
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        rtc.mode1().ctrl.write(|w| unsafe {
+            w.mode().count16();
+            w.prescaler().bits(prescaler)
+        });
+        sync(rtc);
+        rtc.mode1().per.write(|w| unsafe { w.bits(0xffff) });
+    }
+
+    fn count(rtc: &RTC) -> u32 {
+        #[cfg(feature = "min-samd51g")]
+        while rtc.mode1().syncbusy.read().count().bit_is_set() {}
+        rtc.mode1().count.read().bits() as u32
+    }
+
+    fn set_count(rtc: &RTC, count: u32) {
+        rtc.mode1().count.write(|w| unsafe { w.bits(count as u16) });
+    }
+
+    fn set_compare(rtc: &RTC, compare: u32) {
+        rtc.mode1().comp[0].write(|w| unsafe { w.bits(compare as u16) });
+    }
+}
+
+/// Free-running RTC counter extended to 64 bits, ticking at `FREQ` Hz
+pub struct RtcMonotonic<M: MonotonicMode, const FREQ: u32> {
+    rtc: RTC,
+    overflows: u64,
+    _mode: PhantomData<M>,
+}
+
+impl<M: MonotonicMode, const FREQ: u32> RtcMonotonic<M, FREQ> {
+    /// Resets the RTC and starts counting from zero.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `FREQ` is not `rtc_clock_freq` divided by a power of two
+    /// up to 1024.
+    pub fn new(rtc: RTC, rtc_clock_freq: Hertz, pm: &mut PM) -> Self {
+        pm.apbamask.modify(|_, w| w.rtc_().set_bit());
+
+        let divider = rtc_clock_freq.0 / FREQ;
+        assert!(
+            divider * FREQ == rtc_clock_freq.0 && divider.is_power_of_two() && divider <= 1024,
+            "RTC clk not a power of two multiple of FREQ!"
+        );
+        // DIV1 is 0 on SAMD11/21, after OFF on SAMx5x
+        let prescaler = divider.trailing_zeros() as u8;
+        #[cfg(feature = "min-samd51g")]
+        let prescaler = prescaler + 1;
+
+        software_reset(&rtc);
+        M::configure(&rtc, prescaler);
+        sync(&rtc);
+
+        // Continuously synchronize the counter for reading
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        {
+            rtc.mode0()
+                .readreq
+                .write(|w| w.rcont().set_bit().rreq().set_bit());
+            sync(&rtc);
+        }
+
+        let mode0 = rtc.mode0();
+        mode0.intflag.write(|w| w.ovf().set_bit().cmp0().set_bit());
+        mode0.intenset.write(|w| w.ovf().set_bit().cmp0().set_bit());
+        #[cfg(feature = "min-samd51g")]
+        let ctrla = &mode0.ctrla;
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        let ctrla = &mode0.ctrl;
+        ctrla.modify(|_, w| w.enable().set_bit());
+        sync(&rtc);
+
+        Self {
+            rtc,
+            overflows: 0,
+            _mode: PhantomData,
+        }
+    }
+
+    /// Returns the number of ticks since the counter was started or reset
+    pub fn ticks(&self) -> u64 {
+        let half = 1 << (M::BITS - 1);
+        interrupt::free(|_| {
+            let count = M::count(&self.rtc);
+            if self.rtc.mode0().intflag.read().ovf().bit_is_set() {
+                // An overflow is pending. A stale count read before it still
+                // belongs to the previous period.
+                let count = M::count(&self.rtc);
+                let overflows = if count < half {
+                    self.overflows + 1
+                } else {
+                    self.overflows
+                };
+                overflows << M::BITS | count as u64
+            } else {
+                self.overflows << M::BITS | count as u64
+            }
+        })
+    }
+
+    /// Restarts counting from zero
+    pub fn reset(&mut self) {
+        interrupt::free(|_| {
+            M::set_count(&self.rtc, 0);
+            sync(&self.rtc);
+            self.rtc
+                .mode0()
+                .intflag
+                .write(|w| w.ovf().set_bit().cmp0().set_bit());
+            self.overflows = 0;
+        });
+    }
+
+    /// Schedules the compare interrupt at `ticks`. Instants in the past
+    /// fire as soon as possible; instants beyond the current counter period
+    /// are rescheduled by the caller after the next overflow.
+    pub fn set_compare(&mut self, ticks: u64) {
+        let now = self.ticks();
+        let max = (1 << M::BITS) - 1;
+        let compare = ticks.max(now + MIN_COMPARE_DELTA).min(now + max);
+        M::set_compare(&self.rtc, (compare & max) as u32);
+        sync(&self.rtc);
+    }
+
+    /// Returns `true` if the compare interrupt is pending
+    pub fn compare_triggered(&self) -> bool {
+        self.rtc.mode0().intflag.read().cmp0().bit_is_set()
+    }
+
+    /// Clears the compare interrupt flag
+    pub fn clear_compare(&mut self) {
+        self.rtc.mode0().intflag.write(|w| w.cmp0().set_bit());
+    }
+
+    /// Accounts for a counter overflow. Must be called from the RTC
+    /// interrupt handler.
+    pub fn on_interrupt(&mut self) {
+        let intflag = &self.rtc.mode0().intflag;
+        if intflag.read().ovf().bit_is_set() {
+            intflag.write(|w| w.ovf().set_bit());
+            self.overflows += 1;
+        }
+    }
+
+    /// Stops the counter and returns the RTC peripheral
+    pub fn free(self) -> RTC {
+        self.rtc
+            .mode0()
+            .intenclr
+            .write(|w| w.ovf().set_bit().cmp0().set_bit());
+        software_reset(&self.rtc);
+        self.rtc
+    }
+}
+
+#[cfg(feature = "embedded-time")]
+impl<M: MonotonicMode, const FREQ: u32> embedded_time::Clock for RtcMonotonic<M, FREQ> {
+    type T = u64;
+
+    const SCALING_FACTOR: Fraction = Fraction::new(1, FREQ);
+
+    fn try_now(&self) -> Result<Instant<Self>, clock::Error> {
+        Ok(Instant::new(self.ticks()))
+    }
+}
+
+#[cfg(feature = "rtic")]
+impl<M: MonotonicMode, const FREQ: u32> Monotonic for RtcMonotonic<M, FREQ> {
+    // The overflow interrupt extends the counter, so it must stay enabled
+    const DISABLE_INTERRUPT_ON_EMPTY_QUEUE: bool = false;
+
+    unsafe fn reset(&mut self) {
+        RtcMonotonic::reset(self);
+    }
+
+    fn set_compare(&mut self, instant: &Instant<Self>) {
+        RtcMonotonic::set_compare(self, instant.duration_since_epoch().integer());
+    }
+
+    fn clear_compare_flag(&mut self) {
+        self.clear_compare();
+    }
+
+    fn on_interrupt(&mut self) {
+        RtcMonotonic::on_interrupt(self);
+    }
+}
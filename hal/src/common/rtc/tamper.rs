@@ -0,0 +1,158 @@
+//! Tamper detection
+//!
+//! The SAMD51/E5x RTC monitors up to five tamper inputs (IN0 to IN4). A
+//! detection can wake the device up, capture the current time in the
+//! TIMESTAMP register, and clear the general purpose and backup registers.
+//! In active layer mode, an input is compared with the RTC output signal
+//! (OUT) looped back through a protective mesh.
+//!
+//! The tamper configuration is enable-protected: the RTC is briefly
+//! disabled while it is changed.
+
+use super::{CalendarDatetime, Rtc};
+
+/// Tamper input
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TamperInput {
+    In0 = 0,
+    In1,
+    In2,
+    In3,
+    In4,
+}
+
+/// Action on a tamper detection
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TamperAction {
+    /// The input is disabled
+    Off = 0,
+    /// Set the tamper flag, waking the device up if the interrupt is enabled
+    Wake,
+    /// Also capture the current time in the timestamp register
+    Capture,
+    /// Compare the input with the RTC output signal; a mismatch is a
+    /// detection
+    ActiveLayer,
+}
+
+/// Edge of the input signal detected as a tamper event
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TamperEdge {
+    Falling,
+    Rising,
+}
+
+/// Divider of the RTC clock for the debouncer and the active layer output
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TamperClockDiv {
+    Div2 = 0,
+    Div4,
+    Div8,
+    Div16,
+    Div32,
+    Div64,
+    Div128,
+    Div256,
+}
+
+/// Configuration of a tamper input
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct TamperConfig {
+    pub action: TamperAction,
+    pub edge: TamperEdge,
+    /// Debounce the input, see [`DebounceConfig`]
+    pub debounce: bool,
+}
+
+/// Configuration of the debouncer shared by the tamper inputs
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct DebounceConfig {
+    /// Sampling clock of the debouncer
+    pub clock: TamperClockDiv,
+    /// Require two out of three samples to agree, instead of three
+    /// identical samples
+    pub majority: bool,
+    /// Detect the edge asynchronously, without sampling
+    pub asynchronous: bool,
+}
+
+impl Rtc {
+    /// Configure the tamper `input`
+    pub fn configure_tamper(&mut self, input: TamperInput, config: TamperConfig) {
+        let n = input as u32;
+        let action = (config.action as u32) << (2 * n);
+        let level = ((config.edge == TamperEdge::Rising) as u32) << (16 + n);
+        let debounce = (config.debounce as u32) << (24 + n);
+        let mask = 0x3 << (2 * n) | 1 << (16 + n) | 1 << (24 + n);
+        self.while_disabled(|rtc| {
+            rtc.mode2()
+                .tampctrl
+                .modify(|r, w| unsafe { w.bits(r.bits() & !mask | action | level | debounce) });
+        });
+    }
+
+    /// Configure the debouncer of the tamper inputs
+    pub fn configure_debouncer(&mut self, config: DebounceConfig) {
+        self.while_disabled(|rtc| {
+            rtc.mode2().ctrlb.modify(|_, w| {
+                w.debf().bits(config.clock as u8);
+                w.debmaj().bit(config.majority);
+                w.debasync().bit(config.asynchronous)
+            });
+        });
+    }
+
+    /// Drive the RTC output signal used by the inputs in active layer mode
+    pub fn enable_active_layer(&mut self, clock: TamperClockDiv) {
+        self.while_disabled(|rtc| {
+            rtc.mode2()
+                .ctrlb
+                .modify(|_, w| w.actf().bits(clock as u8).rtcout().set_bit());
+        });
+    }
+
+    /// Stop driving the RTC output signal
+    pub fn disable_active_layer(&mut self) {
+        self.while_disabled(|rtc| {
+            rtc.mode2().ctrlb.modify(|_, w| w.rtcout().clear_bit());
+        });
+    }
+
+    /// Select the registers cleared on a tamper detection
+    pub fn clear_on_tamper(&mut self, general_purpose: bool, backup: bool) {
+        self.while_disabled(|rtc| {
+            rtc.mode2()
+                .ctrla
+                .modify(|_, w| w.gptrst().bit(general_purpose).bktrst().bit(backup));
+        });
+    }
+
+    /// Enable the tamper interrupt
+    pub fn enable_tamper_interrupt(&mut self) {
+        self.mode2().intenset.write(|w| w.tamper().set_bit());
+    }
+
+    /// Disable the tamper interrupt
+    pub fn disable_tamper_interrupt(&mut self) {
+        self.mode2().intenclr.write(|w| w.tamper().set_bit());
+    }
+
+    /// Returns `true` if a tamper event was detected on `input`
+    pub fn tamper_detected(&self, input: TamperInput) -> bool {
+        self.mode2().tampid.read().bits() & 1 << input as u32 != 0
+    }
+
+    /// Returns the time captured by the last tamper detection with
+    /// [`TamperAction::Capture`]
+    pub fn tamper_timestamp(&self) -> CalendarDatetime {
+        let bits = self.mode2().timestamp.read().bits();
+        CalendarDatetime::from_register(bits, self.reference_year, self.twelve_hour)
+    }
+
+    /// Clear the tamper flag and the detected inputs
+    pub fn clear_tamper(&mut self) {
+        let tampid = self.mode2().tampid.read().bits();
+        self.mode2().tampid.write(|w| unsafe { w.bits(tampid) });
+        self.mode2().intflag.write(|w| w.tamper().set_bit());
+    }
+}
@@ -1,12 +1,225 @@
 use crate::clock::GenericClockController;
 use crate::gpio::*;
 use crate::hal::adc::{Channel, OneShot};
-use crate::target_device::{adc, ADC, PM};
+use crate::target_device::adc::{ctrlb::RESSEL_A, inputctrl::GAIN_A, winctrl::WINMODE_A};
+use crate::target_device::{adc, ADC, PM, SYSCTRL};
+
+use crate::calibration;
 
 pub struct Adc<ADC> {
     adc: ADC,
 }
 
+/// ADC inputs which can be used as the negative input of a differential
+/// conversion
+pub trait NegativeChannel<ADC>: Channel<ADC, ID = u8> {}
+
+/// Resolution of the conversion results
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Resolution {
+    _8Bit,
+    _10Bit,
+    _12Bit,
+    /// 16-bit results, obtained by accumulating 256 12-bit conversions and
+    /// decimating the sum
+    Oversampled16Bit,
+}
+
+/// Number of conversions accumulated and averaged into each result
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Averaging {
+    _1,
+    _2,
+    _4,
+    _8,
+    _16,
+    _32,
+    _64,
+    _128,
+    _256,
+    _512,
+    _1024,
+}
+
+/// Gain of the input stage
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Gain {
+    X1,
+    X2,
+    X4,
+    X8,
+    X16,
+    Div2,
+}
+
+impl From<Gain> for GAIN_A {
+    fn from(gain: Gain) -> Self {
+        match gain {
+            Gain::X1 => GAIN_A::_1X,
+            Gain::X2 => GAIN_A::_2X,
+            Gain::X4 => GAIN_A::_4X,
+            Gain::X8 => GAIN_A::_8X,
+            Gain::X16 => GAIN_A::_16X,
+            Gain::Div2 => GAIN_A::DIV2,
+        }
+    }
+}
+
+/// Digital correction of the conversion results, computed as
+/// `(conversion - offset) * gain / 2048`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Correction {
+    /// Offset, as a 12-bit two's complement value
+    pub offset: i16,
+    /// Gain factor, as a 12-bit value where 2048 is 1.0
+    pub gain: u16,
+}
+
+/// ADC configuration, applied by [`Adc::configure`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AdcConfig {
+    /// Resolution of the results
+    pub resolution: Resolution,
+    /// Number of conversions averaged into each result. Averaging is only
+    /// available with [`Resolution::_12Bit`], and is ignored otherwise.
+    pub averaging: Averaging,
+    /// Gain of the input stage
+    pub gain: Gain,
+    /// Store the results left-adjusted in the 16-bit result register
+    pub left_adjust: bool,
+    /// Sampling time, in half ADC clock cycles minus one. At most 63.
+    pub sample_time: u8,
+    /// Digital offset and gain correction
+    pub correction: Option<Correction>,
+}
+
+impl Default for AdcConfig {
+    /// The configuration set by the constructor
+    fn default() -> Self {
+        Self {
+            resolution: Resolution::_12Bit,
+            averaging: Averaging::_1,
+            gain: Gain::Div2,
+            left_adjust: false,
+            sample_time: 5,
+            correction: None,
+        }
+    }
+}
+
+impl AdcConfig {
+    /// RESSEL, SAMPLENUM and ADJRES values for this configuration
+    fn result_config(&self) -> (RESSEL_A, u8, u8) {
+        match (self.resolution, self.averaging) {
+            // 256 samples give 4 extra bits without further adjustment
+            (Resolution::Oversampled16Bit, _) => (RESSEL_A::_16BIT, 8, 0),
+            (Resolution::_12Bit, Averaging::_1) => (RESSEL_A::_12BIT, 0, 0),
+            (Resolution::_12Bit, averaging) => {
+                // Table 32-3 (32.6.7) specifies the adjres
+                // values necessary for each SAMPLENUM value.
+                let samplenum = averaging as u8;
+                (RESSEL_A::_16BIT, samplenum, samplenum.min(4))
+            }
+            (Resolution::_10Bit, _) => (RESSEL_A::_10BIT, 0, 0),
+            (Resolution::_8Bit, _) => (RESSEL_A::_8BIT, 0, 0),
+        }
+    }
+}
+
+/// Internal input: temperature sensor
+pub struct Temperature;
+/// Internal input: bandgap voltage reference
+pub struct Bandgap;
+/// Internal input: core supply voltage, scaled by 1/4
+pub struct ScaledVddCore;
+/// Internal input: I/O supply voltage, scaled by 1/4
+pub struct ScaledVddio;
+/// Internal input: DAC output
+pub struct Dac;
+
+/// Condition detected by the window monitor, comparing the conversion results
+/// against thresholds
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WindowMode {
+    /// The result is above the threshold
+    Above(u16),
+    /// The result is below the threshold
+    Below(u16),
+    /// The result is strictly between `lower` and `upper`
+    Inside { lower: u16, upper: u16 },
+    /// The result is outside of `lower..=upper`
+    Outside { lower: u16, upper: u16 },
+}
+
+impl WindowMode {
+    /// WINMODE, WINLT and WINUT values for this mode
+    fn registers(self) -> (WINMODE_A, u16, u16) {
+        match self {
+            WindowMode::Above(lower) => (WINMODE_A::MODE1, lower, 0),
+            WindowMode::Below(upper) => (WINMODE_A::MODE2, 0, upper),
+            WindowMode::Inside { lower, upper } => (WINMODE_A::MODE3, lower, upper),
+            WindowMode::Outside { lower, upper } => (WINMODE_A::MODE4, lower, upper),
+        }
+    }
+}
+
+/// Calibration of the temperature sensor, read from the NVM temperature log
+/// row
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TemperatureCalibration {
+    /// Room and hot calibration temperatures, in °C
+    room_temp: f32,
+    hot_temp: f32,
+    /// Actual voltage of the 1V reference at both temperatures
+    room_int1v: f32,
+    hot_int1v: f32,
+    /// 12-bit conversion results at both temperatures
+    room_adc: f32,
+    hot_adc: f32,
+}
+
+impl TemperatureCalibration {
+    /// Read the calibration from the NVM
+    pub fn read() -> Self {
+        Self::from_log_row(calibration::temperature_log_row())
+    }
+
+    /// Parse the words of the temperature log row
+    pub fn from_log_row(row: [u32; 2]) -> Self {
+        let field = |word: usize, shift: u32, bits: u32| (row[word] >> shift) & ((1 << bits) - 1);
+        let temperature = |int: u32, dec: u32| int as f32 + dec as f32 / 10.0;
+        // The deviation from 1V is stored in mV, as a signed byte
+        let int1v = |value: u32| 1.0 - (value as u8 as i8) as f32 / 1000.0;
+        Self {
+            room_temp: temperature(field(0, 0, 8), field(0, 8, 4)),
+            hot_temp: temperature(field(0, 12, 8), field(0, 20, 4)),
+            room_int1v: int1v(field(0, 24, 8)),
+            hot_int1v: int1v(field(1, 0, 8)),
+            room_adc: field(1, 8, 12) as f32,
+            hot_adc: field(1, 20, 12) as f32,
+        }
+    }
+
+    /// Temperature in °C, computed from a 12-bit conversion result of the
+    /// temperature sensor, with the internal 1V reference and a gain of 1.
+    ///
+    /// A first estimate assumes an exact 1V reference, and is then refined
+    /// with the reference voltage interpolated at that temperature.
+    pub fn temperature(&self, result: u16) -> f32 {
+        let room_v = self.room_adc * self.room_int1v / 4095.0;
+        let hot_v = self.hot_adc * self.hot_int1v / 4095.0;
+        let interpolate = |v: f32| {
+            self.room_temp + (self.hot_temp - self.room_temp) * (v - room_v) / (hot_v - room_v)
+        };
+
+        let coarse = interpolate(result as f32 / 4095.0);
+        let int1v = self.room_int1v
+            + (self.hot_int1v - self.room_int1v) * (coarse - self.room_temp)
+                / (self.hot_temp - self.room_temp);
+        interpolate(result as f32 * int1v / 4095.0)
+    }
+}
+
 impl Adc<ADC> {
     pub fn adc(adc: ADC, pm: &mut PM, clocks: &mut GenericClockController) -> Self {
         pm.apbcmask.modify(|_, w| w.adc_().set_bit());
@@ -70,6 +283,190 @@ impl Adc<ADC> {
         while self.adc.status.read().syncbusy().bit_is_set() {}
     }
 
+    /// Apply `config`
+    pub fn configure(&mut self, config: AdcConfig) {
+        let (ressel, samplenum, adjres) = config.result_config();
+        self.adc.ctrlb.modify(|_, w| {
+            w.ressel().variant(ressel);
+            w.leftadj().bit(config.left_adjust);
+            w.corren().bit(config.correction.is_some())
+        });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.avgctrl.write(|w| unsafe {
+            w.samplenum().bits(samplenum);
+            w.adjres().bits(adjres)
+        });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc
+            .sampctrl
+            .write(|w| unsafe { w.samplen().bits(config.sample_time & 0x3f) });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.gain(config.gain.into());
+
+        if let Some(correction) = config.correction {
+            self.adc
+                .offsetcorr
+                .write(|w| unsafe { w.offsetcorr().bits(correction.offset as u16 & 0xfff) });
+            self.adc
+                .gaincorr
+                .write(|w| unsafe { w.gaincorr().bits(correction.gain & 0xfff) });
+            while self.adc.status.read().syncbusy().bit_is_set() {}
+        }
+    }
+
+    /// Convert the voltage difference between `pos` and `neg`. The result is
+    /// signed.
+    pub fn read_differential<P, N>(&mut self, _pos: &mut P, _neg: &mut N) -> i16
+    where
+        P: Channel<ADC, ID = u8>,
+        N: NegativeChannel<ADC>,
+    {
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.inputctrl.modify(|_, w| unsafe {
+            w.muxpos().bits(P::channel());
+            w.muxneg().bits(N::channel())
+        });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.ctrlb.modify(|_, w| w.diffmode().set_bit());
+        self.power_up();
+        let result = self.convert();
+        self.power_down();
+        self.adc.ctrlb.modify(|_, w| w.diffmode().clear_bit());
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.inputctrl.modify(|_, w| w.muxneg().gnd());
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        result as i16
+    }
+
+    /// Read the chip temperature, in °C. This enables the temperature
+    /// sensor in the system controller.
+    ///
+    /// The conversion uses the internal 1V reference, a gain of 1 and
+    /// 12-bit results; the current configuration is restored afterwards.
+    pub fn read_temperature(
+        &mut self,
+        sysctrl: &mut SYSCTRL,
+        calibration: &TemperatureCalibration,
+    ) -> f32 {
+        sysctrl.vref.modify(|_, w| w.tsen().set_bit());
+
+        let refctrl = self.adc.refctrl.read().bits();
+        let inputctrl = self.adc.inputctrl.read().bits();
+        let ctrlb = self.adc.ctrlb.read().bits();
+        let avgctrl = self.adc.avgctrl.read().bits();
+
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.refctrl.write(|w| w.refsel().int1v());
+        self.adc.inputctrl.write(|w| {
+            w.muxpos().temp();
+            w.muxneg().gnd();
+            w.gain()._1x()
+        });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.ctrlb.modify(|_, w| {
+            w.ressel()._12bit();
+            w.diffmode().clear_bit();
+            w.leftadj().clear_bit();
+            w.corren().clear_bit()
+        });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.avgctrl.reset();
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+
+        self.power_up();
+        let result = self.convert();
+        self.power_down();
+
+        self.adc.refctrl.write(|w| unsafe { w.bits(refctrl) });
+        self.adc.inputctrl.write(|w| unsafe { w.bits(inputctrl) });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.ctrlb.write(|w| unsafe { w.bits(ctrlb) });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.avgctrl.write(|w| unsafe { w.bits(avgctrl) });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+
+        calibration.temperature(result)
+    }
+
+    /// Enable the output of the bandgap reference to the ADC, needed to
+    /// convert the [`Bandgap`] input
+    pub fn enable_bandgap(&mut self, sysctrl: &mut SYSCTRL) {
+        sysctrl.vref.modify(|_, w| w.bgouten().set_bit());
+    }
+
+    /// Enable the window monitor. The comparison applies to the final
+    /// results, after averaging and correction.
+    pub fn enable_window_monitor(&mut self, mode: WindowMode) {
+        let (winmode, lower, upper) = mode.registers();
+        self.adc.winlt.write(|w| unsafe { w.winlt().bits(lower) });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.winut.write(|w| unsafe { w.winut().bits(upper) });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.winctrl.write(|w| w.winmode().variant(winmode));
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.intflag.write(|w| w.winmon().set_bit());
+    }
+
+    /// Disable the window monitor
+    pub fn disable_window_monitor(&mut self) {
+        self.adc.winctrl.write(|w| w.winmode().disable());
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+    }
+
+    /// Enable the interrupt raised when the window condition is met
+    pub fn enable_window_interrupt(&mut self) {
+        self.adc.intenset.write(|w| w.winmon().set_bit());
+    }
+
+    /// Disable the window monitor interrupt
+    pub fn disable_window_interrupt(&mut self) {
+        self.adc.intenclr.write(|w| w.winmon().set_bit());
+    }
+
+    /// Enable or disable the event generated when the window condition is
+    /// met. The event control register is enable-protected, so this must not
+    /// be called while the ADC is running.
+    pub fn window_event(&mut self, enabled: bool) {
+        self.adc.evctrl.modify(|_, w| w.winmoneo().bit(enabled));
+    }
+
+    /// Returns `true` if the window condition was met since the last call,
+    /// and clears the flag
+    pub fn window_triggered(&mut self) -> bool {
+        let triggered = self.adc.intflag.read().winmon().bit_is_set();
+        if triggered {
+            self.adc.intflag.write(|w| w.winmon().set_bit());
+        }
+        triggered
+    }
+
+    /// Convert `pin` continuously in the background, for use with the window
+    /// monitor. No interrupt is raised on completed conversions, so that the
+    /// CPU can sleep until the window interrupt or event fires.
+    pub fn start_monitoring<PIN: Channel<ADC, ID = u8>>(&mut self, _pin: &mut PIN) {
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc
+            .inputctrl
+            .modify(|_, w| unsafe { w.muxpos().bits(PIN::channel()) });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.ctrlb.modify(|_, w| w.freerun().set_bit());
+        self.power_up();
+        self.adc.swtrig.write(|w| w.start().set_bit());
+    }
+
+    /// Stop the conversions started by
+    /// [`start_monitoring`](Self::start_monitoring)
+    pub fn stop_monitoring(&mut self) {
+        self.power_down();
+        self.adc.ctrlb.modify(|_, w| w.freerun().clear_bit());
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+    }
+
+    /// Latest conversion result
+    pub fn last_result(&self) -> u16 {
+        self.adc.result.read().result().bits()
+    }
+
     fn power_up(&mut self) {
         while self.adc.status.read().syncbusy().bit_is_set() {}
         self.adc.ctrla.modify(|_, w| w.enable().set_bit());
@@ -122,6 +519,145 @@ where
     }
 }
 
+/// Input scan, available when the `dma` feature is enabled
+#[cfg(feature = "dma")]
+mod scan {
+    use super::*;
+    use crate::dmac::{
+        channel::{Busy, Channel as DmaChannel, Ready},
+        BufferPair, DmaController, Transfer, TransferConfiguration, TriggerAction, TriggerSource,
+    };
+    use core::marker::PhantomData;
+
+    /// How the conversions of a scan are started
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub enum ScanTrigger {
+        /// The ADC runs in free-running mode, converting the scanned channels
+        /// back-to-back
+        FreeRunning,
+        /// Every ADC start event converts the next channel of the scan. Use
+        /// a timer as the event generator to sample the channels at a fixed
+        /// rate.
+        Event,
+    }
+
+    /// DMA transfer of the results of a scan started by [`Adc::scan`]
+    pub struct AdcScan<const ID: u8> {
+        results:
+            Transfer<BufferPair<u16, &'static mut u16, &'static mut [u16]>, Adc<ADC>, Busy, ID>,
+    }
+
+    impl Adc<ADC> {
+        /// Convert `count` consecutive ADC channels, starting with the channel
+        /// of `first`, and store the results into `results` using DMA.
+        ///
+        /// The results of the scanned channels are stored in order, and the
+        /// scan restarts from `first` once the last channel has been
+        /// converted. The pins of the other scanned channels must be
+        /// configured as ADC inputs too. If `circular` is `true`, the
+        /// transfer restarts when it reaches the end of `results`, until
+        /// [`AdcScan::stop`] is called.
+        ///
+        /// # Panics
+        ///
+        /// Panics if `count` is not between 1 and 16, or if the scan goes
+        /// past the last ADC channel.
+        #[allow(clippy::too_many_arguments)]
+        pub fn scan<PIN: Channel<ADC, ID = u8>, const ID: u8>(
+            mut self,
+            dmac: &mut DmaController,
+            _first: &mut PIN,
+            count: u8,
+            trigger: ScanTrigger,
+            circular: bool,
+            results: &'static mut [u16],
+            chan: DmaChannel<Ready, ID>,
+        ) -> AdcScan<ID> {
+            let first = PIN::channel();
+            assert!((1..=16).contains(&count) && first + count <= 20);
+
+            // EVCTRL is enable-protected
+            self.power_down();
+            self.adc.inputctrl.modify(|_, w| unsafe {
+                w.muxpos().bits(first);
+                w.inputoffset().bits(0);
+                w.inputscan().bits(count - 1)
+            });
+            while self.adc.status.read().syncbusy().bit_is_set() {}
+            self.adc
+                .evctrl
+                .write(|w| w.startei().bit(trigger == ScanTrigger::Event));
+            self.adc
+                .ctrlb
+                .modify(|_, w| w.freerun().bit(trigger == ScanTrigger::FreeRunning));
+            while self.adc.status.read().syncbusy().bit_is_set() {}
+            self.adc.intflag.write(|w| w.resrdy().set_bit());
+
+            // SAFETY: RESULT is a register which stays valid for the lifetime
+            // of the program. The ADC is moved into the transfer, so no other
+            // code accesses it during the scan.
+            let result = unsafe { &mut *self.adc.result.as_ptr() };
+            self.power_up();
+
+            let results = BufferPair {
+                source: result,
+                destination: results,
+                _b: PhantomData,
+            }
+            .setup_xfer(chan, circular, self)
+            .begin(dmac, TriggerSource::ADC_RESRDY, TriggerAction::BEAT);
+
+            if trigger == ScanTrigger::FreeRunning {
+                // SAFETY: Only the software trigger is written, which does
+                // not interfere with the transfer
+                let adc = unsafe { &*ADC::ptr() };
+                adc.swtrig.write(|w| w.start().set_bit());
+            }
+
+            AdcScan { results }
+        }
+
+        /// Restore single conversions after a scan
+        fn end_scan(&mut self) {
+            self.power_down();
+            self.adc.evctrl.reset();
+            self.adc.ctrlb.modify(|_, w| w.freerun().clear_bit());
+            while self.adc.status.read().syncbusy().bit_is_set() {}
+            self.adc.inputctrl.modify(|_, w| unsafe {
+                w.inputoffset().bits(0);
+                w.inputscan().bits(0)
+            });
+            while self.adc.status.read().syncbusy().bit_is_set() {}
+        }
+    }
+
+    impl<const ID: u8> AdcScan<ID> {
+        /// Block until `results` is full, and release the resources. Never
+        /// returns if the transfer is circular.
+        pub fn wait(
+            self,
+            dmac: &mut DmaController,
+        ) -> (Adc<ADC>, &'static mut [u16], DmaChannel<Ready, ID>) {
+            let (res, chan, mut adc) = self.results.wait::<u16, &mut u16, &mut [u16]>(dmac);
+            adc.end_scan();
+            (adc, res.destination, chan)
+        }
+
+        /// Stop the scan immediately, and release the resources.
+        pub fn stop(
+            self,
+            dmac: &mut DmaController,
+        ) -> (Adc<ADC>, &'static mut [u16], DmaChannel<Ready, ID>) {
+            let (res, chan, mut adc) = self.results.stop::<u16, &mut u16, &mut [u16]>(dmac);
+            adc.end_scan();
+            (adc, res.destination, chan)
+        }
+    }
+}
+
+#[cfg(feature = "dma")]
+pub use scan::{AdcScan, ScanTrigger};
+
 macro_rules! adc_pins {
     ($($pin:ident: $chan:expr),+) => {
         $(
@@ -174,3 +710,99 @@ adc_pins! {
     Pb6: 14,
     Pb7: 15
 }
+
+macro_rules! adc_internal {
+    ($($input:ident: $chan:expr),+) => {
+        $(
+
+impl Channel<ADC> for $input {
+   type ID = u8;
+   fn channel() -> u8 { $chan }
+}
+        )+
+    }
+}
+
+adc_internal! {
+    Temperature: 0x18,
+    Bandgap: 0x19,
+    ScaledVddCore: 0x1a,
+    ScaledVddio: 0x1b,
+    Dac: 0x1c
+}
+
+macro_rules! adc_negative_pins {
+    ($($pin:ident),+) => {
+        $(
+impl NegativeChannel<ADC> for $pin<PfB> {}
+        )+
+    }
+}
+
+#[cfg(feature = "samd11")]
+adc_negative_pins!(Pa2, Pa4, Pa5, Pa14, Pa15);
+
+#[cfg(feature = "samd21")]
+adc_negative_pins!(Pa2, Pa3, Pa4, Pa5, Pa6, Pa7);
+
+#[cfg(feature = "min-samd21g")]
+adc_negative_pins!(Pb8, Pb9);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn result_config() {
+        let mut config = AdcConfig::default();
+        assert_eq!(config.result_config(), (RESSEL_A::_12BIT, 0, 0));
+        config.averaging = Averaging::_8;
+        assert_eq!(config.result_config(), (RESSEL_A::_16BIT, 3, 3));
+        config.averaging = Averaging::_256;
+        assert_eq!(config.result_config(), (RESSEL_A::_16BIT, 8, 4));
+        config.resolution = Resolution::_10Bit;
+        assert_eq!(config.result_config(), (RESSEL_A::_10BIT, 0, 0));
+        config.resolution = Resolution::Oversampled16Bit;
+        assert_eq!(config.result_config(), (RESSEL_A::_16BIT, 8, 0));
+    }
+
+    #[test]
+    fn window_registers() {
+        assert_eq!(
+            WindowMode::Below(100).registers(),
+            (WINMODE_A::MODE2, 0, 100)
+        );
+        assert_eq!(
+            WindowMode::Inside {
+                lower: 10,
+                upper: 20
+            }
+            .registers(),
+            (WINMODE_A::MODE3, 10, 20)
+        );
+    }
+
+    #[test]
+    fn temperature_calibration() {
+        // 25.0 °C and 85.5 °C, exact 1V reference, results 2000 and 2400
+        let row = [25 | 85 << 12 | 5 << 20, 2000 << 8 | 2400 << 20];
+        let calibration = TemperatureCalibration::from_log_row(row);
+        assert_eq!(calibration.hot_temp, 85.5);
+        assert_eq!(calibration.room_int1v, 1.0);
+
+        let close = |a: f32, b: f32| (a - b).abs() < 0.01;
+        assert!(close(calibration.temperature(2000), 25.0));
+        assert!(close(calibration.temperature(2400), 85.5));
+        assert!(close(calibration.temperature(2200), 55.25));
+
+        // Reference 2mV low at room temperature, 3mV high when hot
+        let row = [25 | 85 << 12 | 2 << 24, 0xfd | 2000 << 8 | 2400 << 20];
+        let calibration = TemperatureCalibration::from_log_row(row);
+        assert_eq!(calibration.room_int1v, 0.998);
+        assert_eq!(calibration.hot_int1v, 1.003);
+        // A single refinement step gets within a few hundredths of a degree
+        let close = |a: f32, b: f32| (a - b).abs() < 0.05;
+        assert!(close(calibration.temperature(2000), 25.0));
+        assert!(close(calibration.temperature(2400), 85.0));
+    }
+}
@@ -1,4 +1,6 @@
 pub mod eic;
+pub mod power;
+pub mod supply;
 
 mod reset_cause;
 pub use reset_cause::*;
@@ -0,0 +1,141 @@
+//! Sleep modes and wake-up sources
+//!
+//! The SAMD11/21 support four sleep modes. In the three idle modes, the CPU
+//! is stopped and more and more bus clocks are gated, and any enabled
+//! interrupt wakes the device up. In standby mode, all clocks are stopped
+//! except for the peripherals and clock sources configured to run in
+//! standby; only asynchronous interrupts, such as the EIC or the RTC, can
+//! wake the device up.
+//!
+//! [`sleep`] enters a sleep mode with interrupts masked, so that the wake-up
+//! reason can be read before the pending interrupt handler runs.
+
+use crate::target_device::{Interrupt, NVIC, PM, SCB};
+use crate::target_device::{AC, ADC, DAC, SERCOM0, SERCOM1, TCC0, USB};
+#[cfg(feature = "samd21")]
+use crate::target_device::{SERCOM2, SERCOM3, TC3, TC4, TC5, TCC1, TCC2};
+#[cfg(feature = "min-samd21g")]
+use crate::target_device::{SERCOM4, SERCOM5};
+#[cfg(feature = "samd11")]
+use crate::target_device::{TC1, TC2};
+#[cfg(feature = "min-samd21j")]
+use crate::target_device::{TC6, TC7};
+use cortex_m::{asm, interrupt};
+
+/// Sleep modes
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SleepMode {
+    /// The CPU clock is stopped
+    Idle0,
+    /// The CPU and AHB clocks are stopped
+    Idle1,
+    /// The CPU, AHB and APB clocks are stopped
+    Idle2,
+    /// All clocks are stopped, except for those configured to run in standby
+    Standby,
+}
+
+/// Reason why [`sleep`] returned
+#[derive(Debug, Clone, Copy)]
+pub enum WakeReason {
+    /// One of the wake-up sources is pending
+    Interrupt(Interrupt),
+    /// Another enabled interrupt woke the device up
+    Other,
+}
+
+/// Enter `mode`, and return when one of the interrupts in `wake_sources`, or
+/// any other enabled interrupt, is pending.
+///
+/// The interrupts in `wake_sources` are unmasked in the NVIC beforehand. The
+/// sleep mode is entered with interrupts disabled, and the handler of the
+/// pending interrupt runs right before this function returns.
+pub fn sleep(
+    pm: &mut PM,
+    scb: &mut SCB,
+    mode: SleepMode,
+    wake_sources: &[Interrupt],
+) -> WakeReason {
+    match mode {
+        SleepMode::Idle0 => pm.sleep.write(|w| w.idle().cpu()),
+        SleepMode::Idle1 => pm.sleep.write(|w| w.idle().ahb()),
+        SleepMode::Idle2 => pm.sleep.write(|w| w.idle().apb()),
+        SleepMode::Standby => {}
+    }
+    if mode == SleepMode::Standby {
+        scb.set_sleepdeep();
+    } else {
+        scb.clear_sleepdeep();
+    }
+
+    let reason = interrupt::free(|_| {
+        for &irq in wake_sources {
+            // SAFETY: Unmasking the wake-up sources is the caller's intent
+            unsafe { NVIC::unmask(irq) };
+        }
+        asm::dsb();
+        asm::wfi();
+        wake_sources
+            .iter()
+            .find(|&&irq| NVIC::is_pending(irq))
+            .map_or(WakeReason::Other, |&irq| WakeReason::Interrupt(irq))
+    });
+
+    scb.clear_sleepdeep();
+    reason
+}
+
+/// Peripherals which can keep running in standby mode.
+///
+/// Running in standby also requires the generic clock of the peripheral to
+/// run in standby.
+pub trait RunInStandby {
+    /// Keep the peripheral running in standby mode. This setting is
+    /// enable-protected on most peripherals, so it must be changed while
+    /// the peripheral is disabled.
+    fn run_in_standby(&mut self, enabled: bool);
+}
+
+macro_rules! run_in_standby {
+    ($($peripheral:ident $(. $view:ident())?),+) => {
+        $(
+impl RunInStandby for $peripheral {
+    fn run_in_standby(&mut self, enabled: bool) {
+        self$(.$view())?.ctrla.modify(|_, w| w.runstdby().bit(enabled));
+    }
+}
+        )+
+    }
+}
+
+run_in_standby!(AC, ADC, DAC, TCC0);
+run_in_standby!(SERCOM0.usart(), SERCOM1.usart());
+
+impl RunInStandby for USB {
+    fn run_in_standby(&mut self, enabled: bool) {
+        #[cfg(feature = "samd11")]
+        let device = &self.device;
+        #[cfg(feature = "samd21")]
+        let device = self.device();
+        device.ctrla.modify(|_, w| w.runstdby().bit(enabled));
+    }
+}
+#[cfg(feature = "samd11")]
+run_in_standby!(TC1.count16(), TC2.count16());
+
+#[cfg(feature = "samd21")]
+run_in_standby!(
+    SERCOM2.usart(),
+    SERCOM3.usart(),
+    TC3.count16(),
+    TC4.count16(),
+    TC5.count16(),
+    TCC1,
+    TCC2
+);
+
+#[cfg(feature = "min-samd21g")]
+run_in_standby!(SERCOM4.usart(), SERCOM5.usart());
+
+#[cfg(feature = "min-samd21j")]
+run_in_standby!(TC6.count16(), TC7.count16());
@@ -0,0 +1,193 @@
+//! Supply monitoring
+//!
+//! The SYSCTRL monitors the VDD supply with the BOD33 brown-out detector,
+//! and can route the internal 1.1V bandgap reference to the ADC and AC.
+//!
+//! The BOD12 monitoring VDDCORE is calibrated in production and its
+//! register is reserved, so it is not exposed here.
+//!
+//! The threshold is a raw register value; refer to the electrical
+//! characteristics of the datasheet for the matching voltages. After reset,
+//! the BOD33 is configured from the NVM user row, which
+//! [`Bod33Config::read`] returns.
+
+use crate::target_device::SYSCTRL;
+
+/// Action taken when VDD falls below the BOD33 threshold
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BodAction {
+    /// Only set the detection status
+    None,
+    /// Reset the device
+    Reset,
+    /// Raise the BOD33 detection interrupt
+    Interrupt,
+}
+
+/// Clock prescaler of the BOD33 in sampling mode
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BodPrescaler {
+    Div2 = 0,
+    Div4,
+    Div8,
+    Div16,
+    Div32,
+    Div64,
+    Div128,
+    Div256,
+    Div512,
+    Div1K,
+    Div2K,
+    Div4K,
+    Div8K,
+    Div16K,
+    Div32K,
+    Div64K,
+}
+
+/// Operation of the BOD33
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BodMode {
+    /// VDD is monitored continuously
+    Continuous,
+    /// VDD is monitored periodically, on the prescaled OSCULP32K clock
+    Sampling(BodPrescaler),
+}
+
+/// BOD33 configuration
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Bod33Config {
+    /// VDD threshold level, from 0 to 63
+    pub level: u8,
+    /// Enable the hysteresis
+    pub hysteresis: bool,
+    /// Action on detection
+    pub action: BodAction,
+    /// Continuous or sampling operation
+    pub mode: BodMode,
+    /// Keep monitoring in standby mode
+    pub run_in_standby: bool,
+}
+
+impl Bod33Config {
+    /// Read the current configuration of the BOD33
+    pub fn read(sysctrl: &SYSCTRL) -> Self {
+        let bod33 = sysctrl.bod33.read();
+        let action = match bod33.action().bits() {
+            1 => BodAction::Reset,
+            2 => BodAction::Interrupt,
+            _ => BodAction::None,
+        };
+        let mode = if bod33.mode().bit_is_set() {
+            BodMode::Sampling(match bod33.psel().bits() {
+                0 => BodPrescaler::Div2,
+                1 => BodPrescaler::Div4,
+                2 => BodPrescaler::Div8,
+                3 => BodPrescaler::Div16,
+                4 => BodPrescaler::Div32,
+                5 => BodPrescaler::Div64,
+                6 => BodPrescaler::Div128,
+                7 => BodPrescaler::Div256,
+                8 => BodPrescaler::Div512,
+                9 => BodPrescaler::Div1K,
+                10 => BodPrescaler::Div2K,
+                11 => BodPrescaler::Div4K,
+                12 => BodPrescaler::Div8K,
+                13 => BodPrescaler::Div16K,
+                14 => BodPrescaler::Div32K,
+                _ => BodPrescaler::Div64K,
+            })
+        } else {
+            BodMode::Continuous
+        };
+        Self {
+            level: bod33.level().bits(),
+            hysteresis: bod33.hyst().bit_is_set(),
+            action,
+            mode,
+            run_in_standby: bod33.runstdby().bit_is_set(),
+        }
+    }
+}
+
+fn wait_bod33_sync(sysctrl: &SYSCTRL) {
+    while sysctrl.pclksr.read().b33srdy().bit_is_clear() {}
+}
+
+/// Returns `true` if the BOD33 is enabled
+pub fn bod33_enabled(sysctrl: &SYSCTRL) -> bool {
+    sysctrl.bod33.read().enable().bit_is_set()
+}
+
+/// Configure and enable the BOD33.
+///
+/// The BOD33 is disabled while it is reconfigured. With
+/// [`BodAction::Reset`], a level above the current VDD resets the device as
+/// soon as the BOD33 is enabled.
+pub fn enable_bod33(sysctrl: &mut SYSCTRL, config: &Bod33Config) {
+    disable_bod33(sysctrl);
+
+    let (sampling, prescaler) = match config.mode {
+        BodMode::Continuous => (false, 0),
+        BodMode::Sampling(prescaler) => (true, prescaler as u8),
+    };
+    let action = match config.action {
+        BodAction::None => 0,
+        BodAction::Reset => 1,
+        BodAction::Interrupt => 2,
+    };
+    sysctrl.bod33.write(|w| unsafe {
+        w.level().bits(config.level);
+        w.hyst().bit(config.hysteresis);
+        w.action().bits(action);
+        w.mode().bit(sampling);
+        w.psel().bits(prescaler);
+        w.runstdby().bit(config.run_in_standby)
+    });
+    wait_bod33_sync(sysctrl);
+    if sampling {
+        // The sampling clock is enabled once the prescaler is set
+        sysctrl.bod33.modify(|_, w| w.cen().set_bit());
+        wait_bod33_sync(sysctrl);
+    }
+    sysctrl.bod33.modify(|_, w| w.enable().set_bit());
+    while sysctrl.pclksr.read().bod33rdy().bit_is_clear() {}
+}
+
+/// Disable the BOD33
+pub fn disable_bod33(sysctrl: &mut SYSCTRL) {
+    sysctrl
+        .bod33
+        .modify(|_, w| w.enable().clear_bit().cen().clear_bit());
+    wait_bod33_sync(sysctrl);
+}
+
+/// Returns `true` while VDD is below the BOD33 threshold
+pub fn bod33_detected(sysctrl: &SYSCTRL) -> bool {
+    sysctrl.pclksr.read().bod33det().bit_is_set()
+}
+
+/// Enable the BOD33 detection interrupt
+pub fn enable_bod33_interrupt(sysctrl: &mut SYSCTRL) {
+    sysctrl.intenset.write(|w| w.bod33det().set_bit());
+}
+
+/// Disable the BOD33 detection interrupt
+pub fn disable_bod33_interrupt(sysctrl: &mut SYSCTRL) {
+    sysctrl.intenclr.write(|w| w.bod33det().set_bit());
+}
+
+/// Clear the BOD33 detection interrupt flag
+pub fn clear_bod33_interrupt(sysctrl: &mut SYSCTRL) {
+    sysctrl.intflag.write(|w| w.bod33det().set_bit());
+}
+
+/// Route the 1.1V bandgap reference to the ADC and AC
+pub fn enable_reference_output(sysctrl: &mut SYSCTRL) {
+    sysctrl.vref.modify(|_, w| w.bgouten().set_bit());
+}
+
+/// Stop routing the bandgap reference to the peripherals
+pub fn disable_reference_output(sysctrl: &mut SYSCTRL) {
+    sysctrl.vref.modify(|_, w| w.bgouten().clear_bit());
+}
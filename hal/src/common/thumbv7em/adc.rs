@@ -11,9 +11,10 @@ use crate::gpio::{Pc0, Pc1, Pc2, Pc3};
 #[cfg(feature = "min-samd51p")]
 use crate::gpio::{Pc30, Pc31, Pd0, Pd1};
 use crate::hal::adc::{Channel, OneShot};
+use crate::target_device::adc0::ctrlb::{RESSEL_A, WINMODE_A};
 use crate::target_device::gclk::genctrl::SRC_A::DFLL;
 use crate::target_device::gclk::pchctrl::GEN_A;
-use crate::target_device::{adc0, ADC0, ADC1, MCLK};
+use crate::target_device::{adc0, ADC0, ADC1, MCLK, SUPC};
 
 use crate::calibration;
 
@@ -41,6 +42,191 @@ pub trait ConversionMode<ADC> {
 pub struct SingleConversion;
 pub struct FreeRunning;
 
+/// ADC inputs which can be used as the negative input of a differential
+/// conversion
+pub trait NegativeChannel<ADC>: Channel<ADC, ID = u8> {}
+
+/// Resolution of the conversion results
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Resolution {
+    _8Bit,
+    _10Bit,
+    _12Bit,
+    /// 16-bit results, obtained by accumulating 256 12-bit conversions and
+    /// decimating the sum
+    Oversampled16Bit,
+}
+
+/// Number of conversions accumulated and averaged into each result
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Averaging {
+    _1,
+    _2,
+    _4,
+    _8,
+    _16,
+    _32,
+    _64,
+    _128,
+    _256,
+    _512,
+    _1024,
+}
+
+/// Digital correction of the conversion results, computed as
+/// `(conversion - offset) * gain / 2048`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Correction {
+    /// Offset, as a 12-bit two's complement value
+    pub offset: i16,
+    /// Gain factor, as a 12-bit value where 2048 is 1.0
+    pub gain: u16,
+}
+
+/// ADC configuration, applied by [`Adc::configure`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AdcConfig {
+    /// Resolution of the results
+    pub resolution: Resolution,
+    /// Number of conversions averaged into each result. Averaging is only
+    /// available with [`Resolution::_12Bit`], and is ignored otherwise.
+    pub averaging: Averaging,
+    /// Store the results left-adjusted in the 16-bit result register
+    pub left_adjust: bool,
+    /// Sampling time, in ADC clock cycles minus one. At most 63.
+    pub sample_time: u8,
+    /// Digital offset and gain correction
+    pub correction: Option<Correction>,
+}
+
+impl Default for AdcConfig {
+    /// The configuration set by the constructors
+    fn default() -> Self {
+        Self {
+            resolution: Resolution::_12Bit,
+            averaging: Averaging::_1,
+            left_adjust: false,
+            sample_time: 5,
+            correction: None,
+        }
+    }
+}
+
+impl AdcConfig {
+    /// RESSEL, SAMPLENUM and ADJRES values for this configuration
+    fn result_config(&self) -> (RESSEL_A, u8, u8) {
+        match (self.resolution, self.averaging) {
+            // 256 samples give 4 extra bits without further adjustment
+            (Resolution::Oversampled16Bit, _) => (RESSEL_A::_16BIT, 8, 0),
+            (Resolution::_12Bit, Averaging::_1) => (RESSEL_A::_12BIT, 0, 0),
+            (Resolution::_12Bit, averaging) => {
+                // Table 45-3 (45.6.2.10) specifies the adjres
+                // values necessary for each SAMPLENUM value.
+                let samplenum = averaging as u8;
+                (RESSEL_A::_16BIT, samplenum, samplenum.min(4))
+            }
+            (Resolution::_10Bit, _) => (RESSEL_A::_10BIT, 0, 0),
+            (Resolution::_8Bit, _) => (RESSEL_A::_8BIT, 0, 0),
+        }
+    }
+}
+
+/// Internal input: core supply voltage, scaled by 1/4
+pub struct ScaledVddCore;
+/// Internal input: backup battery voltage, scaled by 1/4
+pub struct ScaledVbat;
+/// Internal input: I/O supply voltage, scaled by 1/4
+pub struct ScaledVddio;
+/// Internal input: bandgap voltage reference
+pub struct Bandgap;
+/// Internal input: temperature sensor, proportional to absolute temperature
+pub struct Ptat;
+/// Internal input: temperature sensor, complementary to absolute temperature
+pub struct Ctat;
+/// Internal input: DAC output
+pub struct Dac;
+
+/// Condition detected by the window monitor, comparing the conversion results
+/// against thresholds
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WindowMode {
+    /// The result is above the threshold
+    Above(u16),
+    /// The result is below the threshold
+    Below(u16),
+    /// The result is strictly between `lower` and `upper`
+    Inside { lower: u16, upper: u16 },
+    /// The result is outside of `lower..=upper`
+    Outside { lower: u16, upper: u16 },
+}
+
+impl WindowMode {
+    /// WINMODE, WINLT and WINUT values for this mode
+    fn registers(self) -> (WINMODE_A, u16, u16) {
+        match self {
+            WindowMode::Above(lower) => (WINMODE_A::MODE1, lower, 0),
+            WindowMode::Below(upper) => (WINMODE_A::MODE2, 0, upper),
+            WindowMode::Inside { lower, upper } => (WINMODE_A::MODE3, lower, upper),
+            WindowMode::Outside { lower, upper } => (WINMODE_A::MODE4, lower, upper),
+        }
+    }
+}
+
+/// Calibration of the temperature sensor, read from the NVM temperature log
+/// row
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TemperatureCalibration {
+    /// Low and high calibration temperatures, in °C
+    tl: f32,
+    th: f32,
+    /// PTAT readings at the low and high temperatures
+    vpl: f32,
+    vph: f32,
+    /// CTAT readings at the low and high temperatures
+    vcl: f32,
+    vch: f32,
+}
+
+impl TemperatureCalibration {
+    /// Read the calibration from the NVM
+    pub fn read() -> Self {
+        Self::from_log_row(calibration::temperature_log_row())
+    }
+
+    /// Parse the words of the temperature log row
+    pub fn from_log_row(row: [u32; 3]) -> Self {
+        let field = |word: usize, shift: u32, bits: u32| (row[word] >> shift) & ((1 << bits) - 1);
+        let temperature = |int: u32, dec: u32| int as f32 + dec as f32 / 10.0;
+        Self {
+            tl: temperature(field(0, 0, 8), field(0, 8, 4)),
+            th: temperature(field(0, 12, 8), field(0, 20, 4)),
+            vpl: field(1, 8, 12) as f32,
+            vph: field(1, 20, 12) as f32,
+            vcl: field(2, 0, 12) as f32,
+            vch: field(2, 12, 12) as f32,
+        }
+    }
+
+    /// Temperature in °C, computed from the PTAT and CTAT conversion results.
+    ///
+    /// Only the ratio between both results matters, so any reference and
+    /// resolution can be used, as long as both results are converted with
+    /// the same settings.
+    pub fn temperature(&self, ptat: u16, ctat: u16) -> f32 {
+        let (tp, tc) = (ptat as f32, ctat as f32);
+        let Self {
+            tl,
+            th,
+            vpl,
+            vph,
+            vcl,
+            vch,
+        } = *self;
+        (tl * vph * tc - vpl * th * tc - tl * vch * tp + th * vcl * tp)
+            / (vcl * tp - vch * tp - vpl * tc + vph * tc)
+    }
+}
+
 macro_rules! adc_hal {
     ($($ADC:ident: ($init:ident, $mclk:ident, $apmask:ident, $compcal:ident, $refcal:ident, $r2rcal:ident),)+) => {
         $(
@@ -98,6 +284,149 @@ impl Adc<$ADC> {
         while self.adc.syncbusy.read().refctrl().bit_is_set() {}
     }
 
+    /// Apply `config`
+    pub fn configure(&mut self, config: AdcConfig) {
+        let (ressel, samplenum, adjres) = config.result_config();
+        self.adc.ctrlb.modify(|_, w| {
+            w.ressel().variant(ressel);
+            w.leftadj().bit(config.left_adjust);
+            w.corren().bit(config.correction.is_some())
+        });
+        while self.adc.syncbusy.read().ctrlb().bit_is_set() {}
+        self.adc.avgctrl.write(|w| unsafe {
+            w.samplenum().bits(samplenum);
+            w.adjres().bits(adjres)
+        });
+        while self.adc.syncbusy.read().avgctrl().bit_is_set() {}
+        self.adc.sampctrl.modify(|_, w| unsafe { w.samplen().bits(config.sample_time & 0x3f) });
+        while self.adc.syncbusy.read().sampctrl().bit_is_set() {}
+
+        if let Some(correction) = config.correction {
+            self.adc
+                .offsetcorr
+                .write(|w| unsafe { w.offsetcorr().bits(correction.offset as u16 & 0xfff) });
+            while self.adc.syncbusy.read().offsetcorr().bit_is_set() {}
+            self.adc
+                .gaincorr
+                .write(|w| unsafe { w.gaincorr().bits(correction.gain & 0xfff) });
+            while self.adc.syncbusy.read().gaincorr().bit_is_set() {}
+        }
+    }
+
+    /// Convert the voltage difference between `pos` and `neg`. The result is
+    /// signed.
+    pub fn read_differential<P, N>(&mut self, _pos: &mut P, _neg: &mut N) -> i16
+    where
+        P: Channel<$ADC, ID = u8>,
+        N: NegativeChannel<$ADC>,
+    {
+        while self.adc.syncbusy.read().inputctrl().bit_is_set() {}
+        self.adc.inputctrl.modify(|_, w| unsafe {
+            w.muxpos().bits(P::channel());
+            w.muxneg().bits(N::channel());
+            w.diffmode().set_bit()
+        });
+        self.power_up();
+        let result = self.synchronous_convert();
+        self.power_down();
+        self.adc.inputctrl.modify(|_, w| {
+            w.muxneg().gnd();
+            w.diffmode().clear_bit()
+        });
+        while self.adc.syncbusy.read().inputctrl().bit_is_set() {}
+        result as i16
+    }
+
+    /// Read the chip temperature, in °C. This enables the temperature
+    /// sensor in the supply controller.
+    pub fn read_temperature(
+        &mut self,
+        supc: &mut SUPC,
+        calibration: &TemperatureCalibration,
+    ) -> f32 {
+        supc.vref.modify(|_, w| w.tsen().set_bit());
+        let ptat = self.convert_channel(<Ptat as Channel<$ADC>>::channel());
+        let ctat = self.convert_channel(<Ctat as Channel<$ADC>>::channel());
+        calibration.temperature(ptat, ctat)
+    }
+
+    /// Enable the window monitor. The comparison applies to the final
+    /// results, after averaging and correction.
+    pub fn enable_window_monitor(&mut self, mode: WindowMode) {
+        let (winmode, lower, upper) = mode.registers();
+        self.adc.winlt.write(|w| unsafe { w.winlt().bits(lower) });
+        while self.adc.syncbusy.read().winlt().bit_is_set() {}
+        self.adc.winut.write(|w| unsafe { w.winut().bits(upper) });
+        while self.adc.syncbusy.read().winut().bit_is_set() {}
+        self.adc.ctrlb.modify(|_, w| w.winmode().variant(winmode));
+        while self.adc.syncbusy.read().ctrlb().bit_is_set() {}
+        self.adc.intflag.write(|w| w.winmon().set_bit());
+    }
+
+    /// Disable the window monitor
+    pub fn disable_window_monitor(&mut self) {
+        self.adc.ctrlb.modify(|_, w| w.winmode().disable());
+        while self.adc.syncbusy.read().ctrlb().bit_is_set() {}
+    }
+
+    /// Enable the interrupt raised when the window condition is met
+    pub fn enable_window_interrupt(&mut self) {
+        self.adc.intenset.write(|w| w.winmon().set_bit());
+    }
+
+    /// Disable the window monitor interrupt
+    pub fn disable_window_interrupt(&mut self) {
+        self.adc.intenclr.write(|w| w.winmon().set_bit());
+    }
+
+    /// Enable or disable the event generated when the window condition is
+    /// met. The event control register is enable-protected, so this must not
+    /// be called while the ADC is running.
+    pub fn window_event(&mut self, enabled: bool) {
+        self.adc.evctrl.modify(|_, w| w.winmoneo().bit(enabled));
+    }
+
+    /// Returns `true` if the window condition was met since the last call,
+    /// and clears the flag
+    pub fn window_triggered(&mut self) -> bool {
+        let triggered = self.adc.intflag.read().winmon().bit_is_set();
+        if triggered {
+            self.adc.intflag.write(|w| w.winmon().set_bit());
+        }
+        triggered
+    }
+
+    /// Convert `pin` continuously in the background, for use with the window
+    /// monitor. No interrupt is raised on completed conversions, so that the
+    /// CPU can sleep until the window interrupt or event fires.
+    pub fn start_monitoring<PIN: Channel<$ADC, ID = u8>>(&mut self, pin: &mut PIN) {
+        self.mux(pin);
+        self.enable_freerunning();
+        self.power_up();
+        self.start_conversion();
+    }
+
+    /// Stop the conversions started by
+    /// [`start_monitoring`](Self::start_monitoring)
+    pub fn stop_monitoring(&mut self) {
+        self.power_down();
+        self.disable_freerunning();
+    }
+
+    /// Latest conversion result
+    pub fn last_result(&self) -> u16 {
+        self.adc.result.read().result().bits()
+    }
+
+    fn convert_channel(&mut self, chan: u8) -> u16 {
+        while self.adc.syncbusy.read().inputctrl().bit_is_set() {}
+        self.adc.inputctrl.modify(|_, w| w.muxpos().bits(chan));
+        self.power_up();
+        let result = self.synchronous_convert();
+        self.power_down();
+        result
+    }
+
     fn power_up(&mut self) {
         while self.adc.syncbusy.read().enable().bit_is_set() {}
         self.adc.ctrla.modify(|_, w| w.enable().set_bit());
@@ -124,7 +453,7 @@ impl Adc<$ADC> {
     }
 
     fn disable_freerunning(&mut self) {
-        self.adc.ctrlb.modify(|_, w| w.freerun().set_bit());
+        self.adc.ctrlb.modify(|_, w| w.freerun().clear_bit());
         while self.adc.syncbusy.read().ctrlb().bit_is_set() {}
     }
 
@@ -309,3 +638,302 @@ adc_pins! {
     Pd0:  (ADC1, 14),
     Pd1:  (ADC1, 15),
 }
+
+macro_rules! adc_internal {
+    ($($input:ident: $chan:expr,)+) => {
+        $(
+impl Channel<ADC0> for $input {
+   type ID = u8;
+   fn channel() -> u8 { $chan }
+}
+
+impl Channel<ADC1> for $input {
+   type ID = u8;
+   fn channel() -> u8 { $chan }
+}
+        )+
+    }
+}
+
+adc_internal! {
+    ScaledVddCore: 0x18,
+    ScaledVbat: 0x19,
+    ScaledVddio: 0x1a,
+    Bandgap: 0x1b,
+    Ptat: 0x1c,
+    Ctat: 0x1d,
+    Dac: 0x1e,
+}
+
+macro_rules! adc_negative_pins {
+    ($($pin:ident: $ADC:ident,)+) => {
+        $(
+impl NegativeChannel<$ADC> for $pin<PfB> {}
+        )+
+    }
+}
+
+adc_negative_pins! {
+    Pa2: ADC0,
+    Pa3: ADC0,
+    Pb8: ADC0,
+    Pb9: ADC0,
+    Pa4: ADC0,
+    Pa5: ADC0,
+    Pa6: ADC0,
+    Pa7: ADC0,
+
+    Pb8: ADC1,
+    Pb9: ADC1,
+    Pa8: ADC1,
+    Pa9: ADC1,
+}
+
+#[cfg(feature = "min-samd51j")]
+adc_negative_pins! {
+    Pb4: ADC1,
+    Pb5: ADC1,
+}
+
+#[cfg(feature = "min-samd51n")]
+adc_negative_pins! {
+    Pc2: ADC1,
+    Pc3: ADC1,
+}
+
+/// DMA sequencing, available when the `dma` feature is enabled
+#[cfg(feature = "dma")]
+mod sequence {
+    use super::*;
+    use crate::dmac::{
+        channel::{Busy, Channel as DmaChannel, Ready},
+        BufferPair, BurstLength, DmaController, Transfer, TransferConfiguration, TriggerAction,
+        TriggerSource,
+    };
+    use core::marker::PhantomData;
+
+    /// Mask of the MUXPOS field of INPUTCTRL
+    const MUXPOS_MASK: u32 = 0x1f;
+
+    /// How the conversions of a sequence are started
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub enum SequenceTrigger {
+        /// Every entry is converted as soon as the DMA loads it, so the whole
+        /// sequence is converted back-to-back
+        Auto,
+        /// Every ADC start event converts the current entry, after which the
+        /// DMA loads the next one. Use a timer as the event generator to
+        /// sample the channels at a fixed rate.
+        Event,
+    }
+
+    type SequenceTransfer<const S: u8> =
+        Transfer<BufferPair<u32, &'static mut [u32], &'static mut u32>, (), Busy, S>;
+    type ResultTransfer<ADC, const R: u8> =
+        Transfer<BufferPair<u16, &'static mut u16, &'static mut [u16]>, Adc<ADC>, Busy, R>;
+
+    /// DMA sequence started by [`Adc::sequence`]
+    pub struct AdcSequence<ADC, const S: u8, const R: u8> {
+        sequence: SequenceTransfer<S>,
+        results: ResultTransfer<ADC, R>,
+    }
+
+    macro_rules! adc_sequence {
+        ($($ADC:ident: ($seq:ident, $resrdy:ident),)+) => {
+            $(
+    impl Adc<$ADC> {
+        /// Sequence entry converting `pin`, to be stored in the buffer passed
+        /// to [`sequence`](Self::sequence). The other input settings are
+        /// taken from the current configuration.
+        pub fn sequence_entry<PIN: Channel<$ADC, ID = u8>>(&self, _pin: &PIN) -> u32 {
+            let inputctrl = self.adc.inputctrl.read().bits() as u32;
+            (inputctrl & !MUXPOS_MASK) | PIN::channel() as u32
+        }
+
+        /// Convert a list of channels using DMA sequencing.
+        ///
+        /// Channel `S` loads the entries of `sequence`, built with
+        /// [`sequence_entry`](Self::sequence_entry), into the input control
+        /// register, and channel `R` stores one result per entry into
+        /// `results`. If `circular` is `true`, both transfers restart when
+        /// they reach the end of their buffer, so the ADC keeps converting
+        /// the sequence until [`AdcSequence::stop`] is called.
+        ///
+        /// # Panics
+        ///
+        /// Panics if `sequence` is empty, or if `sequence` and `results` have
+        /// different lengths.
+        #[allow(clippy::too_many_arguments)]
+        pub fn sequence<const S: u8, const R: u8>(
+            mut self,
+            dmac: &mut DmaController,
+            trigger: SequenceTrigger,
+            circular: bool,
+            sequence: &'static mut [u32],
+            results: &'static mut [u16],
+            mut seq_chan: DmaChannel<Ready, S>,
+            mut res_chan: DmaChannel<Ready, R>,
+        ) -> AdcSequence<$ADC, S, R> {
+            assert!(!sequence.is_empty() && sequence.len() == results.len());
+
+            // DSEQCTRL and EVCTRL are enable-protected
+            self.power_down();
+            self.adc.dseqctrl.write(|w| {
+                w.inputctrl().set_bit();
+                w.autostart().bit(trigger == SequenceTrigger::Auto)
+            });
+            self.adc
+                .evctrl
+                .modify(|_, w| w.startei().bit(trigger == SequenceTrigger::Event));
+            self.adc.intflag.write(|w| w.resrdy().set_bit());
+            self.power_up();
+
+            // SAFETY: DSEQDATA and RESULT are registers which stay valid for
+            // the lifetime of the program. The ADC is moved into the
+            // transfer, so no other code accesses it during the sequence.
+            let dseqdata = unsafe { &mut *self.adc.dseqdata.as_ptr() };
+            let result = unsafe { &mut *self.adc.result.as_ptr() };
+
+            // Every trigger moves a single register
+            seq_chan.burst_length(dmac, BurstLength::SINGLE);
+            res_chan.burst_length(dmac, BurstLength::SINGLE);
+
+            let results = BufferPair {
+                source: result,
+                destination: results,
+                _b: PhantomData,
+            }
+            .setup_xfer(res_chan, circular, self)
+            .begin(dmac, TriggerSource::$resrdy, TriggerAction::BURST);
+
+            let sequence = BufferPair {
+                source: sequence,
+                destination: dseqdata,
+                _b: PhantomData,
+            }
+            .setup_xfer(seq_chan, circular, ())
+            .begin(dmac, TriggerSource::$seq, TriggerAction::BURST);
+
+            AdcSequence { sequence, results }
+        }
+
+        /// Restore single conversions after a sequence
+        fn end_sequence(&mut self) {
+            self.power_down();
+            self.adc.dseqctrl.reset();
+            self.adc.evctrl.modify(|_, w| w.startei().clear_bit());
+        }
+    }
+
+    impl<const S: u8, const R: u8> AdcSequence<$ADC, S, R> {
+        /// Block until the sequence has been converted, and release the
+        /// resources. Never returns if the sequence is circular.
+        ///
+        /// Returns the ADC, the sequence and results buffers, and the
+        /// sequence and results channels.
+        #[allow(clippy::type_complexity)]
+        pub fn wait(
+            self,
+            dmac: &mut DmaController,
+        ) -> (
+            Adc<$ADC>,
+            &'static mut [u32],
+            &'static mut [u16],
+            DmaChannel<Ready, S>,
+            DmaChannel<Ready, R>,
+        ) {
+            let (res, res_chan, mut adc) =
+                self.results.wait::<u16, &mut u16, &mut [u16]>(dmac);
+            let (seq, seq_chan, _) = self.sequence.wait::<u32, &mut [u32], &mut u32>(dmac);
+            adc.end_sequence();
+            (adc, seq.source, res.destination, seq_chan, res_chan)
+        }
+
+        /// Stop the sequence immediately, and release the resources.
+        ///
+        /// Returns the ADC, the sequence and results buffers, and the
+        /// sequence and results channels.
+        #[allow(clippy::type_complexity)]
+        pub fn stop(
+            self,
+            dmac: &mut DmaController,
+        ) -> (
+            Adc<$ADC>,
+            &'static mut [u32],
+            &'static mut [u16],
+            DmaChannel<Ready, S>,
+            DmaChannel<Ready, R>,
+        ) {
+            let (seq, seq_chan, _) = self.sequence.stop::<u32, &mut [u32], &mut u32>(dmac);
+            let (res, res_chan, mut adc) =
+                self.results.stop::<u16, &mut u16, &mut [u16]>(dmac);
+            adc.end_sequence();
+            (adc, seq.source, res.destination, seq_chan, res_chan)
+        }
+    }
+            )+
+        }
+    }
+
+    adc_sequence! {
+        ADC0: (ADC0_SEQ, ADC0_RESRDY),
+        ADC1: (ADC1_SEQ, ADC1_RESRDY),
+    }
+}
+
+#[cfg(feature = "dma")]
+pub use sequence::{AdcSequence, SequenceTrigger};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn result_config() {
+        let mut config = AdcConfig::default();
+        assert_eq!(config.result_config(), (RESSEL_A::_12BIT, 0, 0));
+        config.averaging = Averaging::_4;
+        assert_eq!(config.result_config(), (RESSEL_A::_16BIT, 2, 2));
+        config.averaging = Averaging::_1024;
+        assert_eq!(config.result_config(), (RESSEL_A::_16BIT, 10, 4));
+        config.resolution = Resolution::_8Bit;
+        assert_eq!(config.result_config(), (RESSEL_A::_8BIT, 0, 0));
+        config.resolution = Resolution::Oversampled16Bit;
+        assert_eq!(config.result_config(), (RESSEL_A::_16BIT, 8, 0));
+    }
+
+    #[test]
+    fn window_registers() {
+        assert_eq!(
+            WindowMode::Above(100).registers(),
+            (WINMODE_A::MODE1, 100, 0)
+        );
+        assert_eq!(
+            WindowMode::Outside {
+                lower: 10,
+                upper: 20
+            }
+            .registers(),
+            (WINMODE_A::MODE4, 10, 20)
+        );
+    }
+
+    #[test]
+    fn temperature_calibration() {
+        // 25.5 °C and 85.0 °C, PTAT 1000 and 1200, CTAT 2000 and 1800
+        let row = [
+            25 | 5 << 8 | 85 << 12,
+            1000 << 8 | 1200 << 20,
+            2000 | 1800 << 12,
+        ];
+        let calibration = TemperatureCalibration::from_log_row(row);
+        assert_eq!(calibration.tl, 25.5);
+        assert_eq!(calibration.th, 85.0);
+
+        let close = |a: f32, b: f32| (a - b).abs() < 0.01;
+        assert!(close(calibration.temperature(1000, 2000), 25.5));
+        assert!(close(calibration.temperature(1200, 1800), 85.0));
+        // The result only depends on the ratio between the readings
+        assert!(close(calibration.temperature(500, 1000), 25.5));
+    }
+}
@@ -0,0 +1,870 @@
+//! # Advanced Encryption Standard (AES) engine
+//!
+//! The AES peripheral encrypts and decrypts 128-bit blocks with 128, 192 or
+//! 256-bit keys. This driver supports the following modes of operation:
+//!
+//! * ECB, CBC, CFB (128-bit segments) and OFB, using the hardware modes;
+//! * CTR, using hardware ECB encryption of a 128-bit big-endian counter;
+//! * GCM with 96-bit nonces, using hardware ECB encryption for the keystream
+//!   and the hardware GF(2^128) multiplier for GHASH.
+//!
+//! The chained modes take the initialization vector (or counter) by mutable
+//! reference and update it in place, so a long message can be processed in
+//! several calls as long as every call but the last one handles a whole
+//! number of blocks.
+//!
+//! # RustCrypto traits
+//!
+//! When the `crypto` feature is enabled, [`Aes`] implements the
+//! [`BlockCipher`](cipher::BlockCipher), [`BlockEncrypt`](cipher::BlockEncrypt)
+//! and [`BlockDecrypt`](cipher::BlockDecrypt) traits, so that it can be used
+//! with the software block modes of the RustCrypto project. [`Gcm`]
+//! implements [`AeadInPlace`](aead::AeadInPlace).
+//!
+//! # DMA
+//!
+//! When the `dma` feature is enabled, [`Aes::stream`] feeds the peripheral
+//! from one DMA channel and drains it with another, so that large buffers can
+//! be processed without CPU intervention.
+//!
+//! # Self test
+//!
+//! [`Aes::self_test`] runs known-answer tests from FIPS-197 and NIST
+//! SP 800-38A/D, which is a cheap way to check the engine at boot.
+
+use crate::target_device::aes::ctrla::{AESMODE_A, CIPHER_A, KEYSIZE_A};
+use crate::target_device::{AES, MCLK};
+use core::cell::Cell;
+
+/// AES block size, in bytes
+pub const BLOCK_SIZE: usize = 16;
+
+/// A single AES block
+pub type Block = [u8; BLOCK_SIZE];
+
+/// Size of a GCM nonce, in bytes
+pub const GCM_NONCE_SIZE: usize = 12;
+
+/// Size of a GCM authentication tag, in bytes
+pub const GCM_TAG_SIZE: usize = 16;
+
+/// Errors related to the AES engine
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// The buffer length is not a multiple of the block size
+    InvalidLength,
+    /// The GCM authentication tag does not match
+    AuthenticationFailed,
+    /// A known-answer test failed
+    SelfTestFailed,
+}
+
+/// AES key
+#[derive(Clone)]
+pub enum Key {
+    Aes128([u8; 16]),
+    Aes192([u8; 24]),
+    Aes256([u8; 32]),
+}
+
+impl Key {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Key::Aes128(k) => k,
+            Key::Aes192(k) => k,
+            Key::Aes256(k) => k,
+        }
+    }
+
+    fn size(&self) -> KEYSIZE_A {
+        match self {
+            Key::Aes128(_) => KEYSIZE_A::_128BIT,
+            Key::Aes192(_) => KEYSIZE_A::_192BIT,
+            Key::Aes256(_) => KEYSIZE_A::_256BIT,
+        }
+    }
+}
+
+/// AES engine
+pub struct Aes {
+    aes: AES,
+    key: Key,
+    /// Mode and direction currently programmed in CTRLA. `None` forces the
+    /// next operation to reconfigure the peripheral.
+    config: Cell<Option<(AESMODE_A, CIPHER_A)>>,
+}
+
+impl Aes {
+    /// Enable the AES peripheral clock and load `key`
+    pub fn new(mclk: &mut MCLK, aes: AES, key: Key) -> Self {
+        mclk.apbcmask.modify(|_, w| w.aes_().set_bit());
+        aes.ctrla.write(|w| w.swrst().set_bit());
+        while aes.ctrla.read().swrst().bit_is_set() {}
+
+        Self {
+            aes,
+            key,
+            config: Cell::new(None),
+        }
+    }
+
+    /// Disable the peripheral and release it
+    pub fn free(self, mclk: &mut MCLK) -> AES {
+        self.aes.ctrla.write(|w| w.swrst().set_bit());
+        mclk.apbcmask.modify(|_, w| w.aes_().clear_bit());
+        self.aes
+    }
+
+    /// Replace the key used by subsequent operations
+    pub fn set_key(&mut self, key: Key) {
+        self.key = key;
+        self.config.set(None);
+    }
+
+    /// Program CTRLA and the key, unless the peripheral is already set up for
+    /// `mode` and `cipher`
+    fn configure(&self, mode: AESMODE_A, cipher: CIPHER_A) {
+        if self.config.get() == Some((mode, cipher)) {
+            return;
+        }
+
+        // CTRLA is enable-protected
+        self.aes.ctrla.write(|w| w.enable().clear_bit());
+        self.aes.ctrla.write(|w| {
+            w.aesmode().variant(mode);
+            w.cipher().variant(cipher);
+            w.keysize().variant(self.key.size());
+            w.cfbs()._128bit();
+            w.startmode().manual()
+        });
+        self.aes.ctrla.modify(|_, w| w.enable().set_bit());
+
+        for (reg, word) in self.aes.keyword.iter().zip(words(self.key.as_bytes())) {
+            reg.write(|w| unsafe { w.bits(word) });
+        }
+
+        self.config.set(Some((mode, cipher)));
+    }
+
+    fn write_iv(&self, iv: &Block) {
+        for (reg, word) in self.aes.intvectv.iter().zip(words(iv)) {
+            reg.write(|w| unsafe { w.bits(word) });
+        }
+    }
+
+    fn write_indata(&self, block: &[u8]) {
+        self.aes
+            .databufptr
+            .write(|w| unsafe { w.indataptr().bits(0) });
+        for word in words(block) {
+            self.aes.indata.write(|w| unsafe { w.bits(word) });
+        }
+    }
+
+    /// Run the currently configured mode on a single block, in place
+    fn process_block(&self, block: &mut [u8], new_message: bool) {
+        self.write_indata(block);
+        self.aes.ctrlb.write(|w| {
+            if new_message {
+                w.newmsg().set_bit();
+            }
+            w.start().set_bit()
+        });
+        while self.aes.intflag.read().enccmp().bit_is_clear() {}
+
+        self.aes
+            .databufptr
+            .write(|w| unsafe { w.indataptr().bits(0) });
+        for chunk in block.chunks_mut(4) {
+            chunk.copy_from_slice(&self.aes.indata.read().bits().to_le_bytes());
+        }
+        self.aes.intflag.write(|w| w.enccmp().set_bit());
+    }
+
+    /// Run a hardware chaining mode over `buf`. A trailing partial block is
+    /// zero-padded and truncated.
+    fn process_chained(&self, mode: AESMODE_A, cipher: CIPHER_A, iv: &Block, buf: &mut [u8]) {
+        self.configure(mode, cipher);
+        self.write_iv(iv);
+        for (i, chunk) in buf.chunks_mut(BLOCK_SIZE).enumerate() {
+            if chunk.len() == BLOCK_SIZE {
+                self.process_block(chunk, i == 0);
+            } else {
+                let mut block = [0; BLOCK_SIZE];
+                block[..chunk.len()].copy_from_slice(chunk);
+                self.process_block(&mut block, i == 0);
+                chunk.copy_from_slice(&block[..chunk.len()]);
+            }
+        }
+    }
+
+    /// Encrypt a single block with the raw cipher
+    fn encrypt_raw(&self, block: &mut [u8]) {
+        self.configure(AESMODE_A::ECB, CIPHER_A::ENC);
+        self.process_block(block, false);
+    }
+
+    /// Decrypt a single block with the raw cipher
+    fn decrypt_raw(&self, block: &mut [u8]) {
+        self.configure(AESMODE_A::ECB, CIPHER_A::DEC);
+        self.process_block(block, false);
+    }
+
+    /// Encrypt `buf` in place in ECB mode. `buf` must hold a whole number of
+    /// blocks.
+    pub fn encrypt_ecb(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        check_blocks(buf)?;
+        buf.chunks_mut(BLOCK_SIZE).for_each(|b| self.encrypt_raw(b));
+        Ok(())
+    }
+
+    /// Decrypt `buf` in place in ECB mode. `buf` must hold a whole number of
+    /// blocks.
+    pub fn decrypt_ecb(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        check_blocks(buf)?;
+        buf.chunks_mut(BLOCK_SIZE).for_each(|b| self.decrypt_raw(b));
+        Ok(())
+    }
+
+    /// Encrypt `buf` in place in CBC mode. `buf` must hold a whole number of
+    /// blocks. `iv` is updated to continue the chain.
+    pub fn encrypt_cbc(&mut self, iv: &mut Block, buf: &mut [u8]) -> Result<(), Error> {
+        check_blocks(buf)?;
+        if !buf.is_empty() {
+            self.process_chained(AESMODE_A::CBC, CIPHER_A::ENC, iv, buf);
+            iv.copy_from_slice(&buf[buf.len() - BLOCK_SIZE..]);
+        }
+        Ok(())
+    }
+
+    /// Decrypt `buf` in place in CBC mode. `buf` must hold a whole number of
+    /// blocks. `iv` is updated to continue the chain.
+    pub fn decrypt_cbc(&mut self, iv: &mut Block, buf: &mut [u8]) -> Result<(), Error> {
+        check_blocks(buf)?;
+        if let Some(next_iv) = last_block(buf) {
+            self.process_chained(AESMODE_A::CBC, CIPHER_A::DEC, iv, buf);
+            *iv = next_iv;
+        }
+        Ok(())
+    }
+
+    /// Encrypt `buf` in place in CFB-128 mode. `iv` is updated to continue
+    /// the chain, which is only meaningful if `buf` holds a whole number of
+    /// blocks.
+    pub fn encrypt_cfb(&mut self, iv: &mut Block, buf: &mut [u8]) {
+        if buf.is_empty() {
+            return;
+        }
+        self.process_chained(AESMODE_A::CFB, CIPHER_A::ENC, iv, buf);
+        if let Some(next_iv) = last_block(buf) {
+            *iv = next_iv;
+        }
+    }
+
+    /// Decrypt `buf` in place in CFB-128 mode. `iv` is updated to continue
+    /// the chain, which is only meaningful if `buf` holds a whole number of
+    /// blocks.
+    pub fn decrypt_cfb(&mut self, iv: &mut Block, buf: &mut [u8]) {
+        if buf.is_empty() {
+            return;
+        }
+        let next_iv = last_block(buf);
+        self.process_chained(AESMODE_A::CFB, CIPHER_A::DEC, iv, buf);
+        if let Some(next_iv) = next_iv {
+            *iv = next_iv;
+        }
+    }
+
+    /// Encrypt or decrypt `buf` in place in OFB mode. `iv` is updated to
+    /// continue the keystream, which is only meaningful if `buf` holds a whole
+    /// number of blocks.
+    pub fn apply_ofb(&mut self, iv: &mut Block, buf: &mut [u8]) {
+        if buf.is_empty() {
+            return;
+        }
+        let input = last_block(buf);
+        self.process_chained(AESMODE_A::OFB, CIPHER_A::ENC, iv, buf);
+        // The next IV is the last keystream block
+        if let (Some(input), Some(output)) = (input, last_block(buf)) {
+            for (v, (i, o)) in iv.iter_mut().zip(input.iter().zip(output.iter())) {
+                *v = i ^ o;
+            }
+        }
+    }
+
+    /// Encrypt or decrypt `buf` in place in CTR mode. `counter` is the
+    /// initial 128-bit big-endian counter block; it is incremented for every
+    /// block processed.
+    pub fn apply_ctr(&mut self, counter: &mut Block, buf: &mut [u8]) {
+        self.keystream(counter, buf, inc128);
+    }
+
+    /// XOR `buf` with the encryption of successive counter blocks
+    fn keystream(&self, counter: &mut Block, buf: &mut [u8], inc: fn(&mut Block)) {
+        for chunk in buf.chunks_mut(BLOCK_SIZE) {
+            let mut ks = *counter;
+            self.encrypt_raw(&mut ks);
+            chunk.iter_mut().zip(ks.iter()).for_each(|(b, k)| *b ^= k);
+            inc(counter);
+        }
+    }
+
+    /// Returns a GCM view of the engine, using the current key
+    pub fn gcm(&self) -> Gcm<'_> {
+        Gcm { aes: self }
+    }
+
+    /// Run known-answer tests for every key size and mode supported by the
+    /// driver. The current key is restored afterwards.
+    pub fn self_test(&mut self) -> Result<(), Error> {
+        let key = self.key.clone();
+        let result = test_vectors::run(self);
+        self.set_key(key);
+        result
+    }
+}
+
+/// These methods are available when the `dma` feature is enabled
+#[cfg(all(feature = "unproven", feature = "dma"))]
+mod stream {
+    use super::*;
+    use crate::dmac::{
+        channel::{Busy, Channel, Ready},
+        BufferPair, BurstLength, DmaController, Transfer, TransferConfiguration, TriggerAction,
+        TriggerSource,
+    };
+    use core::marker::PhantomData;
+
+    /// Mode of operation of a DMA stream
+    #[derive(Clone, Copy)]
+    pub enum StreamMode {
+        EcbEncrypt,
+        EcbDecrypt,
+        CbcEncrypt(Block),
+        CbcDecrypt(Block),
+        CfbEncrypt(Block),
+        CfbDecrypt(Block),
+        Ofb(Block),
+    }
+
+    type WriteTransfer<const W: u8> =
+        Transfer<BufferPair<u32, &'static mut [u32], &'static mut u32>, (), Busy, W>;
+    type ReadTransfer<const R: u8> =
+        Transfer<BufferPair<u32, &'static mut u32, &'static mut [u32]>, Aes, Busy, R>;
+
+    /// DMA stream started by [`Aes::stream`]
+    pub struct AesTransfer<const W: u8, const R: u8> {
+        write: WriteTransfer<W>,
+        read: ReadTransfer<R>,
+    }
+
+    impl Aes {
+        /// Process `src` into `dst` using two DMA channels: channel `W` feeds
+        /// the input data register and channel `R` drains the output data
+        /// register. Both buffers hold the data as little-endian 32-bit words.
+        ///
+        /// # Panics
+        ///
+        /// Panics if `src` and `dst` have different lengths, or if their
+        /// length is not a whole number of blocks.
+        pub fn stream<const W: u8, const R: u8>(
+            self,
+            dmac: &mut DmaController,
+            mode: StreamMode,
+            src: &'static mut [u32],
+            dst: &'static mut [u32],
+            mut write_chan: Channel<Ready, W>,
+            mut read_chan: Channel<Ready, R>,
+        ) -> AesTransfer<W, R> {
+            assert!(src.len() == dst.len() && src.len() & (BLOCK_SIZE / 4 - 1) == 0);
+
+            let (mode, cipher, iv) = match mode {
+                StreamMode::EcbEncrypt => (AESMODE_A::ECB, CIPHER_A::ENC, None),
+                StreamMode::EcbDecrypt => (AESMODE_A::ECB, CIPHER_A::DEC, None),
+                StreamMode::CbcEncrypt(iv) => (AESMODE_A::CBC, CIPHER_A::ENC, Some(iv)),
+                StreamMode::CbcDecrypt(iv) => (AESMODE_A::CBC, CIPHER_A::DEC, Some(iv)),
+                StreamMode::CfbEncrypt(iv) => (AESMODE_A::CFB, CIPHER_A::ENC, Some(iv)),
+                StreamMode::CfbDecrypt(iv) => (AESMODE_A::CFB, CIPHER_A::DEC, Some(iv)),
+                StreamMode::Ofb(iv) => (AESMODE_A::OFB, CIPHER_A::ENC, Some(iv)),
+            };
+
+            self.configure(mode, cipher);
+            // A new block is started as soon as the four input words are written
+            self.aes.ctrla.modify(|_, w| w.enable().clear_bit());
+            self.aes.ctrla.modify(|_, w| w.startmode().auto());
+            self.aes.ctrla.modify(|_, w| w.enable().set_bit());
+            // Force reconfiguration once the stream is done
+            self.config.set(None);
+            for (reg, word) in self.aes.keyword.iter().zip(words(self.key.as_bytes())) {
+                reg.write(|w| unsafe { w.bits(word) });
+            }
+            if let Some(iv) = iv {
+                self.write_iv(&iv);
+            }
+            self.aes.ctrlb.write(|w| w.newmsg().set_bit());
+            self.aes
+                .databufptr
+                .write(|w| unsafe { w.indataptr().bits(0) });
+
+            // SAFETY: INDATA is a 32-bit register which stays valid for the
+            // lifetime of the program. The AES peripheral is moved into the
+            // transfer, so no other code accesses it during the stream.
+            let indata_w = unsafe { &mut *self.aes.indata.as_ptr() };
+            let indata_r = unsafe { &mut *self.aes.indata.as_ptr() };
+
+            // Every trigger moves a whole block
+            write_chan.burst_length(dmac, BurstLength::_4BEAT);
+            read_chan.burst_length(dmac, BurstLength::_4BEAT);
+
+            let read = BufferPair {
+                source: indata_r,
+                destination: dst,
+                _b: PhantomData,
+            }
+            .setup_xfer(read_chan, false, self)
+            .begin(dmac, TriggerSource::AES_RD, TriggerAction::BURST);
+
+            let write = BufferPair {
+                source: src,
+                destination: indata_w,
+                _b: PhantomData,
+            }
+            .setup_xfer(write_chan, false, ())
+            .begin(dmac, TriggerSource::AES_WR, TriggerAction::BURST);
+
+            AesTransfer { write, read }
+        }
+    }
+
+    impl<const W: u8, const R: u8> AesTransfer<W, R> {
+        /// Block until the stream is complete, and release the resources.
+        ///
+        /// Returns the AES engine, the source and destination buffers, and
+        /// the write and read channels.
+        #[allow(clippy::type_complexity)]
+        pub fn wait(
+            self,
+            dmac: &mut DmaController,
+        ) -> (
+            Aes,
+            &'static mut [u32],
+            &'static mut [u32],
+            Channel<Ready, W>,
+            Channel<Ready, R>,
+        ) {
+            let (src, write_chan, _) = self.write.wait::<u32, &mut [u32], &mut u32>(dmac);
+            let (dst, read_chan, aes) = self.read.wait::<u32, &mut u32, &mut [u32]>(dmac);
+            (aes, src.source, dst.destination, write_chan, read_chan)
+        }
+    }
+}
+
+#[cfg(all(feature = "unproven", feature = "dma"))]
+pub use stream::{AesTransfer, StreamMode};
+
+/// Galois/Counter Mode view of the AES engine, returned by [`Aes::gcm`].
+///
+/// Only 96-bit nonces and full 128-bit tags are supported.
+pub struct Gcm<'a> {
+    aes: &'a Aes,
+}
+
+impl<'a> Gcm<'a> {
+    /// Compute GHASH over `aad` and `data` with the hardware multiplier
+    fn ghash(&self, h: &Block, aad: &[u8], data: &[u8]) -> Block {
+        let aes = &self.aes.aes;
+        self.aes.configure(AESMODE_A::GCM, CIPHER_A::ENC);
+
+        for (reg, word) in aes.hashkey.iter().zip(words(h)) {
+            reg.write(|w| unsafe { w.bits(word) });
+        }
+        for reg in aes.ghash.iter() {
+            reg.write(|w| unsafe { w.bits(0) });
+        }
+
+        let lengths = gcm_lengths(aad.len(), data.len());
+        let blocks = aad
+            .chunks(BLOCK_SIZE)
+            .chain(data.chunks(BLOCK_SIZE))
+            .chain(core::iter::once(&lengths[..]));
+        for chunk in blocks {
+            let mut block = [0; BLOCK_SIZE];
+            block[..chunk.len()].copy_from_slice(chunk);
+            // GHASH = (GHASH ^ INDATA) * HASHKEY
+            self.aes.write_indata(&block);
+            aes.ctrlb.write(|w| w.gfmul().set_bit());
+            while aes.intflag.read().gfmcmp().bit_is_clear() {}
+            aes.intflag.write(|w| w.gfmcmp().set_bit());
+        }
+
+        let mut s = [0; BLOCK_SIZE];
+        for (chunk, reg) in s.chunks_mut(4).zip(aes.ghash.iter()) {
+            chunk.copy_from_slice(&reg.read().bits().to_le_bytes());
+        }
+        s
+    }
+
+    /// Returns the hash subkey, the tag mask `E(K, J0)` and the first counter
+    /// block
+    fn setup(&self, nonce: &[u8; GCM_NONCE_SIZE]) -> (Block, Block, Block) {
+        let mut h = [0; BLOCK_SIZE];
+        self.aes.encrypt_raw(&mut h);
+        let mut counter = gcm_j0(nonce);
+        let mut mask = counter;
+        self.aes.encrypt_raw(&mut mask);
+        inc32(&mut counter);
+        (h, mask, counter)
+    }
+
+    /// Encrypt `buf` in place and return the authentication tag over `aad`
+    /// and the ciphertext
+    pub fn encrypt(
+        &self,
+        nonce: &[u8; GCM_NONCE_SIZE],
+        aad: &[u8],
+        buf: &mut [u8],
+    ) -> [u8; GCM_TAG_SIZE] {
+        let (h, mut tag, mut counter) = self.setup(nonce);
+        self.aes.keystream(&mut counter, buf, inc32);
+        let s = self.ghash(&h, aad, buf);
+        tag.iter_mut().zip(s.iter()).for_each(|(t, s)| *t ^= s);
+        tag
+    }
+
+    /// Check the authentication tag over `aad` and `buf`, then decrypt `buf`
+    /// in place. `buf` is left untouched if authentication fails.
+    pub fn decrypt(
+        &self,
+        nonce: &[u8; GCM_NONCE_SIZE],
+        aad: &[u8],
+        buf: &mut [u8],
+        tag: &[u8; GCM_TAG_SIZE],
+    ) -> Result<(), Error> {
+        let (h, mask, mut counter) = self.setup(nonce);
+        let s = self.ghash(&h, aad, buf);
+
+        // Constant-time comparison
+        let diff = s
+            .iter()
+            .zip(mask.iter())
+            .zip(tag.iter())
+            .fold(0, |acc, ((s, m), t)| acc | (s ^ m ^ t));
+        if diff != 0 {
+            return Err(Error::AuthenticationFailed);
+        }
+
+        self.aes.keystream(&mut counter, buf, inc32);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "crypto")]
+mod crypto_traits {
+    use super::*;
+    use aead::{consts::U0, AeadCore, AeadInPlace, Nonce, Tag};
+    use cipher::{
+        consts::{U1, U12, U16},
+        BlockCipher, BlockDecrypt, BlockEncrypt,
+    };
+    use core::convert::TryInto;
+
+    impl BlockCipher for Aes {
+        type BlockSize = U16;
+        type ParBlocks = U1;
+    }
+
+    impl BlockEncrypt for Aes {
+        fn encrypt_block(&self, block: &mut cipher::Block<Self>) {
+            self.encrypt_raw(&mut block[..]);
+        }
+    }
+
+    impl BlockDecrypt for Aes {
+        fn decrypt_block(&self, block: &mut cipher::Block<Self>) {
+            self.decrypt_raw(&mut block[..]);
+        }
+    }
+
+    impl<'a> AeadCore for Gcm<'a> {
+        type NonceSize = U12;
+        type TagSize = U16;
+        type CiphertextOverhead = U0;
+    }
+
+    impl<'a> AeadInPlace for Gcm<'a> {
+        fn encrypt_in_place_detached(
+            &self,
+            nonce: &Nonce<Self>,
+            associated_data: &[u8],
+            buffer: &mut [u8],
+        ) -> aead::Result<Tag<Self>> {
+            let nonce: &[u8; GCM_NONCE_SIZE] = nonce[..].try_into().unwrap();
+            let tag = self.encrypt(nonce, associated_data, buffer);
+            Ok(tag.into())
+        }
+
+        fn decrypt_in_place_detached(
+            &self,
+            nonce: &Nonce<Self>,
+            associated_data: &[u8],
+            buffer: &mut [u8],
+            tag: &Tag<Self>,
+        ) -> aead::Result<()> {
+            let nonce: &[u8; GCM_NONCE_SIZE] = nonce[..].try_into().unwrap();
+            let tag: &[u8; GCM_TAG_SIZE] = tag[..].try_into().unwrap();
+            self.decrypt(nonce, associated_data, buffer, tag)
+                .map_err(|_| aead::Error)
+        }
+    }
+}
+
+/// Split a byte slice into little-endian 32-bit words, as expected by the
+/// key, data and vector registers
+fn words(bytes: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    bytes
+        .chunks(4)
+        .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+}
+
+fn check_blocks(buf: &[u8]) -> Result<(), Error> {
+    if buf.len() & (BLOCK_SIZE - 1) == 0 {
+        Ok(())
+    } else {
+        Err(Error::InvalidLength)
+    }
+}
+
+/// Copy of the last whole block of `buf`, if any
+fn last_block(buf: &[u8]) -> Option<Block> {
+    let mut block = [0; BLOCK_SIZE];
+    if buf.len() < BLOCK_SIZE {
+        return None;
+    }
+    let start = (buf.len() - 1) / BLOCK_SIZE * BLOCK_SIZE;
+    if buf.len() - start != BLOCK_SIZE {
+        return None;
+    }
+    block.copy_from_slice(&buf[start..]);
+    Some(block)
+}
+
+/// Increment a 128-bit big-endian counter
+fn inc128(counter: &mut Block) {
+    let value = u128::from_be_bytes(*counter).wrapping_add(1);
+    *counter = value.to_be_bytes();
+}
+
+/// Increment the rightmost 32 bits of a counter block, as specified for GCM
+fn inc32(counter: &mut Block) {
+    let mut low = [0; 4];
+    low.copy_from_slice(&counter[12..]);
+    let value = u32::from_be_bytes(low).wrapping_add(1);
+    counter[12..].copy_from_slice(&value.to_be_bytes());
+}
+
+/// Pre-counter block for a 96-bit nonce
+fn gcm_j0(nonce: &[u8; GCM_NONCE_SIZE]) -> Block {
+    let mut j0 = [0; BLOCK_SIZE];
+    j0[..GCM_NONCE_SIZE].copy_from_slice(nonce);
+    j0[BLOCK_SIZE - 1] = 1;
+    j0
+}
+
+/// Final GHASH block holding the bit lengths of the AAD and ciphertext
+fn gcm_lengths(aad_len: usize, data_len: usize) -> Block {
+    let mut block = [0; BLOCK_SIZE];
+    block[..8].copy_from_slice(&((aad_len as u64) * 8).to_be_bytes());
+    block[8..].copy_from_slice(&((data_len as u64) * 8).to_be_bytes());
+    block
+}
+
+/// Known-answer tests
+mod test_vectors {
+    use super::*;
+
+    // FIPS-197 appendix C
+    const PLAINTEXT: Block = hex16(0x00112233445566778899aabbccddeeff);
+    const KEY_128: [u8; 16] = hex16(0x000102030405060708090a0b0c0d0e0f);
+    const CIPHERTEXT_128: Block = hex16(0x69c4e0d86a7b0430d8cdb78070b4c55a);
+    const CIPHERTEXT_192: Block = hex16(0xdda97ca4864cdfe06eaf70a0ec0d7191);
+    const CIPHERTEXT_256: Block = hex16(0x8ea2b7ca516745bfeafc49904b496089);
+
+    // NIST SP 800-38A appendix F, first block of each mode
+    const SP_KEY: [u8; 16] = hex16(0x2b7e151628aed2a6abf7158809cf4f3c);
+    const SP_IV: Block = hex16(0x000102030405060708090a0b0c0d0e0f);
+    const SP_CTR: Block = hex16(0xf0f1f2f3f4f5f6f7f8f9fafbfcfdfeff);
+    const SP_PLAINTEXT: Block = hex16(0x6bc1bee22e409f96e93d7e117393172a);
+    const SP_CBC: Block = hex16(0x7649abac8119b246cee98e9b12e9197d);
+    const SP_CFB: Block = hex16(0x3b3fd92eb72dad20333449f8e83cfb4a);
+    const SP_OFB: Block = hex16(0x3b3fd92eb72dad20333449f8e83cfb4a);
+    const SP_CTR_OUT: Block = hex16(0x874d6191b620e3261bef6864990db6ce);
+
+    // GCM specification, test case 4
+    const GCM_KEY: [u8; 16] = hex16(0xfeffe9928665731c6d6a8f9467308308);
+    const GCM_NONCE: [u8; 12] = [
+        0xca, 0xfe, 0xba, 0xbe, 0xfa, 0xce, 0xdb, 0xad, 0xde, 0xca, 0xf8, 0x88,
+    ];
+    const GCM_AAD: [u8; 20] = [
+        0xfe, 0xed, 0xfa, 0xce, 0xde, 0xad, 0xbe, 0xef, 0xfe, 0xed, 0xfa, 0xce, 0xde, 0xad, 0xbe,
+        0xef, 0xab, 0xad, 0xda, 0xd2,
+    ];
+    const GCM_PLAINTEXT: [[u8; 16]; 4] = [
+        hex16(0xd9313225f88406e5a55909c5aff5269a),
+        hex16(0x86a7a9531534f7da2e4c303d8a318a72),
+        hex16(0x1c3c0c95956809532fcf0e2449a6b525),
+        hex16(0xb16aedf5aa0de657ba637b3900000000),
+    ];
+    const GCM_CIPHERTEXT: [[u8; 16]; 4] = [
+        hex16(0x42831ec2217774244b7221b784d0d49c),
+        hex16(0xe3aa212f2c02a4e035c17e2329aca12e),
+        hex16(0x21d514b25466931c7d8f6a5aac84aa05),
+        hex16(0x1ba30b396a0aac973d58e09100000000),
+    ];
+    const GCM_LEN: usize = 60;
+    const GCM_TAG: Block = hex16(0x5bc94fbc3221a5db94fae95ae7121a47);
+
+    const fn hex16(value: u128) -> [u8; 16] {
+        value.to_be_bytes()
+    }
+
+    fn expect(ok: bool) -> Result<(), Error> {
+        if ok {
+            Ok(())
+        } else {
+            Err(Error::SelfTestFailed)
+        }
+    }
+
+    fn flatten(blocks: &[Block; 4], buf: &mut [u8; 64]) {
+        for (chunk, block) in buf.chunks_mut(BLOCK_SIZE).zip(blocks.iter()) {
+            chunk.copy_from_slice(block);
+        }
+    }
+
+    fn key_192() -> [u8; 24] {
+        let mut key = [0; 24];
+        key.iter_mut().enumerate().for_each(|(i, k)| *k = i as u8);
+        key
+    }
+
+    fn key_256() -> [u8; 32] {
+        let mut key = [0; 32];
+        key.iter_mut().enumerate().for_each(|(i, k)| *k = i as u8);
+        key
+    }
+
+    pub(super) fn run(aes: &mut Aes) -> Result<(), Error> {
+        let ecb: [(Key, Block); 3] = [
+            (Key::Aes128(KEY_128), CIPHERTEXT_128),
+            (Key::Aes192(key_192()), CIPHERTEXT_192),
+            (Key::Aes256(key_256()), CIPHERTEXT_256),
+        ];
+        for (key, expected) in ecb.iter() {
+            aes.set_key(key.clone());
+            let mut block = PLAINTEXT;
+            aes.encrypt_ecb(&mut block)?;
+            expect(block == *expected)?;
+            aes.decrypt_ecb(&mut block)?;
+            expect(block == PLAINTEXT)?;
+        }
+
+        aes.set_key(Key::Aes128(SP_KEY));
+        let mut iv = SP_IV;
+        let mut block = SP_PLAINTEXT;
+        aes.encrypt_cbc(&mut iv, &mut block)?;
+        expect(block == SP_CBC && iv == SP_CBC)?;
+        let mut iv = SP_IV;
+        aes.decrypt_cbc(&mut iv, &mut block)?;
+        expect(block == SP_PLAINTEXT)?;
+
+        let mut iv = SP_IV;
+        let mut block = SP_PLAINTEXT;
+        aes.encrypt_cfb(&mut iv, &mut block);
+        expect(block == SP_CFB)?;
+        let mut iv = SP_IV;
+        aes.decrypt_cfb(&mut iv, &mut block);
+        expect(block == SP_PLAINTEXT)?;
+
+        let mut iv = SP_IV;
+        let mut block = SP_PLAINTEXT;
+        aes.apply_ofb(&mut iv, &mut block);
+        expect(block == SP_OFB)?;
+
+        let mut counter = SP_CTR;
+        let mut block = SP_PLAINTEXT;
+        aes.apply_ctr(&mut counter, &mut block);
+        expect(block == SP_CTR_OUT)?;
+
+        aes.set_key(Key::Aes128(GCM_KEY));
+        let mut buf = [0; 64];
+        flatten(&GCM_PLAINTEXT, &mut buf);
+        let tag = aes.gcm().encrypt(&GCM_NONCE, &GCM_AAD, &mut buf[..GCM_LEN]);
+        let mut expected = [0; 64];
+        flatten(&GCM_CIPHERTEXT, &mut expected);
+        expect(buf == expected && tag == GCM_TAG)?;
+        aes.gcm()
+            .decrypt(&GCM_NONCE, &GCM_AAD, &mut buf[..GCM_LEN], &tag)?;
+        flatten(&GCM_PLAINTEXT, &mut expected);
+        expect(buf == expected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::TryInto;
+
+    #[test]
+    fn words_are_little_endian() {
+        let mut w = words(&[0x01, 0x02, 0x03, 0x04, 0xaa, 0xbb, 0xcc, 0xdd]);
+        assert_eq!(w.next(), Some(0x0403_0201));
+        assert_eq!(w.next(), Some(0xddcc_bbaa));
+        assert_eq!(w.next(), None);
+    }
+
+    #[test]
+    fn ctr_increments_whole_block() {
+        let mut counter = [0xff; BLOCK_SIZE];
+        counter[0] = 0;
+        inc128(&mut counter);
+        let mut expected = [0; BLOCK_SIZE];
+        expected[0] = 1;
+        assert_eq!(counter, expected);
+    }
+
+    #[test]
+    fn gcm_increments_low_word_only() {
+        let mut counter = [0xff; BLOCK_SIZE];
+        inc32(&mut counter);
+        let mut expected = [0xff; BLOCK_SIZE];
+        expected[12..].copy_from_slice(&[0; 4]);
+        assert_eq!(counter, expected);
+    }
+
+    #[test]
+    fn gcm_pre_counter_block() {
+        let nonce = [
+            0xca, 0xfe, 0xba, 0xbe, 0xfa, 0xce, 0xdb, 0xad, 0xde, 0xca, 0xf8, 0x88,
+        ];
+        let j0 = gcm_j0(&nonce);
+        assert_eq!(&j0[..12], &nonce[..]);
+        assert_eq!(&j0[12..], &[0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn gcm_length_block() {
+        let block = gcm_lengths(20, 60);
+        assert_eq!(u64::from_be_bytes(block[..8].try_into().unwrap()), 160);
+        assert_eq!(u64::from_be_bytes(block[8..].try_into().unwrap()), 480);
+    }
+
+    #[test]
+    fn last_block_only_returns_whole_blocks() {
+        let mut buf = [0u8; 40];
+        buf.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+        assert_eq!(last_block(&buf), None);
+        let block = last_block(&buf[..32]).unwrap();
+        assert_eq!(block[0], 16);
+        assert_eq!(last_block(&buf[..8]), None);
+    }
+}
@@ -0,0 +1,271 @@
+//! Backup domain RAM and registers
+//!
+//! The 8 KiB backup RAM and the four RTC general purpose registers are
+//! retained in backup mode, where the rest of the device is powered off.
+//!
+//! Variables are placed in the backup RAM with the [`backup_ram!`] macro,
+//! which requires a `.bkupram` section in `memory.x`. The `memory.x` of the
+//! SAMD51 and SAME5x boards already provide it:
+//!
+//! ```text
+//! MEMORY
+//! {
+//!   ...
+//!   BKUPRAM (rw) : ORIGIN = 0x47000000, LENGTH = 8K
+//! }
+//!
+//! SECTIONS
+//! {
+//!   .bkupram (NOLOAD) : ALIGN(4)
+//!   {
+//!     *(.bkupram .bkupram.*);
+//!   } > BKUPRAM
+//! }
+//! INSERT AFTER .uninit;
+//! ```
+//!
+//! The section is not initialized at startup, so each variable is wrapped in
+//! a [`BackupRam`], which tells whether it holds a value written before the
+//! last reset.
+//!
+//! The RTC general purpose registers are cleared by a software reset of the
+//! RTC, which [`Rtc::new`](crate::rtc::Rtc::new) performs: read them before
+//! creating the [`Rtc`](crate::rtc::Rtc).
+
+use crate::target_device::RTC;
+use core::mem::{size_of, MaybeUninit};
+use core::ptr;
+
+/// Start address of the backup RAM
+pub const BACKUP_RAM_ADDR: usize = 0x4700_0000;
+
+/// Size of the backup RAM, in bytes
+pub const BACKUP_RAM_SIZE: usize = 0x2000;
+
+/// Number of RTC general purpose registers
+pub const GP_REGISTERS: usize = 4;
+
+/// Marker of a valid [`BackupRam`], combined with the size of its value
+const MARKER: u32 = 0xb4c3_5a1e;
+
+/// Types which can be stored in a [`BackupRam`]
+///
+/// # Safety
+///
+/// The type must not contain padding bytes, since its bytes are covered by
+/// the checksum, and every bit pattern must be a valid value, since the
+/// marker and checksum could match random contents of the backup RAM.
+pub unsafe trait Retained: Copy {}
+
+macro_rules! retained {
+    ($($t:ty),+) => {
+        $(
+unsafe impl Retained for $t {}
+        )+
+    }
+}
+
+retained!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+unsafe impl<T: Retained, const N: usize> Retained for [T; N] {}
+
+/// Value stored in the backup RAM, guarded by a marker and a checksum
+///
+/// The value is only returned if it was written with [`BackupRam::set`]
+/// since the backup RAM was last lost, and has not been corrupted since.
+#[repr(C)]
+pub struct BackupRam<T: Retained> {
+    marker: u32,
+    checksum: u32,
+    value: MaybeUninit<T>,
+}
+
+impl<T: Retained> BackupRam<T> {
+    /// Create an invalid `BackupRam`. Used as the initializer of statics in
+    /// the backup RAM, which is never applied.
+    pub const fn new() -> Self {
+        Self {
+            marker: 0,
+            checksum: 0,
+            value: MaybeUninit::uninit(),
+        }
+    }
+
+    fn expected_marker() -> u32 {
+        MARKER ^ size_of::<T>() as u32
+    }
+
+    fn value_checksum(&self) -> u32 {
+        let value = self.value.as_ptr() as *const u8;
+        // SAFETY: The bytes are in bounds of the value. The value is not
+        // initialized when the marker happens to match the power-on contents
+        // of the backup RAM: the bytes are read volatile, so whatever is in
+        // the RAM is read, as a `&[u8]` of uninitialized memory would be
+        // undefined behavior.
+        crc32((0..size_of::<T>()).map(|i| unsafe { ptr::read_volatile(value.add(i)) }))
+    }
+
+    /// Returns `true` if the stored value is valid
+    pub fn is_valid(&self) -> bool {
+        self.marker == Self::expected_marker() && self.checksum == self.value_checksum()
+    }
+
+    /// Returns the stored value, if it is valid
+    pub fn get(&self) -> Option<T> {
+        if self.is_valid() {
+            // SAFETY: The value was written by `set`, unless the marker and
+            // checksum match by chance. Any bit pattern is a valid `T`, and
+            // the value is read volatile like its checksum.
+            Some(unsafe { ptr::read_volatile(self.value.as_ptr()) })
+        } else {
+            None
+        }
+    }
+
+    /// Store `value`
+    pub fn set(&mut self, value: T) {
+        self.value = MaybeUninit::new(value);
+        self.checksum = self.value_checksum();
+        self.marker = Self::expected_marker();
+    }
+
+    /// Returns the stored value if it is valid, or stores and returns the
+    /// value returned by `f`
+    pub fn get_or_insert_with<F: FnOnce() -> T>(&mut self, f: F) -> T {
+        match self.get() {
+            Some(value) => value,
+            None => {
+                let value = f();
+                self.set(value);
+                value
+            }
+        }
+    }
+
+    /// Modify the stored value in place, if it is valid. Returns `false` if
+    /// there is no valid value.
+    pub fn update<F: FnOnce(&mut T)>(&mut self, f: F) -> bool {
+        match self.get() {
+            Some(mut value) => {
+                f(&mut value);
+                self.set(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Discard the stored value
+    pub fn invalidate(&mut self) {
+        self.marker = 0;
+    }
+}
+
+impl<T: Retained> Default for BackupRam<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Place a [`BackupRam`] in the `.bkupram` section, and return a mutable
+/// reference to it the first time the expression is evaluated, `None` after
+/// that.
+///
+/// ```ignore
+/// let state = backup_ram!(STATE: [u32; 16]).unwrap();
+/// let samples = state.get_or_insert_with(|| [0; 16]);
+/// ```
+#[macro_export]
+macro_rules! backup_ram {
+    ($name:ident: $ty:ty) => {{
+        use core::sync::atomic::{AtomicBool, Ordering};
+
+        #[link_section = ".bkupram"]
+        static mut $name: $crate::backup::BackupRam<$ty> = $crate::backup::BackupRam::new();
+        static TAKEN: AtomicBool = AtomicBool::new(false);
+
+        if TAKEN.swap(true, Ordering::AcqRel) {
+            None
+        } else {
+            // SAFETY: The reference is only handed out once
+            Some(unsafe { &mut *core::ptr::addr_of_mut!($name) })
+        }
+    }};
+}
+
+fn wait_gp_sync(rtc: &RTC, index: usize) {
+    while rtc.mode0().syncbusy.read().bits() & (1 << (16 + index)) != 0 {}
+}
+
+/// Read the RTC general purpose register `index`
+///
+/// # Panics
+///
+/// Panics if `index` is not lower than [`GP_REGISTERS`].
+pub fn read_gp(rtc: &RTC, index: usize) -> u32 {
+    wait_gp_sync(rtc, index);
+    rtc.mode0().gp[index].read().bits()
+}
+
+/// Write `value` to the RTC general purpose register `index`
+///
+/// # Panics
+///
+/// Panics if `index` is not lower than [`GP_REGISTERS`].
+pub fn write_gp(rtc: &mut RTC, index: usize, value: u32) {
+    rtc.mode0().gp[index].write(|w| unsafe { w.bits(value) });
+    wait_gp_sync(rtc, index);
+}
+
+/// CRC-32 (IEEE 802.3) of `bytes`
+fn crc32(bytes: impl IntoIterator<Item = u8>) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789".iter().copied()), 0xcbf4_3926);
+        assert_eq!(crc32(core::iter::empty()), 0);
+    }
+
+    #[test]
+    fn value_is_invalid_until_set() {
+        let mut ram = BackupRam::<[u32; 4]>::new();
+        assert_eq!(ram.get(), None);
+        assert!(!ram.update(|v| v[0] = 1));
+        assert_eq!(ram.get_or_insert_with(|| [1, 2, 3, 4]), [1, 2, 3, 4]);
+        assert!(ram.update(|v| v[0] = 5));
+        assert_eq!(ram.get(), Some([5, 2, 3, 4]));
+        ram.invalidate();
+        assert_eq!(ram.get(), None);
+    }
+
+    #[test]
+    fn corruption_is_detected() {
+        let mut ram = BackupRam::<u64>::new();
+        ram.set(0x0123_4567_89ab_cdef);
+        ram.value = MaybeUninit::new(0x0123_4567_89ab_cdee);
+        assert!(!ram.is_valid());
+    }
+
+    #[test]
+    fn macro_hands_out_reference_once() {
+        fn take() -> Option<&'static mut BackupRam<u32>> {
+            backup_ram!(COUNTER: u32)
+        }
+        assert!(take().is_some());
+        assert!(take().is_none());
+    }
+}
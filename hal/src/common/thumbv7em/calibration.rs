@@ -59,3 +59,9 @@ pub fn adc1_biasref_scale_cal() -> u8 {
 pub fn adc1_biasr2r_scale_cal() -> u8 {
     cal(3, 0, 0b111) as u8
 }
+
+/// Temperature log row, holding the calibration values of the temperature
+/// sensor
+pub fn temperature_log_row() -> [u32; 3] {
+    [cal(0x80, 0, !0), cal(0x84, 0, !0), cal(0x88, 0, !0)]
+}
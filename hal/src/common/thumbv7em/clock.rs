@@ -212,9 +212,6 @@ impl GenericClockController {
 
         while state.gclk.syncbusy.read().genctrl().is_gclk0() {}
 
-        #[cfg(feature = "usb")]
-        configure_usb_correction(oscctrl);
-
         // GCLK5 set to 2MHz
         unsafe {
             state.gclk.genctrl[5].write(|w| {
@@ -403,6 +400,7 @@ impl GenericClockController {
 }
 
 clock_generator!(
+    (dfll48, Dfll48Clock, DFLL48),
     (tc0_tc1, Tc0Tc1Clock, TC0_TC1),
     (tcc0_tcc1, Tcc0Tcc1Clock, TCC0_TCC1),
     (tc2_tc3, Tc2Tc3Clock, TC2_TC3),
@@ -524,7 +522,7 @@ fn configure_and_enable_dpll0(oscctrl: &mut OSCCTRL, gclk: &mut GCLK) {
 
 #[cfg(feature = "usb")]
 /// Configure the dfll48m to calibrate against the 1Khz USB SOF reference.
-fn configure_usb_correction(oscctrl: &mut OSCCTRL) {
+pub(crate) fn configure_usb_correction(oscctrl: &mut OSCCTRL) {
     oscctrl.dfllmul.write(|w| unsafe {
         w.cstep().bits(0x1)
         .fstep().bits(0x1)
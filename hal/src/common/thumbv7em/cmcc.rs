@@ -0,0 +1,428 @@
+//! Cortex-M cache controller
+//!
+//! The CMCC is a 4 KB, 4-way set associative cache with 16-byte lines,
+//! shared by the instruction fetches and data reads of the core. It is
+//! disabled at reset.
+//!
+//! ```ignore
+//! let mut cmcc = Cmcc::new(&mut peripherals.MCLK, peripherals.CMCC);
+//! cmcc.enable();
+//! ```
+//!
+//! # Coherency
+//!
+//! The CMCC only caches the code region, that is the flash and the QSPI
+//! memory space, while the SRAM is accessed directly: DMAC writes to the
+//! SRAM are therefore unaffected by the cache. The cache is however not aware
+//! of the flash written by the NVMCTRL, nor of the memory written through
+//! the QSPI. Invalidate the lines of the code space written that way before
+//! reading or executing it through the cache, with
+//! [`invalidate_region`](Cmcc::invalidate_region).
+//!
+//! # Tightly-coupled memory
+//!
+//! A locked way is never replaced, so code or data loaded into it before it
+//! is locked stays in the cache: accesses to it never wait for the memory,
+//! like a tightly-coupled memory. [`lock_way`](Cmcc::lock_way) loads a region
+//! of up to a way, 1 KB at the full cache size, into a way and locks it.
+//!
+//! # Monitor
+//!
+//! The monitor counts cycles, instruction hits or data hits, for profiling
+//! the cache with [`enable_monitor`](Cmcc::enable_monitor).
+
+use crate::target_device::cmcc::cfg::CSIZESW_A;
+use crate::target_device::cmcc::maint1::WAY_AW;
+use crate::target_device::cmcc::mcfg::MODE_A;
+use crate::target_device::{CMCC, MCLK};
+
+/// Size of a cache line, in bytes
+pub const LINE_SIZE: usize = 16;
+
+/// Number of ways
+pub const WAYS: usize = 4;
+
+/// CMCC errors
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// The region does not fit in a way
+    RegionTooLarge,
+    /// The way is already locked
+    WayLocked,
+}
+
+/// Cache way
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Way {
+    Way0,
+    Way1,
+    Way2,
+    Way3,
+}
+
+impl Way {
+    const ALL: [Way; WAYS] = [Way::Way0, Way::Way1, Way::Way2, Way::Way3];
+
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl From<Way> for WAY_AW {
+    fn from(way: Way) -> Self {
+        match way {
+            Way::Way0 => WAY_AW::WAY0,
+            Way::Way1 => WAY_AW::WAY1,
+            Way::Way2 => WAY_AW::WAY2,
+            Way::Way3 => WAY_AW::WAY3,
+        }
+    }
+}
+
+/// Size of the cache used
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CacheSize {
+    _1KB,
+    _2KB,
+    _4KB,
+}
+
+impl CacheSize {
+    /// Size in bytes
+    pub fn bytes(self) -> usize {
+        match self {
+            CacheSize::_1KB => 1024,
+            CacheSize::_2KB => 2048,
+            CacheSize::_4KB => 4096,
+        }
+    }
+
+    /// Number of lines of a way
+    fn lines_per_way(self) -> usize {
+        self.bytes() / WAYS / LINE_SIZE
+    }
+}
+
+impl From<CacheSize> for CSIZESW_A {
+    fn from(size: CacheSize) -> Self {
+        match size {
+            CacheSize::_1KB => CSIZESW_A::CONF_CSIZE_1KB,
+            CacheSize::_2KB => CSIZESW_A::CONF_CSIZE_2KB,
+            CacheSize::_4KB => CSIZESW_A::CONF_CSIZE_4KB,
+        }
+    }
+}
+
+/// Events counted by the monitor
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MonitorMode {
+    /// Core clock cycles
+    Cycles,
+    /// Instruction fetches hitting the cache
+    InstructionHits,
+    /// Data reads hitting the cache
+    DataHits,
+}
+
+impl From<MonitorMode> for MODE_A {
+    fn from(mode: MonitorMode) -> Self {
+        match mode {
+            MonitorMode::Cycles => MODE_A::CYCLE_COUNT,
+            MonitorMode::InstructionHits => MODE_A::IHIT_COUNT,
+            MonitorMode::DataHits => MODE_A::DHIT_COUNT,
+        }
+    }
+}
+
+/// First line and number of lines spanned by the `len` bytes at `start`
+fn line_span(start: usize, len: usize) -> (usize, usize) {
+    if len == 0 {
+        return (start / LINE_SIZE, 0);
+    }
+    let first = start / LINE_SIZE;
+    let last = (start + len - 1) / LINE_SIZE;
+    (first, last - first + 1)
+}
+
+/// Lines of the unlocked ways, as a way and an index, which may hold the
+/// `count` lines from line `first`
+fn region_lines(
+    first: usize,
+    count: usize,
+    lines_per_way: usize,
+    locked: u8,
+) -> impl Iterator<Item = (Way, usize)> {
+    (first..first + count.min(lines_per_way)).flat_map(move |line| {
+        Way::ALL
+            .iter()
+            .filter(move |way| locked & way.mask() == 0)
+            .map(move |way| (*way, line % lines_per_way))
+    })
+}
+
+/// Cache controller
+pub struct Cmcc {
+    cmcc: CMCC,
+    size: CacheSize,
+}
+
+impl Cmcc {
+    /// Take the CMCC, disabled, with the full cache size and both the
+    /// instruction and data caches selected
+    pub fn new(mclk: &mut MCLK, cmcc: CMCC) -> Self {
+        mclk.ahbmask.modify(|_, w| w.cmcc_().set_bit());
+
+        let mut cmcc = Self {
+            cmcc,
+            size: CacheSize::_4KB,
+        };
+        cmcc.disable();
+        cmcc.cmcc
+            .cfg
+            .write(|w| w.csizesw().variant(CacheSize::_4KB.into()));
+        cmcc.cmcc.lckway.write(|w| unsafe { w.lckway().bits(0) });
+        cmcc.cmcc.maint0.write(|w| w.invall().set_bit());
+        cmcc
+    }
+
+    /// Enable the cache
+    pub fn enable(&mut self) {
+        self.cmcc.ctrl.write(|w| w.cen().set_bit());
+    }
+
+    /// Disable the cache. The cached lines are kept.
+    pub fn disable(&mut self) {
+        self.cmcc.ctrl.write(|w| w.cen().clear_bit());
+        while self.cmcc.sr.read().csts().bit_is_set() {}
+    }
+
+    /// The cache is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.cmcc.sr.read().csts().bit_is_set()
+    }
+
+    /// Run `f` with the cache disabled, as required by the configuration
+    /// and line maintenance registers
+    fn while_disabled<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let enabled = self.is_enabled();
+        self.disable();
+        let result = f(self);
+        if enabled {
+            self.enable();
+        }
+        result
+    }
+
+    /// Select whether instruction fetches and data reads are cached
+    pub fn set_caching(&mut self, instructions: bool, data: bool) {
+        self.while_disabled(|cmcc| {
+            cmcc.cmcc.cfg.modify(|_, w| {
+                w.icdis().bit(!instructions);
+                w.dcdis().bit(!data)
+            })
+        });
+    }
+
+    /// Reduce the size of the cache, which invalidates it
+    pub fn set_size(&mut self, size: CacheSize) {
+        self.while_disabled(|cmcc| {
+            cmcc.cmcc
+                .cfg
+                .modify(|_, w| w.csizesw().variant(size.into()));
+            cmcc.cmcc.maint0.write(|w| w.invall().set_bit());
+            cmcc.size = size;
+        });
+    }
+
+    /// Size of the cache
+    pub fn size(&self) -> CacheSize {
+        self.size
+    }
+
+    /// Invalidate every line of the cache. Use
+    /// [`invalidate_region`](Self::invalidate_region) to keep the content of
+    /// the locked ways.
+    pub fn invalidate_all(&mut self) {
+        self.cmcc.maint0.write(|w| w.invall().set_bit());
+    }
+
+    /// Invalidate line `index` of `way`
+    fn invalidate_line_disabled(&mut self, way: Way, index: usize) {
+        self.cmcc.maint1.write(|w| unsafe {
+            w.index().bits(index as u8);
+            w.way().variant(way.into())
+        });
+    }
+
+    /// Invalidate line `index` of `way`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not a line of the way.
+    pub fn invalidate_line(&mut self, way: Way, index: usize) {
+        assert!(index < self.size.lines_per_way(), "Invalid cache line");
+        self.while_disabled(|cmcc| cmcc.invalidate_line_disabled(way, index));
+    }
+
+    /// Invalidate the lines that may hold the `len` bytes at `start`, in the
+    /// unlocked ways. Call it after another bus master wrote the region, and
+    /// before reading the region.
+    pub fn invalidate_region(&mut self, start: usize, len: usize) {
+        let lines_per_way = self.size.lines_per_way();
+        let (first, count) = line_span(start, len);
+        let locked = self.locked_ways();
+        if locked == 0 && count >= lines_per_way {
+            self.invalidate_all();
+            return;
+        }
+        self.while_disabled(|cmcc| {
+            for (way, index) in region_lines(first, count, lines_per_way, locked) {
+                cmcc.invalidate_line_disabled(way, index);
+            }
+        });
+    }
+
+    /// Mask of the locked ways, with way `n` in bit `n`
+    pub fn locked_ways(&self) -> u8 {
+        self.cmcc.lckway.read().lckway().bits()
+    }
+
+    /// Load the `len` bytes at `start` into `way`, and lock the way, so that
+    /// the region is always read from the cache. The region is loaded with
+    /// data reads, and is then also hit by instruction fetches.
+    ///
+    /// The region must be in the code space cached by the CMCC. Its lines
+    /// are first invalidated in the other unlocked ways, so that they are
+    /// all allocated into `way`.
+    ///
+    /// The cache is enabled on return.
+    pub fn lock_way(&mut self, way: Way, start: usize, len: usize) -> Result<(), Error> {
+        let lines_per_way = self.size.lines_per_way();
+        let (first, count) = line_span(start, len);
+        if count > lines_per_way {
+            return Err(Error::RegionTooLarge);
+        }
+        let locked = self.locked_ways();
+        if locked & way.mask() != 0 {
+            return Err(Error::WayLocked);
+        }
+
+        self.disable();
+        let cfg = self.cmcc.cfg.read().bits();
+        for index in 0..lines_per_way {
+            self.invalidate_line_disabled(way, index);
+        }
+        // A line cached in another way would be hit there, and not loaded
+        // into the way
+        for (other, index) in region_lines(first, count, lines_per_way, locked | way.mask()) {
+            self.invalidate_line_disabled(other, index);
+        }
+        // Only the way can be allocated, and only by data reads
+        self.cmcc
+            .lckway
+            .write(|w| unsafe { w.lckway().bits(0xf & !way.mask()) });
+        self.cmcc.cfg.modify(|_, w| {
+            w.icdis().set_bit();
+            w.dcdis().clear_bit()
+        });
+        self.enable();
+        for line in first..first + count {
+            // SAFETY: the region is only read, one byte per line
+            unsafe { core::ptr::read_volatile((line * LINE_SIZE) as *const u8) };
+        }
+        self.disable();
+        self.cmcc
+            .lckway
+            .write(|w| unsafe { w.lckway().bits(locked | way.mask()) });
+        self.cmcc.cfg.write(|w| unsafe { w.bits(cfg) });
+        self.enable();
+        Ok(())
+    }
+
+    /// Unlock `way`, which is then replaced like the other ways
+    pub fn unlock_way(&mut self, way: Way) {
+        let locked = self.locked_ways() & !way.mask();
+        self.cmcc
+            .lckway
+            .write(|w| unsafe { w.lckway().bits(locked) });
+    }
+
+    /// Reset the monitor counter and count `mode` events
+    pub fn enable_monitor(&mut self, mode: MonitorMode) {
+        self.cmcc.men.write(|w| w.menable().clear_bit());
+        self.cmcc.mcfg.write(|w| w.mode().variant(mode.into()));
+        self.cmcc.mctrl.write(|w| w.swrst().set_bit());
+        self.cmcc.men.write(|w| w.menable().set_bit());
+    }
+
+    /// Stop counting, keeping the counter value
+    pub fn disable_monitor(&mut self) {
+        self.cmcc.men.write(|w| w.menable().clear_bit());
+    }
+
+    /// Reset the monitor counter
+    pub fn reset_monitor(&mut self) {
+        self.cmcc.mctrl.write(|w| w.swrst().set_bit());
+    }
+
+    /// Number of events counted by the monitor
+    pub fn monitor_count(&self) -> u32 {
+        self.cmcc.msr.read().event_cnt().bits()
+    }
+
+    /// Disable the cache and return the peripheral
+    pub fn free(mut self) -> CMCC {
+        self.disable();
+        self.cmcc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans() {
+        assert_eq!(line_span(0x1000, 16), (0x100, 1));
+        assert_eq!(line_span(0x1000, 1024), (0x100, 64));
+        // Unaligned regions touch one more line
+        assert_eq!(line_span(0x1008, 16), (0x100, 2));
+        assert_eq!(line_span(0x1008, 1024), (0x100, 65));
+        assert_eq!(line_span(0x100f, 1), (0x100, 1));
+        assert_eq!(line_span(0x1000, 0).1, 0);
+    }
+
+    #[test]
+    fn ways() {
+        assert_eq!(CacheSize::_4KB.lines_per_way(), 64);
+        assert_eq!(CacheSize::_1KB.lines_per_way(), 16);
+        assert_eq!(Way::Way2.mask(), 0b0100);
+    }
+
+    #[test]
+    fn region_lines_skip_locked_ways() {
+        let expected = [
+            (Way::Way1, 0),
+            (Way::Way3, 0),
+            (Way::Way1, 1),
+            (Way::Way3, 1),
+        ];
+        assert!(region_lines(0x100, 2, 64, 0b0101).eq(expected.iter().copied()));
+        // Regions larger than a way wrap around the indices once
+        assert_eq!(region_lines(0x100, 100, 64, 0b1110).count(), 64);
+        assert_eq!(region_lines(0x100, 0, 64, 0).count(), 0);
+    }
+
+    #[test]
+    fn lock_invalidates_region_cached_in_other_ways() {
+        // The region, lines 0x10f to 0x112, was cached in way 2 before way 0
+        // is locked, while way 3 is already locked
+        let (first, count) = line_span(0x10f0, 64);
+        let lines = || region_lines(first, count, 64, 0b1000 | Way::Way0.mask());
+        for index in [0x0f, 0x10, 0x11, 0x12].iter() {
+            assert!(lines().any(|line| line == (Way::Way2, *index)));
+            assert!(lines().any(|line| line == (Way::Way1, *index)));
+        }
+        assert!(lines().all(|(way, _)| way != Way::Way0 && way != Way::Way3));
+    }
+}
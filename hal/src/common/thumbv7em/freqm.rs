@@ -0,0 +1,156 @@
+//! Frequency meter
+//!
+//! The FREQM counts the cycles of the measured clock (GCLK_FREQM_MSR) during
+//! a number of cycles of the reference clock (GCLK_FREQM_REF). Any clock
+//! source can be measured by routing it through a clock generator to the
+//! `FREQM_MSR` channel:
+//!
+//! ```ignore
+//! let xosc = clocks
+//!     .configure_gclk_divider_and_source(ClockGenId::GCLK2, 1, ClockSource::XOSC0, false)
+//!     .unwrap();
+//! let reference = clocks.gclk1();
+//! let mut freqm = Freqm::new(
+//!     &mut peripherals.MCLK,
+//!     peripherals.FREQM,
+//!     clocks.freq_m_msr(&xosc).unwrap(),
+//!     clocks.freq_m_ref(&reference).unwrap(),
+//! );
+//! let freq = freqm.measure(255).unwrap();
+//! ```
+//!
+//! The resolution of a measurement is the reference frequency divided by
+//! the number of reference cycles: longer measurements are more precise,
+//! but overflow sooner when the measured clock is fast.
+
+use crate::clock::{FreqmMsrClock, FreqmRefClock};
+use crate::target_device::{FREQM, MCLK};
+use crate::time::Hertz;
+
+/// Frequency measurement errors
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// The measured clock ran more than 2^24 cycles during the measurement.
+    /// Use fewer reference cycles.
+    Overflow,
+}
+
+/// Measured frequency, from the count of measured clock cycles during
+/// `ref_cycles` cycles of the `reference` clock, saturating at `u32::MAX`
+fn frequency(value: u32, ref_cycles: u8, reference: Hertz) -> Hertz {
+    let ref_cycles = ref_cycles as u64;
+    let freq = (value as u64 * reference.0 as u64 + ref_cycles / 2) / ref_cycles;
+    Hertz(freq.min(u32::MAX as u64) as u32)
+}
+
+/// Frequency meter
+pub struct Freqm {
+    freqm: FREQM,
+    reference: Hertz,
+    ref_cycles: u8,
+}
+
+impl Freqm {
+    /// Reset and enable the FREQM. The measured clock is the source of the
+    /// generator of `_msr`; the frequency of `reference` must be known
+    /// accurately.
+    pub fn new(
+        mclk: &mut MCLK,
+        freqm: FREQM,
+        _msr: FreqmMsrClock,
+        reference: FreqmRefClock,
+    ) -> Self {
+        mclk.apbamask.modify(|_, w| w.freqm_().set_bit());
+
+        freqm.ctrla.write(|w| w.swrst().set_bit());
+        while freqm.syncbusy.read().swrst().bit_is_set() {}
+        freqm.ctrla.write(|w| w.enable().set_bit());
+        while freqm.syncbusy.read().enable().bit_is_set() {}
+
+        Self {
+            freqm,
+            reference: reference.freq(),
+            ref_cycles: 1,
+        }
+    }
+
+    /// Frequency of the reference clock
+    pub fn reference(&self) -> Hertz {
+        self.reference
+    }
+
+    /// Start a measurement over `ref_cycles` cycles of the reference clock
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ref_cycles` is zero.
+    pub fn start(&mut self, ref_cycles: u8) {
+        assert!(ref_cycles != 0, "FREQM needs at least one reference cycle");
+        self.ref_cycles = ref_cycles;
+
+        self.freqm
+            .cfga
+            .write(|w| unsafe { w.refnum().bits(ref_cycles) });
+        self.freqm.status.write(|w| w.ovf().set_bit());
+        self.freqm.intflag.write(|w| w.done().set_bit());
+        self.freqm.ctrlb.write(|w| w.start().set_bit());
+    }
+
+    /// Returns the result of the measurement started by
+    /// [`start`](Freqm::start)
+    pub fn read(&mut self) -> nb::Result<Hertz, Error> {
+        if self.freqm.intflag.read().done().bit_is_clear() {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.freqm.intflag.write(|w| w.done().set_bit());
+        if self.freqm.status.read().ovf().bit_is_set() {
+            self.freqm.status.write(|w| w.ovf().set_bit());
+            return Err(nb::Error::Other(Error::Overflow));
+        }
+        let value = self.freqm.value.read().value().bits();
+        Ok(frequency(value, self.ref_cycles, self.reference))
+    }
+
+    /// Measure the frequency over `ref_cycles` cycles of the reference
+    /// clock, blocking until done
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ref_cycles` is zero.
+    pub fn measure(&mut self, ref_cycles: u8) -> Result<Hertz, Error> {
+        self.start(ref_cycles);
+        nb::block!(self.read())
+    }
+
+    /// Enable the measurement done interrupt
+    pub fn enable_interrupt(&mut self) {
+        self.freqm.intenset.write(|w| w.done().set_bit());
+    }
+
+    /// Disable the measurement done interrupt
+    pub fn disable_interrupt(&mut self) {
+        self.freqm.intenclr.write(|w| w.done().set_bit());
+    }
+
+    /// Disable the FREQM and return the peripheral
+    pub fn free(self) -> FREQM {
+        self.freqm.ctrla.write(|w| w.enable().clear_bit());
+        while self.freqm.syncbusy.read().enable().bit_is_set() {}
+        self.freqm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frequency_from_count() {
+        // 12 MHz crystal against 255 cycles of the 32.768 kHz oscillator
+        assert_eq!(frequency(93_384, 255, Hertz(32_768)), Hertz(12_000_027));
+        assert_eq!(frequency(1, 1, Hertz(32_768)), Hertz(32_768));
+        // Rounded to the nearest hertz
+        assert_eq!(frequency(3, 2, Hertz(3)), Hertz(5));
+        assert_eq!(frequency(0xff_ffff, 1, Hertz(48_000_000)), Hertz(u32::MAX));
+    }
+}
@@ -0,0 +1,784 @@
+//! # Integrity Check Monitor (ICM)
+//!
+//! The ICM is a DMA-capable SHA engine. It reads memory regions described
+//! by a list of descriptors in RAM, and either writes their digest back to
+//! memory or compares it against a previously computed digest. It supports
+//! SHA-1, SHA-224 and SHA-256.
+//!
+//! This driver offers two modes of operation:
+//!
+//! * One-shot hashing through [`Icm::digest`], or through the
+//!   [`IcmDigest`] hashers, which implement the RustCrypto `Digest` trait when
+//!   the `crypto` feature is enabled;
+//! * Region monitoring through [`Icm::monitor`], where the ICM continuously
+//!   rehashes up to four memory regions and raises a digest mismatch flag
+//!   whenever one of them is modified.
+//!
+//! The ICM does not pad messages, so the final padded blocks are built in
+//! software. Intermediate digests are chained through the User Initial Hash
+//! Value registers, which lets the hashers process data in arbitrary chunks.
+//!
+//! The ICM reads memory as a bus master, bypassing the CMCC. If the cache is
+//! enabled, make sure the hashed memory is not stale in the cache.
+//!
+//! # Digest trait
+//!
+//! [`IcmDigest`] does not borrow the peripheral, so that it can implement
+//! `Default` as required by `Digest`. Instead, the hashers can only be used
+//! while a [`DigestEngine`], obtained with [`Icm::into_digest_engine`], is
+//! alive. Using a hasher at any other time panics.
+
+use crate::target_device::icm::{cfg::UALGO_A, RegisterBlock};
+use crate::target_device::{ICM, MCLK};
+use crate::typelevel::Sealed;
+use core::marker::PhantomData;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Number of regions the ICM can monitor
+pub const NUM_REGIONS: usize = 4;
+
+/// SHA block size, in bytes
+pub const BLOCK_SIZE: usize = 64;
+
+/// Maximum number of blocks in a single region
+const MAX_REGION_BLOCKS: usize = 1 << 16;
+
+/// Number of 32-bit words reserved for each region in the hash area
+const HASH_WORDS: usize = 8;
+
+// Region configuration (RCFG) bits
+const RCFG_WRAP: u32 = 1 << 1;
+const RCFG_EOM: u32 = 1 << 2;
+const RCFG_ALGO_SHIFT: u32 = 12;
+
+/// Errors related to the ICM
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// A region is empty, too large, not word-aligned, or its length is not
+    /// a multiple of the block size
+    InvalidRegion,
+    /// The ICM reported a bus error while reading memory
+    BusError,
+}
+
+/// SHA algorithm used by the ICM
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HashAlgorithm {
+    Sha1,
+    Sha224,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    fn ualgo(self) -> UALGO_A {
+        match self {
+            HashAlgorithm::Sha1 => UALGO_A::SHA1,
+            HashAlgorithm::Sha224 => UALGO_A::SHA224,
+            HashAlgorithm::Sha256 => UALGO_A::SHA256,
+        }
+    }
+
+    /// Size of the digest, in bytes
+    pub fn output_size(self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha224 => 28,
+            HashAlgorithm::Sha256 => 32,
+        }
+    }
+}
+
+/// Type-level SHA algorithm, used by [`IcmDigest`] and [`Icm::digest`]
+pub trait Algorithm: Sealed {
+    /// Runtime equivalent of the algorithm
+    const ALGORITHM: HashAlgorithm;
+    /// Algorithm used to compress intermediate blocks.
+    ///
+    /// SHA-224 only writes back seven words of its state, so it is computed
+    /// as SHA-256 with the SHA-224 initial hash value.
+    const COMPRESSION: HashAlgorithm;
+    /// Initial hash value, in digest byte order
+    const INITIAL_STATE: [u8; 32];
+    /// Digest output
+    type Output: Default + AsMut<[u8]>;
+}
+
+/// SHA-1 marker type
+pub enum Sha1 {}
+/// SHA-224 marker type
+pub enum Sha224 {}
+/// SHA-256 marker type
+pub enum Sha256 {}
+
+impl Sealed for Sha1 {}
+impl Sealed for Sha224 {}
+impl Sealed for Sha256 {}
+
+impl Algorithm for Sha1 {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha1;
+    const COMPRESSION: HashAlgorithm = HashAlgorithm::Sha1;
+    const INITIAL_STATE: [u8; 32] = state_bytes([
+        0x6745_2301,
+        0xefcd_ab89,
+        0x98ba_dcfe,
+        0x1032_5476,
+        0xc3d2_e1f0,
+        0,
+        0,
+        0,
+    ]);
+    type Output = [u8; 20];
+}
+
+impl Algorithm for Sha224 {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha224;
+    const COMPRESSION: HashAlgorithm = HashAlgorithm::Sha256;
+    const INITIAL_STATE: [u8; 32] = state_bytes([
+        0xc105_9ed8,
+        0x367c_d507,
+        0x3070_dd17,
+        0xf70e_5939,
+        0xffc0_0b31,
+        0x6858_1511,
+        0x64f9_8fa7,
+        0xbefa_4fa4,
+    ]);
+    type Output = [u8; 28];
+}
+
+impl Algorithm for Sha256 {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha256;
+    const COMPRESSION: HashAlgorithm = HashAlgorithm::Sha256;
+    const INITIAL_STATE: [u8; 32] = state_bytes([
+        0x6a09_e667,
+        0xbb67_ae85,
+        0x3c6e_f372,
+        0xa54f_f53a,
+        0x510e_527f,
+        0x9b05_688c,
+        0x1f83_d9ab,
+        0x5be0_cd19,
+    ]);
+    type Output = [u8; 32];
+}
+
+/// Serialize SHA state words in digest byte order
+const fn state_bytes(words: [u32; 8]) -> [u8; 32] {
+    let mut bytes = [0; 32];
+    let mut i = 0;
+    while i < 32 {
+        bytes[i] = (words[i / 4] >> (24 - 8 * (i % 4))) as u8;
+        i += 1;
+    }
+    bytes
+}
+
+/// Region descriptor, as read by the ICM
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RegionDescriptor {
+    raddr: u32,
+    rcfg: u32,
+    rctrl: u32,
+    rnext: u32,
+}
+
+const EMPTY_DESCRIPTOR: RegionDescriptor = RegionDescriptor {
+    raddr: 0,
+    rcfg: 0,
+    rctrl: 0,
+    rnext: 0,
+};
+
+/// Region descriptor area. DSCR must be aligned on 64 bytes.
+#[repr(C, align(64))]
+struct DescriptorArea([RegionDescriptor; NUM_REGIONS]);
+
+/// Region hash area. HASH must be aligned on 128 bytes.
+#[repr(C, align(128))]
+struct HashArea([u32; NUM_REGIONS * HASH_WORDS]);
+
+// Descriptor and hash areas. They are only accessed by the owner of the ICM
+// peripheral, or by the hashers in a critical section while the
+// `DigestEngine` is alive.
+static mut DESCRIPTORS: DescriptorArea = DescriptorArea([EMPTY_DESCRIPTOR; NUM_REGIONS]);
+static mut HASHES: HashArea = HashArea([0; NUM_REGIONS * HASH_WORDS]);
+
+/// Set while a `DigestEngine` exists
+static ENGINE: AtomicBool = AtomicBool::new(false);
+
+/// Reset the ICM and point it to the descriptor and hash areas
+fn reset(icm: &RegisterBlock) {
+    icm.ctrl.write(|w| w.swrst().set_bit());
+    // SAFETY: The areas are statics, so their address is always valid
+    unsafe {
+        icm.dscr
+            .write(|w| w.bits(ptr::addr_of!(DESCRIPTORS) as u32));
+        icm.hash.write(|w| w.bits(ptr::addr_of!(HASHES) as u32));
+    }
+}
+
+/// Disable the ICM and wait until it is idle
+fn disable(icm: &RegisterBlock) {
+    icm.ctrl.write(|w| w.disable().set_bit());
+    while icm.sr.read().enable().bit_is_set() {}
+}
+
+/// Read the digest of `region` from the hash area
+fn read_hash(region: usize) -> [u8; 32] {
+    let mut digest = [0; 32];
+    for (i, chunk) in digest.chunks_mut(4).enumerate() {
+        // SAFETY: The ICM writes the hash area as a bus master, so it must be
+        // read with volatile accesses.
+        let word = unsafe { ptr::read_volatile(ptr::addr_of!(HASHES.0[region * HASH_WORDS + i])) };
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+/// Hash `blocks` blocks starting at `addr`, chaining from `state`.
+///
+/// `addr` must be word-aligned and point to at least `blocks * 64` readable
+/// bytes. The caller must have exclusive access to the ICM.
+fn compress(
+    icm: &RegisterBlock,
+    algorithm: HashAlgorithm,
+    state: &mut [u8; 32],
+    addr: u32,
+    blocks: usize,
+) -> Result<(), Error> {
+    debug_assert!(blocks > 0 && blocks <= MAX_REGION_BLOCKS);
+
+    reset(icm);
+    icm.cfg.write(|w| {
+        w.uihash().set_bit();
+        w.ualgo().variant(algorithm.ualgo())
+    });
+    for (reg, chunk) in icm.uihval.iter().zip(state.chunks(4)) {
+        let word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        reg.write(|w| unsafe { w.bits(word) });
+    }
+
+    // SAFETY: The caller has exclusive access to the ICM, and therefore to
+    // the descriptor area.
+    unsafe {
+        ptr::write_volatile(
+            ptr::addr_of_mut!(DESCRIPTORS.0[0]),
+            RegionDescriptor {
+                raddr: addr,
+                rcfg: RCFG_EOM,
+                rctrl: (blocks - 1) as u32,
+                rnext: 0,
+            },
+        );
+    }
+
+    icm.ctrl.write(|w| w.enable().set_bit());
+    let result = loop {
+        let isr = icm.isr.read();
+        if isr.rbe().bits() & 1 != 0 {
+            break Err(Error::BusError);
+        }
+        if isr.rhc().bits() & 1 != 0 {
+            break Ok(());
+        }
+    };
+    disable(icm);
+
+    result?;
+    *state = read_hash(0);
+    Ok(())
+}
+
+/// Build the padded final block(s) of a message of `length` bytes, whose
+/// unprocessed tail is `tail`. Returns the number of blocks written to `out`.
+fn pad(tail: &[u8], length: u64, out: &mut [u8; 2 * BLOCK_SIZE]) -> usize {
+    debug_assert!(tail.len() < BLOCK_SIZE);
+
+    *out = [0; 2 * BLOCK_SIZE];
+    out[..tail.len()].copy_from_slice(tail);
+    out[tail.len()] = 0x80;
+    let blocks = if tail.len() < BLOCK_SIZE - 8 { 1 } else { 2 };
+    let end = blocks * BLOCK_SIZE;
+    out[end - 8..end].copy_from_slice(&(length * 8).to_be_bytes());
+    blocks
+}
+
+/// Copy `block` to a word-aligned buffer and hash it
+fn compress_block(
+    icm: &RegisterBlock,
+    algorithm: HashAlgorithm,
+    state: &mut [u8; 32],
+    block: &[u8],
+) -> Result<(), Error> {
+    let mut aligned = [0u32; BLOCK_SIZE / 4];
+    for (word, chunk) in aligned.iter_mut().zip(block.chunks(4)) {
+        *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    compress(icm, algorithm, state, aligned.as_ptr() as u32, 1)
+}
+
+/// Hash the padded final block(s) and produce the digest
+fn finish<A: Algorithm>(
+    icm: &RegisterBlock,
+    state: &mut [u8; 32],
+    tail: &[u8],
+    length: u64,
+) -> Result<A::Output, Error> {
+    let mut last = [0; 2 * BLOCK_SIZE];
+    let blocks = pad(tail, length, &mut last);
+    for block in last[..blocks * BLOCK_SIZE].chunks(BLOCK_SIZE) {
+        compress_block(icm, A::COMPRESSION, state, block)?;
+    }
+
+    let mut output = A::Output::default();
+    let output_bytes = output.as_mut();
+    let len = output_bytes.len();
+    output_bytes.copy_from_slice(&state[..len]);
+    Ok(output)
+}
+
+/// Integrity Check Monitor
+pub struct Icm {
+    icm: ICM,
+}
+
+impl Icm {
+    /// Enable the ICM clocks and reset the peripheral
+    pub fn new(mclk: &mut MCLK, icm: ICM) -> Self {
+        mclk.ahbmask.modify(|_, w| w.icm_().set_bit());
+        mclk.apbcmask.modify(|_, w| w.icm_().set_bit());
+        reset(&icm);
+        Self { icm }
+    }
+
+    /// Disable the ICM clocks and release the peripheral
+    pub fn free(self, mclk: &mut MCLK) -> ICM {
+        reset(&self.icm);
+        mclk.apbcmask.modify(|_, w| w.icm_().clear_bit());
+        mclk.ahbmask.modify(|_, w| w.icm_().clear_bit());
+        self.icm
+    }
+
+    /// Compute the digest of `data`.
+    ///
+    /// Whole blocks are read by the ICM directly from `data` if it is
+    /// word-aligned, so hashing a firmware image in flash does not require
+    /// copying it to RAM.
+    pub fn digest<A: Algorithm>(&mut self, data: &[u8]) -> Result<A::Output, Error> {
+        let mut state = A::INITIAL_STATE;
+        let whole = data.len() & !(BLOCK_SIZE - 1);
+        let (body, tail) = data.split_at(whole);
+
+        if body.as_ptr() as usize & 3 == 0 {
+            for chunk in body.chunks(MAX_REGION_BLOCKS * BLOCK_SIZE) {
+                let blocks = chunk.len() / BLOCK_SIZE;
+                compress(
+                    &self.icm,
+                    A::COMPRESSION,
+                    &mut state,
+                    chunk.as_ptr() as u32,
+                    blocks,
+                )?;
+            }
+        } else {
+            for block in body.chunks(BLOCK_SIZE) {
+                compress_block(&self.icm, A::COMPRESSION, &mut state, block)?;
+            }
+        }
+
+        finish::<A>(&self.icm, &mut state, tail, data.len() as u64)
+    }
+
+    /// Hand the ICM over to the [`IcmDigest`] hashers
+    pub fn into_digest_engine(self) -> DigestEngine {
+        ENGINE.store(true, Ordering::Release);
+        DigestEngine { icm: self }
+    }
+
+    /// Start monitoring `regions`.
+    ///
+    /// The ICM first computes the reference digest of every region, then
+    /// switches to compare mode and keeps rehashing the regions in a loop.
+    /// A modified region raises its digest mismatch flag.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `regions` is empty or holds more than [`NUM_REGIONS`]
+    /// regions.
+    pub fn monitor(self, regions: &[Region]) -> Monitor {
+        assert!(!regions.is_empty() && regions.len() <= NUM_REGIONS);
+
+        let icm = &self.icm;
+        reset(icm);
+        // Switch to compare mode once the reference digests are computed
+        icm.cfg.write(|w| w.ascd().set_bit());
+
+        for (i, region) in regions.iter().enumerate() {
+            let mut rcfg = (region.algorithm.ualgo() as u32) << RCFG_ALGO_SHIFT;
+            if i == regions.len() - 1 {
+                rcfg |= RCFG_WRAP;
+            }
+            // SAFETY: We own the ICM, and therefore the descriptor area
+            unsafe {
+                ptr::write_volatile(
+                    ptr::addr_of_mut!(DESCRIPTORS.0[i]),
+                    RegionDescriptor {
+                        raddr: region.data.as_ptr() as u32,
+                        rcfg,
+                        rctrl: (region.data.len() / BLOCK_SIZE - 1) as u32,
+                        rnext: 0,
+                    },
+                );
+            }
+        }
+
+        icm.ctrl.write(|w| w.enable().set_bit());
+        Monitor {
+            icm: self,
+            regions: regions.len(),
+        }
+    }
+}
+
+/// Memory region monitored by the ICM
+#[derive(Clone, Copy)]
+pub struct Region {
+    data: &'static [u8],
+    algorithm: HashAlgorithm,
+}
+
+impl Region {
+    /// Create a new region.
+    ///
+    /// `data` must be word-aligned, and its length must be a non-zero
+    /// multiple of [`BLOCK_SIZE`], up to 4 MiB. The region digest is computed
+    /// without padding, so it is not the standard SHA digest of `data`.
+    pub fn new(data: &'static [u8], algorithm: HashAlgorithm) -> Result<Self, Error> {
+        let len = data.len();
+        if data.as_ptr() as usize & 3 != 0
+            || len == 0
+            || len & (BLOCK_SIZE - 1) != 0
+            || len / BLOCK_SIZE > MAX_REGION_BLOCKS
+        {
+            return Err(Error::InvalidRegion);
+        }
+        Ok(Self { data, algorithm })
+    }
+}
+
+/// Status flags, one bit per region
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Status {
+    /// Region hash completed
+    pub hash_completed: u8,
+    /// Region digest mismatch
+    pub digest_mismatch: u8,
+    /// Region bus error
+    pub bus_error: u8,
+    /// Region wrap condition detected
+    pub wrap: u8,
+    /// Region end bit condition detected
+    pub end: u8,
+    /// Region status updated
+    pub status_updated: u8,
+    /// Undefined register access detected
+    pub undefined_access: bool,
+}
+
+/// ICM in region monitoring mode, returned by [`Icm::monitor`]
+pub struct Monitor {
+    icm: Icm,
+    regions: usize,
+}
+
+impl Monitor {
+    fn region_mask(&self) -> u8 {
+        (1 << self.regions) - 1
+    }
+
+    /// Read the status flags. Reading the flags clears them.
+    pub fn status(&mut self) -> Status {
+        let isr = self.icm.icm.isr.read();
+        Status {
+            hash_completed: isr.rhc().bits(),
+            digest_mismatch: isr.rdm().bits(),
+            bus_error: isr.rbe().bits(),
+            wrap: isr.rwc().bits(),
+            end: isr.rec().bits(),
+            status_updated: isr.rsu().bits(),
+            undefined_access: isr.urad().bit_is_set(),
+        }
+    }
+
+    /// Enable the digest mismatch and bus error interrupts of every
+    /// monitored region
+    pub fn enable_mismatch_interrupt(&mut self) {
+        let mask = self.region_mask();
+        self.icm.icm.ier.write(|w| unsafe {
+            w.rdm().bits(mask);
+            w.rbe().bits(mask)
+        });
+    }
+
+    /// Disable the digest mismatch and bus error interrupts
+    pub fn disable_mismatch_interrupt(&mut self) {
+        let mask = self.region_mask();
+        self.icm.icm.idr.write(|w| unsafe {
+            w.rdm().bits(mask);
+            w.rbe().bits(mask)
+        });
+    }
+
+    /// Stop monitoring `region`
+    pub fn disable_region(&mut self, region: usize) {
+        assert!(region < self.regions);
+        self.icm
+            .icm
+            .ctrl
+            .write(|w| unsafe { w.rmdis().bits(1 << region) });
+    }
+
+    /// Resume monitoring `region`
+    pub fn enable_region(&mut self, region: usize) {
+        assert!(region < self.regions);
+        self.icm
+            .icm
+            .ctrl
+            .write(|w| unsafe { w.rmen().bits(1 << region) });
+    }
+
+    /// Recompute the reference digest of `region`, for instance after it
+    /// was legitimately updated. The region must be disabled first.
+    pub fn rehash(&mut self, region: usize) {
+        assert!(region < self.regions);
+        self.icm
+            .icm
+            .ctrl
+            .write(|w| unsafe { w.rehash().bits(1 << region) });
+    }
+
+    /// Reference digest of `region`. Only the first
+    /// [`output_size`](HashAlgorithm::output_size) bytes are meaningful.
+    pub fn reference_digest(&self, region: usize) -> [u8; 32] {
+        assert!(region < self.regions);
+        read_hash(region)
+    }
+
+    /// Stop monitoring and return the ICM
+    pub fn stop(self) -> Icm {
+        self.icm.icm.idr.write(|w| unsafe {
+            w.rhc().bits(0xf);
+            w.rdm().bits(0xf);
+            w.rbe().bits(0xf);
+            w.rwc().bits(0xf);
+            w.rec().bits(0xf);
+            w.rsu().bits(0xf);
+            w.urad().set_bit()
+        });
+        disable(&self.icm.icm);
+        reset(&self.icm.icm);
+        self.icm
+    }
+}
+
+/// Token allowing the [`IcmDigest`] hashers to use the ICM
+pub struct DigestEngine {
+    icm: Icm,
+}
+
+impl DigestEngine {
+    /// Stop the engine and return the ICM. Using a hasher afterwards panics.
+    pub fn free(self) -> Icm {
+        ENGINE.store(false, Ordering::Release);
+        self.icm
+    }
+}
+
+/// Run `f` with exclusive access to the ICM registers
+///
+/// # Panics
+///
+/// Panics if no [`DigestEngine`] exists.
+fn with_engine<R>(f: impl FnOnce(&RegisterBlock) -> R) -> R {
+    cortex_m::interrupt::free(|_| {
+        assert!(
+            ENGINE.load(Ordering::Acquire),
+            "ICM hashers require a DigestEngine"
+        );
+        // SAFETY: The DigestEngine owns the ICM, and we are in a critical
+        // section, so no other hasher is using it.
+        f(unsafe { &*ICM::ptr() })
+    })
+}
+
+/// Incremental SHA hasher backed by the ICM.
+///
+/// Requires a [`DigestEngine`]; see the [module documentation](self).
+pub struct IcmDigest<A: Algorithm> {
+    state: [u8; 32],
+    buffer: [u8; BLOCK_SIZE],
+    buffered: usize,
+    length: u64,
+    _algorithm: PhantomData<A>,
+}
+
+/// SHA-1 hasher backed by the ICM
+pub type IcmSha1 = IcmDigest<Sha1>;
+/// SHA-224 hasher backed by the ICM
+pub type IcmSha224 = IcmDigest<Sha224>;
+/// SHA-256 hasher backed by the ICM
+pub type IcmSha256 = IcmDigest<Sha256>;
+
+impl<A: Algorithm> IcmDigest<A> {
+    /// Create a new hasher
+    pub fn new() -> Self {
+        Self {
+            state: A::INITIAL_STATE,
+            buffer: [0; BLOCK_SIZE],
+            buffered: 0,
+            length: 0,
+            _algorithm: PhantomData,
+        }
+    }
+
+    /// Feed `data` to the hasher
+    ///
+    /// # Panics
+    ///
+    /// Panics if no [`DigestEngine`] exists, or if the ICM reports a bus
+    /// error.
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u64;
+
+        while !data.is_empty() {
+            let n = core::cmp::min(BLOCK_SIZE - self.buffered, data.len());
+            self.buffer[self.buffered..self.buffered + n].copy_from_slice(&data[..n]);
+            self.buffered += n;
+            data = &data[n..];
+
+            if self.buffered == BLOCK_SIZE {
+                let (state, buffer) = (&mut self.state, &self.buffer);
+                with_engine(|icm| compress_block(icm, A::COMPRESSION, state, buffer))
+                    .expect("ICM bus error");
+                self.buffered = 0;
+            }
+        }
+    }
+
+    /// Compute the digest of the data fed so far
+    ///
+    /// # Panics
+    ///
+    /// Panics if no [`DigestEngine`] exists, or if the ICM reports a bus
+    /// error.
+    pub fn finalize(mut self) -> A::Output {
+        let tail = &self.buffer[..self.buffered];
+        let (state, length) = (&mut self.state, self.length);
+        with_engine(|icm| finish::<A>(icm, state, tail, length)).expect("ICM bus error")
+    }
+
+    /// Reset the hasher to its initial state
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl<A: Algorithm> Default for IcmDigest<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Algorithm> Clone for IcmDigest<A> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state,
+            buffer: self.buffer,
+            buffered: self.buffered,
+            length: self.length,
+            _algorithm: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "crypto")]
+mod digest_traits {
+    use super::*;
+    use digest::consts::{U20, U28, U32, U64};
+    use digest::{BlockInput, FixedOutputDirty, Reset, Update};
+
+    impl<A: Algorithm> BlockInput for IcmDigest<A> {
+        type BlockSize = U64;
+    }
+
+    impl<A: Algorithm> Update for IcmDigest<A> {
+        fn update(&mut self, data: impl AsRef<[u8]>) {
+            IcmDigest::update(self, data.as_ref());
+        }
+    }
+
+    impl<A: Algorithm> Reset for IcmDigest<A> {
+        fn reset(&mut self) {
+            IcmDigest::reset(self);
+        }
+    }
+
+    macro_rules! fixed_output {
+        ($($algorithm:ident: $size:ident),+) => {
+            $(
+                impl FixedOutputDirty for IcmDigest<$algorithm> {
+                    type OutputSize = $size;
+
+                    fn finalize_into_dirty(&mut self, out: &mut digest::Output<Self>) {
+                        out.copy_from_slice(&self.clone().finalize());
+                    }
+                }
+            )+
+        };
+    }
+
+    fixed_output!(Sha1: U20, Sha224: U28, Sha256: U32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initial_state_byte_order() {
+        assert_eq!(&Sha256::INITIAL_STATE[..4], &[0x6a, 0x09, 0xe6, 0x67]);
+        assert_eq!(&Sha1::INITIAL_STATE[16..20], &[0xc3, 0xd2, 0xe1, 0xf0]);
+        assert_eq!(&Sha1::INITIAL_STATE[20..], &[0; 12]);
+    }
+
+    #[test]
+    fn pad_single_block() {
+        let mut out = [0xaa; 2 * BLOCK_SIZE];
+        let blocks = pad(b"abc", 3, &mut out);
+        assert_eq!(blocks, 1);
+        assert_eq!(&out[..4], &[b'a', b'b', b'c', 0x80]);
+        assert!(out[4..56].iter().all(|&b| b == 0));
+        assert_eq!(&out[56..64], &24u64.to_be_bytes());
+    }
+
+    #[test]
+    fn pad_spills_into_second_block() {
+        let tail = [0x11; 56];
+        let mut out = [0; 2 * BLOCK_SIZE];
+        let blocks = pad(&tail, 120, &mut out);
+        assert_eq!(blocks, 2);
+        assert_eq!(out[56], 0x80);
+        assert!(out[57..120].iter().all(|&b| b == 0));
+        assert_eq!(&out[120..], &960u64.to_be_bytes());
+    }
+
+    #[test]
+    fn pad_empty_message() {
+        let mut out = [0; 2 * BLOCK_SIZE];
+        assert_eq!(pad(&[], 0, &mut out), 1);
+        assert_eq!(out[0], 0x80);
+        assert!(out[1..64].iter().all(|&b| b == 0));
+    }
+}
@@ -1,8 +1,19 @@
+pub mod aes;
+pub mod backup;
 pub mod calibration;
 pub mod clock;
+pub mod cmcc;
 pub mod eic;
+pub mod freqm;
+pub mod icm;
+pub mod nvm;
+pub mod pcc;
+pub mod pdec;
+pub mod power;
+pub mod pukcc;
 pub mod qspi;
 pub(crate) mod sercom;
+pub mod supply;
 pub mod timer;
 pub mod trng;
 
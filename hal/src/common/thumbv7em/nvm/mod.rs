@@ -0,0 +1,184 @@
+//! # Non-volatile Memory Controller
+//!
+//! The NVMCTRL peripheral manages the on-chip flash, the NVM user page and
+//! the SmartEEPROM emulation. This module wraps the peripheral in an [`Nvm`]
+//! struct which issues commands to the controller and reports their errors.
+//!
+//! # SmartEEPROM
+//!
+//! The SmartEEPROM is a hardware-managed, wear-levelled EEPROM emulation
+//! backed by the last flash blocks of the active bank. Its size is set by the
+//! `SBLK` and `PSZ` fuses of the NVM user page, which are only sampled at
+//! startup. See the [`smart_eeprom`] module for more information.
+//!
+//! # User row
+//!
+//! The startup fuses stored in the NVM user page can be read and updated
+//! through [`Nvm::user_row`] and [`Nvm::write_user_row`]. See the [`user_row`]
+//! module for more information.
+
+pub mod smart_eeprom;
+pub mod user_row;
+
+use crate::target_device::nvmctrl::ctrlb::CMD_AW;
+use crate::target_device::NVMCTRL;
+use core::ptr;
+
+/// Start address of the NVM user page
+pub const USER_PAGE_ADDR: u32 = 0x0080_4000;
+
+/// Size of the NVM user page, in 32-bit words
+pub const USER_PAGE_WORDS: usize = 128;
+
+/// Number of 32-bit words written by a single Write Quad Word command
+const QUAD_WORD: usize = 4;
+
+/// Errors reported by the NVM controller
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// The command targeted an invalid address
+    Address,
+    /// The command could not be executed because of a programming error
+    Programming,
+    /// The command targeted a locked region
+    Lock,
+    /// The controller reported an NVM error
+    Nvm,
+}
+
+/// Non-volatile memory controller
+pub struct Nvm {
+    nvm: NVMCTRL,
+}
+
+impl Nvm {
+    /// Create a new NVM controller.
+    ///
+    /// NVMCTRL is clocked by default, so there is no need to enable its
+    /// clocks in MCLK.
+    pub fn new(nvm: NVMCTRL) -> Self {
+        Self { nvm }
+    }
+
+    /// Release the underlying peripheral
+    pub fn free(self) -> NVMCTRL {
+        self.nvm
+    }
+
+    /// Returns `true` if the controller is ready to accept a new command
+    #[inline]
+    pub fn is_ready(&self) -> bool {
+        self.nvm.status.read().ready().bit_is_set()
+    }
+
+    #[inline]
+    fn wait_ready(&self) {
+        while !self.is_ready() {}
+    }
+
+    /// Read back and clear the error flags raised by the last command
+    fn take_error(&mut self) -> Result<(), Error> {
+        let flags = self.nvm.intflag.read();
+        let result = if flags.addre().bit_is_set() {
+            Err(Error::Address)
+        } else if flags.proge().bit_is_set() {
+            Err(Error::Programming)
+        } else if flags.locke().bit_is_set() {
+            Err(Error::Lock)
+        } else if flags.nvme().bit_is_set() {
+            Err(Error::Nvm)
+        } else {
+            Ok(())
+        };
+
+        self.nvm.intflag.write(|w| {
+            w.addre().set_bit();
+            w.proge().set_bit();
+            w.locke().set_bit();
+            w.nvme().set_bit()
+        });
+
+        result
+    }
+
+    /// Issue a command and block until the controller is ready again.
+    ///
+    /// If `addr` is `Some`, the ADDR register is updated before the command
+    /// is issued.
+    fn command(&mut self, cmd: CMD_AW, addr: Option<u32>) -> Result<(), Error> {
+        self.wait_ready();
+        // Discard flags left over by a previous operation
+        let _ = self.take_error();
+
+        if let Some(addr) = addr {
+            self.nvm.addr.write(|w| unsafe { w.addr().bits(addr) });
+        }
+        self.nvm.ctrlb.write(|w| {
+            w.cmdex().key();
+            w.cmd().variant(cmd)
+        });
+
+        self.wait_ready();
+        self.take_error()
+    }
+
+    /// Read the whole NVM user page
+    pub fn read_user_page(&self) -> [u32; USER_PAGE_WORDS] {
+        let mut page = [0; USER_PAGE_WORDS];
+        for (i, word) in page.iter_mut().enumerate() {
+            // SAFETY: The user page is always mapped and readable
+            *word = unsafe { ptr::read_volatile((USER_PAGE_ADDR as *const u32).add(i)) };
+        }
+        page
+    }
+
+    /// Erase the NVM user page and program it with `page`.
+    ///
+    /// The user page contains the startup fuses as well as factory
+    /// calibration values. Callers must start from the contents returned by
+    /// [`read_user_page`](Nvm::read_user_page) and only modify the bits they
+    /// own. Changes take effect after the next reset.
+    ///
+    /// # Safety
+    ///
+    /// Factory calibration bits must be written back unmodified. Writing
+    /// invalid fuse values may also brick the device, for instance by enabling
+    /// the BOD33 at a level above the supply voltage. Prefer
+    /// [`write_user_row`](Nvm::write_user_row) to update the fuses.
+    pub unsafe fn write_user_page(&mut self, page: &[u32; USER_PAGE_WORDS]) -> Result<(), Error> {
+        // Manual write mode: the page buffer is only committed by WQW. The
+        // previous mode is restored for the other writes.
+        self.wait_ready();
+        let wmode = self.nvm.ctrla.read().wmode().bits();
+        self.nvm.ctrla.modify(|_, w| w.wmode().man());
+
+        let result = self.program_user_page(page);
+
+        self.wait_ready();
+        self.nvm.ctrla.modify(|_, w| w.wmode().bits(wmode));
+        result
+    }
+
+    /// Erase and program the user page, in manual write mode
+    unsafe fn program_user_page(&mut self, page: &[u32; USER_PAGE_WORDS]) -> Result<(), Error> {
+        self.command(CMD_AW::EP, Some(USER_PAGE_ADDR))?;
+
+        for (i, quad) in page.chunks(QUAD_WORD).enumerate() {
+            // Erased flash reads all ones; skip programming quad words that
+            // would not change anything.
+            if quad.iter().all(|&w| w == 0xFFFF_FFFF) {
+                continue;
+            }
+
+            let addr = USER_PAGE_ADDR + (i * QUAD_WORD * 4) as u32;
+            self.command(CMD_AW::PBC, None)?;
+            for (j, &word) in quad.iter().enumerate() {
+                // Writing to the NVM address space loads the page buffer
+                ptr::write_volatile((addr as *mut u32).add(j), word);
+            }
+            self.command(CMD_AW::WQW, Some(addr))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,379 @@
+//! # SmartEEPROM
+//!
+//! The SmartEEPROM emulates a byte-addressable EEPROM on top of two flash
+//! sectors, each made of `SBLK` 8 KiB blocks at the end of the active flash
+//! bank. The hardware takes care of wear levelling and sector reallocation;
+//! software only has to wait for the controller to be idle before each write.
+//!
+//! # Allocation
+//!
+//! The SmartEEPROM is sized by the `SBLK` and `PSZ` fuses of the NVM user
+//! page, which are sampled at startup. When `SBLK` is zero no flash is
+//! allocated, and [`Nvm::smart_eeprom`] returns [`Error::Disabled`]. Use
+//! [`Nvm::smart_eeprom_allocate`] to program the fuses, then reset the device
+//! for the new size to take effect. Make sure the application does not extend
+//! into the flash blocks reserved for the SmartEEPROM.
+//!
+//! # Locking
+//!
+//! A [`SmartEeprom`] is either [`Locked`] or [`Unlocked`]. A locked
+//! SmartEEPROM can be read, but any write is discarded by the hardware. The
+//! lock state is retrieved from the peripheral when calling
+//! [`Nvm::smart_eeprom`], so both variants are returned through
+//! [`SmartEepromMode`].
+//!
+//! # Write modes
+//!
+//! In [`WriteMode::Unbuffered`] mode every write is immediately committed to
+//! flash. In [`WriteMode::Buffered`] mode writes are held in the page buffer
+//! until a full SmartEEPROM page is written, the page boundary is crossed or
+//! [`SmartEeprom::flush`] is called.
+
+use super::user_row::Error as UserRowError;
+use super::{Error as NvmError, Nvm};
+use crate::target_device::nvmctrl::ctrlb::CMD_AW;
+use crate::typelevel::Sealed;
+use core::marker::PhantomData;
+use core::ptr;
+
+/// Start address of the SmartEEPROM virtual address space
+const SEEPROM_ADDR: u32 = 0x4400_0000;
+
+/// Errors related to the SmartEEPROM
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// No flash is allocated to the SmartEEPROM (`SBLK` fuse is zero)
+    Disabled,
+    /// The `SBLK` fuse holds an unsupported number of blocks
+    InvalidBlockCount(u8),
+    /// The access would go past the end of the SmartEEPROM
+    OutOfBounds,
+    /// The active sector is full and automatic reallocation is disabled, so
+    /// the write was discarded
+    Overflow,
+    /// The underlying NVM command failed
+    Nvm(NvmError),
+}
+
+impl From<NvmError> for Error {
+    fn from(e: NvmError) -> Self {
+        Error::Nvm(e)
+    }
+}
+
+/// SmartEEPROM write mode (SEECFG.WMODE)
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WriteMode {
+    /// Every write is committed to flash immediately
+    Unbuffered,
+    /// Writes are held in the page buffer until the page is full or flushed
+    Buffered,
+}
+
+/// SmartEEPROM configuration, as read back from the NVM controller
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Config {
+    /// Number of 8 KiB flash blocks per sector (`SBLK` fuse)
+    pub blocks_per_sector: u8,
+    /// SmartEEPROM page size, in bytes (derived from the `PSZ` fuse)
+    pub page_size: usize,
+    /// Usable size of the SmartEEPROM, in bytes
+    pub virtual_size: usize,
+    /// Current write mode
+    pub write_mode: WriteMode,
+    /// `true` if automatic sector reallocation is disabled
+    pub auto_realloc_disabled: bool,
+    /// Index of the active sector (0 or 1)
+    pub active_sector: u8,
+}
+
+/// Usable SmartEEPROM sizes
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Size {
+    _512B,
+    _1KB,
+    _2KB,
+    _4KB,
+    _8KB,
+    _16KB,
+    _32KB,
+    _64KB,
+}
+
+impl Size {
+    /// Smallest `(SBLK, PSZ)` fuse pair providing this virtual size
+    fn fuses(self) -> (u8, u8) {
+        match self {
+            Size::_512B => (1, 0),
+            Size::_1KB => (1, 1),
+            Size::_2KB => (1, 2),
+            Size::_4KB => (1, 3),
+            Size::_8KB => (2, 4),
+            Size::_16KB => (3, 5),
+            Size::_32KB => (5, 6),
+            Size::_64KB => (9, 7),
+        }
+    }
+}
+
+/// Page size in bytes encoded by the `PSZ` fuse
+#[inline]
+fn page_size(psz: u8) -> usize {
+    4 << (psz & 0x7)
+}
+
+/// Virtual size in bytes for a given `SBLK` and `PSZ` pair, see table 25-6 of
+/// the datasheet. Returns `None` if `SBLK` is invalid.
+fn virtual_size(sblk: u8, psz: u8) -> Option<usize> {
+    let max = match sblk {
+        0 => return Some(0),
+        1 => 4096,
+        2 => 8192,
+        3..=4 => 16384,
+        5..=8 => 32768,
+        9..=10 => 65536,
+        _ => return None,
+    };
+    Some(core::cmp::min(page_size(psz) * 128, max))
+}
+
+impl Nvm {
+    /// Read the current SmartEEPROM configuration
+    pub fn smart_eeprom_config(&self) -> Config {
+        let seestat = self.nvm.seestat.read();
+        let seecfg = self.nvm.seecfg.read();
+        let sblk = seestat.sblk().bits();
+        let psz = seestat.psz().bits();
+
+        Config {
+            blocks_per_sector: sblk,
+            page_size: page_size(psz),
+            virtual_size: virtual_size(sblk, psz).unwrap_or(0),
+            write_mode: if seecfg.wmode().is_buffered() {
+                WriteMode::Buffered
+            } else {
+                WriteMode::Unbuffered
+            },
+            auto_realloc_disabled: seecfg.aprdis().bit_is_set(),
+            active_sector: seestat.asees().bit() as u8,
+        }
+    }
+
+    /// Retrieve the SmartEEPROM, in its current lock state
+    pub fn smart_eeprom(&mut self) -> Result<SmartEepromMode<'_>, Error> {
+        let seestat = self.nvm.seestat.read();
+        let sblk = seestat.sblk().bits();
+        let virtual_size = match virtual_size(sblk, seestat.psz().bits()) {
+            Some(0) => return Err(Error::Disabled),
+            Some(size) => size,
+            None => return Err(Error::InvalidBlockCount(sblk)),
+        };
+
+        if seestat.lock().bit_is_set() {
+            Ok(SmartEepromMode::Locked(SmartEeprom::new(
+                self,
+                virtual_size,
+            )))
+        } else {
+            Ok(SmartEepromMode::Unlocked(SmartEeprom::new(
+                self,
+                virtual_size,
+            )))
+        }
+    }
+
+    /// Program the `SBLK` and `PSZ` user page fuses so that a SmartEEPROM of
+    /// the requested size is allocated after the next reset. Passing `None`
+    /// deallocates the SmartEEPROM.
+    ///
+    /// Every other bit of the user page is preserved. The caller is
+    /// responsible for resetting the device afterwards, for instance through
+    /// [`SCB::sys_reset`](cortex_m::peripheral::SCB::sys_reset).
+    pub fn smart_eeprom_allocate(&mut self, size: Option<Size>) -> Result<(), Error> {
+        let (sblk, psz) = size.map(Size::fuses).unwrap_or((0, 0));
+        let mut row = self.user_row();
+        row.smart_eeprom_blocks = sblk;
+        row.smart_eeprom_page_size = psz;
+
+        self.write_user_row(&row).map_err(|e| match e {
+            UserRowError::Nvm(e) => Error::Nvm(e),
+            // The fuse values come from `Size::fuses` and are always valid
+            UserRowError::OutOfRange(_) => unreachable!(),
+        })?;
+        Ok(())
+    }
+}
+
+/// Lock state of a [`SmartEeprom`]
+pub trait LockState: Sealed {}
+
+/// The SmartEEPROM can be read and written
+pub struct Unlocked;
+impl Sealed for Unlocked {}
+impl LockState for Unlocked {}
+
+/// The SmartEEPROM can only be read
+pub struct Locked;
+impl Sealed for Locked {}
+impl LockState for Locked {}
+
+/// SmartEEPROM returned by [`Nvm::smart_eeprom`]
+pub enum SmartEepromMode<'a> {
+    Locked(SmartEeprom<'a, Locked>),
+    Unlocked(SmartEeprom<'a, Unlocked>),
+}
+
+/// Byte-addressable view of the SmartEEPROM
+pub struct SmartEeprom<'a, S: LockState> {
+    nvm: &'a mut Nvm,
+    virtual_size: usize,
+    _state: PhantomData<S>,
+}
+
+impl<'a, S: LockState> SmartEeprom<'a, S> {
+    fn new(nvm: &'a mut Nvm, virtual_size: usize) -> Self {
+        Self {
+            nvm,
+            virtual_size,
+            _state: PhantomData,
+        }
+    }
+
+    fn into_state<N: LockState>(self) -> SmartEeprom<'a, N> {
+        SmartEeprom::new(self.nvm, self.virtual_size)
+    }
+
+    /// Usable size of the SmartEEPROM, in bytes
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.virtual_size
+    }
+
+    /// Returns `true` if no byte is usable. A disabled SmartEEPROM cannot be
+    /// retrieved, so this is always `false` in practice.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.virtual_size == 0
+    }
+
+    /// Returns `true` while the SmartEEPROM is busy programming flash
+    #[inline]
+    pub fn is_busy(&self) -> bool {
+        self.nvm.nvm.seestat.read().busy().bit_is_set()
+    }
+
+    #[inline]
+    fn wait_busy(&self) {
+        while self.is_busy() {}
+    }
+
+    fn check_bounds(&self, offset: usize, len: usize) -> Result<(), Error> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.virtual_size => Ok(()),
+            _ => Err(Error::OutOfBounds),
+        }
+    }
+
+    /// Read `buf.len()` bytes starting at `offset`
+    pub fn read(&self, offset: usize, buf: &mut [u8]) -> Result<(), Error> {
+        self.check_bounds(offset, buf.len())?;
+        self.wait_busy();
+        let base = (SEEPROM_ADDR as usize + offset) as *const u8;
+        for (i, byte) in buf.iter_mut().enumerate() {
+            // SAFETY: The address is within the SmartEEPROM address space
+            *byte = unsafe { ptr::read_volatile(base.add(i)) };
+        }
+        Ok(())
+    }
+
+    /// Read a single byte
+    pub fn get(&self, offset: usize) -> Result<u8, Error> {
+        let mut byte = [0];
+        self.read(offset, &mut byte)?;
+        Ok(byte[0])
+    }
+}
+
+impl<'a> SmartEeprom<'a, Unlocked> {
+    /// Write `buf` starting at `offset`.
+    ///
+    /// Bytes which already hold the requested value are skipped, so that
+    /// rewriting unchanged data does not consume flash endurance.
+    pub fn write(&mut self, offset: usize, buf: &[u8]) -> Result<(), Error> {
+        self.check_bounds(offset, buf.len())?;
+        let base = (SEEPROM_ADDR as usize + offset) as *mut u8;
+        for (i, &byte) in buf.iter().enumerate() {
+            self.wait_busy();
+            // SAFETY: The address is within the SmartEEPROM address space
+            unsafe {
+                let addr = base.add(i);
+                if ptr::read_volatile(addr) != byte {
+                    ptr::write_volatile(addr, byte);
+                }
+            }
+            if self.nvm.nvm.intflag.read().seesovf().bit_is_set() {
+                self.nvm.nvm.intflag.write(|w| w.seesovf().set_bit());
+                return Err(Error::Overflow);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a single byte
+    pub fn set(&mut self, offset: usize, byte: u8) -> Result<(), Error> {
+        self.write(offset, &[byte])
+    }
+
+    /// Select the write mode
+    pub fn set_write_mode(&mut self, mode: WriteMode) -> Result<(), Error> {
+        // Pending buffered data must be committed before switching modes
+        self.flush()?;
+        self.nvm.nvm.seecfg.modify(|_, w| match mode {
+            WriteMode::Unbuffered => w.wmode().unbuffered(),
+            WriteMode::Buffered => w.wmode().buffered(),
+        });
+        Ok(())
+    }
+
+    /// Enable or disable automatic sector reallocation
+    pub fn set_auto_realloc(&mut self, enabled: bool) {
+        self.nvm.nvm.seecfg.modify(|_, w| w.aprdis().bit(!enabled));
+    }
+
+    /// Commit data held in the page buffer when in
+    /// [`WriteMode::Buffered`] mode. Does nothing if the page buffer is empty.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.wait_busy();
+        if self.nvm.nvm.seestat.read().load().bit_is_set() {
+            self.nvm.command(CMD_AW::SEEFLUSH, None)?;
+            self.wait_busy();
+        }
+        Ok(())
+    }
+
+    /// Trigger a sector reallocation, compacting the active sector into the
+    /// other one
+    pub fn reallocate(&mut self) -> Result<(), Error> {
+        self.flush()?;
+        self.nvm.command(CMD_AW::SEERALOC, None)?;
+        self.wait_busy();
+        Ok(())
+    }
+
+    /// Lock the SmartEEPROM against writes. Pending buffered data is flushed
+    /// first.
+    pub fn lock(mut self) -> Result<SmartEeprom<'a, Locked>, Error> {
+        self.flush()?;
+        self.nvm.command(CMD_AW::LSEE, None)?;
+        Ok(self.into_state())
+    }
+}
+
+impl<'a> SmartEeprom<'a, Locked> {
+    /// Unlock the SmartEEPROM, allowing writes
+    pub fn unlock(self) -> Result<SmartEeprom<'a, Unlocked>, Error> {
+        self.wait_busy();
+        self.nvm.command(CMD_AW::USEE, None)?;
+        Ok(self.into_state())
+    }
+}
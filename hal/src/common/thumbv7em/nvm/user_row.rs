@@ -0,0 +1,361 @@
+//! # NVM User Row
+//!
+//! The first 96 bits of the NVM user page hold the startup fuses: BOD33
+//! configuration, bootloader protection size, SmartEEPROM size, RAM ECC and
+//! watchdog defaults, and the flash region lock bits. The same words also
+//! contain factory calibration values for BOD12 and other reserved bits which
+//! must never be modified.
+//!
+//! [`UserRow`] is a typed view of the fuses. [`UserRow::parse`] decodes the
+//! raw words, and [`UserRow::serialize`] writes the fields back into existing
+//! raw words, leaving every bit it does not own untouched. Both are pure
+//! functions so they can be tested on the host. Use [`Nvm::user_row`] and
+//! [`Nvm::write_user_row`] to access the fuses on the device. New fuse values
+//! are only sampled at startup, so a reset is required for them to take
+//! effect.
+//!
+//! See SAMD5x/E5x datasheet section 9.4, NVM User Page Mapping.
+
+use super::{Error as NvmError, Nvm, USER_PAGE_ADDR};
+use core::ptr;
+
+/// Number of 32-bit words of the user page holding the fuses
+pub const USER_ROW_WORDS: usize = 3;
+
+/// Fields of the user row holding a numeric value
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Field {
+    Bod33Hysteresis,
+    BootProt,
+    SmartEepromBlocks,
+    SmartEepromPageSize,
+    WdtPeriod,
+    WdtWindow,
+    WdtEarlyWarningOffset,
+}
+
+/// Errors related to the user row
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// The value of a field does not fit in its fuse, or is not supported
+    OutOfRange(Field),
+    /// Programming the user page failed
+    Nvm(NvmError),
+}
+
+impl From<NvmError> for Error {
+    fn from(e: NvmError) -> Self {
+        Error::Nvm(e)
+    }
+}
+
+/// Action taken when the BOD33 detects a brown-out
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Bod33Action {
+    None = 0,
+    Reset = 1,
+    Interrupt = 2,
+    Backup = 3,
+}
+
+impl From<u32> for Bod33Action {
+    fn from(bits: u32) -> Self {
+        match bits & 0x3 {
+            0 => Bod33Action::None,
+            1 => Bod33Action::Reset,
+            2 => Bod33Action::Interrupt,
+            _ => Bod33Action::Backup,
+        }
+    }
+}
+
+/// Position of a fuse within the user row words
+struct Fuse {
+    word: usize,
+    shift: u32,
+    width: u32,
+}
+
+impl Fuse {
+    const fn new(word: usize, shift: u32, width: u32) -> Self {
+        Self { word, shift, width }
+    }
+
+    #[inline]
+    fn mask(&self) -> u32 {
+        if self.width == 32 {
+            0xFFFF_FFFF
+        } else {
+            (1 << self.width) - 1
+        }
+    }
+
+    #[inline]
+    fn get(&self, words: &[u32; USER_ROW_WORDS]) -> u32 {
+        (words[self.word] >> self.shift) & self.mask()
+    }
+
+    #[inline]
+    fn set(&self, words: &mut [u32; USER_ROW_WORDS], value: u32) {
+        let mask = self.mask() << self.shift;
+        words[self.word] = (words[self.word] & !mask) | ((value << self.shift) & mask);
+    }
+}
+
+const BOD33_DISABLE: Fuse = Fuse::new(0, 0, 1);
+const BOD33_LEVEL: Fuse = Fuse::new(0, 1, 8);
+const BOD33_ACTION: Fuse = Fuse::new(0, 9, 2);
+const BOD33_HYST: Fuse = Fuse::new(0, 11, 4);
+const BOOTPROT: Fuse = Fuse::new(0, 26, 4);
+const SEE_SBLK: Fuse = Fuse::new(1, 0, 4);
+const SEE_PSZ: Fuse = Fuse::new(1, 4, 3);
+const RAM_ECCDIS: Fuse = Fuse::new(1, 7, 1);
+const WDT_ENABLE: Fuse = Fuse::new(1, 16, 1);
+const WDT_ALWAYSON: Fuse = Fuse::new(1, 17, 1);
+const WDT_PER: Fuse = Fuse::new(1, 18, 4);
+const WDT_WINDOW: Fuse = Fuse::new(1, 22, 4);
+const WDT_EWOFFSET: Fuse = Fuse::new(1, 26, 4);
+const WDT_WEN: Fuse = Fuse::new(1, 30, 1);
+const NVM_LOCKS: Fuse = Fuse::new(2, 0, 32);
+
+/// Maximum value of the watchdog period, window and offset fuses
+const WDT_MAX: u8 = 11;
+
+/// Size of a bootloader protection step, in bytes
+const BOOTPROT_STEP: u32 = 8 * 1024;
+
+/// Typed view of the NVM user row fuses
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct UserRow {
+    /// BOD33 is disabled at startup
+    pub bod33_disable: bool,
+    /// BOD33 threshold level
+    pub bod33_level: u8,
+    /// Action taken on a BOD33 brown-out
+    pub bod33_action: Bod33Action,
+    /// BOD33 hysteresis (0-15)
+    pub bod33_hysteresis: u8,
+    /// Raw bootloader protection fuse (0-15), see
+    /// [`bootloader_size`](UserRow::bootloader_size)
+    pub bootprot: u8,
+    /// Number of 8 KiB blocks per SmartEEPROM sector (0-10). Zero disables
+    /// the SmartEEPROM.
+    pub smart_eeprom_blocks: u8,
+    /// SmartEEPROM page size code (0-7), the page size is `4 << code` bytes
+    pub smart_eeprom_page_size: u8,
+    /// RAM ECC is disabled
+    pub ram_ecc_disable: bool,
+    /// Watchdog is enabled at startup
+    pub wdt_enable: bool,
+    /// Watchdog is locked in always-on mode at startup
+    pub wdt_always_on: bool,
+    /// Watchdog timeout period (0-11)
+    pub wdt_period: u8,
+    /// Watchdog window mode closed period (0-11)
+    pub wdt_window: u8,
+    /// Watchdog early warning interrupt offset (0-11)
+    pub wdt_early_warning_offset: u8,
+    /// Watchdog window mode is enabled at startup
+    pub wdt_window_enable: bool,
+    /// Flash region lock bits. A cleared bit locks the matching region.
+    pub region_locks: u32,
+}
+
+impl UserRow {
+    /// Decode the fuses from the raw user row words
+    pub fn parse(words: &[u32; USER_ROW_WORDS]) -> Self {
+        Self {
+            bod33_disable: BOD33_DISABLE.get(words) != 0,
+            bod33_level: BOD33_LEVEL.get(words) as u8,
+            bod33_action: Bod33Action::from(BOD33_ACTION.get(words)),
+            bod33_hysteresis: BOD33_HYST.get(words) as u8,
+            bootprot: BOOTPROT.get(words) as u8,
+            smart_eeprom_blocks: SEE_SBLK.get(words) as u8,
+            smart_eeprom_page_size: SEE_PSZ.get(words) as u8,
+            ram_ecc_disable: RAM_ECCDIS.get(words) != 0,
+            wdt_enable: WDT_ENABLE.get(words) != 0,
+            wdt_always_on: WDT_ALWAYSON.get(words) != 0,
+            wdt_period: WDT_PER.get(words) as u8,
+            wdt_window: WDT_WINDOW.get(words) as u8,
+            wdt_early_warning_offset: WDT_EWOFFSET.get(words) as u8,
+            wdt_window_enable: WDT_WEN.get(words) != 0,
+            region_locks: NVM_LOCKS.get(words),
+        }
+    }
+
+    /// Check that every field fits in its fuse
+    pub fn validate(&self) -> Result<(), Error> {
+        let checks = [
+            (self.bod33_hysteresis <= 15, Field::Bod33Hysteresis),
+            (self.bootprot <= 15, Field::BootProt),
+            (self.smart_eeprom_blocks <= 10, Field::SmartEepromBlocks),
+            (self.smart_eeprom_page_size <= 7, Field::SmartEepromPageSize),
+            (self.wdt_period <= WDT_MAX, Field::WdtPeriod),
+            (self.wdt_window <= WDT_MAX, Field::WdtWindow),
+            (
+                self.wdt_early_warning_offset <= WDT_MAX,
+                Field::WdtEarlyWarningOffset,
+            ),
+        ];
+        match checks.iter().find(|(valid, _)| !valid) {
+            Some((_, field)) => Err(Error::OutOfRange(*field)),
+            None => Ok(()),
+        }
+    }
+
+    /// Encode the fuses into `words`.
+    ///
+    /// Only the bits backing a field are modified; factory calibration and
+    /// reserved bits keep the value they had in `words`. Nothing is written
+    /// if a field is out of range.
+    pub fn serialize(&self, words: &mut [u32; USER_ROW_WORDS]) -> Result<(), Error> {
+        self.validate()?;
+
+        BOD33_DISABLE.set(words, self.bod33_disable as u32);
+        BOD33_LEVEL.set(words, self.bod33_level as u32);
+        BOD33_ACTION.set(words, self.bod33_action as u32);
+        BOD33_HYST.set(words, self.bod33_hysteresis as u32);
+        BOOTPROT.set(words, self.bootprot as u32);
+        SEE_SBLK.set(words, self.smart_eeprom_blocks as u32);
+        SEE_PSZ.set(words, self.smart_eeprom_page_size as u32);
+        RAM_ECCDIS.set(words, self.ram_ecc_disable as u32);
+        WDT_ENABLE.set(words, self.wdt_enable as u32);
+        WDT_ALWAYSON.set(words, self.wdt_always_on as u32);
+        WDT_PER.set(words, self.wdt_period as u32);
+        WDT_WINDOW.set(words, self.wdt_window as u32);
+        WDT_EWOFFSET.set(words, self.wdt_early_warning_offset as u32);
+        WDT_WEN.set(words, self.wdt_window_enable as u32);
+        NVM_LOCKS.set(words, self.region_locks);
+
+        Ok(())
+    }
+
+    /// Size of the protected bootloader section, in bytes
+    pub fn bootloader_size(&self) -> u32 {
+        (15 - (self.bootprot & 0xF) as u32) * BOOTPROT_STEP
+    }
+
+    /// Set the size of the protected bootloader section. `size` must be a
+    /// multiple of 8 KiB, up to 120 KiB.
+    pub fn set_bootloader_size(&mut self, size: u32) -> Result<(), Error> {
+        if size & (BOOTPROT_STEP - 1) != 0 || size > 15 * BOOTPROT_STEP {
+            return Err(Error::OutOfRange(Field::BootProt));
+        }
+        self.bootprot = 15 - (size / BOOTPROT_STEP) as u8;
+        Ok(())
+    }
+}
+
+impl Nvm {
+    /// Read the NVM user row fuses
+    pub fn user_row(&self) -> UserRow {
+        let mut words = [0; USER_ROW_WORDS];
+        for (i, word) in words.iter_mut().enumerate() {
+            // SAFETY: The user page is always mapped and readable
+            *word = unsafe { ptr::read_volatile((USER_PAGE_ADDR as *const u32).add(i)) };
+        }
+        UserRow::parse(&words)
+    }
+
+    /// Program the NVM user row fuses.
+    ///
+    /// The user page is read back, the fuses are updated in place and the page
+    /// is only rewritten if its contents changed. Factory calibration bits and
+    /// the rest of the user page are preserved. The new values take effect
+    /// after the next reset.
+    ///
+    /// Take care when enabling the BOD33: a threshold above the supply
+    /// voltage keeps the device in reset until the fuses are restored through
+    /// a debugger.
+    pub fn write_user_row(&mut self, row: &UserRow) -> Result<(), Error> {
+        let mut page = self.read_user_page();
+        let mut words = [0; USER_ROW_WORDS];
+        words.copy_from_slice(&page[..USER_ROW_WORDS]);
+        row.serialize(&mut words)?;
+
+        if words[..] == page[..USER_ROW_WORDS] {
+            return Ok(());
+        }
+        page[..USER_ROW_WORDS].copy_from_slice(&words);
+
+        // SAFETY: Only the bits owned by `UserRow` were modified
+        unsafe { self.write_user_page(&page)? };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Factory default values, see datasheet table 9-2
+    const DEFAULT: [u32; USER_ROW_WORDS] = [0xFE9A_9239, 0xAEEC_FF80, 0xFFFF_FFFF];
+
+    #[test]
+    fn parse_factory_defaults() {
+        let row = UserRow::parse(&DEFAULT);
+        assert!(row.bod33_disable);
+        assert_eq!(row.bod33_level, 0x1C);
+        assert_eq!(row.bod33_action, Bod33Action::Reset);
+        assert_eq!(row.bod33_hysteresis, 2);
+        assert_eq!(row.bootloader_size(), 0);
+        assert_eq!(row.smart_eeprom_blocks, 0);
+        assert_eq!(row.smart_eeprom_page_size, 0);
+        assert!(row.ram_ecc_disable);
+        assert!(!row.wdt_enable);
+        assert!(!row.wdt_always_on);
+        assert_eq!(row.wdt_period, 0xB);
+        assert_eq!(row.wdt_window, 0xB);
+        assert_eq!(row.wdt_early_warning_offset, 0xB);
+        assert!(!row.wdt_window_enable);
+        assert_eq!(row.region_locks, 0xFFFF_FFFF);
+    }
+
+    #[test]
+    fn roundtrip_is_identity() {
+        let mut words = DEFAULT;
+        UserRow::parse(&DEFAULT).serialize(&mut words).unwrap();
+        assert_eq!(words, DEFAULT);
+    }
+
+    #[test]
+    fn serialize_preserves_reserved_bits() {
+        let mut row = UserRow::parse(&DEFAULT);
+        row.bod33_disable = false;
+        row.smart_eeprom_blocks = 1;
+        row.smart_eeprom_page_size = 3;
+        row.set_bootloader_size(16 * 1024).unwrap();
+
+        let mut words = DEFAULT;
+        row.serialize(&mut words).unwrap();
+
+        // BOD12 calibration and reserved bits of word 0
+        let reserved0 = 0xC3FF_8000;
+        // Reserved bits of word 1
+        let reserved1 = 0x8000_FF00;
+        assert_eq!(words[0] & reserved0, DEFAULT[0] & reserved0);
+        assert_eq!(words[1] & reserved1, DEFAULT[1] & reserved1);
+        assert_eq!(UserRow::parse(&words), row);
+        assert_eq!(row.bootprot, 13);
+    }
+
+    #[test]
+    fn out_of_range_fields_are_rejected() {
+        let mut row = UserRow::parse(&DEFAULT);
+        row.smart_eeprom_blocks = 11;
+        let mut words = DEFAULT;
+        assert_eq!(
+            row.serialize(&mut words),
+            Err(Error::OutOfRange(Field::SmartEepromBlocks))
+        );
+        assert_eq!(words, DEFAULT);
+
+        let mut row = UserRow::parse(&DEFAULT);
+        assert_eq!(
+            row.set_bootloader_size(4 * 1024),
+            Err(Error::OutOfRange(Field::BootProt))
+        );
+        row.wdt_period = 12;
+        assert_eq!(row.validate(), Err(Error::OutOfRange(Field::WdtPeriod)));
+    }
+}
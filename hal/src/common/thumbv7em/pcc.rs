@@ -0,0 +1,323 @@
+//! Parallel capture controller
+//!
+//! The PCC samples 8 to 14 data lines on the rising edges of its clock
+//! input while both data enable inputs are high, which is the interface of
+//! camera sensors like the OV7670: PCLK on CLK, VSYNC and HREF on DEN1 and
+//! DEN2. The sampling edge is fixed, so a sensor with a configurable PCLK
+//! polarity must be set to change its data on the falling edges.
+//!
+//! The PCC signals are only available on fixed pins, of function K:
+//!
+//! | Signal    | Pins                          |
+//! |-----------|-------------------------------|
+//! | DEN1      | PA12                          |
+//! | DEN2      | PA13                          |
+//! | CLK       | PA14                          |
+//! | DATA0..7  | PA16..PA23                    |
+//! | DATA8..9  | PB14, PB15                    |
+//! | DATA10..13| PC12..PC15                    |
+//!
+//! With an 8-bit sensor, four samples are packed in every `u32` read from
+//! the PCC. Wider samples are read one per `u16`.
+//!
+//! With the `dma` feature, frames are streamed into a ring of buffers by a
+//! [`RingTransfer`](crate::dmac::RingTransfer). The buffers must hold a whole
+//! frame, for example 38400 words for a 320x240 RGB565 frame:
+//!
+//! ```ignore
+//! static mut FRAMES: [[u32; 38400]; 2] = [[0; 38400]; 2];
+//! static mut DESCRIPTORS: [DmacDescriptor; 1] = [DEFAULT_DESCRIPTOR; 1];
+//!
+//! let pcc = Pcc::new(&mut peripherals.MCLK, peripherals.PCC, pins, PccConfig::default());
+//! let mut frames = pcc.stream(&mut dmac, chan0, unsafe { &mut FRAMES }, unsafe {
+//!     &mut DESCRIPTORS
+//! });
+//! loop {
+//!     if let Some(frame) = frames.next_block(&mut dmac) {
+//!         // Frames completed meanwhile are skipped, see `frame.missed`
+//!         display(frames.buffer(frame.index));
+//!     }
+//! }
+//! ```
+//!
+//! The buffers only stay aligned with the frames if the stream is started
+//! between two frames, while DEN1 is low.
+
+use crate::gpio::v2::{AlternateK, Pin, PA12, PA13, PA14, PA16, PA17, PA18, PA19, PA20};
+use crate::gpio::v2::{PA21, PA22, PA23};
+#[cfg(feature = "min-samd51j")]
+use crate::gpio::v2::{PB14, PB15};
+#[cfg(feature = "min-samd51n")]
+use crate::gpio::v2::{PC12, PC13, PC14, PC15};
+use crate::target_device::{MCLK, PCC};
+use crate::typelevel::Sealed;
+
+#[cfg(feature = "dma")]
+pub use dma::Frames;
+
+/// PCC errors
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// Data was received before the previous data was read
+    Overrun,
+}
+
+//==============================================================================
+//  Pins
+//==============================================================================
+
+/// Data pins DATA0 to DATA7
+pub struct Data8 {
+    pub d0: Pin<PA16, AlternateK>,
+    pub d1: Pin<PA17, AlternateK>,
+    pub d2: Pin<PA18, AlternateK>,
+    pub d3: Pin<PA19, AlternateK>,
+    pub d4: Pin<PA20, AlternateK>,
+    pub d5: Pin<PA21, AlternateK>,
+    pub d6: Pin<PA22, AlternateK>,
+    pub d7: Pin<PA23, AlternateK>,
+}
+
+/// Data pins DATA0 to DATA9
+#[cfg(feature = "min-samd51j")]
+pub struct Data10 {
+    pub low: Data8,
+    pub d8: Pin<PB14, AlternateK>,
+    pub d9: Pin<PB15, AlternateK>,
+}
+
+/// Data pins DATA0 to DATA11
+#[cfg(feature = "min-samd51n")]
+pub struct Data12 {
+    pub low: Data10,
+    pub d10: Pin<PC12, AlternateK>,
+    pub d11: Pin<PC13, AlternateK>,
+}
+
+/// Data pins DATA0 to DATA13
+#[cfg(feature = "min-samd51n")]
+pub struct Data14 {
+    pub low: Data12,
+    pub d12: Pin<PC14, AlternateK>,
+    pub d13: Pin<PC15, AlternateK>,
+}
+
+/// Set of data pins, giving the width of the samples
+pub trait DataPins: Sealed {
+    /// Data read from the PCC
+    #[cfg(feature = "dma")]
+    type Word: crate::dmac::Beat + 'static;
+    #[cfg(not(feature = "dma"))]
+    type Word;
+    #[doc(hidden)]
+    const ISIZE: u8;
+    #[doc(hidden)]
+    const DSIZE: u8;
+    #[doc(hidden)]
+    fn from_rhr(rhr: u32) -> Self::Word;
+}
+
+macro_rules! data_pins {
+    ($($(#[$cfg:meta])* $Data:ident: $Word:ident, isize = $isize:literal, dsize = $dsize:literal;)+) => {
+        $(
+            $(#[$cfg])*
+            impl Sealed for $Data {}
+
+            $(#[$cfg])*
+            impl DataPins for $Data {
+                type Word = $Word;
+                const ISIZE: u8 = $isize;
+                const DSIZE: u8 = $dsize;
+
+                fn from_rhr(rhr: u32) -> $Word {
+                    rhr as $Word
+                }
+            }
+        )+
+    };
+}
+
+data_pins! {
+    // Four samples per word
+    Data8: u32, isize = 0, dsize = 2;
+    // One sample per half-word
+    #[cfg(feature = "min-samd51j")]
+    Data10: u16, isize = 1, dsize = 1;
+    #[cfg(feature = "min-samd51n")]
+    Data12: u16, isize = 2, dsize = 1;
+    #[cfg(feature = "min-samd51n")]
+    Data14: u16, isize = 3, dsize = 1;
+}
+
+/// PCC pins
+pub struct PccPins<D> {
+    /// Data enable 1, like the VSYNC of a camera
+    pub den1: Pin<PA12, AlternateK>,
+    /// Data enable 2, like the HREF of a camera
+    pub den2: Pin<PA13, AlternateK>,
+    /// Sampling clock, like the PCLK of a camera
+    pub clk: Pin<PA14, AlternateK>,
+    /// Data pins
+    pub data: D,
+}
+
+//==============================================================================
+//  Configuration
+//==============================================================================
+
+/// First sample kept by half sampling
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FirstSample {
+    /// Keep the samples of even index
+    Even,
+    /// Keep the samples of odd index
+    Odd,
+}
+
+/// PCC configuration
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub struct PccConfig {
+    /// Restart the packing of the samples when DEN1 goes low, so that every
+    /// frame starts on a new word
+    pub clear_on_den1: bool,
+    /// Restart the packing of the samples when DEN2 goes low, so that every
+    /// line starts on a new word
+    pub clear_on_den2: bool,
+    /// Sample on every clock edge, ignoring DEN1 and DEN2
+    pub always_sample: bool,
+    /// Only keep one sample out of two, like the luminance of YUV data
+    pub half_sampling: Option<FirstSample>,
+}
+
+//==============================================================================
+//  Pcc
+//==============================================================================
+
+/// Parallel capture controller with the data pins `D`
+pub struct Pcc<D: DataPins> {
+    pcc: PCC,
+    pins: PccPins<D>,
+}
+
+impl<D: DataPins> Pcc<D> {
+    /// Configure the PCC. The capture is started by
+    /// [`enable`](Self::enable), or by [`stream`](Self::stream).
+    pub fn new(mclk: &mut MCLK, pcc: PCC, pins: PccPins<D>, config: PccConfig) -> Self {
+        mclk.apbdmask.modify(|_, w| w.pcc_().set_bit());
+
+        let cid = config.clear_on_den1 as u8 | (config.clear_on_den2 as u8) << 1;
+        pcc.mr.write(|w| unsafe {
+            w.isize().bits(D::ISIZE);
+            w.dsize().bits(D::DSIZE);
+            w.alwys().bit(config.always_sample);
+            w.halfs().bit(config.half_sampling.is_some());
+            w.frsts()
+                .bit(config.half_sampling == Some(FirstSample::Odd));
+            w.cid().bits(cid)
+        });
+        // Clear the status flags
+        pcc.isr.read();
+
+        Self { pcc, pins }
+    }
+
+    /// Start capturing
+    pub fn enable(&mut self) {
+        self.pcc.mr.modify(|_, w| w.pcen().set_bit());
+    }
+
+    /// Stop capturing
+    pub fn disable(&mut self) {
+        self.pcc.mr.modify(|_, w| w.pcen().clear_bit());
+    }
+
+    /// Read the next data. An overrun is reported once, and the data
+    /// received after it can be read.
+    pub fn read(&mut self) -> nb::Result<D::Word, Error> {
+        // Reading ISR clears its flags
+        let isr = self.pcc.isr.read();
+        if isr.ovre().bit_is_set() {
+            return Err(nb::Error::Other(Error::Overrun));
+        }
+        if isr.drdy().bit_is_clear() {
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(D::from_rhr(self.pcc.rhr.read().rdata().bits()))
+    }
+
+    /// Returns whether data was lost since the last call, or the last
+    /// [`read`](Self::read)
+    pub fn overrun(&self) -> bool {
+        self.pcc.isr.read().ovre().bit_is_set()
+    }
+
+    /// Enable the data ready interrupt
+    pub fn enable_interrupt(&mut self) {
+        self.pcc.ier.write(|w| w.drdy().set_bit());
+    }
+
+    /// Disable the data ready interrupt
+    pub fn disable_interrupt(&mut self) {
+        self.pcc.idr.write(|w| w.drdy().set_bit());
+    }
+
+    /// Disable the PCC and return the peripheral and pins
+    pub fn free(mut self) -> (PCC, PccPins<D>) {
+        self.disable();
+        (self.pcc, self.pins)
+    }
+}
+
+/// Frame streaming, available when the `dma` feature is enabled
+#[cfg(feature = "dma")]
+mod dma {
+    use super::*;
+    use crate::dmac::{
+        channel::{Channel, Ready},
+        DmaController, DmacDescriptor, RingTransfer, TriggerAction, TriggerSource,
+    };
+
+    /// Frames streamed by [`Pcc::stream`], in a ring of `N`-word buffers
+    pub type Frames<D, const N: usize, const ID: u8> =
+        RingTransfer<<D as DataPins>::Word, Pcc<D>, N, ID>;
+
+    impl<D: DataPins> Pcc<D> {
+        /// Capture into `frames` in turn with DMA channel `chan`, until the
+        /// returned [`Frames`] are stopped. `descriptors` needs an entry per
+        /// frame after the first.
+        ///
+        /// The data of every `N`-word buffer is taken as a frame: `N` must be
+        /// the size of a frame. Lost data is reported by
+        /// [`overrun`](Pcc::overrun) on the payload of the transfer, and frames
+        /// not processed in time by [`Block::missed`](crate::dmac::Block).
+        ///
+        /// # Panics
+        ///
+        /// Panics if `frames` has less than two frames, if `N` is 0 or more than 65535, or if
+        /// `descriptors` is too short.
+        pub fn stream<const N: usize, const ID: u8>(
+            mut self,
+            dmac: &mut DmaController,
+            chan: Channel<Ready, ID>,
+            frames: &'static mut [[D::Word; N]],
+            descriptors: &'static mut [DmacDescriptor],
+        ) -> Frames<D, N, ID> {
+            // SAFETY: RHR is a register which stays valid for the lifetime
+            // of the program. The PCC is moved into the transfer, so no other
+            // code reads it during the stream.
+            let rhr = unsafe { &mut *(self.pcc.rhr.as_ptr() as *mut D::Word) };
+            self.pcc.isr.read();
+            self.enable();
+            RingTransfer::start(
+                dmac,
+                chan,
+                rhr,
+                frames,
+                descriptors,
+                self,
+                TriggerSource::PCC_RX,
+                TriggerAction::BURST,
+            )
+        }
+    }
+}
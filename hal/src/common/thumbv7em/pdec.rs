@@ -0,0 +1,764 @@
+//! Position decoder
+//!
+//! The PDEC decodes the signals of a quadrature encoder or of the three Hall
+//! sensors of a brushless motor in hardware, so that reading an encoder does
+//! not need an interrupt on every edge. It can also run as a plain 16-bit
+//! counter.
+//!
+//! The PDEC inputs are typed to the pins of function G that carry them:
+//!
+//! | Input | Pins                   |
+//! |-------|------------------------|
+//! | QDI0  | PA24, PB18, PB23, PC16 |
+//! | QDI1  | PA25, PB19, PB24, PC17 |
+//! | QDI2  | PB20, PB22, PB25, PC18 |
+//!
+//! In QDEC mode, QDI0 and QDI1 are the phases A and B of the encoder and the
+//! optional QDI2 is its index:
+//!
+//! ```ignore
+//! let pdec_clock = clocks.pdec(&clocks.gclk0()).unwrap();
+//! let pins = QdecPins {
+//!     a: pins.pa24.into_alternate::<G>(),
+//!     b: pins.pa25.into_alternate::<G>(),
+//!     index: NoneT,
+//! };
+//! let encoder = Pdec::qdec(
+//!     &mut peripherals.MCLK,
+//!     peripherals.PDEC,
+//!     pdec_clock,
+//!     pins,
+//!     QdecConfig::default(),
+//! );
+//! let angle = encoder.angle();
+//! ```
+//!
+//! The 16-bit counter of the QDEC mode is split in an angular counter, in the
+//! low bits, and a revolution counter, in the remaining high bits. The index
+//! resets the angular counter once per revolution; without an index, the
+//! angular counter can wrap at a period instead.
+
+use crate::clock::PdecClock;
+use crate::gpio::v2::{AlternateG, Pin, PA24, PA25, PB22, PB23};
+#[cfg(feature = "min-samd51n")]
+use crate::gpio::v2::{PB18, PB19, PB20, PB24, PB25, PC16, PC17, PC18};
+use crate::target_device::pdec::ctrla::{self, CONF_A, MODE_A};
+use crate::target_device::pdec::ctrlbset::CMD_A;
+use crate::target_device::{MCLK, PDEC};
+use crate::typelevel::{NoneT, Sealed};
+
+pub use crate::target_device::pdec::presc::PRESC_A as Prescaler;
+
+//==============================================================================
+//  Pins
+//==============================================================================
+
+/// Pin that can be used as the PDEC input QDI0
+pub trait Qdi0Pin: Sealed {}
+/// Pin that can be used as the PDEC input QDI1
+pub trait Qdi1Pin: Sealed {}
+/// Pin that can be used as the PDEC input QDI2
+pub trait Qdi2Pin: Sealed {}
+
+/// Optional QDI2 pin, implemented for every [`Qdi2Pin`] and for [`NoneT`]
+pub trait OptionalQdi2Pin: Sealed {
+    #[doc(hidden)]
+    const ENABLED: bool;
+}
+
+impl OptionalQdi2Pin for NoneT {
+    const ENABLED: bool = false;
+}
+
+impl<P: Qdi2Pin> OptionalQdi2Pin for P {
+    const ENABLED: bool = true;
+}
+
+macro_rules! pdec_pins {
+    ($($Trait:ident: [$($(#[$cfg:meta])* $Id:ident),+]),+) => {
+        $($(
+            $(#[$cfg])*
+            impl $Trait for Pin<$Id, AlternateG> {}
+        )+)+
+    };
+}
+
+pdec_pins! {
+    Qdi0Pin: [
+        PA24,
+        #[cfg(feature = "min-samd51n")]
+        PB18,
+        PB23,
+        #[cfg(feature = "min-samd51n")]
+        PC16
+    ],
+    Qdi1Pin: [
+        PA25,
+        #[cfg(feature = "min-samd51n")]
+        PB19,
+        #[cfg(feature = "min-samd51n")]
+        PB24,
+        #[cfg(feature = "min-samd51n")]
+        PC17
+    ],
+    Qdi2Pin: [
+        #[cfg(feature = "min-samd51n")]
+        PB20,
+        PB22,
+        #[cfg(feature = "min-samd51n")]
+        PB25,
+        #[cfg(feature = "min-samd51n")]
+        PC18
+    ]
+}
+
+/// Pins of a quadrature encoder
+pub struct QdecPins<A, B, I = NoneT> {
+    /// Phase A, on QDI0
+    pub a: A,
+    /// Phase B, on QDI1
+    pub b: B,
+    /// Index, on QDI2, or [`NoneT`]
+    pub index: I,
+}
+
+/// Pins of the three Hall sensors
+pub struct HallPins<A, B, C> {
+    /// Sensor on QDI0
+    pub a: A,
+    /// Sensor on QDI1
+    pub b: B,
+    /// Sensor on QDI2
+    pub c: C,
+}
+
+//==============================================================================
+//  Configuration
+//==============================================================================
+
+/// Input filter
+///
+/// An edge is only taken into account once the input has been stable for
+/// `length` cycles of the prescaled PDEC clock. A `length` of 0 disables the
+/// filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Filter {
+    pub prescaler: Prescaler,
+    pub length: u8,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            prescaler: Prescaler::DIV1,
+            length: 0,
+        }
+    }
+}
+
+/// Decoding of the quadrature signals
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum QdecMode {
+    /// Count the edges of both phases
+    #[default]
+    X4,
+    /// Count the edges of both phases and detect the quadrature, index and
+    /// missing pulse errors
+    X4Secure,
+    /// Count the edges of phase A, phase B giving the direction
+    X2,
+    /// Count the edges of phase A, phase B giving the direction, and detect
+    /// the quadrature, index and missing pulse errors
+    X2Secure,
+    /// Count the edges of both phases, and correct the angular counter at
+    /// the index
+    AutoCorrection,
+}
+
+impl From<QdecMode> for CONF_A {
+    fn from(mode: QdecMode) -> Self {
+        match mode {
+            QdecMode::X4 => CONF_A::X4,
+            QdecMode::X4Secure => CONF_A::X4S,
+            QdecMode::X2 => CONF_A::X2,
+            QdecMode::X2Secure => CONF_A::X2S,
+            QdecMode::AutoCorrection => CONF_A::AUTOC,
+        }
+    }
+}
+
+/// Period of the position counters
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Period {
+    /// Largest value of the angular counter, one less than the counts per
+    /// revolution
+    pub angle: u16,
+    /// Largest value of the revolution counter
+    pub revolutions: u16,
+}
+
+/// Configuration of the QDEC mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QdecConfig {
+    pub mode: QdecMode,
+    /// Swap the phases A and B, reversing the direction
+    pub swap: bool,
+    /// Invert the phase A, phase B and index inputs
+    pub invert: [bool; 3],
+    /// Length of the angular counter, from 9 to 16 bits. The revolution
+    /// counter has the remaining bits of the 16-bit counter.
+    pub angular_bits: u8,
+    /// Wrap the counters at a period instead of their length
+    pub period: Option<Period>,
+    /// Number of consecutive missing index pulses tolerated before an index
+    /// error, in the secure modes
+    pub max_missing_pulses: u8,
+    pub filter: Filter,
+}
+
+impl Default for QdecConfig {
+    fn default() -> Self {
+        Self {
+            mode: QdecMode::default(),
+            swap: false,
+            invert: [false; 3],
+            angular_bits: 16,
+            period: None,
+            max_missing_pulses: 0,
+            filter: Filter::default(),
+        }
+    }
+}
+
+/// Configuration of the HALL mode
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HallConfig {
+    /// Invert the three Hall inputs
+    pub invert: [bool; 3],
+    pub filter: Filter,
+}
+
+/// Configuration of the COUNTER mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CounterConfig {
+    /// Prescaler of the PDEC clock counted
+    pub prescaler: Prescaler,
+    /// Wrap the counter after this value
+    pub period: Option<u16>,
+}
+
+impl Default for CounterConfig {
+    fn default() -> Self {
+        Self {
+            prescaler: Prescaler::DIV1,
+            period: None,
+        }
+    }
+}
+
+//==============================================================================
+//  Status
+//==============================================================================
+
+/// Error flags
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub struct Errors {
+    /// Invalid transition of the quadrature signals
+    pub quadrature: bool,
+    /// Index at an unexpected position
+    pub index: bool,
+    /// Too many consecutive missing index pulses
+    pub missing_pulse: bool,
+    /// Hall transition outside of the window
+    pub window: bool,
+    /// Invalid Hall code
+    pub hall: bool,
+}
+
+impl Errors {
+    /// Any error flag is set
+    pub fn any(&self) -> bool {
+        self.quadrature || self.index || self.missing_pulse || self.window || self.hall
+    }
+}
+
+/// PDEC interrupt sources
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Interrupt {
+    /// Counter overflow or underflow
+    Overflow,
+    /// Any error flag set
+    Error,
+    /// Direction change
+    Direction,
+    /// Velocity
+    Velocity,
+    /// Match of compare channel 0
+    Match0,
+    /// Match of compare channel 1
+    Match1,
+}
+
+//==============================================================================
+//  Position arithmetic
+//==============================================================================
+
+/// Angular counter of `count`
+fn angle(count: u16, angular_bits: u8) -> u16 {
+    if angular_bits >= 16 {
+        count
+    } else {
+        count & ((1 << angular_bits) - 1)
+    }
+}
+
+/// Revolution counter of `count`
+fn revolutions(count: u16, angular_bits: u8) -> u16 {
+    if angular_bits >= 16 {
+        0
+    } else {
+        count >> angular_bits
+    }
+}
+
+/// Count with the angular counter `angle` and revolution counter
+/// `revolutions`
+fn count(angle: u16, revolutions: u16, angular_bits: u8) -> u16 {
+    if angular_bits >= 16 {
+        angle
+    } else {
+        (revolutions << angular_bits) | (angle & ((1 << angular_bits) - 1))
+    }
+}
+
+/// Number of distinct positions, and the position of `count` among them
+fn position(count: u16, angular_bits: u8, period: Option<Period>) -> (u32, u32) {
+    match period {
+        None => (1 << 16, count as u32),
+        Some(period) => {
+            let per_revolution = period.angle as u32 + 1;
+            let range = per_revolution * (period.revolutions as u32 + 1);
+            let position = revolutions(count, angular_bits) as u32 * per_revolution
+                + angle(count, angular_bits) as u32;
+            (range, position)
+        }
+    }
+}
+
+/// Shortest signed distance from `from` to `to` on positions wrapping at
+/// `range`
+fn wrapping_delta(from: u32, to: u32, range: u32) -> i32 {
+    let delta = (to + range - from % range) % range;
+    if delta > range / 2 {
+        delta as i32 - range as i32
+    } else {
+        delta as i32
+    }
+}
+
+//==============================================================================
+//  Pdec
+//==============================================================================
+
+/// QDEC mode, with the pins of the encoder
+pub struct Qdec<A, B, I> {
+    pins: QdecPins<A, B, I>,
+    angular_bits: u8,
+    period: Option<Period>,
+    last_position: u32,
+}
+
+/// HALL mode, with the pins of the sensors
+pub struct Hall<A, B, C> {
+    pins: HallPins<A, B, C>,
+}
+
+/// COUNTER mode
+pub struct Counter;
+
+/// Position decoder in the mode `M`
+pub struct Pdec<M> {
+    pdec: PDEC,
+    mode: M,
+}
+
+/// Reset the PDEC and write its enable-protected configuration
+fn configure<F>(mclk: &mut MCLK, pdec: &PDEC, f: F)
+where
+    F: FnOnce(&mut ctrla::W) -> &mut ctrla::W,
+{
+    mclk.apbcmask.modify(|_, w| w.pdec_().set_bit());
+
+    pdec.ctrla.write(|w| w.swrst().set_bit());
+    while pdec.syncbusy.read().swrst().bit_is_set() {}
+    pdec.ctrla.write(f);
+}
+
+impl<A: Qdi0Pin, B: Qdi1Pin, I: OptionalQdi2Pin> Pdec<Qdec<A, B, I>> {
+    /// Configure the PDEC in QDEC mode and start decoding
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config.angular_bits` is not between 9 and 16.
+    pub fn qdec(
+        mclk: &mut MCLK,
+        pdec: PDEC,
+        _clock: PdecClock,
+        pins: QdecPins<A, B, I>,
+        config: QdecConfig,
+    ) -> Self {
+        assert!(
+            (9..=16).contains(&config.angular_bits),
+            "PDEC angular counter must have 9 to 16 bits"
+        );
+        configure(mclk, &pdec, |w| unsafe {
+            w.mode().variant(MODE_A::QDEC);
+            w.conf().variant(config.mode.into());
+            w.swap().bit(config.swap);
+            w.peren().bit(config.period.is_some());
+            w.pinen0().set_bit();
+            w.pinen1().set_bit();
+            w.pinen2().bit(I::ENABLED);
+            w.pinven0().bit(config.invert[0]);
+            w.pinven1().bit(config.invert[1]);
+            w.pinven2().bit(config.invert[2]);
+            w.angular().bits(config.angular_bits - 9);
+            w.maxcmp().bits(config.max_missing_pulses.min(15));
+            w
+        });
+
+        let mut pdec = Self {
+            pdec,
+            mode: Qdec {
+                pins,
+                angular_bits: config.angular_bits,
+                period: config.period,
+                last_position: 0,
+            },
+        };
+        pdec.set_filter(config.filter);
+        if let Some(period) = config.period {
+            pdec.set_compare(0, period.angle);
+            pdec.set_compare(1, period.revolutions);
+        }
+        pdec.enable();
+        pdec
+    }
+
+    /// Angular counter, the position within the revolution
+    pub fn angle(&self) -> u16 {
+        angle(self.count(), self.mode.angular_bits)
+    }
+
+    /// Revolution counter
+    pub fn revolutions(&self) -> u16 {
+        revolutions(self.count(), self.mode.angular_bits)
+    }
+
+    /// Set the angular and revolution counters
+    pub fn set_position(&mut self, angle: u16, revolutions: u16) {
+        self.set_count(count(angle, revolutions, self.mode.angular_bits));
+    }
+
+    /// Signed number of counts since the previous call, or since the PDEC
+    /// was configured
+    ///
+    /// Called at a fixed rate, for example from a timer interrupt, this
+    /// captures the velocity of the encoder in counts per period of the
+    /// timer. The encoder must move less than half of the counter range
+    /// between two captures.
+    pub fn capture_velocity(&mut self) -> i32 {
+        let (range, position) = position(self.count(), self.mode.angular_bits, self.mode.period);
+        let delta = wrapping_delta(self.mode.last_position, position, range);
+        self.mode.last_position = position;
+        delta
+    }
+
+    /// Disable the PDEC and return the peripheral and pins
+    pub fn free(self) -> (PDEC, QdecPins<A, B, I>) {
+        self.disable();
+        (self.pdec, self.mode.pins)
+    }
+}
+
+impl<A: Qdi0Pin, B: Qdi1Pin, C: Qdi2Pin> Pdec<Hall<A, B, C>> {
+    /// Configure the PDEC in HALL mode and start decoding
+    pub fn hall(
+        mclk: &mut MCLK,
+        pdec: PDEC,
+        _clock: PdecClock,
+        pins: HallPins<A, B, C>,
+        config: HallConfig,
+    ) -> Self {
+        configure(mclk, &pdec, |w| {
+            w.mode().variant(MODE_A::HALL);
+            w.pinen0().set_bit();
+            w.pinen1().set_bit();
+            w.pinen2().set_bit();
+            w.pinven0().bit(config.invert[0]);
+            w.pinven1().bit(config.invert[1]);
+            w.pinven2().bit(config.invert[2])
+        });
+
+        let mut pdec = Self {
+            pdec,
+            mode: Hall { pins },
+        };
+        pdec.set_filter(config.filter);
+        pdec.enable();
+        pdec
+    }
+
+    /// Current Hall code, with the sensor on QDI0 in bit 0
+    pub fn hall_code(&self) -> u8 {
+        (self.count() & 0b111) as u8
+    }
+
+    /// Disable the PDEC and return the peripheral and pins
+    pub fn free(self) -> (PDEC, HallPins<A, B, C>) {
+        self.disable();
+        (self.pdec, self.mode.pins)
+    }
+}
+
+impl Pdec<Counter> {
+    /// Configure the PDEC as a 16-bit counter of the prescaled PDEC clock,
+    /// and start counting
+    pub fn counter(mclk: &mut MCLK, pdec: PDEC, _clock: PdecClock, config: CounterConfig) -> Self {
+        configure(mclk, &pdec, |w| {
+            w.mode().variant(MODE_A::COUNTER);
+            w.peren().bit(config.period.is_some())
+        });
+
+        let mut pdec = Self {
+            pdec,
+            mode: Counter,
+        };
+        pdec.set_filter(Filter {
+            prescaler: config.prescaler,
+            length: 0,
+        });
+        if let Some(period) = config.period {
+            pdec.set_compare(0, period);
+        }
+        pdec.enable();
+        pdec
+    }
+
+    /// Set the value of compare channel 1, raising [`Interrupt::Match1`]
+    /// when reached
+    pub fn set_match(&mut self, value: u16) {
+        self.set_compare(1, value);
+    }
+
+    /// Disable the PDEC and return the peripheral
+    pub fn free(self) -> PDEC {
+        self.disable();
+        self.pdec
+    }
+}
+
+/// Operations available in any mode
+impl<M> Pdec<M> {
+    fn enable(&mut self) {
+        self.pdec.ctrla.modify(|_, w| w.enable().set_bit());
+        while self.pdec.syncbusy.read().enable().bit_is_set() {}
+        self.start();
+    }
+
+    fn disable(&self) {
+        self.pdec.ctrla.modify(|_, w| w.enable().clear_bit());
+        while self.pdec.syncbusy.read().enable().bit_is_set() {}
+    }
+
+    fn command(&self, command: CMD_A) {
+        self.pdec.ctrlbset.write(|w| w.cmd().variant(command));
+        while self.pdec.syncbusy.read().ctrlb().bit_is_set() {}
+    }
+
+    fn set_compare(&mut self, channel: usize, value: u16) {
+        self.pdec.cc[channel].write(|w| unsafe { w.cc().bits(value) });
+        match channel {
+            0 => while self.pdec.syncbusy.read().cc0().bit_is_set() {},
+            _ => while self.pdec.syncbusy.read().cc1().bit_is_set() {},
+        }
+    }
+
+    /// Set the input filter
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.pdec
+            .presc
+            .write(|w| w.presc().variant(filter.prescaler));
+        while self.pdec.syncbusy.read().presc().bit_is_set() {}
+        self.pdec
+            .filter
+            .write(|w| unsafe { w.filter().bits(filter.length) });
+        while self.pdec.syncbusy.read().filter().bit_is_set() {}
+    }
+
+    /// Start or resume counting
+    pub fn start(&mut self) {
+        self.command(CMD_A::START);
+    }
+
+    /// Stop counting, keeping the counter value
+    pub fn stop(&mut self) {
+        self.command(CMD_A::STOP);
+    }
+
+    /// Counting is stopped
+    pub fn is_stopped(&self) -> bool {
+        self.pdec.status.read().stop().bit_is_set()
+    }
+
+    /// Restart the counter from zero
+    pub fn retrigger(&mut self) {
+        self.command(CMD_A::RETRIGGER);
+    }
+
+    /// Value of the 16-bit counter
+    pub fn count(&self) -> u16 {
+        self.command(CMD_A::READSYNC);
+        while self.pdec.syncbusy.read().count().bit_is_set() {}
+        self.pdec.count.read().count().bits()
+    }
+
+    /// Set the value of the 16-bit counter
+    pub fn set_count(&mut self, count: u16) {
+        self.pdec.count.write(|w| unsafe { w.count().bits(count) });
+        while self.pdec.syncbusy.read().count().bit_is_set() {}
+    }
+
+    /// The counter is counting down
+    pub fn is_counting_down(&self) -> bool {
+        self.pdec.status.read().dir().bit_is_set()
+    }
+
+    /// Read the error flags
+    pub fn errors(&self) -> Errors {
+        let status = self.pdec.status.read();
+        Errors {
+            quadrature: status.qerr().bit_is_set(),
+            index: status.idxerr().bit_is_set(),
+            missing_pulse: status.mperr().bit_is_set(),
+            window: status.winerr().bit_is_set(),
+            hall: status.herr().bit_is_set(),
+        }
+    }
+
+    /// Clear the error flags
+    pub fn clear_errors(&mut self) {
+        self.pdec.status.write(|w| {
+            w.qerr().set_bit();
+            w.idxerr().set_bit();
+            w.mperr().set_bit();
+            w.winerr().set_bit();
+            w.herr().set_bit()
+        });
+    }
+
+    /// Enable an interrupt
+    pub fn enable_interrupt(&mut self, interrupt: Interrupt) {
+        self.pdec.intenset.write(|w| match interrupt {
+            Interrupt::Overflow => w.ovf().set_bit(),
+            Interrupt::Error => w.err().set_bit(),
+            Interrupt::Direction => w.dir().set_bit(),
+            Interrupt::Velocity => w.vlc().set_bit(),
+            Interrupt::Match0 => w.mc0().set_bit(),
+            Interrupt::Match1 => w.mc1().set_bit(),
+        });
+    }
+
+    /// Disable an interrupt
+    pub fn disable_interrupt(&mut self, interrupt: Interrupt) {
+        self.pdec.intenclr.write(|w| match interrupt {
+            Interrupt::Overflow => w.ovf().set_bit(),
+            Interrupt::Error => w.err().set_bit(),
+            Interrupt::Direction => w.dir().set_bit(),
+            Interrupt::Velocity => w.vlc().set_bit(),
+            Interrupt::Match0 => w.mc0().set_bit(),
+            Interrupt::Match1 => w.mc1().set_bit(),
+        });
+    }
+
+    /// The flag of an interrupt is set
+    pub fn is_interrupt(&self, interrupt: Interrupt) -> bool {
+        let flags = self.pdec.intflag.read();
+        match interrupt {
+            Interrupt::Overflow => flags.ovf().bit_is_set(),
+            Interrupt::Error => flags.err().bit_is_set(),
+            Interrupt::Direction => flags.dir().bit_is_set(),
+            Interrupt::Velocity => flags.vlc().bit_is_set(),
+            Interrupt::Match0 => flags.mc0().bit_is_set(),
+            Interrupt::Match1 => flags.mc1().bit_is_set(),
+        }
+    }
+
+    /// Clear the flag of an interrupt
+    pub fn clear_interrupt(&mut self, interrupt: Interrupt) {
+        self.pdec.intflag.write(|w| match interrupt {
+            Interrupt::Overflow => w.ovf().set_bit(),
+            Interrupt::Error => w.err().set_bit(),
+            Interrupt::Direction => w.dir().set_bit(),
+            Interrupt::Velocity => w.vlc().set_bit(),
+            Interrupt::Match0 => w.mc0().set_bit(),
+            Interrupt::Match1 => w.mc1().set_bit(),
+        });
+    }
+}
+
+#[cfg(feature = "unproven")]
+impl<A: Qdi0Pin, B: Qdi1Pin, I: OptionalQdi2Pin> crate::hal::Qei for Pdec<Qdec<A, B, I>> {
+    type Count = u16;
+
+    fn count(&self) -> u16 {
+        Pdec::count(self)
+    }
+
+    fn direction(&self) -> crate::hal::Direction {
+        if self.is_counting_down() {
+            crate::hal::Direction::Downcounting
+        } else {
+            crate::hal::Direction::Upcounting
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_count() {
+        assert_eq!(angle(0x1234, 16), 0x1234);
+        assert_eq!(revolutions(0x1234, 16), 0);
+        // 10-bit angular counter, 6-bit revolution counter
+        assert_eq!(angle(0b000101_1100000011, 10), 0b1100000011);
+        assert_eq!(revolutions(0b000101_1100000011, 10), 5);
+        assert_eq!(count(0b1100000011, 5, 10), 0b000101_1100000011);
+        // The angle does not overflow into the revolutions
+        assert_eq!(count(0x7ff, 1, 10), 0b000001_1111111111);
+    }
+
+    #[test]
+    fn positions_with_period() {
+        // 400 counts per revolution, 4 revolutions
+        let period = Period {
+            angle: 399,
+            revolutions: 3,
+        };
+        assert_eq!(position(count(10, 2, 9), 9, Some(period)), (1600, 810));
+        assert_eq!(position(0xabcd, 12, None), (65536, 0xabcd));
+    }
+
+    #[test]
+    fn velocity_deltas() {
+        assert_eq!(wrapping_delta(100, 150, 65536), 50);
+        assert_eq!(wrapping_delta(150, 100, 65536), -50);
+        // Across the wrap of the counter, in both directions
+        assert_eq!(wrapping_delta(65530, 4, 65536), 10);
+        assert_eq!(wrapping_delta(4, 65530, 65536), -10);
+        assert_eq!(wrapping_delta(1590, 5, 1600), 15);
+        assert_eq!(wrapping_delta(5, 1590, 1600), -15);
+    }
+}
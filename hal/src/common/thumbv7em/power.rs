@@ -0,0 +1,196 @@
+//! Sleep modes and wake-up sources
+//!
+//! The SAMD51/E5x support five sleep modes:
+//!
+//! * In idle mode, the CPU is stopped and any enabled interrupt wakes the
+//!   device up.
+//! * In standby mode, all clocks are stopped except for the peripherals and
+//!   clock sources configured to run in standby; only asynchronous
+//!   interrupts, such as the EIC or the RTC, can wake the device up.
+//! * In hibernate, backup and off modes, most of the device is powered off.
+//!   The device wakes up through a reset, which is why [`deep_sleep`] never
+//!   returns. After the reset, [`backup_exit`] tells what ended the sleep.
+//!
+//! [`sleep`] enters idle or standby mode with interrupts masked, so that the
+//! wake-up reason can be read before the pending interrupt handler runs.
+
+use crate::target_device::pm::sleepcfg::SLEEPMODE_A;
+use crate::target_device::{Interrupt, NVIC, PM, RSTC};
+use crate::target_device::{
+    AC, ADC0, ADC1, DAC, SERCOM0, SERCOM1, SERCOM2, SERCOM3, SERCOM4, SERCOM5, TC0, TC1, TC2, TC3,
+    TCC0, TCC1, TCC2, USB,
+};
+#[cfg(feature = "min-samd51n")]
+use crate::target_device::{SERCOM6, SERCOM7, TC6, TC7};
+#[cfg(feature = "min-samd51j")]
+use crate::target_device::{TC4, TC5, TCC3, TCC4};
+use cortex_m::{asm, interrupt};
+
+/// Sleep modes from which the device wakes up without a reset
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SleepMode {
+    /// The CPU clock is stopped
+    Idle,
+    /// All clocks are stopped, except for those configured to run in standby
+    Standby,
+}
+
+/// Sleep modes from which the device wakes up through a reset
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DeepSleepMode {
+    /// Only the backup domain and the PDSYSRAM retained by the hibernate
+    /// configuration stay powered. Woken up by the RTC or a reset.
+    Hibernate,
+    /// Only the backup domain stays powered. Woken up by the RTC, the
+    /// battery backup power switch or a reset.
+    Backup,
+    /// Everything is powered off. Only a reset wakes the device up.
+    Off,
+}
+
+/// Reason why [`sleep`] returned
+#[derive(Debug, Clone, Copy)]
+pub enum WakeReason {
+    /// One of the wake-up sources is pending
+    Interrupt(Interrupt),
+    /// Another enabled interrupt woke the device up
+    Other,
+}
+
+/// Causes of the exit from hibernate or backup mode, read after the reset
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BackupExit {
+    /// An RTC interrupt
+    pub rtc: bool,
+    /// The battery backup power switch
+    pub battery_switch: bool,
+    /// The device was in hibernate mode
+    pub hibernate: bool,
+}
+
+fn set_mode(pm: &mut PM, mode: SLEEPMODE_A) {
+    pm.sleepcfg.write(|w| w.sleepmode().variant(mode));
+    // The mode must be read back before sleeping
+    while pm.sleepcfg.read().sleepmode().bits() != mode as u8 {}
+}
+
+/// Enter `mode`, and return when one of the interrupts in `wake_sources`, or
+/// any other enabled interrupt, is pending.
+///
+/// The interrupts in `wake_sources` are unmasked in the NVIC beforehand. The
+/// sleep mode is entered with interrupts disabled, and the handler of the
+/// pending interrupt runs right before this function returns.
+pub fn sleep(pm: &mut PM, mode: SleepMode, wake_sources: &[Interrupt]) -> WakeReason {
+    set_mode(
+        pm,
+        match mode {
+            SleepMode::Idle => SLEEPMODE_A::IDLE,
+            SleepMode::Standby => SLEEPMODE_A::STANDBY,
+        },
+    );
+
+    interrupt::free(|_| {
+        for &irq in wake_sources {
+            // SAFETY: Unmasking the wake-up sources is the caller's intent
+            unsafe { NVIC::unmask(irq) };
+        }
+        asm::dsb();
+        asm::wfi();
+        wake_sources
+            .iter()
+            .find(|&&irq| NVIC::is_pending(irq))
+            .map_or(WakeReason::Other, |&irq| WakeReason::Interrupt(irq))
+    })
+}
+
+/// Enter `mode`. The device wakes up through a reset.
+pub fn deep_sleep(pm: &mut PM, mode: DeepSleepMode) -> ! {
+    set_mode(
+        pm,
+        match mode {
+            DeepSleepMode::Hibernate => SLEEPMODE_A::HIBERNATE,
+            DeepSleepMode::Backup => SLEEPMODE_A::BACKUP,
+            DeepSleepMode::Off => SLEEPMODE_A::OFF,
+        },
+    );
+    loop {
+        asm::dsb();
+        asm::wfi();
+    }
+}
+
+/// Returns the causes of the exit from hibernate or backup mode
+pub fn backup_exit(rstc: &RSTC) -> BackupExit {
+    let bkupexit = rstc.bkupexit.read();
+    BackupExit {
+        rtc: bkupexit.rtc().bit_is_set(),
+        battery_switch: bkupexit.bbps().bit_is_set(),
+        hibernate: bkupexit.hib().bit_is_set(),
+    }
+}
+
+/// Peripherals which can keep running in standby mode.
+///
+/// Running in standby also requires the generic clock of the peripheral to
+/// run in standby.
+pub trait RunInStandby {
+    /// Keep the peripheral running in standby mode. This setting is
+    /// enable-protected on most peripherals, so it must be changed while
+    /// the peripheral is disabled.
+    fn run_in_standby(&mut self, enabled: bool);
+}
+
+macro_rules! run_in_standby {
+    ($($peripheral:ident $(. $view:ident())?),+) => {
+        $(
+impl RunInStandby for $peripheral {
+    fn run_in_standby(&mut self, enabled: bool) {
+        self$(.$view())?.ctrla.modify(|_, w| w.runstdby().bit(enabled));
+    }
+}
+        )+
+    }
+}
+
+run_in_standby!(ADC0, ADC1, TCC0, TCC1, TCC2, USB.device());
+run_in_standby!(
+    SERCOM0.usart_int(),
+    SERCOM1.usart_int(),
+    SERCOM2.usart_int(),
+    SERCOM3.usart_int(),
+    SERCOM4.usart_int(),
+    SERCOM5.usart_int(),
+    TC0.count16(),
+    TC1.count16(),
+    TC2.count16(),
+    TC3.count16()
+);
+
+#[cfg(feature = "min-samd51j")]
+run_in_standby!(TC4.count16(), TC5.count16(), TCC3, TCC4);
+
+#[cfg(feature = "min-samd51n")]
+run_in_standby!(
+    SERCOM6.usart_int(),
+    SERCOM7.usart_int(),
+    TC6.count16(),
+    TC7.count16()
+);
+
+impl RunInStandby for AC {
+    /// Applies to both comparators
+    fn run_in_standby(&mut self, enabled: bool) {
+        for compctrl in self.compctrl.iter() {
+            compctrl.modify(|_, w| w.runstdby().bit(enabled));
+        }
+    }
+}
+
+impl RunInStandby for DAC {
+    /// Applies to both channels
+    fn run_in_standby(&mut self, enabled: bool) {
+        for dacctrl in self.dacctrl.iter() {
+            dacctrl.modify(|_, w| w.runstdby().bit(enabled));
+        }
+    }
+}
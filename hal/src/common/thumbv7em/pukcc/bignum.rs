@@ -0,0 +1,181 @@
+//! Helpers on big-endian unsigned integers stored in byte slices
+
+use core::cmp::Ordering;
+
+/// Compare two numbers of the same length
+pub(super) fn cmp(a: &[u8], b: &[u8]) -> Ordering {
+    debug_assert_eq!(a.len(), b.len());
+    a.cmp(b)
+}
+
+pub(super) fn is_zero(a: &[u8]) -> bool {
+    a.iter().all(|&b| b == 0)
+}
+
+pub(super) fn is_one(a: &[u8]) -> bool {
+    match a.split_last() {
+        Some((&last, rest)) => last == 1 && is_zero(rest),
+        None => false,
+    }
+}
+
+/// Add `value` to `a` in place. Returns `true` on overflow.
+pub(super) fn add_small(a: &mut [u8], value: u8) -> bool {
+    let mut carry = value as u16;
+    for byte in a.iter_mut().rev() {
+        if carry == 0 {
+            break;
+        }
+        let sum = *byte as u16 + carry;
+        *byte = sum as u8;
+        carry = sum >> 8;
+    }
+    carry != 0
+}
+
+/// Subtract `value` from `a` in place. Returns `true` on underflow.
+pub(super) fn sub_small(a: &mut [u8], value: u8) -> bool {
+    let mut borrow = value as i16;
+    for byte in a.iter_mut().rev() {
+        if borrow == 0 {
+            break;
+        }
+        let diff = *byte as i16 - borrow;
+        *byte = diff as u8;
+        borrow = if diff < 0 { 1 } else { 0 };
+    }
+    borrow != 0
+}
+
+/// Number of trailing zero bits
+pub(super) fn trailing_zeros(a: &[u8]) -> usize {
+    let mut zeros = 0;
+    for &byte in a.iter().rev() {
+        if byte != 0 {
+            return zeros + byte.trailing_zeros() as usize;
+        }
+        zeros += 8;
+    }
+    zeros
+}
+
+/// Shift `a` right by `bits` in place
+pub(super) fn shift_right(a: &mut [u8], bits: usize) {
+    let bytes = bits / 8;
+    let bits = bits % 8;
+    let len = a.len();
+
+    for i in (0..len).rev() {
+        let hi = if i >= bytes { a[i - bytes] } else { 0 };
+        let lo = if i > bytes { a[i - bytes - 1] } else { 0 };
+        a[i] = if bits == 0 {
+            hi
+        } else {
+            (hi >> bits) | (lo << (8 - bits))
+        };
+    }
+}
+
+/// Number of significant bits
+pub(super) fn bit_length(a: &[u8]) -> usize {
+    match a.iter().position(|&b| b != 0) {
+        Some(i) => (a.len() - i) * 8 - a[i].leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+/// Clear every bit of `a` above `bits`
+pub(super) fn truncate_bits(a: &mut [u8], bits: usize) {
+    let len = a.len();
+    for (i, byte) in a.iter_mut().enumerate() {
+        let position = (len - i) * 8;
+        if position <= bits {
+            break;
+        }
+        let keep = bits.saturating_sub(position - 8);
+        *byte &= ((1u16 << keep) - 1) as u8;
+    }
+}
+
+/// Remainder of the division of `a` by a small number
+pub(super) fn rem_small(a: &[u8], divisor: u32) -> u32 {
+    a.iter()
+        .fold(0, |rem, &byte| ((rem << 8) | byte as u32) % divisor)
+}
+
+/// Convert a message hash to an ECDSA scalar of `out.len()` bytes, keeping
+/// its leftmost bytes
+pub(super) fn hash_to_scalar(hash: &[u8], out: &mut [u8]) {
+    if hash.len() >= out.len() {
+        out.copy_from_slice(&hash[..out.len()]);
+    } else {
+        let (zeros, rest) = out.split_at_mut(out.len() - hash.len());
+        zeros.iter_mut().for_each(|b| *b = 0);
+        rest.copy_from_slice(hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_and_sub_propagate_carries() {
+        let mut a = [0x00, 0xff, 0xff];
+        assert!(!add_small(&mut a, 2));
+        assert_eq!(a, [0x01, 0x00, 0x01]);
+        assert!(!sub_small(&mut a, 2));
+        assert_eq!(a, [0x00, 0xff, 0xff]);
+
+        let mut b = [0xff, 0xff];
+        assert!(add_small(&mut b, 1));
+        assert_eq!(b, [0, 0]);
+        assert!(sub_small(&mut b, 1));
+        assert_eq!(b, [0xff, 0xff]);
+    }
+
+    #[test]
+    fn trailing_zeros_and_shift() {
+        let mut a = [0x01, 0x00, 0x40];
+        assert_eq!(trailing_zeros(&a), 6);
+        shift_right(&mut a, 6);
+        assert_eq!(a, [0x00, 0x04, 0x01]);
+        shift_right(&mut a, 10);
+        assert_eq!(a, [0x00, 0x00, 0x01]);
+        assert_eq!(trailing_zeros(&[0, 0]), 16);
+    }
+
+    #[test]
+    fn bit_length_and_truncation() {
+        let mut a = [0xff, 0xff, 0xff];
+        assert_eq!(bit_length(&a), 24);
+        truncate_bits(&mut a, 13);
+        assert_eq!(a, [0x00, 0x1f, 0xff]);
+        assert_eq!(bit_length(&a), 13);
+        assert_eq!(bit_length(&[0, 0]), 0);
+    }
+
+    #[test]
+    fn small_remainder() {
+        // 0x010203 = 66051 = 3 * 22017
+        assert_eq!(rem_small(&[0x01, 0x02, 0x03], 3), 0);
+        assert_eq!(rem_small(&[0x01, 0x02, 0x03], 7), 66051 % 7);
+    }
+
+    #[test]
+    fn comparisons() {
+        assert!(is_one(&[0, 0, 1]));
+        assert!(!is_one(&[1, 0, 1]));
+        assert!(is_zero(&[0, 0]));
+        assert_eq!(cmp(&[1, 0], &[0, 0xff]), Ordering::Greater);
+    }
+
+    #[test]
+    fn hash_is_truncated_or_padded() {
+        let mut out = [0; 4];
+        hash_to_scalar(&[1, 2, 3, 4, 5, 6], &mut out);
+        assert_eq!(out, [1, 2, 3, 4]);
+        hash_to_scalar(&[7, 8], &mut out);
+        assert_eq!(out, [0, 0, 7, 8]);
+    }
+}
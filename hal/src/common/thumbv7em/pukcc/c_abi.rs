@@ -0,0 +1,186 @@
+//! Parameter blocks and entry points of the PUKCL ROM library.
+//!
+//! Every service takes a pointer to a [`Params`] block, made of a common
+//! header and a service-specific parameter struct. Operands are passed as the
+//! 16 lower bits of their address in the crypto RAM.
+
+use core::mem;
+
+/// Common header of every service call
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub(super) struct Header {
+    pub service: u8,
+    pub sub_service: u8,
+    pub option: u16,
+    pub specific: u32,
+    pub status: u16,
+    pub _padding0: u16,
+    pub _padding1: u32,
+}
+
+/// `Specific` flag selecting GF(2^n) arithmetic
+pub(super) const SPECIFIC_GF2N: u32 = 1 << 3;
+
+/// Status written in the header before a call, overwritten by the service
+pub(super) const STATUS_COMPUTATION_NOT_STARTED: u16 = 0xc001;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub(super) struct SelfTest {
+    pub version: u32,
+    pub pukcc_version: u32,
+    pub check_num1: u32,
+    pub check_num2: u32,
+    pub step: u8,
+}
+
+/// Option turning `RedMod` into the reduction constant setup
+pub(super) const REDMOD_SETUP: u16 = 0x0100;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub(super) struct RedMod {
+    pub mod_base: u16,
+    pub cns_base: u16,
+    pub mod_length: u16,
+    pub r_base: u16,
+    pub _padding0: u16,
+    pub _padding1: u16,
+    pub x_base: u16,
+}
+
+/// Option signalling that the exponent is stored in the crypto RAM
+pub(super) const EXPMOD_EXP_IN_PUKCC_RAM: u16 = 0x02;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub(super) struct ExpMod {
+    pub x_base: u16,
+    pub mod_base: u16,
+    pub cns_base: u16,
+    pub precomp_base: u16,
+    pub exp_base: u32,
+    pub mod_length: u16,
+    pub exp_length: u16,
+    pub blinding: u8,
+    pub _padding0: u8,
+    pub _padding1: u16,
+}
+
+/// ECDSA signature generation, over GF(p) or GF(2^n)
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub(super) struct EcDsaGenerate {
+    pub point_a_base: u16,
+    pub order_point_base: u16,
+    pub mod_base: u16,
+    pub cns_base: u16,
+    pub private_key: u16,
+    pub scalar_number: u16,
+    pub a_base: u16,
+    pub hash_base: u16,
+    pub workspace: u16,
+    pub mod_length: u16,
+    pub scalar_length: u16,
+    pub _padding0: u16,
+}
+
+/// ECDSA signature verification, over GF(p) or GF(2^n)
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub(super) struct EcDsaVerify {
+    pub point_a_base: u16,
+    pub order_point_base: u16,
+    pub mod_base: u16,
+    pub cns_base: u16,
+    pub point_public_key: u16,
+    pub point_signature: u16,
+    pub a_base: u16,
+    pub hash_base: u16,
+    pub workspace: u16,
+    pub mod_length: u16,
+    pub scalar_length: u16,
+    pub _padding0: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(super) union ServiceParams {
+    pub self_test: SelfTest,
+    pub red_mod: RedMod,
+    pub exp_mod: ExpMod,
+    pub ecdsa_generate: EcDsaGenerate,
+    pub ecdsa_verify: EcDsaVerify,
+}
+
+/// Parameter block passed to the services
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(super) struct Params {
+    pub header: Header,
+    pub params: ServiceParams,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        // SAFETY: Every parameter struct is plain old data, for which all
+        // zeros is a valid value.
+        unsafe { mem::zeroed() }
+    }
+}
+
+/// Start of the ROM jump table
+const JUMP_TABLE: usize = 0x0200_0001;
+
+/// PUKCL service, identified by its number and jump table entry
+#[derive(Clone, Copy)]
+pub(super) struct Service {
+    number: u8,
+    offset: usize,
+}
+
+pub(super) const SELF_TEST: Service = Service {
+    number: 0x5b,
+    offset: 0x54,
+};
+pub(super) const RED_MOD: Service = Service {
+    number: 0x50,
+    offset: 0x08,
+};
+pub(super) const EXP_MOD: Service = Service {
+    number: 0x6c,
+    offset: 0x80,
+};
+pub(super) const ZP_ECDSA_GENERATE: Service = Service {
+    number: 0x53,
+    offset: 0x28,
+};
+pub(super) const ZP_ECDSA_VERIFY: Service = Service {
+    number: 0x55,
+    offset: 0x2c,
+};
+pub(super) const GF2N_ECDSA_GENERATE: Service = Service {
+    number: 0x70,
+    offset: 0x64,
+};
+pub(super) const GF2N_ECDSA_VERIFY: Service = Service {
+    number: 0x74,
+    offset: 0x44,
+};
+
+impl Service {
+    /// Call the service and return its status code
+    ///
+    /// # Safety
+    ///
+    /// The PUKCC clock must be enabled, and `params` must describe operands
+    /// laid out in the crypto RAM as the service expects.
+    pub(super) unsafe fn call(self, params: &mut Params) -> u16 {
+        params.header.service = self.number;
+        params.header.status = STATUS_COMPUTATION_NOT_STARTED;
+        let entry: extern "C" fn(*mut Params) = mem::transmute(JUMP_TABLE + self.offset);
+        entry(params);
+        params.header.status
+    }
+}
@@ -0,0 +1,175 @@
+//! Crypto RAM management
+//!
+//! The PUKCC services only operate on operands stored in the dedicated 4 KiB
+//! crypto RAM, as little-endian numbers followed by zero padding. Operands
+//! are allocated linearly for the duration of a single high-level operation;
+//! the rest of the crypto RAM is handed to the service as its workspace.
+
+use super::Error;
+use core::ptr;
+
+/// Start address of the crypto RAM
+pub const CRYPTO_RAM_ADDR: usize = 0x0201_1000;
+
+/// Size of the crypto RAM, in bytes
+pub const CRYPTO_RAM_SIZE: usize = 0x1000;
+
+/// PUKCC status register, used to wait for the crypto RAM clearing process
+/// that runs after reset
+const PUKCCSR: *const u32 = 0x4200_302c as *const u32;
+const PUKCCSR_CLRRAM_BUSY: u32 = 1;
+
+/// Wait until the crypto RAM has been cleared after reset
+pub(super) fn wait_ready() {
+    // SAFETY: PUKCCSR is a read-only status register
+    while unsafe { ptr::read_volatile(PUKCCSR) } & PUKCCSR_CLRRAM_BUSY != 0 {}
+}
+
+/// Operand allocated in the crypto RAM
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) struct Operand {
+    /// Offset from the start of the crypto RAM
+    offset: usize,
+    /// Allocated size, in bytes
+    len: usize,
+}
+
+impl Operand {
+    /// Address of the operand, as expected by the services
+    pub(super) fn base(&self) -> u16 {
+        ((CRYPTO_RAM_ADDR + self.offset) & 0xffff) as u16
+    }
+
+    /// Full address of the operand
+    pub(super) fn addr(&self) -> u32 {
+        (CRYPTO_RAM_ADDR + self.offset) as u32
+    }
+
+    /// Operand starting `skip` bytes into `self`
+    pub(super) fn skip(&self, skip: usize) -> Operand {
+        Operand {
+            offset: self.offset + skip,
+            len: self.len - skip,
+        }
+    }
+}
+
+/// Linear allocator over the crypto RAM
+#[derive(Clone)]
+pub(super) struct CryptoRam {
+    next: usize,
+}
+
+impl CryptoRam {
+    /// Start allocating from the beginning of the crypto RAM. Operands
+    /// allocated by a previous `CryptoRam` are overwritten.
+    pub(super) fn new() -> Self {
+        Self { next: 0 }
+    }
+
+    /// Number of bytes left for the workspace
+    pub(super) fn remaining(&self) -> usize {
+        CRYPTO_RAM_SIZE - self.next
+    }
+
+    /// Reserve `len` bytes, rounded up to a whole number of words
+    pub(super) fn alloc(&mut self, len: usize) -> Result<Operand, Error> {
+        let len = (len + 3) & !3;
+        if len > self.remaining() {
+            return Err(Error::OutOfCryptoRam);
+        }
+        let operand = Operand {
+            offset: self.next,
+            len,
+        };
+        self.next += len;
+        Ok(operand)
+    }
+
+    /// Reserve `len` bytes and store the big-endian number `value` in them,
+    /// in little-endian order and zero-padded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is longer than `len`.
+    pub(super) fn load(&mut self, value: &[u8], len: usize) -> Result<Operand, Error> {
+        assert!(value.len() <= len);
+        let operand = self.alloc(len)?;
+        write(operand, value);
+        Ok(operand)
+    }
+
+    /// Reserve `len + 4` bytes and store `value` in them. Most services
+    /// require one zero word above the most significant word of their
+    /// operands.
+    pub(super) fn load_padded(&mut self, value: &[u8], len: usize) -> Result<Operand, Error> {
+        self.load(value, len + 4)
+    }
+
+    /// Return the rest of the crypto RAM, to be used as the service
+    /// workspace. At least `min_len` bytes must be available.
+    pub(super) fn workspace(&self, min_len: usize) -> Result<Operand, Error> {
+        if min_len > self.remaining() {
+            return Err(Error::OutOfCryptoRam);
+        }
+        Ok(Operand {
+            offset: self.next,
+            len: self.remaining(),
+        })
+    }
+}
+
+/// Store the big-endian number `value` in `operand`, zero-padded
+pub(super) fn write(operand: Operand, value: &[u8]) {
+    let base = (CRYPTO_RAM_ADDR + operand.offset) as *mut u8;
+    let bytes = value.iter().rev().copied().chain(core::iter::repeat(0));
+    for (i, byte) in bytes.take(operand.len).enumerate() {
+        // SAFETY: The operand lies within the crypto RAM
+        unsafe { ptr::write_volatile(base.add(i), byte) };
+    }
+}
+
+/// Read the `out.len()` least significant bytes of `operand` as a
+/// big-endian number
+pub(super) fn read(operand: Operand, out: &mut [u8]) {
+    assert!(out.len() <= operand.len);
+    let base = (CRYPTO_RAM_ADDR + operand.offset) as *const u8;
+    for (i, byte) in out.iter_mut().rev().enumerate() {
+        // SAFETY: The operand lies within the crypto RAM
+        *byte = unsafe { ptr::read_volatile(base.add(i)) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocations_are_word_aligned() {
+        let mut ram = CryptoRam::new();
+        let a = ram.alloc(5).unwrap();
+        let b = ram.alloc(4).unwrap();
+        assert_eq!(a.len, 8);
+        assert_eq!(b.addr(), a.addr() + 8);
+        assert_eq!(ram.remaining(), CRYPTO_RAM_SIZE - 12);
+    }
+
+    #[test]
+    fn base_is_low_address_half() {
+        let mut ram = CryptoRam::new();
+        ram.alloc(0x20).unwrap();
+        let op = ram.alloc(4).unwrap();
+        assert_eq!(op.base(), 0x1020);
+        assert_eq!(op.skip(4).len, 0);
+    }
+
+    #[test]
+    fn allocation_fails_when_full() {
+        let mut ram = CryptoRam::new();
+        ram.alloc(CRYPTO_RAM_SIZE - 8).unwrap();
+        assert!(ram.workspace(8).is_ok());
+        assert_eq!(ram.workspace(9), Err(Error::OutOfCryptoRam));
+        assert_eq!(ram.alloc(9), Err(Error::OutOfCryptoRam));
+        assert!(ram.alloc(8).is_ok());
+    }
+}
@@ -0,0 +1,153 @@
+//! Elliptic curve domain parameters
+//!
+//! Curves are described by the [`Curve`] trait. All parameters are stored as
+//! big-endian numbers without padding; the driver takes care of the layout
+//! expected by the PUKCC services. [`P256`] and [`P384`] are provided;
+//! other curves, including curves over GF(2^n), can be used by implementing
+//! [`Curve`].
+
+use super::Error;
+
+/// Field over which a curve is defined
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Field {
+    /// Prime field GF(p)
+    Prime,
+    /// Binary field GF(2^n)
+    Binary,
+}
+
+/// Elliptic curve domain parameters
+///
+/// For prime curves, the curve equation is `y^2 = x^3 + a*x + b` and
+/// `MODULUS` is the prime `p`. For binary curves, the curve equation is
+/// `y^2 + x*y = x^3 + a*x^2 + b` and `MODULUS` is the reduction polynomial.
+pub trait Curve {
+    /// Field of the curve
+    const FIELD: Field;
+    /// Length of the field elements, in bytes. Must be a multiple of 4.
+    const MOD_LENGTH: usize;
+    /// Length of the scalars, in bytes. Must be a multiple of 4.
+    const SCALAR_LENGTH: usize;
+    /// Field modulus, `MOD_LENGTH` bytes
+    const MODULUS: &'static [u8];
+    /// `a` coefficient of the curve equation, `MOD_LENGTH` bytes
+    const A: &'static [u8];
+    /// X coordinate of the base point, `MOD_LENGTH` bytes
+    const GX: &'static [u8];
+    /// Y coordinate of the base point, `MOD_LENGTH` bytes
+    const GY: &'static [u8];
+    /// Order of the base point, `SCALAR_LENGTH` bytes
+    const ORDER: &'static [u8];
+}
+
+/// Check the lengths of the curve parameters
+pub(super) fn check<C: Curve>() -> Result<(), Error> {
+    let aligned = C::MOD_LENGTH & 3 == 0 && C::SCALAR_LENGTH & 3 == 0;
+    let lengths = C::MODULUS.len() == C::MOD_LENGTH
+        && C::A.len() == C::MOD_LENGTH
+        && C::GX.len() == C::MOD_LENGTH
+        && C::GY.len() == C::MOD_LENGTH
+        && C::ORDER.len() == C::SCALAR_LENGTH;
+    if aligned && lengths {
+        Ok(())
+    } else {
+        Err(Error::InvalidCurve)
+    }
+}
+
+/// NIST P-256 (secp256r1)
+pub enum P256 {}
+
+impl Curve for P256 {
+    const FIELD: Field = Field::Prime;
+    const MOD_LENGTH: usize = 32;
+    const SCALAR_LENGTH: usize = 32;
+    const MODULUS: &'static [u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff,
+    ];
+    const A: &'static [u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xfc,
+    ];
+    const GX: &'static [u8] = &[
+        0x6b, 0x17, 0xd1, 0xf2, 0xe1, 0x2c, 0x42, 0x47, 0xf8, 0xbc, 0xe6, 0xe5, 0x63, 0xa4, 0x40,
+        0xf2, 0x77, 0x03, 0x7d, 0x81, 0x2d, 0xeb, 0x33, 0xa0, 0xf4, 0xa1, 0x39, 0x45, 0xd8, 0x98,
+        0xc2, 0x96,
+    ];
+    const GY: &'static [u8] = &[
+        0x4f, 0xe3, 0x42, 0xe2, 0xfe, 0x1a, 0x7f, 0x9b, 0x8e, 0xe7, 0xeb, 0x4a, 0x7c, 0x0f, 0x9e,
+        0x16, 0x2b, 0xce, 0x33, 0x57, 0x6b, 0x31, 0x5e, 0xce, 0xcb, 0xb6, 0x40, 0x68, 0x37, 0xbf,
+        0x51, 0xf5,
+    ];
+    const ORDER: &'static [u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xbc, 0xe6, 0xfa, 0xad, 0xa7, 0x17, 0x9e, 0x84, 0xf3, 0xb9, 0xca, 0xc2, 0xfc, 0x63,
+        0x25, 0x51,
+    ];
+}
+
+/// NIST P-384 (secp384r1)
+pub enum P384 {}
+
+impl Curve for P384 {
+    const FIELD: Field = Field::Prime;
+    const MOD_LENGTH: usize = 48;
+    const SCALAR_LENGTH: usize = 48;
+    const MODULUS: &'static [u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xfe, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff,
+        0xff, 0xff, 0xff,
+    ];
+    const A: &'static [u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xfe, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff,
+        0xff, 0xff, 0xfc,
+    ];
+    const GX: &'static [u8] = &[
+        0xaa, 0x87, 0xca, 0x22, 0xbe, 0x8b, 0x05, 0x37, 0x8e, 0xb1, 0xc7, 0x1e, 0xf3, 0x20, 0xad,
+        0x74, 0x6e, 0x1d, 0x3b, 0x62, 0x8b, 0xa7, 0x9b, 0x98, 0x59, 0xf7, 0x41, 0xe0, 0x82, 0x54,
+        0x2a, 0x38, 0x55, 0x02, 0xf2, 0x5d, 0xbf, 0x55, 0x29, 0x6c, 0x3a, 0x54, 0x5e, 0x38, 0x72,
+        0x76, 0x0a, 0xb7,
+    ];
+    const GY: &'static [u8] = &[
+        0x36, 0x17, 0xde, 0x4a, 0x96, 0x26, 0x2c, 0x6f, 0x5d, 0x9e, 0x98, 0xbf, 0x92, 0x92, 0xdc,
+        0x29, 0xf8, 0xf4, 0x1d, 0xbd, 0x28, 0x9a, 0x14, 0x7c, 0xe9, 0xda, 0x31, 0x13, 0xb5, 0xf0,
+        0xb8, 0xc0, 0x0a, 0x60, 0xb1, 0xce, 0x1d, 0x7e, 0x81, 0x9d, 0x7a, 0x43, 0x1d, 0x7c, 0x90,
+        0xea, 0x0e, 0x5f,
+    ];
+    const ORDER: &'static [u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xc7, 0x63, 0x4d, 0x81, 0xf4, 0x37,
+        0x2d, 0xdf, 0x58, 0x1a, 0x0d, 0xb2, 0x48, 0xb0, 0xa7, 0x7a, 0xec, 0xec, 0x19, 0x6a, 0xcc,
+        0xc5, 0x29, 0x73,
+    ];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_curves_are_well_formed() {
+        assert_eq!(check::<P256>(), Ok(()));
+        assert_eq!(check::<P384>(), Ok(()));
+    }
+
+    #[test]
+    fn a_is_p_minus_3() {
+        fn check_a<C: Curve>() {
+            let (p_head, p_tail) = C::MODULUS.split_at(C::MOD_LENGTH - 1);
+            let (a_head, a_tail) = C::A.split_at(C::MOD_LENGTH - 1);
+            assert_eq!(p_head, a_head);
+            assert_eq!(p_tail[0] - 3, a_tail[0]);
+        }
+        check_a::<P256>();
+        check_a::<P384>();
+    }
+}
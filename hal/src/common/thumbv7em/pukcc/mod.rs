@@ -0,0 +1,688 @@
+//! # Public Key Cryptography Controller (PUKCC)
+//!
+//! The PUKCC is a big number coprocessor driven by the PUKCL library stored
+//! in ROM. This module wraps the ROM services in a safe API:
+//!
+//! * [`Pukcc::modexp`], a modular exponentiation suitable for RSA up to 4096
+//!   bits;
+//! * [`Pukcc::ecdsa_sign`] and [`Pukcc::ecdsa_verify`], ECDSA over the
+//!   curves described in the [`curves`] module, either over GF(p) or
+//!   GF(2^n);
+//! * [`Pukcc::generate_prime`] and [`Pukcc::is_probable_prime`], a
+//!   Miller-Rabin primality test built on the exponentiation service.
+//!
+//! All numbers are passed as big-endian byte slices. The ROM services only
+//! operate on operands stored in the 4 KiB crypto RAM, in little-endian order
+//! and with specific zero padding. The driver copies operands in and out of
+//! the crypto RAM for every operation, so the crypto RAM contents are not
+//! preserved between calls.
+//!
+//! The ROM library is checked by [`Pukcc::new`], which runs the self test
+//! service and compares its version and checksums against the expected
+//! values.
+//!
+//! # Security
+//!
+//! This driver has not been audited. The crypto RAM is not cleared after an
+//! operation, so secret operands such as private keys remain there until they
+//! are overwritten.
+
+pub mod curves;
+
+mod bignum;
+mod c_abi;
+mod crypto_ram;
+
+pub use crypto_ram::{CRYPTO_RAM_ADDR, CRYPTO_RAM_SIZE};
+
+use crate::target_device::MCLK;
+use bignum::{
+    add_small, bit_length, cmp, hash_to_scalar, is_one, is_zero, rem_small, shift_right, sub_small,
+    trailing_zeros, truncate_bits,
+};
+use c_abi::Params;
+use core::cmp::Ordering;
+use core::sync::atomic::{self, AtomicBool};
+use crypto_ram::{CryptoRam, Operand};
+use curves::{Curve, Field};
+use rand_core::{CryptoRng, RngCore};
+
+/// Expected PUKCL version reported by the self test
+const PUKCL_VERSION: u32 = 0x0407_0100;
+/// Expected self test checksums
+const CHECKNUM_1: u32 = 0x6e70_ddd2;
+const CHECKNUM_2: u32 = 0x25c8_d64f;
+
+/// Largest supported curve field element or scalar, in bytes
+const MAX_CURVE_LENGTH: usize = 72;
+
+/// Largest supported prime candidate, in bytes
+pub const MAX_PRIME_LENGTH: usize = 256;
+
+/// Smallest modulus supported by the exponentiation service, in bytes
+pub const MIN_MODULUS_LENGTH: usize = 12;
+
+/// Odd primes used to sieve prime candidates before running Miller-Rabin
+const SMALL_PRIMES: [u32; 24] = [
+    3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+];
+
+/// Errors related to the PUKCC
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// The ROM self test failed, or the ROM version is not supported
+    SelfTest,
+    /// An input has the wrong length or alignment
+    InvalidLength,
+    /// The curve parameters are malformed
+    InvalidCurve,
+    /// The operands do not fit in the crypto RAM
+    OutOfCryptoRam,
+    /// The ECDSA signature does not match
+    InvalidSignature,
+    /// A point is not on the curve
+    PointNotOnCurve,
+    /// A computation resulted in the point at infinity
+    PointAtInfinity,
+    /// The ROM service returned an unexpected status
+    Rom(u16),
+    /// A `Pukcc` already exists
+    InUse,
+}
+
+impl Error {
+    /// Convert a PUKCL status code
+    fn check(status: u16) -> Result<(), Error> {
+        match status {
+            0x0000 => Ok(()),
+            0x8001 => Err(Error::PointAtInfinity),
+            0x8002 => Err(Error::InvalidSignature),
+            0x8004 => Err(Error::PointNotOnCurve),
+            status => Err(Error::Rom(status)),
+        }
+    }
+}
+
+/// Exponentiation algorithm
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ExpModMode {
+    /// Regular algorithm, which performs the same operations whatever the
+    /// exponent. Use it for secret exponents.
+    Regular,
+    /// Faster algorithm, for public exponents
+    Fast,
+}
+
+impl ExpModMode {
+    fn option(self) -> u16 {
+        match self {
+            ExpModMode::Regular => 0x01,
+            ExpModMode::Fast => 0x04,
+        }
+    }
+}
+
+/// Window size of the exponentiation. Larger windows are faster but need a
+/// larger workspace in the crypto RAM.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ExpModWindow {
+    One,
+    Two,
+    Three,
+    Four,
+}
+
+impl ExpModWindow {
+    fn option(self) -> u16 {
+        match self {
+            ExpModWindow::One => 0x00,
+            ExpModWindow::Two => 0x08,
+            ExpModWindow::Three => 0x10,
+            ExpModWindow::Four => 0x18,
+        }
+    }
+
+    /// Workspace size for a modulus of `len` bytes
+    fn workspace(self, len: usize) -> usize {
+        let factor = match self {
+            ExpModWindow::One => 3,
+            ExpModWindow::Two => 4,
+            ExpModWindow::Three => 6,
+            ExpModWindow::Four => 10,
+        };
+        factor * (len + 4) + 8
+    }
+}
+
+/// Modulus and reduction constant loaded in the crypto RAM
+struct Modulus {
+    /// Allocator state after the modulus and reduction constant
+    ram: CryptoRam,
+    modulus: Operand,
+    cns: Operand,
+    len: usize,
+}
+
+/// Set while a `Pukcc` exists, as the PUKCC and its crypto RAM have no
+/// peripheral to own
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Public Key Cryptography Controller
+pub struct Pukcc {
+    _private: (),
+}
+
+impl Pukcc {
+    /// Enable the PUKCC clock and check the ROM library with its self test.
+    ///
+    /// Only one `Pukcc` can exist at a time, so that operations do not
+    /// overwrite the operands of each other in the crypto RAM: this returns
+    /// [`Error::InUse`] until the existing one is freed.
+    pub fn new(mclk: &mut MCLK) -> Result<Self, Error> {
+        if TAKEN.swap(true, atomic::Ordering::AcqRel) {
+            return Err(Error::InUse);
+        }
+        crypto_ram::wait_ready();
+        mclk.ahbmask.modify(|_, w| w.pukcc_().set_bit());
+
+        let mut pukcc = Self { _private: () };
+        match pukcc.self_test() {
+            Ok(()) => Ok(pukcc),
+            Err(e) => {
+                pukcc.free(mclk);
+                Err(e)
+            }
+        }
+    }
+
+    /// Disable the PUKCC clock
+    pub fn free(self, mclk: &mut MCLK) {
+        mclk.ahbmask.modify(|_, w| w.pukcc_().clear_bit());
+        TAKEN.store(false, atomic::Ordering::Release);
+    }
+
+    /// Run the ROM self test service. This clears the crypto RAM.
+    pub fn self_test(&mut self) -> Result<(), Error> {
+        let mut params = Params::default();
+        // SAFETY: The self test does not take any operand
+        let status = unsafe { c_abi::SELF_TEST.call(&mut params) };
+        // SAFETY: The union was written by the self test service
+        let result = unsafe { params.params.self_test };
+
+        if status == 0
+            && result.version == PUKCL_VERSION
+            && result.check_num1 == CHECKNUM_1
+            && result.check_num2 == CHECKNUM_2
+        {
+            Ok(())
+        } else {
+            Err(Error::SelfTest)
+        }
+    }
+
+    /// Load `modulus` in the crypto RAM and compute its reduction constant
+    fn load_modulus(&mut self, modulus: &[u8], field: Field) -> Result<Modulus, Error> {
+        let len = modulus.len();
+        let mut ram = CryptoRam::new();
+        let modulus = ram.load_padded(modulus, len)?;
+        let cns = ram.alloc(len + 12)?;
+
+        // The setup workspace is only needed while computing the constant
+        let mut scratch = ram.clone();
+        let r = scratch.alloc(64)?;
+        let x = scratch.workspace(0)?;
+
+        let mut params = Params::default();
+        params.header.option = c_abi::REDMOD_SETUP;
+        if field == Field::Binary {
+            params.header.specific = c_abi::SPECIFIC_GF2N;
+        }
+        params.params.red_mod = c_abi::RedMod {
+            mod_base: modulus.base(),
+            cns_base: cns.base(),
+            mod_length: len as u16,
+            r_base: r.base(),
+            x_base: x.base(),
+            ..Default::default()
+        };
+        // SAFETY: The operands were laid out in the crypto RAM above
+        Error::check(unsafe { c_abi::RED_MOD.call(&mut params) })?;
+
+        Ok(Modulus {
+            ram,
+            modulus,
+            cns,
+            len,
+        })
+    }
+
+    /// Run the exponentiation service with a modulus already loaded
+    fn exponentiate(
+        &mut self,
+        modulus: &Modulus,
+        base: &[u8],
+        exponent: &[u8],
+        mode: ExpModMode,
+        window: ExpModWindow,
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        let len = modulus.len;
+        let mut ram = modulus.ram.clone();
+
+        // The input is replaced by the result, and needs four extra words
+        let x = ram.load(base, len + 16)?;
+        // The exponent is preceded by a zero word
+        let exp_len = (exponent.len() + 3) & !3;
+        let exp = ram.alloc(exp_len + 4)?;
+        crypto_ram::write(exp, &[]);
+        crypto_ram::write(exp.skip(4), exponent);
+        let precomp = ram.workspace(window.workspace(len))?;
+
+        let mut params = Params::default();
+        params.header.option = c_abi::EXPMOD_EXP_IN_PUKCC_RAM | window.option() | mode.option();
+        params.params.exp_mod = c_abi::ExpMod {
+            x_base: x.base(),
+            mod_base: modulus.modulus.base(),
+            cns_base: modulus.cns.base(),
+            precomp_base: precomp.base(),
+            exp_base: exp.addr(),
+            mod_length: len as u16,
+            exp_length: exp_len as u16,
+            blinding: 0,
+            ..Default::default()
+        };
+        // SAFETY: The operands were laid out in the crypto RAM above
+        Error::check(unsafe { c_abi::EXP_MOD.call(&mut params) })?;
+
+        crypto_ram::read(x, out);
+        Ok(())
+    }
+
+    /// Compute `base ^ exponent mod modulus` into `out`.
+    ///
+    /// `modulus` must be at least [`MIN_MODULUS_LENGTH`] bytes long, and its
+    /// length must be a multiple of 4. `base` and `exponent` must not be
+    /// longer than `modulus`, and `out` must have the same length as
+    /// `modulus`.
+    ///
+    /// For RSA, use the public exponent in [`ExpModMode::Fast`] mode to
+    /// encrypt or verify a signature, and the private exponent in
+    /// [`ExpModMode::Regular`] mode to decrypt or sign.
+    pub fn modexp(
+        &mut self,
+        base: &[u8],
+        exponent: &[u8],
+        modulus: &[u8],
+        mode: ExpModMode,
+        window: ExpModWindow,
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        let len = modulus.len();
+        if len < MIN_MODULUS_LENGTH
+            || len & 3 != 0
+            || base.len() > len
+            || exponent.len() > len
+            || out.len() != len
+        {
+            return Err(Error::InvalidLength);
+        }
+
+        let modulus = self.load_modulus(modulus, Field::Prime)?;
+        self.exponentiate(&modulus, base, exponent, mode, window, out)
+    }
+
+    /// Sign `hash` with `private_key`, using a random nonce drawn from `rng`.
+    ///
+    /// `private_key` is `C::SCALAR_LENGTH` bytes long. `hash` is truncated or
+    /// zero-extended to `C::SCALAR_LENGTH` bytes. The signature is written
+    /// as `r || s` to `signature`, which must be `2 * C::SCALAR_LENGTH`
+    /// bytes long.
+    pub fn ecdsa_sign<C: Curve, R: RngCore + CryptoRng>(
+        &mut self,
+        private_key: &[u8],
+        hash: &[u8],
+        rng: &mut R,
+        signature: &mut [u8],
+    ) -> Result<(), Error> {
+        curves::check::<C>()?;
+        let len = C::SCALAR_LENGTH;
+        if len > MAX_CURVE_LENGTH {
+            return Err(Error::InvalidCurve);
+        }
+
+        // Draw the nonce in [1, n - 1]
+        let mut k = [0; MAX_CURVE_LENGTH];
+        let k = &mut k[..len];
+        let order_bits = bit_length(C::ORDER);
+        loop {
+            rng.fill_bytes(k);
+            truncate_bits(k, order_bits);
+            if !is_zero(k) && cmp(k, C::ORDER) == Ordering::Less {
+                break;
+            }
+        }
+
+        self.ecdsa_sign_with_nonce::<C>(private_key, hash, k, signature)
+    }
+
+    /// Sign `hash` with `private_key` and the caller-provided nonce `k`.
+    ///
+    /// See [`ecdsa_sign`](Pukcc::ecdsa_sign) for the parameters. `k` must be
+    /// `C::SCALAR_LENGTH` bytes long, secret, uniformly distributed in
+    /// `[1, n - 1]`, and never reused: a biased or reused nonce reveals the
+    /// private key. Prefer [`ecdsa_sign`](Pukcc::ecdsa_sign) unless the nonce
+    /// is derived deterministically, as in RFC 6979.
+    pub fn ecdsa_sign_with_nonce<C: Curve>(
+        &mut self,
+        private_key: &[u8],
+        hash: &[u8],
+        k: &[u8],
+        signature: &mut [u8],
+    ) -> Result<(), Error> {
+        curves::check::<C>()?;
+        let (mod_len, len) = (C::MOD_LENGTH, C::SCALAR_LENGTH);
+        if len > MAX_CURVE_LENGTH {
+            return Err(Error::InvalidCurve);
+        }
+        if private_key.len() != len || k.len() != len || signature.len() != 2 * len {
+            return Err(Error::InvalidLength);
+        }
+        let mut scalar = [0; MAX_CURVE_LENGTH];
+        let scalar = &mut scalar[..len];
+        hash_to_scalar(hash, scalar);
+
+        let modulus = self.load_modulus(C::MODULUS, C::FIELD)?;
+        let mut ram = modulus.ram.clone();
+        let a = ram.load_padded(C::A, mod_len)?;
+        let gx = ram.load_padded(C::GX, mod_len)?;
+        let gy = ram.load_padded(C::GY, mod_len)?;
+        ram.load_padded(&[1], mod_len)?;
+        let order = ram.load_padded(C::ORDER, len)?;
+        let hash = ram.load_padded(scalar, len)?;
+        let private_key = ram.load_padded(private_key, len)?;
+        let k = ram.load_padded(k, len)?;
+        let workspace = ram.workspace(0)?;
+
+        let mut params = Params::default();
+        params.params.ecdsa_generate = c_abi::EcDsaGenerate {
+            point_a_base: gx.base(),
+            order_point_base: order.base(),
+            mod_base: modulus.modulus.base(),
+            cns_base: modulus.cns.base(),
+            private_key: private_key.base(),
+            scalar_number: k.base(),
+            a_base: a.base(),
+            hash_base: hash.base(),
+            workspace: workspace.base(),
+            mod_length: mod_len as u16,
+            scalar_length: len as u16,
+            ..Default::default()
+        };
+        let service = match C::FIELD {
+            Field::Prime => c_abi::ZP_ECDSA_GENERATE,
+            Field::Binary => c_abi::GF2N_ECDSA_GENERATE,
+        };
+        // SAFETY: The operands were laid out in the crypto RAM above
+        Error::check(unsafe { service.call(&mut params) })?;
+
+        // The service writes r and s over the base point coordinates
+        let (r, s) = signature.split_at_mut(len);
+        crypto_ram::read(gx, r);
+        crypto_ram::read(gy, s);
+        Ok(())
+    }
+
+    /// Verify the signature `r || s` of `hash` against `public_key`.
+    ///
+    /// `public_key` is the uncompressed point `x || y`, each coordinate
+    /// being `C::MOD_LENGTH` bytes long. `hash` is truncated or
+    /// zero-extended to `C::SCALAR_LENGTH` bytes. Returns
+    /// [`Error::InvalidSignature`] if the signature does not match.
+    pub fn ecdsa_verify<C: Curve>(
+        &mut self,
+        public_key: &[u8],
+        hash: &[u8],
+        signature: &[u8],
+    ) -> Result<(), Error> {
+        curves::check::<C>()?;
+        let (mod_len, len) = (C::MOD_LENGTH, C::SCALAR_LENGTH);
+        if len > MAX_CURVE_LENGTH {
+            return Err(Error::InvalidCurve);
+        }
+        if public_key.len() != 2 * mod_len || signature.len() != 2 * len {
+            return Err(Error::InvalidLength);
+        }
+        let mut scalar = [0; MAX_CURVE_LENGTH];
+        let scalar = &mut scalar[..len];
+        hash_to_scalar(hash, scalar);
+
+        let modulus = self.load_modulus(C::MODULUS, C::FIELD)?;
+        let mut ram = modulus.ram.clone();
+        let a = ram.load_padded(C::A, mod_len)?;
+        let gx = ram.load_padded(C::GX, mod_len)?;
+        ram.load_padded(C::GY, mod_len)?;
+        ram.load_padded(&[1], mod_len)?;
+        let order = ram.load_padded(C::ORDER, len)?;
+        let r = ram.load_padded(&signature[..len], len)?;
+        ram.load_padded(&signature[len..], len)?;
+        let hash = ram.load_padded(scalar, len)?;
+        let qx = ram.load_padded(&public_key[..mod_len], mod_len)?;
+        ram.load_padded(&public_key[mod_len..], mod_len)?;
+        ram.load_padded(&[1], mod_len)?;
+        let workspace = ram.workspace(0)?;
+
+        let mut params = Params::default();
+        params.params.ecdsa_verify = c_abi::EcDsaVerify {
+            point_a_base: gx.base(),
+            order_point_base: order.base(),
+            mod_base: modulus.modulus.base(),
+            cns_base: modulus.cns.base(),
+            point_public_key: qx.base(),
+            point_signature: r.base(),
+            a_base: a.base(),
+            hash_base: hash.base(),
+            workspace: workspace.base(),
+            mod_length: mod_len as u16,
+            scalar_length: len as u16,
+            ..Default::default()
+        };
+        let service = match C::FIELD {
+            Field::Prime => c_abi::ZP_ECDSA_VERIFY,
+            Field::Binary => c_abi::GF2N_ECDSA_VERIFY,
+        };
+        // SAFETY: The operands were laid out in the crypto RAM above
+        Error::check(unsafe { service.call(&mut params) })
+    }
+
+    /// Probabilistic primality test.
+    ///
+    /// Runs `rounds` iterations of the Miller-Rabin test on `candidate`,
+    /// after trial division by small primes. A composite number passes with
+    /// a probability of at most `4^-rounds`.
+    ///
+    /// `candidate` must be at least [`MIN_MODULUS_LENGTH`] and at most
+    /// [`MAX_PRIME_LENGTH`] bytes long, and its length must be a multiple
+    /// of 4.
+    pub fn is_probable_prime<R: RngCore + CryptoRng>(
+        &mut self,
+        candidate: &[u8],
+        rounds: usize,
+        rng: &mut R,
+    ) -> Result<bool, Error> {
+        let len = candidate.len();
+        if !(MIN_MODULUS_LENGTH..=MAX_PRIME_LENGTH).contains(&len) || len & 3 != 0 {
+            return Err(Error::InvalidLength);
+        }
+
+        if bit_length(candidate) <= 16 {
+            return Ok(is_small_prime(candidate));
+        }
+        if !sieve(candidate) {
+            return Ok(false);
+        }
+
+        self.miller_rabin(candidate, rounds, rng)
+    }
+
+    fn miller_rabin<R: RngCore + CryptoRng>(
+        &mut self,
+        n: &[u8],
+        rounds: usize,
+        rng: &mut R,
+    ) -> Result<bool, Error> {
+        let len = n.len();
+        let modulus = self.load_modulus(n, Field::Prime)?;
+
+        // n - 1 = d * 2^s
+        let mut buffers = [[0; MAX_PRIME_LENGTH]; 4];
+        let [n_minus_1, d, a, x] = &mut buffers;
+        let (n_minus_1, d, a, x) = (
+            &mut n_minus_1[..len],
+            &mut d[..len],
+            &mut a[..len],
+            &mut x[..len],
+        );
+        n_minus_1.copy_from_slice(n);
+        sub_small(n_minus_1, 1);
+        let s = trailing_zeros(n_minus_1);
+        d.copy_from_slice(n_minus_1);
+        shift_right(d, s);
+
+        let n_bits = bit_length(n);
+        let window = ExpModWindow::Two;
+        let mode = ExpModMode::Regular;
+
+        'round: for _ in 0..rounds {
+            // Draw a witness in [2, n - 2]
+            loop {
+                rng.fill_bytes(a);
+                truncate_bits(a, n_bits);
+                if bit_length(a) > 1 && cmp(a, n_minus_1) == Ordering::Less {
+                    break;
+                }
+            }
+
+            self.exponentiate(&modulus, a, d, mode, window, x)?;
+            if is_one(x) || cmp(x, n_minus_1) == Ordering::Equal {
+                continue;
+            }
+            for _ in 1..s {
+                a.copy_from_slice(x);
+                self.exponentiate(&modulus, a, &[2], mode, window, x)?;
+                if cmp(x, n_minus_1) == Ordering::Equal {
+                    continue 'round;
+                }
+                if is_one(x) {
+                    return Ok(false);
+                }
+            }
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// Generate a random probable prime filling `out`.
+    ///
+    /// The two most significant bits of the prime are set, so that the
+    /// product of two such primes has exactly twice their bit length, as
+    /// needed for RSA keys. See
+    /// [`is_probable_prime`](Pukcc::is_probable_prime) for the length
+    /// requirements and the meaning of `rounds`.
+    pub fn generate_prime<R: RngCore + CryptoRng>(
+        &mut self,
+        out: &mut [u8],
+        rounds: usize,
+        rng: &mut R,
+    ) -> Result<(), Error> {
+        let len = out.len();
+        if !(MIN_MODULUS_LENGTH..=MAX_PRIME_LENGTH).contains(&len) || len & 3 != 0 {
+            return Err(Error::InvalidLength);
+        }
+
+        loop {
+            rng.fill_bytes(out);
+            out[0] |= 0xc0;
+            out[len - 1] |= 1;
+
+            // Search the next odd numbers until the top bits would change
+            while out[0] & 0xc0 == 0xc0 {
+                if sieve(out) && self.miller_rabin(out, rounds, rng)? {
+                    return Ok(());
+                }
+                if add_small(out, 2) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Returns `false` if `n` is even or divisible by a small prime
+fn sieve(n: &[u8]) -> bool {
+    n.last().map(|b| b & 1) == Some(1) && SMALL_PRIMES.iter().all(|&p| rem_small(n, p) != 0)
+}
+
+/// Deterministic primality test for numbers below 2^16
+fn is_small_prime(n: &[u8]) -> bool {
+    let n = n.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+    if n < 2 {
+        return false;
+    }
+    let mut i = 2;
+    while i * i <= n {
+        if n % i == 0 {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_codes() {
+        assert_eq!(Error::check(0), Ok(()));
+        assert_eq!(Error::check(0x8002), Err(Error::InvalidSignature));
+        assert_eq!(Error::check(0xc001), Err(Error::Rom(0xc001)));
+    }
+
+    #[test]
+    fn small_primes() {
+        let n = |v: u16| {
+            let mut b = [0; 12];
+            b[10..].copy_from_slice(&v.to_be_bytes());
+            b
+        };
+        assert!(is_small_prime(&n(2)));
+        assert!(is_small_prime(&n(65521)));
+        assert!(!is_small_prime(&n(1)));
+        assert!(!is_small_prime(&n(65535)));
+    }
+
+    #[test]
+    fn sieve_rejects_small_factors() {
+        // 2^89 - 1 is a Mersenne prime
+        let mut m89 = [0xff; 12];
+        m89[0] = 0;
+        m89[1] = 0;
+        m89[2] = 0x01;
+        assert!(sieve(&m89));
+        // 3 * 5 * 7 * 11
+        let mut composite = [0; 12];
+        composite[11] = 0x83;
+        composite[10] = 0x04;
+        assert!(!sieve(&composite));
+        // Even numbers
+        assert!(!sieve(&[0; 12]));
+    }
+
+    #[test]
+    fn expmod_workspace() {
+        assert_eq!(ExpModWindow::One.workspace(256), 3 * 260 + 8);
+        assert_eq!(ExpModWindow::Four.workspace(12), 10 * 16 + 8);
+    }
+}
@@ -0,0 +1,390 @@
+//! Serial NOR flash
+//!
+//! [`SerialFlash`] drives a serial NOR flash described by its SFDP tables,
+//! and implements the `embedded-storage` NOR flash traits. It issues the
+//! write enable instructions, splits writes at page boundaries, polls the
+//! busy bit after programs and erases, enables the quad mode of the flash
+//! and switches large flashes to 4 byte addresses.
+//!
+//! ```ignore
+//! let mut flash = SerialFlash::new(qspi)?;
+//! flash.erase(0, 4096)?;
+//! flash.write(0, b"hello")?;
+//! ```
+//!
+//! The data moves through the AHB window of the QSPI, copied by the CPU, or
+//! by a DMA channel with the `dma` feature:
+//!
+//! ```ignore
+//! let mut flash = SerialFlash::new(qspi)?.with_dma(&mut dmac, chan0);
+//! ```
+
+use super::{
+    sfdp::{AddressBytes, EraseType, FlashParameters, QuadEnable},
+    Error, OneShot, Qspi, ReadMode, TransferMode, Width, READ, WRITE,
+};
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+
+/// Erase size of [`SerialFlash`]. SFDP flashes have a 4 KiB sector erase.
+pub const SECTOR_SIZE: usize = 4096;
+
+const PAGE_PROGRAM: u8 = 0x02;
+const WRITE_ENABLE: u8 = 0x06;
+const READ_STATUS: u8 = 0x05;
+const READ_STATUS2: u8 = 0x35;
+const WRITE_STATUS: u8 = 0x01;
+const WRITE_STATUS2: u8 = 0x31;
+const READ_STATUS2_BIT7: u8 = 0x3f;
+const WRITE_STATUS2_BIT7: u8 = 0x3e;
+const ENTER_4_BYTE_ADDRESS: u8 = 0xb7;
+
+/// Write in progress bit of status register 1
+const BUSY: u8 = 1 << 0;
+
+/// Flashes larger than 3 byte addresses reach
+const MAX_3_BYTE_SIZE: u32 = 1 << 24;
+
+/// Moves the data of memory reads and page programs through the AHB window
+pub trait MemoryAccess {
+    /// Read `buf.len()` bytes from `addr` with the read mode of `qspi`
+    fn read(&mut self, qspi: &mut Qspi<OneShot>, addr: u32, buf: &mut [u8]);
+
+    /// Program `data` at `addr` with the single bit page program
+    /// instruction. `data` must not cross a page.
+    fn program(&mut self, qspi: &mut Qspi<OneShot>, addr: u32, data: &[u8]);
+}
+
+/// Copies the data with the CPU
+pub struct NoDma;
+
+impl MemoryAccess for NoDma {
+    fn read(&mut self, qspi: &mut Qspi<OneShot>, addr: u32, buf: &mut [u8]) {
+        qspi.read_memory(addr, buf);
+    }
+
+    fn program(&mut self, qspi: &mut Qspi<OneShot>, addr: u32, data: &[u8]) {
+        unsafe {
+            let dst = qspi.start_memory_write(PAGE_PROGRAM, Width::SingleBitSpi, addr, data.len());
+            core::ptr::copy(data.as_ptr(), dst, data.len());
+            qspi.finalize();
+        }
+    }
+}
+
+#[cfg(feature = "dma")]
+pub use self::dma::Dma;
+
+/// DMA data moves, available when the `dma` feature is enabled
+#[cfg(feature = "dma")]
+mod dma {
+    use super::*;
+    use crate::dmac::{
+        channel::{Channel, Ready},
+        DmaController,
+    };
+    use core::borrow::BorrowMut;
+
+    /// Copies the data with DMA channel `ID` of the controller `C`, which
+    /// may be owned or borrowed.
+    pub struct Dma<C: BorrowMut<DmaController>, const ID: u8> {
+        dmac: C,
+        // Always `Some` outside of transfers
+        chan: Option<Channel<Ready, ID>>,
+    }
+
+    impl<C: BorrowMut<DmaController>, const ID: u8> Dma<C, ID> {
+        pub fn new(dmac: C, chan: Channel<Ready, ID>) -> Self {
+            Self {
+                dmac,
+                chan: Some(chan),
+            }
+        }
+
+        /// Release the controller and the channel
+        pub fn free(self) -> (C, Channel<Ready, ID>) {
+            (self.dmac, self.chan.unwrap())
+        }
+    }
+
+    impl<C: BorrowMut<DmaController>, const ID: u8> MemoryAccess for Dma<C, ID> {
+        fn read(&mut self, qspi: &mut Qspi<OneShot>, addr: u32, buf: &mut [u8]) {
+            let chan = self.chan.take().unwrap();
+            let chan = qspi.read_memory_dma(self.dmac.borrow_mut(), chan, addr, buf);
+            self.chan = Some(chan);
+        }
+
+        fn program(&mut self, qspi: &mut Qspi<OneShot>, addr: u32, data: &[u8]) {
+            let chan = self.chan.take().unwrap();
+            unsafe {
+                let dst =
+                    qspi.start_memory_write(PAGE_PROGRAM, Width::SingleBitSpi, addr, data.len());
+                let chan = super::super::dma::copy(
+                    self.dmac.borrow_mut(),
+                    chan,
+                    data.as_ptr(),
+                    dst,
+                    data.len(),
+                );
+                qspi.finalize();
+                self.chan = Some(chan);
+            }
+        }
+    }
+
+    impl SerialFlash<NoDma> {
+        /// Move the data with DMA channel `chan`
+        pub fn with_dma<C: BorrowMut<DmaController>, const ID: u8>(
+            self,
+            dmac: C,
+            chan: Channel<Ready, ID>,
+        ) -> SerialFlash<Dma<C, ID>> {
+            SerialFlash {
+                qspi: self.qspi,
+                parameters: self.parameters,
+                sector_erase: self.sector_erase,
+                block_erase: self.block_erase,
+                access: Dma::new(dmac, chan),
+            }
+        }
+    }
+}
+
+/// Serial NOR flash on the QSPI
+pub struct SerialFlash<D: MemoryAccess = NoDma> {
+    qspi: Qspi<OneShot>,
+    parameters: FlashParameters,
+    sector_erase: u8,
+    /// Largest erase instruction above the sector size
+    block_erase: Option<EraseType>,
+    access: D,
+}
+
+impl SerialFlash<NoDma> {
+    /// Read the SFDP parameters of the flash, and prepare it for quad reads
+    /// and, when larger than 16 MiB, 4 byte addresses.
+    pub fn new(qspi: Qspi<OneShot>) -> Result<Self, Error> {
+        let parameters = qspi.read_flash_parameters()?;
+        Self::with_parameters(qspi, parameters)
+    }
+
+    /// Use `parameters` instead of the SFDP parameters of the flash
+    pub fn with_parameters(
+        mut qspi: Qspi<OneShot>,
+        parameters: FlashParameters,
+    ) -> Result<Self, Error> {
+        let sector_erase = parameters
+            .erase_types
+            .iter()
+            .flatten()
+            .find(|erase_type| erase_type.size as usize == SECTOR_SIZE)
+            .ok_or(Error::UnsupportedFlash)?
+            .opcode;
+        let block_erase = parameters
+            .largest_erase()
+            .filter(|erase_type| erase_type.size as usize > SECTOR_SIZE);
+
+        match parameters.address_bytes {
+            AddressBytes::Three => (),
+            AddressBytes::ThreeOrFour if parameters.size <= MAX_3_BYTE_SIZE => (),
+            AddressBytes::ThreeOrFour => {
+                instruction(&qspi, WRITE_ENABLE);
+                instruction(&qspi, ENTER_4_BYTE_ADDRESS);
+                qspi.set_four_byte_address(true);
+            }
+            AddressBytes::Four => qspi.set_four_byte_address(true),
+        }
+
+        // Without the quad enable method, quad instructions may not work
+        let read_mode = match parameters.quad_enable {
+            Some(method) => {
+                enable_quad(&qspi, method);
+                parameters.read_mode()
+            }
+            None => ReadMode::FAST_READ,
+        };
+        qspi.set_read_mode(read_mode);
+
+        Ok(Self {
+            qspi,
+            parameters,
+            sector_erase,
+            block_erase,
+            access: NoDma,
+        })
+    }
+}
+
+impl<D: MemoryAccess> SerialFlash<D> {
+    /// Parameters of the flash
+    pub fn parameters(&self) -> &FlashParameters {
+        &self.parameters
+    }
+
+    /// Returns true while a program or erase is in progress
+    pub fn is_busy(&self) -> bool {
+        read_register(&self.qspi, READ_STATUS) & BUSY != 0
+    }
+
+    /// Block until the program or erase in progress is done
+    pub fn wait_busy(&self) {
+        while self.is_busy() {}
+    }
+
+    /// Release the QSPI and the data mover
+    pub fn free(self) -> (Qspi<OneShot>, D) {
+        (self.qspi, self.access)
+    }
+
+    /// Check that `len` bytes at `offset` are inside the flash
+    fn check_bounds(&self, offset: u32, len: usize) -> Result<(), Error> {
+        match (offset as usize).checked_add(len) {
+            Some(end) if end <= self.capacity() => Ok(()),
+            _ => Err(Error::OutOfBounds),
+        }
+    }
+}
+
+impl<D: MemoryAccess> ReadNorFlash for SerialFlash<D> {
+    type Error = Error;
+
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Error> {
+        self.check_bounds(offset, bytes.len())?;
+        self.access.read(&mut self.qspi, offset, bytes);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.parameters.size as usize
+    }
+}
+
+impl<D: MemoryAccess> NorFlash for SerialFlash<D> {
+    const WRITE_SIZE: usize = 1;
+
+    const ERASE_SIZE: usize = SECTOR_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Error> {
+        if from > to {
+            return Err(Error::OutOfBounds);
+        }
+        self.check_bounds(from, (to - from) as usize)?;
+        if (from | to) as usize & (SECTOR_SIZE - 1) != 0 {
+            return Err(Error::NotAligned);
+        }
+
+        let mut addr = from;
+        while addr < to {
+            // Erase whole blocks with a single instruction
+            let (opcode, size) = match self.block_erase {
+                Some(block) if addr & (block.size - 1) == 0 && to - addr >= block.size => {
+                    (block.opcode, block.size)
+                }
+                _ => (self.sector_erase, SECTOR_SIZE as u32),
+            };
+            instruction(&self.qspi, WRITE_ENABLE);
+            let tfm = TransferMode {
+                address_enable: true,
+                instruction_enable: true,
+                ..TransferMode::default()
+            };
+            unsafe {
+                self.qspi
+                    .run_write_instruction(opcode, tfm, WRITE, addr, &[])
+            };
+            self.wait_busy();
+            addr += size;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Error> {
+        self.check_bounds(offset, bytes.len())?;
+
+        let page_size = self.parameters.page_size as usize;
+        let mut addr = offset;
+        let mut bytes = bytes;
+        while !bytes.is_empty() {
+            // Programs wrap around at the end of the page
+            let page_left = page_size - (addr as usize & (page_size - 1));
+            let len = bytes.len().min(page_left);
+            instruction(&self.qspi, WRITE_ENABLE);
+            self.access.program(&mut self.qspi, addr, &bytes[..len]);
+            self.wait_busy();
+            addr += len as u32;
+            bytes = &bytes[len..];
+        }
+        Ok(())
+    }
+}
+
+/// Run an instruction without address nor data
+fn instruction(qspi: &Qspi<OneShot>, opcode: u8) {
+    let tfm = TransferMode {
+        instruction_enable: true,
+        ..TransferMode::default()
+    };
+    unsafe { qspi.run_read_instruction(opcode, tfm, READ, 0, &mut [], true) };
+}
+
+/// Read a one byte register
+fn read_register(qspi: &Qspi<OneShot>, opcode: u8) -> u8 {
+    let tfm = TransferMode {
+        instruction_enable: true,
+        data_enable: true,
+        ..TransferMode::default()
+    };
+    let mut value = [0];
+    unsafe { qspi.run_read_instruction(opcode, tfm, READ, 0, &mut value, true) };
+    value[0]
+}
+
+/// Write the status registers, and wait for the write to complete
+fn write_status(qspi: &Qspi<OneShot>, opcode: u8, data: &[u8]) {
+    instruction(qspi, WRITE_ENABLE);
+    let tfm = TransferMode {
+        instruction_enable: true,
+        data_enable: true,
+        ..TransferMode::default()
+    };
+    unsafe { qspi.run_write_instruction(opcode, tfm, WRITE, 0, data) };
+    while read_register(qspi, READ_STATUS) & BUSY != 0 {}
+}
+
+/// Set the quad enable bit of the flash with `method`
+fn enable_quad(qspi: &Qspi<OneShot>, method: QuadEnable) {
+    match method {
+        QuadEnable::NotRequired => (),
+        QuadEnable::Sr1Bit6 => {
+            let status = read_register(qspi, READ_STATUS);
+            if status & (1 << 6) == 0 {
+                write_status(qspi, WRITE_STATUS, &[status | 1 << 6]);
+            }
+        }
+        QuadEnable::Sr2Bit7 => {
+            let status = read_register(qspi, READ_STATUS2_BIT7);
+            if status & (1 << 7) == 0 {
+                write_status(qspi, WRITE_STATUS2_BIT7, &[status | 1 << 7]);
+            }
+        }
+        // Status register 2 cannot be read, its other bits are cleared
+        QuadEnable::Sr2Bit1WriteTwoBytes | QuadEnable::Sr2Bit1 => {
+            let status = read_register(qspi, READ_STATUS);
+            write_status(qspi, WRITE_STATUS, &[status, 1 << 1]);
+        }
+        QuadEnable::Sr2Bit1Read35 => {
+            let status2 = read_register(qspi, READ_STATUS2);
+            if status2 & (1 << 1) == 0 {
+                let status = read_register(qspi, READ_STATUS);
+                write_status(qspi, WRITE_STATUS, &[status, status2 | 1 << 1]);
+            }
+        }
+        QuadEnable::Sr2Bit1Write31 => {
+            let status2 = read_register(qspi, READ_STATUS2);
+            if status2 & (1 << 1) == 0 {
+                write_status(qspi, WRITE_STATUS2, &[status2 | 1 << 1]);
+            }
+        }
+    }
+}
@@ -0,0 +1,994 @@
+//! Quad SPI interface to serial memories
+//!
+//! [`Qspi::with_config`] sets the SCK frequency from the frequency of the
+//! main clock, which clocks the QSPI peripheral:
+//!
+//! ```ignore
+//! let config = QspiConfig {
+//!     freq: 30.mhz().into(),
+//!     ..QspiConfig::default()
+//! };
+//! let mut qspi = Qspi::with_config(
+//!     &mut peripherals.MCLK,
+//!     &mut pins.port,
+//!     peripherals.QSPI,
+//!     sck, cs, io0, io1, io2, io3,
+//!     clocks.gclk0(),
+//!     config,
+//! );
+//! let parameters = qspi.read_flash_parameters()?;
+//! qspi.set_read_mode(parameters.read_mode());
+//! ```
+//!
+//! Flashes supporting SFDP describe their page size, erase instructions and
+//! quad enable method, parsed by the [`sfdp`] module.
+
+use crate::{
+    gpio::{Floating, Input, Pa10, Pa11, Pa8, Pa9, Pb10, Pb11, PfH, Port},
+    hal::spi::{Mode, Phase, Polarity, MODE_0},
+    target_device::qspi::instrframe::{
+        self,
+        TFRTYPE_A::{READ, READMEMORY, WRITE, WRITEMEMORY},
+    },
+    target_device::{MCLK, QSPI},
+    time::Hertz,
+};
+use core::marker::PhantomData;
+
+pub mod flash;
+pub mod sfdp;
+
+pub use self::flash::SerialFlash;
+use self::sfdp::FlashParameters;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// The command you selected cannot be performed by this function
+    CommandFunctionMismatch,
+    /// The flash has no valid SFDP basic flash parameter table
+    InvalidSfdp,
+    /// The flash lacks an instruction needed by the driver
+    UnsupportedFlash,
+    /// The address range is outside of the flash
+    OutOfBounds,
+    /// The address range is not aligned to the erase size
+    NotAligned,
+}
+
+/// QSPI configuration
+#[derive(Clone, Copy)]
+pub struct QspiConfig {
+    /// SCK frequency. The SCK runs at the fastest frequency not above it.
+    pub freq: Hertz,
+    /// Clock polarity and phase. Serial flashes support modes 0 and 3.
+    pub mode: Mode,
+    /// Sample the data of memory reads on both SCK edges. The read
+    /// instruction must be a double transfer rate (DTR) instruction of the
+    /// flash.
+    pub ddr: bool,
+}
+
+impl Default for QspiConfig {
+    /// 4 MHz in mode 0, single data rate
+    fn default() -> Self {
+        Self {
+            freq: Hertz(4_000_000),
+            mode: MODE_0,
+            ddr: false,
+        }
+    }
+}
+
+/// Lines used by the instruction, address and data phases
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum Width {
+    /// All phases on one line (1-1-1)
+    #[default]
+    SingleBitSpi,
+    /// Data on two lines (1-1-2)
+    DualOutput,
+    /// Data on four lines (1-1-4)
+    QuadOutput,
+    /// Address and data on two lines (1-2-2)
+    DualIo,
+    /// Address and data on four lines (1-4-4)
+    QuadIo,
+    /// All phases on two lines (2-2-2)
+    DualCmd,
+    /// All phases on four lines (4-4-4)
+    QuadCmd,
+}
+
+/// Instruction used by memory reads and XIP
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ReadMode {
+    pub opcode: u8,
+    pub width: Width,
+    pub dummy_cycles: u8,
+}
+
+impl ReadMode {
+    /// Single bit fast read (0x0B) with 8 dummy cycles, supported by all
+    /// SFDP flashes
+    pub const FAST_READ: ReadMode = ReadMode {
+        opcode: 0x0B,
+        width: Width::SingleBitSpi,
+        dummy_cycles: 8,
+    };
+}
+
+impl Default for ReadMode {
+    /// Quad output fast read with 8 dummy cycles
+    fn default() -> Self {
+        Self {
+            opcode: Command::QuadRead.bits(),
+            width: Width::QuadOutput,
+            dummy_cycles: 8,
+        }
+    }
+}
+
+/// Length of an option code
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OptionCodeLength {
+    _1Bit,
+    _2Bits,
+    _4Bits,
+    _8Bits,
+}
+
+/// Option code sent after the address, like the mode bits of the fast read
+/// instructions. It is sent on the lines of the address.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct OptionCode {
+    pub code: u8,
+    pub length: OptionCodeLength,
+}
+
+/// Configuration of the XIP mode
+///
+/// Reads of the AHB window fill the cache lines of the CMCC one at a time.
+/// The continuous read mode of the flash saves the instruction of each read:
+/// for example a quad I/O fast read (0xEB) with the 0xA0 mode bits of
+/// Winbond flashes.
+///
+/// ```ignore
+/// let config = XipConfig {
+///     read_mode: ReadMode {
+///         opcode: 0xEB,
+///         width: Width::QuadIo,
+///         dummy_cycles: 4,
+///     },
+///     option_code: Some(OptionCode {
+///         code: 0xA0,
+///         length: OptionCodeLength::_8Bits,
+///     }),
+///     continuous_read: true,
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct XipConfig {
+    pub read_mode: ReadMode,
+    pub option_code: Option<OptionCode>,
+    /// Only send the instruction for the first read. The option code must
+    /// switch the flash to its continuous read mode.
+    pub continuous_read: bool,
+}
+
+/// Option code leaving the continuous read mode
+const EXIT_CONTINUOUS_READ: u8 = 0xff;
+
+/// Scrambling of the memory reads and writes in the AHB window
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Scrambling {
+    Disabled,
+    /// Scramble with a user key. The scrambled data can be programmed into
+    /// the flash of any device.
+    Key(u32),
+    /// Scramble with a user key and a random value unique to each device.
+    /// The scrambled data must be programmed by the device itself.
+    KeyAndDeviceRandom(u32),
+}
+
+/// Qspi used for read/write of fixed-size octet buffers
+pub struct OneShot;
+/// Qspi is memory-mapped as read/execute
+pub struct XIP;
+
+pub struct Qspi<MODE> {
+    qspi: QSPI,
+    _sck: Pb10<PfH>,
+    _cs: Pb11<PfH>,
+    _io0: Pa8<PfH>,
+    _io1: Pa9<PfH>,
+    _io2: Pa10<PfH>,
+    _io3: Pa11<PfH>,
+    read_mode: ReadMode,
+    ddr: bool,
+    four_byte_address: bool,
+    /// Configuration of the XIP mode, once entered
+    xip: Option<XipConfig>,
+    _mode: PhantomData<MODE>,
+}
+
+impl Qspi<OneShot> {
+    /// Enable the clocks for the qspi peripheral in single data rate mode
+    /// assuming 120mhz system clock, for 4mhz spi mode 0 operation.
+    ///
+    /// Use [`with_config`](Self::with_config) with other clocks.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        mclk: &mut MCLK,
+        port: &mut Port,
+        qspi: QSPI,
+        _sck: Pb10<Input<Floating>>,
+        _cs: Pb11<Input<Floating>>,
+        _io0: Pa8<Input<Floating>>,
+        _io1: Pa9<Input<Floating>>,
+        _io2: Pa10<Input<Floating>>,
+        _io3: Pa11<Input<Floating>>,
+    ) -> Qspi<OneShot> {
+        Self::with_config(
+            mclk,
+            port,
+            qspi,
+            _sck,
+            _cs,
+            _io0,
+            _io1,
+            _io2,
+            _io3,
+            Hertz(120_000_000),
+            QspiConfig::default(),
+        )
+    }
+
+    /// Enable the clocks for the qspi peripheral, and configure it for the
+    /// main `clock` frequency, which clocks the peripheral.
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_config<F: Into<Hertz>>(
+        mclk: &mut MCLK,
+        port: &mut Port,
+        qspi: QSPI,
+        _sck: Pb10<Input<Floating>>,
+        _cs: Pb11<Input<Floating>>,
+        _io0: Pa8<Input<Floating>>,
+        _io1: Pa9<Input<Floating>>,
+        _io2: Pa10<Input<Floating>>,
+        _io3: Pa11<Input<Floating>>,
+        clock: F,
+        config: QspiConfig,
+    ) -> Qspi<OneShot> {
+        mclk.apbcmask.modify(|_, w| w.qspi_().set_bit());
+        // The double rate clock is only needed in DDR mode
+        mclk.ahbmask.modify(|_, w| {
+            w.qspi_().set_bit();
+            w.qspi_2x_().bit(config.ddr)
+        });
+
+        let _sck = _sck.into_function_h(port);
+        let _cs = _cs.into_function_h(port);
+        let _io0 = _io0.into_function_h(port);
+        let _io1 = _io1.into_function_h(port);
+        let _io2 = _io2.into_function_h(port);
+        let _io3 = _io3.into_function_h(port);
+
+        qspi.ctrla.write(|w| w.swrst().set_bit());
+        let baud = calculate_baud(clock.into(), config.freq);
+        qspi.baud.write(|w| unsafe {
+            w.baud().bits(baud);
+            match config.mode.polarity {
+                Polarity::IdleLow => w.cpol().clear_bit(),
+                Polarity::IdleHigh => w.cpol().set_bit(),
+            };
+            match config.mode.phase {
+                Phase::CaptureOnFirstTransition => w.cpha().clear_bit(),
+                Phase::CaptureOnSecondTransition => w.cpha().set_bit(),
+            }
+        });
+
+        qspi.ctrlb.write(|w| {
+            w.mode().memory();
+            w.csmode().noreload();
+            w.csmode().lastxfer();
+            w.datalen()._8bits()
+        });
+
+        qspi.ctrla.modify(|_, w| w.enable().set_bit());
+
+        Self {
+            qspi,
+            _sck,
+            _cs,
+            _io0,
+            _io1,
+            _io2,
+            _io3,
+            read_mode: ReadMode::default(),
+            ddr: config.ddr,
+            four_byte_address: false,
+            xip: None,
+            _mode: PhantomData,
+        }
+    }
+
+    /// Run a generic command that neither takes nor receives data
+    pub fn run_command(&self, command: Command) -> Result<(), Error> {
+        match command {
+            //TODO verify this list of commands
+            Command::WriteEnable
+            | Command::WriteDisable
+            | Command::Reset
+            | Command::EnableReset => (),
+            _ => return Err(Error::CommandFunctionMismatch),
+        }
+
+        let tfm = TransferMode {
+            instruction_enable: true,
+            ..TransferMode::default()
+        };
+        unsafe {
+            self.run_read_instruction(command.bits(), tfm, READ, 0, &mut [], true);
+        }
+        Ok(())
+    }
+
+    /// Run one of the read commands
+    pub fn read_command(&self, command: Command, response: &mut [u8]) -> Result<(), Error> {
+        match command {
+            //TODO verify this list of commands
+            Command::Read
+            | Command::QuadRead
+            | Command::ReadId
+            | Command::ReadStatus
+            | Command::ReadStatus2 => (),
+            _ => return Err(Error::CommandFunctionMismatch),
+        }
+
+        let tfm = TransferMode {
+            data_enable: true,
+            instruction_enable: true,
+            ..TransferMode::default()
+        };
+        let tfrtype = if command == Command::QuadRead {
+            READMEMORY
+        } else {
+            READ
+        };
+        unsafe {
+            self.run_read_instruction(command.bits(), tfm, tfrtype, 0, response, true);
+        }
+        Ok(())
+    }
+
+    /// Run one of the write commands
+    pub fn write_command(&self, command: Command, data: &[u8]) -> Result<(), Error> {
+        match command {
+            //TODO verify this list of commands
+            Command::PageProgram
+            | Command::QuadPageProgram
+            | Command::WriteStatus
+            | Command::WriteStatus2 => (),
+            _ => return Err(Error::CommandFunctionMismatch),
+        }
+
+        let tfm = TransferMode {
+            data_enable: !data.is_empty(),
+            instruction_enable: true,
+            ..TransferMode::default()
+        };
+        let tfrtype = if command == Command::QuadPageProgram {
+            WRITEMEMORY
+        } else {
+            WRITE
+        };
+        unsafe {
+            self.run_write_instruction(command.bits(), tfm, tfrtype, 0, data);
+        }
+        Ok(())
+    }
+
+    /// Run one of the erase commands
+    pub fn erase_command(&self, command: Command, address: u32) -> Result<(), Error> {
+        match command {
+            //TODO verify this list of commands
+            Command::EraseSector | Command::EraseBlock => {
+                let tfm = TransferMode {
+                    address_enable: true,
+                    instruction_enable: true,
+                    ..TransferMode::default()
+                };
+                unsafe {
+                    self.run_write_instruction(command.bits(), tfm, WRITE, address, &[]);
+                }
+            }
+            Command::EraseChip => {
+                let tfm = TransferMode {
+                    instruction_enable: true,
+                    ..TransferMode::default()
+                };
+                unsafe {
+                    self.run_read_instruction(command.bits(), tfm, READ, 0, &mut [], true);
+                }
+            }
+            _ => return Err(Error::CommandFunctionMismatch),
+        }
+
+        Ok(())
+    }
+
+    /// Read a sequential block of memory to buf, with the instruction set by
+    /// [`set_read_mode`](Self::set_read_mode)
+    pub fn read_memory(&mut self, addr: u32, buf: &mut [u8]) {
+        unsafe {
+            let src = self.start_memory_read(addr, buf.len());
+            core::ptr::copy(src, buf.as_mut_ptr(), buf.len());
+            self.finalize();
+        }
+    }
+
+    /// Set the instruction of memory reads and XIP. Defaults to the quad
+    /// output fast read (0x6B) with 8 dummy cycles.
+    pub fn set_read_mode(&mut self, read_mode: ReadMode) {
+        self.read_mode = read_mode;
+    }
+
+    /// Instruction of memory reads and XIP
+    pub fn read_mode(&self) -> ReadMode {
+        self.read_mode
+    }
+
+    /// Read the SFDP tables of the flash from `addr`
+    pub fn read_sfdp(&self, addr: u32, buf: &mut [u8]) {
+        let tfm = TransferMode {
+            address_enable: true,
+            data_enable: true,
+            instruction_enable: true,
+            dummy_cycles: sfdp::READ_SFDP_DUMMY_CYCLES,
+            ..TransferMode::default()
+        };
+        unsafe { self.run_read_instruction(sfdp::READ_SFDP, tfm, READ, addr, buf, true) };
+    }
+
+    /// Read the JEDEC basic flash parameter table of the flash. Returns the
+    /// longest one if the flash has several revisions of the table.
+    pub fn read_flash_parameters(&self) -> Result<FlashParameters, Error> {
+        let mut bytes = [0; sfdp::Header::LENGTH];
+        self.read_sfdp(0, &mut bytes);
+        let header = sfdp::Header::parse(&bytes)?;
+
+        let mut basic: Option<sfdp::ParameterHeader> = None;
+        for index in 0..header.parameter_headers {
+            self.read_sfdp(sfdp::ParameterHeader::address(index), &mut bytes);
+            let parameter = sfdp::ParameterHeader::parse(&bytes)?;
+            if parameter.id == sfdp::BASIC_TABLE_ID
+                && parameter.length > basic.map_or(0, |basic| basic.length)
+            {
+                basic = Some(parameter);
+            }
+        }
+        let basic = basic.ok_or(Error::InvalidSfdp)?;
+
+        let mut table = [0; sfdp::BASIC_TABLE_MAX_LEN];
+        let len = (basic.length as usize * 4).min(table.len());
+        self.read_sfdp(basic.pointer, &mut table[..len]);
+        FlashParameters::parse(&table[..len])
+    }
+
+    /// Page Program a sequential block of memory to addr.
+    ///
+    /// Note more than page size bytes are sent to the device, some bytes will
+    /// be discarded. Check your device for specific handling.
+    pub fn write_memory(&mut self, addr: u32, buf: &[u8]) {
+        unsafe {
+            let dst = self.start_memory_write(
+                Command::QuadPageProgram.bits(),
+                Width::QuadOutput,
+                addr,
+                buf.len(),
+            );
+            core::ptr::copy(buf.as_ptr(), dst, buf.len());
+            self.finalize();
+        }
+    }
+
+    /// Send 32 bit addresses instead of 24 bit addresses. The flash must be
+    /// in its 4 byte address mode.
+    pub fn set_four_byte_address(&mut self, enabled: bool) {
+        self.four_byte_address = enabled;
+    }
+
+    /// Scramble the memory reads and writes in the AHB window, including the
+    /// reads of the XIP mode. Data must be read with the scrambling it was
+    /// written with.
+    pub fn set_scrambling(&mut self, scrambling: Scrambling) {
+        let (key, random) = match scrambling {
+            Scrambling::Disabled => {
+                self.qspi.scrambctrl.write(|w| w.enable().clear_bit());
+                return;
+            }
+            Scrambling::Key(key) => (key, false),
+            Scrambling::KeyAndDeviceRandom(key) => (key, true),
+        };
+        self.qspi.scrambkey.write(|w| unsafe { w.key().bits(key) });
+        self.qspi.scrambctrl.write(|w| {
+            w.randomdis().bit(!random);
+            w.enable().set_bit()
+        });
+    }
+
+    /// Latches the peripheral in a read/execute state, so it can be used to
+    /// read or execute directly from flash, with the instruction set by
+    /// [`set_read_mode`](Self::set_read_mode).
+    pub fn into_xip(self) -> Qspi<XIP> {
+        let config = XipConfig {
+            read_mode: self.read_mode,
+            option_code: None,
+            continuous_read: false,
+        };
+        self.into_xip_with_config(config)
+    }
+
+    /// Latches the peripheral in a read/execute state with `config`, so it
+    /// can be used to read or execute directly from flash at 0x04000000.
+    ///
+    /// Code and data can be linked to the flash with a section of the
+    /// `memory.x` linker script of the application:
+    ///
+    /// ```text
+    /// MEMORY
+    /// {
+    ///   QSPI (rx) : ORIGIN = 0x04000000, LENGTH = 4M
+    /// }
+    ///
+    /// SECTIONS
+    /// {
+    ///   .qspi :
+    ///   {
+    ///     *(.qspi .qspi.*);
+    ///   } > QSPI
+    /// }
+    /// INSERT AFTER .rodata;
+    /// ```
+    ///
+    /// and placed in the section with the `#[link_section = ".qspi"]`
+    /// attribute. The section is extracted with
+    /// `objcopy -O binary -j .qspi`, removed from the internal flash image
+    /// with `objcopy -R .qspi`, and programmed at the start of the flash with
+    /// the scrambling used in XIP mode.
+    pub fn into_xip_with_config(mut self, config: XipConfig) -> Qspi<XIP> {
+        self.read_mode = config.read_mode;
+        let mut tfm = self.read_transfer_mode();
+        if let Some(option_code) = config.option_code {
+            tfm.option_code = Some(option_code.length);
+            self.qspi
+                .instrctrl
+                .write(|w| unsafe { w.optcode().bits(option_code.code) });
+        }
+        tfm.continuous_read = config.continuous_read;
+        unsafe {
+            self.start_instruction(self.read_mode.opcode, tfm, READMEMORY, 0);
+        }
+
+        self.xip = Some(config);
+        self.into_mode()
+    }
+}
+
+/// Operations available in XIP mode
+impl Qspi<XIP> {
+    /// Ends the read/execute state, so that instructions can be run.
+    ///
+    /// A flash in continuous read mode is sent a last read with the 0xFF
+    /// option code, which leaves the continuous read mode.
+    pub fn into_oneshot(mut self) -> Qspi<OneShot> {
+        if let Some(XipConfig {
+            option_code: Some(option_code),
+            continuous_read: true,
+            ..
+        }) = self.xip.take()
+        {
+            unsafe {
+                let mut tfm = self.read_transfer_mode();
+                tfm.option_code = Some(option_code.length);
+                tfm.continuous_read = true;
+                self.qspi
+                    .instrctrl
+                    .modify(|_, w| w.optcode().bits(EXIT_CONTINUOUS_READ));
+                let window = self.start_instruction(self.read_mode.opcode, tfm, READMEMORY, 0);
+                core::ptr::read_volatile(window);
+            }
+        }
+        unsafe { self.finalize() };
+
+        self.into_mode()
+    }
+
+    /// Configuration of the XIP mode
+    pub fn xip_config(&self) -> XipConfig {
+        self.xip.unwrap()
+    }
+}
+
+// (Mostly internal) methods available in any mode.
+impl<MODE> Qspi<MODE> {
+    fn into_mode<M>(self) -> Qspi<M> {
+        Qspi {
+            qspi: self.qspi,
+            _sck: self._sck,
+            _cs: self._cs,
+            _io0: self._io0,
+            _io1: self._io1,
+            _io2: self._io2,
+            _io3: self._io3,
+            read_mode: self.read_mode,
+            ddr: self.ddr,
+            four_byte_address: self.four_byte_address,
+            xip: self.xip,
+            _mode: PhantomData,
+        }
+    }
+
+    unsafe fn finalize(&self) {
+        self.qspi.ctrla.write(|w| {
+            w.enable().set_bit();
+            w.lastxfer().set_bit()
+        });
+
+        while self.qspi.intflag.read().instrend().bit_is_clear() {}
+        self.qspi.intflag.write(|w| w.instrend().set_bit());
+        while self.qspi.intflag.read().csrise().bit_is_clear() {}
+        self.qspi.intflag.write(|w| w.csrise().set_bit());
+    }
+
+    /// Transfer mode of memory reads
+    fn read_transfer_mode(&self) -> TransferMode {
+        TransferMode {
+            width: self.read_mode.width,
+            address_enable: true,
+            data_enable: true,
+            instruction_enable: true,
+            dummy_cycles: self.read_mode.dummy_cycles,
+            ddr: self.ddr,
+            ..TransferMode::default()
+        }
+    }
+
+    /// Start an instruction, and return the AHB address of its data.
+    ///
+    /// Memory transfers are addressed by the AHB address, other transfers by
+    /// the INSTRADDR register.
+    unsafe fn start_instruction(
+        &self,
+        opcode: u8,
+        tfm: TransferMode,
+        tfrtype: instrframe::TFRTYPE_A,
+        addr: u32,
+    ) -> *mut u8 {
+        let tfm = TransferMode {
+            four_byte_address: self.four_byte_address,
+            ..tfm
+        };
+        if tfm.address_enable {
+            self.qspi.instraddr.write(|w| w.addr().bits(addr));
+        }
+        self.qspi.instrctrl.modify(|_, w| w.instr().bits(opcode));
+        self.qspi.instrframe.write(|w| tfm.instrframe(w, tfrtype));
+        self.qspi.instrframe.read().bits();
+
+        match tfrtype {
+            READMEMORY | WRITEMEMORY => (QSPI_AHB + addr) as *mut u8,
+            READ | WRITE => QSPI_AHB as *mut u8,
+        }
+    }
+
+    /// Start a memory read of `len` bytes with the read mode, and return the
+    /// AHB address of the data. Reads past the AHB window are register
+    /// transfers.
+    unsafe fn start_memory_read(&self, addr: u32, len: usize) -> *mut u8 {
+        let tfrtype = if in_window(addr, len) {
+            READMEMORY
+        } else {
+            READ
+        };
+        self.start_instruction(
+            self.read_mode.opcode,
+            self.read_transfer_mode(),
+            tfrtype,
+            addr,
+        )
+    }
+
+    /// Start a program instruction of `len` bytes, and return the AHB
+    /// address of the data. Writes past the AHB window are register
+    /// transfers.
+    unsafe fn start_memory_write(
+        &self,
+        opcode: u8,
+        width: Width,
+        addr: u32,
+        len: usize,
+    ) -> *mut u8 {
+        let tfm = TransferMode {
+            width,
+            address_enable: true,
+            data_enable: true,
+            instruction_enable: true,
+            ..TransferMode::default()
+        };
+        let tfrtype = if in_window(addr, len) {
+            WRITEMEMORY
+        } else {
+            WRITE
+        };
+        self.start_instruction(opcode, tfm, tfrtype, addr)
+    }
+
+    unsafe fn run_write_instruction(
+        &self,
+        opcode: u8,
+        tfm: TransferMode,
+        tfrtype: instrframe::TFRTYPE_A,
+        addr: u32,
+        buf: &[u8],
+    ) {
+        let dst = self.start_instruction(opcode, tfm, tfrtype, addr);
+        if !buf.is_empty() {
+            core::ptr::copy(buf.as_ptr(), dst, buf.len());
+        }
+
+        self.finalize();
+    }
+
+    unsafe fn run_read_instruction(
+        &self,
+        opcode: u8,
+        tfm: TransferMode,
+        tfrtype: instrframe::TFRTYPE_A,
+        addr: u32,
+        buf: &mut [u8],
+        finalize: bool,
+    ) {
+        let src = self.start_instruction(opcode, tfm, tfrtype, addr);
+        if !buf.is_empty() {
+            core::ptr::copy(src, buf.as_mut_ptr(), buf.len());
+        }
+
+        if finalize {
+            self.finalize();
+        }
+    }
+
+    /// Set the clock divider, relative to the main clock
+    ///
+    /// This fn safely subtracts 1 from your input value as the underlying fn is
+    /// SCK Baud = MCKL / (value + 1)
+    ///
+    /// ex if MCLK is 120mhz
+    /// value  0 is reduced to  0 results in 120mhz clock
+    /// value  1 is reduced to  0 results in 120mhz clock
+    /// value  2 is reduced to  1 results in  60mhz clock
+    pub fn set_clk_divider(&mut self, value: u8) {
+        // The baud register is divisor - 1
+        self.qspi
+            .baud
+            .modify(|_, w| unsafe { w.baud().bits(value.saturating_sub(1)) });
+    }
+}
+
+/// These methods are available when the `dma` feature is enabled
+#[cfg(feature = "dma")]
+mod dma {
+    use super::*;
+    use crate::dmac::{
+        channel::{Channel, Ready},
+        BufferPair, DmaController, TransferConfiguration, TriggerAction, TriggerSource,
+    };
+
+    impl Qspi<OneShot> {
+        /// Read a sequential block of memory to buf like
+        /// [`read_memory`](Qspi::read_memory), moving the data through the AHB
+        /// window with DMA channel `chan`. Blocks until the transfer is done.
+        pub fn read_memory_dma<const ID: u8>(
+            &mut self,
+            dmac: &mut DmaController,
+            chan: Channel<Ready, ID>,
+            addr: u32,
+            buf: &mut [u8],
+        ) -> Channel<Ready, ID> {
+            unsafe {
+                let src = self.start_memory_read(addr, buf.len());
+                let chan = copy(dmac, chan, src, buf.as_mut_ptr(), buf.len());
+                self.finalize();
+                chan
+            }
+        }
+
+        /// Page Program a sequential block of memory to addr like
+        /// [`write_memory`](Qspi::write_memory), moving the data through the
+        /// AHB window with DMA channel `chan`. Blocks until the transfer is
+        /// done.
+        pub fn write_memory_dma<const ID: u8>(
+            &mut self,
+            dmac: &mut DmaController,
+            chan: Channel<Ready, ID>,
+            addr: u32,
+            buf: &[u8],
+        ) -> Channel<Ready, ID> {
+            unsafe {
+                let dst = self.start_memory_write(
+                    Command::QuadPageProgram.bits(),
+                    Width::QuadOutput,
+                    addr,
+                    buf.len(),
+                );
+                let chan = copy(dmac, chan, buf.as_ptr(), dst, buf.len());
+                self.finalize();
+                chan
+            }
+        }
+    }
+
+    /// Copy `len` bytes from `src` to `dst` with DMA channel `chan`, and wait
+    /// for the copy to complete.
+    ///
+    /// # Safety
+    ///
+    /// `src` and `dst` must be valid for `len` bytes and must not overlap.
+    pub(super) unsafe fn copy<const ID: u8>(
+        dmac: &mut DmaController,
+        mut chan: Channel<Ready, ID>,
+        src: *const u8,
+        dst: *mut u8,
+        len: usize,
+    ) -> Channel<Ready, ID> {
+        let mut done = 0;
+        while done < len {
+            // Block transfers are limited to 65535 beats
+            let count = (len - done).min(u16::MAX as usize);
+            // The transfer is waited for before returning, so the buffers
+            // outlive it even though they are not 'static. The source is only
+            // read by the DMAC, so it may come from a shared reference.
+            let source = core::slice::from_raw_parts_mut(src.add(done) as *mut u8, count);
+            let destination = core::slice::from_raw_parts_mut(dst.add(done), count);
+            let (_, ready, _) = BufferPair {
+                source,
+                destination,
+                _b: PhantomData,
+            }
+            .setup_xfer(chan, false, ())
+            .begin(dmac, TriggerSource::DISABLE, TriggerAction::BLOCK)
+            .wait::<u8, &mut [u8], &mut [u8]>(dmac);
+            chan = ready;
+            done += count;
+        }
+        chan
+    }
+}
+
+/// Returns true if `len` bytes at `addr` are inside the AHB window
+fn in_window(addr: u32, len: usize) -> bool {
+    addr as u64 + len as u64 <= QSPI_AHB_SIZE as u64
+}
+
+/// BAUD value of the fastest SCK not above `freq`, with
+/// SCK = `clock` / (BAUD + 1)
+fn calculate_baud(clock: Hertz, freq: Hertz) -> u8 {
+    let freq = freq.0.max(1);
+    // The divider BAUD + 1 is clock / freq, rounded up
+    (clock.0.saturating_sub(1) / freq).min(u8::MAX as u32) as u8
+}
+
+#[derive(Default, Debug, Copy, Clone)]
+struct TransferMode {
+    width: Width,
+    data_enable: bool,
+    option_code: Option<OptionCodeLength>,
+    address_enable: bool,
+    instruction_enable: bool,
+    dummy_cycles: u8,
+    ddr: bool,
+    four_byte_address: bool,
+    continuous_read: bool,
+}
+
+impl TransferMode {
+    unsafe fn instrframe(
+        self,
+        instrframe: &mut instrframe::W,
+        tfrtype: instrframe::TFRTYPE_A,
+    ) -> &mut instrframe::W {
+        match self.width {
+            Width::SingleBitSpi => instrframe.width().single_bit_spi(),
+            Width::DualOutput => instrframe.width().dual_output(),
+            Width::QuadOutput => instrframe.width().quad_output(),
+            Width::DualIo => instrframe.width().dual_io(),
+            Width::QuadIo => instrframe.width().quad_io(),
+            Width::DualCmd => instrframe.width().dual_cmd(),
+            Width::QuadCmd => instrframe.width().quad_cmd(),
+        };
+
+        if self.data_enable {
+            instrframe.dataen().set_bit();
+        }
+        if let Some(length) = self.option_code {
+            instrframe.optcodeen().set_bit();
+            match length {
+                OptionCodeLength::_1Bit => instrframe.optcodelen()._1bit(),
+                OptionCodeLength::_2Bits => instrframe.optcodelen()._2bits(),
+                OptionCodeLength::_4Bits => instrframe.optcodelen()._4bits(),
+                OptionCodeLength::_8Bits => instrframe.optcodelen()._8bits(),
+            };
+        }
+        if self.continuous_read {
+            instrframe.crmode().set_bit();
+        }
+        if self.address_enable {
+            instrframe.addren().set_bit();
+        }
+        if self.instruction_enable {
+            instrframe.instren().set_bit();
+        }
+
+        if self.ddr {
+            instrframe.ddren().set_bit();
+        }
+        if self.dummy_cycles > 0 {
+            instrframe.dummylen().bits(self.dummy_cycles);
+        }
+        if self.four_byte_address {
+            instrframe.addrlen()._32bits();
+        } else {
+            instrframe.addrlen()._24bits();
+        }
+        instrframe.tfrtype().variant(tfrtype);
+        instrframe
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Command {
+    Read = 0x03,
+    QuadRead = 0x6B,
+    ReadId = 0x9F,
+    PageProgram = 0x02,
+    QuadPageProgram = 0x32,
+    ReadStatus = 0x05,
+    ReadStatus2 = 0x35,
+    WriteStatus = 0x01,
+    WriteStatus2 = 0x31,
+    EnableReset = 0x66,
+    Reset = 0x99,
+    WriteEnable = 0x06,
+    WriteDisable = 0x04,
+    EraseSector = 0x20,
+    EraseBlock = 0xD8,
+    EraseChip = 0xC7,
+}
+
+impl Command {
+    fn bits(self) -> u8 {
+        self as u8
+    }
+}
+
+const QSPI_AHB: u32 = 0x04000000;
+/// Size of the AHB window of the serial memory
+const QSPI_AHB_SIZE: u32 = 0x01000000;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn baud() {
+        // 120 MHz / 30 = 4 MHz
+        assert_eq!(calculate_baud(Hertz(120_000_000), Hertz(4_000_000)), 29);
+        // Rounded down to 120 MHz / 4 = 30 MHz
+        assert_eq!(calculate_baud(Hertz(120_000_000), Hertz(32_000_000)), 3);
+        assert_eq!(calculate_baud(Hertz(48_000_000), Hertz(100_000_000)), 0);
+        assert_eq!(calculate_baud(Hertz(120_000_000), Hertz(100_000)), 255);
+        assert_eq!(calculate_baud(Hertz(120_000_000), Hertz(0)), 255);
+    }
+}
@@ -0,0 +1,450 @@
+//! Serial Flash Discoverable Parameters (JESD216)
+//!
+//! SFDP flashes describe themselves in tables read with the `0x5A`
+//! instruction: a header at address 0, followed by parameter headers
+//! pointing to the parameter tables. The JEDEC basic flash parameter table
+//! gives the density, page size, erase instructions, fast read instructions
+//! and quad enable method of the flash.
+//!
+//! [`Qspi::read_flash_parameters`] reads and parses the basic table of the
+//! attached flash.
+//!
+//! [`Qspi::read_flash_parameters`]: super::Qspi::read_flash_parameters
+
+use super::{Error, ReadMode, Width};
+
+/// Read SFDP instruction
+pub const READ_SFDP: u8 = 0x5A;
+/// Dummy cycles of the read SFDP instruction
+pub const READ_SFDP_DUMMY_CYCLES: u8 = 8;
+
+/// "SFDP" in little endian
+const SIGNATURE: u32 = 0x5044_4653;
+
+/// ID of the JEDEC basic flash parameter table
+pub const BASIC_TABLE_ID: u16 = 0xff00;
+/// Length of the basic flash parameter table of JESD216 revision 1.0, in
+/// bytes
+pub const BASIC_TABLE_MIN_LEN: usize = 9 * 4;
+/// Length of the basic flash parameter table of JESD216B and later, in
+/// bytes
+pub const BASIC_TABLE_MAX_LEN: usize = 16 * 4;
+
+/// SFDP header
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Header {
+    pub major: u8,
+    pub minor: u8,
+    /// Number of parameter headers following the SFDP header
+    pub parameter_headers: u8,
+}
+
+impl Header {
+    /// Length of the header, and of each parameter header
+    pub const LENGTH: usize = 8;
+
+    /// Parse the SFDP header
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < Self::LENGTH || dword(bytes, 0) != SIGNATURE {
+            return Err(Error::InvalidSfdp);
+        }
+        Ok(Self {
+            minor: bytes[4],
+            major: bytes[5],
+            // The count is stored minus one
+            parameter_headers: bytes[6].wrapping_add(1),
+        })
+    }
+}
+
+/// Header of a parameter table
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ParameterHeader {
+    pub id: u16,
+    pub major: u8,
+    pub minor: u8,
+    /// Length of the table, in 32 bit words
+    pub length: u8,
+    /// SFDP address of the table
+    pub pointer: u32,
+}
+
+impl ParameterHeader {
+    /// Parse a parameter header
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < Header::LENGTH {
+            return Err(Error::InvalidSfdp);
+        }
+        Ok(Self {
+            id: u16::from_le_bytes([bytes[0], bytes[7]]),
+            minor: bytes[1],
+            major: bytes[2],
+            length: bytes[3],
+            pointer: dword(bytes, 4) & 0x00ff_ffff,
+        })
+    }
+
+    /// SFDP address of parameter header `index`
+    pub fn address(index: u8) -> u32 {
+        (Header::LENGTH * (index as usize + 1)) as u32
+    }
+}
+
+/// Number of address bytes of the flash
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AddressBytes {
+    /// 3 byte addresses only
+    Three,
+    /// 3 byte addresses by default, 4 byte addresses once enabled
+    ThreeOrFour,
+    /// 4 byte addresses only
+    Four,
+}
+
+/// Erase instruction
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct EraseType {
+    /// Size of the erased area, in bytes
+    pub size: u32,
+    pub opcode: u8,
+}
+
+/// Fast read instruction
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct FastRead {
+    pub opcode: u8,
+    /// Wait states between the address or mode bits and the data
+    pub dummy_cycles: u8,
+    /// Clocks of the mode bits following the address
+    pub mode_clocks: u8,
+}
+
+impl FastRead {
+    fn parse(bits: u16) -> Self {
+        Self {
+            opcode: (bits >> 8) as u8,
+            dummy_cycles: (bits & 0x1f) as u8,
+            mode_clocks: ((bits >> 5) & 0x7) as u8,
+        }
+    }
+}
+
+/// Method to enable the quad mode of the flash, setting its QE bit
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum QuadEnable {
+    /// The flash has no QE bit, or needs no setting for quad instructions
+    NotRequired,
+    /// Bit 1 of status register 2. Writing one byte with the write status
+    /// instruction (0x01) clears status register 2, so both registers must
+    /// be written.
+    Sr2Bit1WriteTwoBytes,
+    /// Bit 6 of status register 1, written with the write status
+    /// instruction (0x01)
+    Sr1Bit6,
+    /// Bit 7 of status register 2, read with 0x3F and written with 0x3E
+    Sr2Bit7,
+    /// Bit 1 of status register 2, written with two bytes of the write
+    /// status instruction (0x01)
+    Sr2Bit1,
+    /// Bit 1 of status register 2, read with 0x35 and written with two bytes
+    /// of the write status instruction (0x01)
+    Sr2Bit1Read35,
+    /// Bit 1 of status register 2, read with 0x35 and written with 0x31
+    Sr2Bit1Write31,
+}
+
+impl QuadEnable {
+    fn parse(bits: u8) -> Option<Self> {
+        Some(match bits {
+            0 => QuadEnable::NotRequired,
+            1 => QuadEnable::Sr2Bit1WriteTwoBytes,
+            2 => QuadEnable::Sr1Bit6,
+            3 => QuadEnable::Sr2Bit7,
+            4 => QuadEnable::Sr2Bit1,
+            5 => QuadEnable::Sr2Bit1Read35,
+            6 => QuadEnable::Sr2Bit1Write31,
+            _ => return None,
+        })
+    }
+}
+
+/// Contents of the basic flash parameter table
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct FlashParameters {
+    /// Capacity, in bytes, saturating at 4 GiB
+    pub size: u32,
+    /// Largest number of bytes programmed by one program instruction
+    pub page_size: u32,
+    pub address_bytes: AddressBytes,
+    /// The flash supports double transfer rate clocking
+    pub dtr: bool,
+    /// Erase instructions, `None` for unused erase types
+    pub erase_types: [Option<EraseType>; 4],
+    /// Quad output fast read (1-1-4), if supported
+    pub fast_read_1_1_4: Option<FastRead>,
+    /// Quad I/O fast read (1-4-4), if supported
+    pub fast_read_1_4_4: Option<FastRead>,
+    /// Quad enable method, `None` if the table predates JESD216A or reports
+    /// a reserved method
+    pub quad_enable: Option<QuadEnable>,
+}
+
+impl FlashParameters {
+    /// Parse a basic flash parameter table of at least
+    /// [`BASIC_TABLE_MIN_LEN`] bytes
+    pub fn parse(table: &[u8]) -> Result<Self, Error> {
+        if table.len() < BASIC_TABLE_MIN_LEN {
+            return Err(Error::InvalidSfdp);
+        }
+        let dwords = table.len() / 4;
+        let dword = |index: usize| dword(table, (index - 1) * 4);
+
+        let first = dword(1);
+        let address_bytes = match (first >> 17) & 0x3 {
+            0 => AddressBytes::Three,
+            1 => AddressBytes::ThreeOrFour,
+            2 => AddressBytes::Four,
+            _ => return Err(Error::InvalidSfdp),
+        };
+        let dtr = first & (1 << 19) != 0;
+        let fast_read_1_4_4 = if first & (1 << 21) != 0 {
+            Some(FastRead::parse(dword(3) as u16))
+        } else {
+            None
+        };
+        let fast_read_1_1_4 = if first & (1 << 22) != 0 {
+            Some(FastRead::parse((dword(3) >> 16) as u16))
+        } else {
+            None
+        };
+
+        let density = dword(2);
+        let size = if density & (1 << 31) == 0 {
+            ((density as u64 + 1) / 8) as u32
+        } else {
+            // Density of 2^N bits
+            match (density & 0x7fff_ffff).checked_sub(3) {
+                Some(shift) if shift < 32 => 1 << shift,
+                Some(_) => u32::MAX,
+                None => return Err(Error::InvalidSfdp),
+            }
+        };
+
+        let mut erase_types = [None; 4];
+        let erase = u64::from(dword(8)) | u64::from(dword(9)) << 32;
+        for (index, erase_type) in erase_types.iter_mut().enumerate() {
+            let bits = (erase >> (index * 16)) as u16;
+            let shift = bits & 0xff;
+            if shift != 0 && shift < 32 {
+                *erase_type = Some(EraseType {
+                    size: 1 << shift,
+                    opcode: (bits >> 8) as u8,
+                });
+            }
+        }
+
+        let page_size = if dwords >= 11 {
+            1 << ((dword(11) >> 4) & 0xf)
+        } else if first & (1 << 2) != 0 {
+            // Write granularity of at least 64 bytes, without the size
+            256
+        } else {
+            1
+        };
+
+        let quad_enable = if dwords >= 15 {
+            QuadEnable::parse(((dword(15) >> 20) & 0x7) as u8)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            size,
+            page_size,
+            address_bytes,
+            dtr,
+            erase_types,
+            fast_read_1_1_4,
+            fast_read_1_4_4,
+            quad_enable,
+        })
+    }
+
+    /// Smallest erase instruction
+    pub fn smallest_erase(&self) -> Option<EraseType> {
+        self.erase_types
+            .iter()
+            .flatten()
+            .min_by_key(|erase_type| erase_type.size)
+            .copied()
+    }
+
+    /// Largest erase instruction
+    pub fn largest_erase(&self) -> Option<EraseType> {
+        self.erase_types
+            .iter()
+            .flatten()
+            .max_by_key(|erase_type| erase_type.size)
+            .copied()
+    }
+
+    /// Memory read instruction for [`Qspi::set_read_mode`]: the quad output
+    /// fast read if supported without mode bits, else the single bit fast
+    /// read.
+    ///
+    /// The quad output fast read needs the quad mode enabled in the flash,
+    /// with the [`quad_enable`](Self::quad_enable) method.
+    ///
+    /// [`Qspi::set_read_mode`]: super::Qspi::set_read_mode
+    pub fn read_mode(&self) -> ReadMode {
+        match self.fast_read_1_1_4 {
+            Some(fast_read) if fast_read.mode_clocks == 0 => ReadMode {
+                opcode: fast_read.opcode,
+                width: Width::QuadOutput,
+                dummy_cycles: fast_read.dummy_cycles,
+            },
+            _ => ReadMode::FAST_READ,
+        }
+    }
+}
+
+/// Little endian 32 bit word at `offset`
+fn dword(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SFDP header and parameter header of a JESD216B flash
+    const HEADER: [u8; 16] = [
+        0x53, 0x46, 0x44, 0x50, 0x06, 0x01, 0x00, 0xff, //
+        0x00, 0x06, 0x01, 0x10, 0x80, 0x00, 0x00, 0xff,
+    ];
+
+    /// JESD216B basic flash parameter table of a 16 Mbit quad SPI flash
+    /// with 4, 32 and 64 KiB erase
+    const TABLE_16MBIT: [u8; 64] = [
+        0xe5, 0x20, 0xf1, 0xff, 0xff, 0xff, 0xff, 0x00, 0x44, 0xeb, 0x08, 0x6b, 0x08, 0x3b, 0x42,
+        0xbb, 0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0xff, 0xff, 0x40, 0xeb, 0x0c, 0x20,
+        0x0f, 0x52, 0x10, 0xd8, 0x00, 0x00, 0x36, 0x02, 0xa6, 0x00, 0x82, 0xea, 0x14, 0xc9, 0xe9,
+        0x63, 0x76, 0x33, 0x7a, 0x75, 0x7a, 0x75, 0xf7, 0xa2, 0xd5, 0x5c, 0x19, 0xf7, 0x4d, 0xff,
+        0xe9, 0x30, 0xf8, 0x80,
+    ];
+
+    /// JESD216 revision 1.0 basic flash parameter table of a 256 Mbit flash
+    /// with 3 or 4 byte addressing, without quad output fast read
+    const TABLE_256MBIT: [u8; 36] = [
+        0xe5, 0x20, 0xa3, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x44, 0xeb, 0x08, 0x6b, 0x08, 0x3b, 0x80,
+        0xbb, 0xee, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0xff, 0xff, 0xff, 0x00, 0xff, 0x0c, 0x20,
+        0x10, 0xd8, 0x00, 0xff, 0x00, 0xff,
+    ];
+
+    #[test]
+    fn headers() {
+        let header = Header::parse(&HEADER[..8]).unwrap();
+        assert_eq!(
+            header,
+            Header {
+                major: 1,
+                minor: 6,
+                parameter_headers: 1
+            }
+        );
+        assert_eq!(ParameterHeader::address(0), 8);
+        let parameter = ParameterHeader::parse(&HEADER[8..]).unwrap();
+        assert_eq!(
+            parameter,
+            ParameterHeader {
+                id: BASIC_TABLE_ID,
+                major: 1,
+                minor: 6,
+                length: 16,
+                pointer: 0x80
+            }
+        );
+        assert_eq!(Header::parse(&[0xff; 8]), Err(Error::InvalidSfdp));
+        assert_eq!(Header::parse(&HEADER[..4]), Err(Error::InvalidSfdp));
+    }
+
+    #[test]
+    fn jesd216b_table() {
+        let parameters = FlashParameters::parse(&TABLE_16MBIT).unwrap();
+        assert_eq!(parameters.size, 2 * 1024 * 1024);
+        assert_eq!(parameters.page_size, 256);
+        assert_eq!(parameters.address_bytes, AddressBytes::Three);
+        assert!(!parameters.dtr);
+        assert_eq!(
+            parameters.erase_types,
+            [
+                Some(EraseType {
+                    size: 4096,
+                    opcode: 0x20
+                }),
+                Some(EraseType {
+                    size: 32768,
+                    opcode: 0x52
+                }),
+                Some(EraseType {
+                    size: 65536,
+                    opcode: 0xd8
+                }),
+                None,
+            ]
+        );
+        assert_eq!(parameters.smallest_erase().unwrap().opcode, 0x20);
+        assert_eq!(parameters.largest_erase().unwrap().opcode, 0xd8);
+        assert_eq!(
+            parameters.fast_read_1_4_4,
+            Some(FastRead {
+                opcode: 0xeb,
+                dummy_cycles: 4,
+                mode_clocks: 2
+            })
+        );
+        assert_eq!(parameters.quad_enable, Some(QuadEnable::Sr2Bit1));
+        assert_eq!(
+            parameters.read_mode(),
+            ReadMode {
+                opcode: 0x6b,
+                width: Width::QuadOutput,
+                dummy_cycles: 8
+            }
+        );
+    }
+
+    #[test]
+    fn jesd216_table() {
+        let parameters = FlashParameters::parse(&TABLE_256MBIT).unwrap();
+        assert_eq!(parameters.size, 32 * 1024 * 1024);
+        // No page size before JESD216B, but a write granularity of 64 bytes
+        // or more
+        assert_eq!(parameters.page_size, 256);
+        assert_eq!(parameters.address_bytes, AddressBytes::ThreeOrFour);
+        assert_eq!(parameters.fast_read_1_1_4, None);
+        assert_eq!(parameters.quad_enable, None);
+        assert_eq!(parameters.smallest_erase().unwrap().size, 4096);
+        assert_eq!(parameters.erase_types[2], None);
+        assert_eq!(parameters.read_mode(), ReadMode::FAST_READ);
+        assert_eq!(
+            FlashParameters::parse(&TABLE_256MBIT[..32]),
+            Err(Error::InvalidSfdp)
+        );
+    }
+
+    #[test]
+    fn power_of_two_density() {
+        let mut table = TABLE_256MBIT;
+        // 2^34 bits
+        table[4..8].copy_from_slice(&(0x8000_0000u32 | 34).to_le_bytes());
+        assert_eq!(FlashParameters::parse(&table).unwrap().size, 1 << 31);
+        table[4..8].copy_from_slice(&(0x8000_0000u32 | 40).to_le_bytes());
+        assert_eq!(FlashParameters::parse(&table).unwrap().size, u32::MAX);
+    }
+}
@@ -0,0 +1,282 @@
+//! Supply controller
+//!
+//! The SUPC monitors the VDD supply with the BOD33 brown-out detector,
+//! selects the main voltage regulator, generates the internal voltage
+//! reference used by the ADC, DAC and AC, and switches the backup domain to
+//! the VBAT supply.
+//!
+//! The BOD12 monitoring VDDCORE is calibrated in production and its
+//! register is reserved, so it is not exposed here.
+//!
+//! Threshold and hysteresis values are raw register values; refer to the
+//! electrical characteristics of the datasheet for the matching voltages.
+//! After reset, the BOD33 is configured from the NVM user row, which
+//! [`Bod33Config::read`] returns.
+
+use crate::target_device::supc::bbps::CONF_A;
+use crate::target_device::supc::vref::SEL_A;
+use crate::target_device::supc::vreg::SEL_A as REGSEL_A;
+use crate::target_device::SUPC;
+
+/// Action taken when VDD falls below the BOD33 threshold
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BodAction {
+    /// Only set the detection status
+    None,
+    /// Reset the device
+    Reset,
+    /// Raise the BOD33 detection interrupt
+    Interrupt,
+    /// Put the device in backup sleep mode
+    Backup,
+}
+
+/// Clock prescaler of the BOD33 in sampling mode
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BodPrescaler {
+    Div1 = 0,
+    Div4,
+    Div8,
+    Div16,
+    Div32,
+    Div64,
+    Div128,
+    Div256,
+}
+
+/// Operation of the BOD33 in standby mode
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BodMode {
+    /// VDD is monitored continuously
+    Continuous,
+    /// VDD is monitored periodically, on the prescaled OSCULP32K clock
+    Sampling(BodPrescaler),
+}
+
+/// BOD33 configuration
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Bod33Config {
+    /// VDD threshold level
+    pub level: u8,
+    /// VBAT threshold level in backup sleep mode
+    pub backup_level: u8,
+    /// Hysteresis, from 0 (none) to 15
+    pub hysteresis: u8,
+    /// Action on detection
+    pub action: BodAction,
+    /// Operation in standby mode
+    pub standby_mode: BodMode,
+    /// Keep monitoring in standby mode
+    pub run_in_standby: bool,
+    /// Keep monitoring in hibernate mode
+    pub run_in_hibernate: bool,
+    /// Keep monitoring in backup mode
+    pub run_in_backup: bool,
+}
+
+impl Bod33Config {
+    /// Read the current configuration of the BOD33
+    pub fn read(supc: &SUPC) -> Self {
+        let bod33 = supc.bod33.read();
+        let action = match bod33.action().bits() {
+            0 => BodAction::None,
+            1 => BodAction::Reset,
+            2 => BodAction::Interrupt,
+            _ => BodAction::Backup,
+        };
+        let standby_mode = if bod33.stdbycfg().bit_is_set() {
+            BodMode::Sampling(match bod33.psel().bits() {
+                0 => BodPrescaler::Div1,
+                1 => BodPrescaler::Div4,
+                2 => BodPrescaler::Div8,
+                3 => BodPrescaler::Div16,
+                4 => BodPrescaler::Div32,
+                5 => BodPrescaler::Div64,
+                6 => BodPrescaler::Div128,
+                _ => BodPrescaler::Div256,
+            })
+        } else {
+            BodMode::Continuous
+        };
+        Self {
+            level: bod33.level().bits(),
+            backup_level: bod33.vbatlevel().bits(),
+            hysteresis: bod33.hyst().bits(),
+            action,
+            standby_mode,
+            run_in_standby: bod33.runstdby().bit_is_set(),
+            run_in_hibernate: bod33.runhib().bit_is_set(),
+            run_in_backup: bod33.runbkup().bit_is_set(),
+        }
+    }
+}
+
+/// Returns `true` if the BOD33 is enabled
+pub fn bod33_enabled(supc: &SUPC) -> bool {
+    supc.bod33.read().enable().bit_is_set()
+}
+
+/// Configure and enable the BOD33.
+///
+/// The BOD33 is disabled while it is reconfigured. With
+/// [`BodAction::Reset`], a level above the current VDD resets the device as
+/// soon as the BOD33 is enabled.
+pub fn enable_bod33(supc: &mut SUPC, config: &Bod33Config) {
+    disable_bod33(supc);
+
+    let (sampling, prescaler) = match config.standby_mode {
+        BodMode::Continuous => (false, 0),
+        BodMode::Sampling(prescaler) => (true, prescaler as u8),
+    };
+    let action = match config.action {
+        BodAction::None => 0,
+        BodAction::Reset => 1,
+        BodAction::Interrupt => 2,
+        BodAction::Backup => 3,
+    };
+    supc.bod33.write(|w| unsafe {
+        w.level().bits(config.level);
+        w.vbatlevel().bits(config.backup_level);
+        w.hyst().bits(config.hysteresis);
+        w.action().bits(action);
+        w.stdbycfg().bit(sampling);
+        w.psel().bits(prescaler);
+        w.runstdby().bit(config.run_in_standby);
+        w.runhib().bit(config.run_in_hibernate);
+        w.runbkup().bit(config.run_in_backup)
+    });
+    supc.bod33.modify(|_, w| w.enable().set_bit());
+    while supc.status.read().bod33rdy().bit_is_clear() {}
+}
+
+/// Disable the BOD33
+pub fn disable_bod33(supc: &mut SUPC) {
+    supc.bod33.modify(|_, w| w.enable().clear_bit());
+    while supc.status.read().b33srdy().bit_is_clear() {}
+}
+
+/// Returns `true` while VDD is below the BOD33 threshold
+pub fn bod33_detected(supc: &SUPC) -> bool {
+    supc.status.read().bod33det().bit_is_set()
+}
+
+/// Enable the BOD33 detection interrupt
+pub fn enable_bod33_interrupt(supc: &mut SUPC) {
+    supc.intenset.write(|w| w.bod33det().set_bit());
+}
+
+/// Disable the BOD33 detection interrupt
+pub fn disable_bod33_interrupt(supc: &mut SUPC) {
+    supc.intenclr.write(|w| w.bod33det().set_bit());
+}
+
+/// Clear the BOD33 detection interrupt flag
+pub fn clear_bod33_interrupt(supc: &mut SUPC) {
+    supc.intflag.write(|w| w.bod33det().set_bit());
+}
+
+/// Main voltage regulator
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Regulator {
+    /// Linear regulator
+    Ldo,
+    /// Switching regulator. Requires the external inductor on VSW.
+    Buck,
+}
+
+/// Select the main voltage regulator, and wait until it is ready
+pub fn set_regulator(supc: &mut SUPC, regulator: Regulator) {
+    let sel = match regulator {
+        Regulator::Ldo => REGSEL_A::LDO,
+        Regulator::Buck => REGSEL_A::BUCK,
+    };
+    supc.vreg.modify(|_, w| w.sel().variant(sel));
+    while supc.status.read().vregrdy().bit_is_clear() {}
+}
+
+/// Returns the selected main voltage regulator
+pub fn regulator(supc: &SUPC) -> Regulator {
+    match supc.vreg.read().sel().variant() {
+        REGSEL_A::LDO => Regulator::Ldo,
+        REGSEL_A::BUCK => Regulator::Buck,
+    }
+}
+
+/// Internal voltage reference
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Reference {
+    _1V0,
+    _1V1,
+    _1V2,
+    _1V25,
+    _2V0,
+    _2V2,
+    _2V4,
+    _2V5,
+}
+
+impl From<Reference> for SEL_A {
+    fn from(reference: Reference) -> Self {
+        match reference {
+            Reference::_1V0 => SEL_A::_1V0,
+            Reference::_1V1 => SEL_A::_1V1,
+            Reference::_1V2 => SEL_A::_1V2,
+            Reference::_1V25 => SEL_A::_1V25,
+            Reference::_2V0 => SEL_A::_2V0,
+            Reference::_2V2 => SEL_A::_2V2,
+            Reference::_2V4 => SEL_A::_2V4,
+            Reference::_2V5 => SEL_A::_2V5,
+        }
+    }
+}
+
+/// Select the voltage of the internal reference, which the ADC, DAC and AC
+/// use as their `INTREF` reference, and make it available as an input
+/// channel of the ADC (`VREFOE`).
+///
+/// When `on_demand` is set, the reference only runs while a peripheral
+/// requests it. `run_in_standby` keeps it running in standby mode.
+pub fn enable_reference_output(
+    supc: &mut SUPC,
+    reference: Reference,
+    on_demand: bool,
+    run_in_standby: bool,
+) {
+    supc.vref.modify(|_, w| {
+        w.sel().variant(reference.into());
+        w.ondemand().bit(on_demand);
+        w.runstdby().bit(run_in_standby);
+        w.vrefoe().set_bit()
+    });
+}
+
+/// Stop making the internal voltage reference available as an ADC input
+/// channel. It is still used as the `INTREF` reference.
+pub fn disable_reference_output(supc: &mut SUPC) {
+    supc.vref.modify(|_, w| w.vrefoe().clear_bit());
+}
+
+/// Supply of the backup domain
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BackupSupply {
+    /// Switch to VBAT when the BOD33 detects a VDD failure, and back to VDD
+    /// when it is restored. The BOD33 should use [`BodAction::Backup`].
+    Automatic,
+    /// Always supply the backup domain from VBAT in backup mode
+    Forced,
+}
+
+/// Configure the battery backup power switch.
+///
+/// When `wake_on_main` is set, the device wakes up from backup mode when the
+/// backup domain switches back to VDD.
+pub fn configure_backup_supply(supc: &mut SUPC, supply: BackupSupply, wake_on_main: bool) {
+    let conf = match supply {
+        BackupSupply::Automatic => CONF_A::BOD33,
+        BackupSupply::Forced => CONF_A::FORCED,
+    };
+    supc.bbps.write(|w| {
+        w.conf().variant(conf);
+        w.wakeen().bit(wake_on_main)
+    });
+}
@@ -0,0 +1,109 @@
+//! Crystal-less USB clock recovery
+//!
+//! Without a crystal, the DFLL48M providing the 48 MHz USB clock is tuned
+//! to the 1 kHz start of frame (SOF) packets sent by the host. Without SOFs,
+//! as when the bus is suspended or the device unplugged, the DFLL must be
+//! switched to another mode to keep a stable frequency.
+//!
+//! The clock controller leaves the DFLL48M in open loop mode, so clock
+//! recovery is only enabled by [`ClockRecovery::start`].
+//!
+//! [`ClockRecovery`] switches the DFLL between USB clock recovery and a
+//! fallback mode. Given to [`UsbBus::set_clock_recovery`], it is started on
+//! bus resets and resumes, and stopped when the bus is suspended, which
+//! also happens shortly after the device is unplugged, provided the bus
+//! detects suspends.
+//!
+//! [`UsbBus::set_clock_recovery`]: super::UsbBus::set_clock_recovery
+
+use crate::clock::{configure_usb_correction, Dfll48Clock};
+use crate::target_device::OSCCTRL;
+
+/// DFLL48M multiplication factor locking to the 32.768 kHz reference
+const MUL_32K: u16 = ((48_000_000 + 16_384) / 32_768) as u16;
+
+/// Mode of the DFLL48M while USB clock recovery is stopped
+pub enum DfllFallback {
+    /// Keep the last frequency of the clock recovery, without correction.
+    /// The frequency drifts with temperature.
+    OpenLoop,
+    /// Lock to the 32.768 kHz reference clock of the DFLL48M. The reference
+    /// channel must be fed by a generator running from a 32.768 kHz
+    /// oscillator.
+    Closed32k(Dfll48Clock),
+}
+
+/// State of the DFLL48M
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ClockRecoveryStatus {
+    /// Tuned to the start of frame packets
+    Locked,
+    /// Recovering the clock from the start of frame packets, not locked yet
+    Locking,
+    /// Running in the fallback mode
+    Fallback,
+}
+
+/// USB clock recovery of the DFLL48M
+pub struct ClockRecovery {
+    oscctrl: OSCCTRL,
+    fallback: DfllFallback,
+}
+
+impl ClockRecovery {
+    /// Take control of the DFLL48M mode, and switch it to `fallback` until
+    /// clock recovery is started.
+    pub fn new(oscctrl: OSCCTRL, fallback: DfllFallback) -> Self {
+        let mut clock_recovery = Self { oscctrl, fallback };
+        clock_recovery.stop();
+        clock_recovery
+    }
+
+    /// Tune the DFLL48M to the USB start of frame packets
+    pub fn start(&mut self) {
+        if !self.is_started() {
+            configure_usb_correction(&mut self.oscctrl);
+        }
+    }
+
+    /// Switch the DFLL48M to the fallback mode
+    pub fn stop(&mut self) {
+        let oscctrl = &mut self.oscctrl;
+        match self.fallback {
+            // The DFLL keeps its current tuning value in open loop mode
+            DfllFallback::OpenLoop => oscctrl.dfllctrlb.write(|w| w.mode().clear_bit()),
+            DfllFallback::Closed32k(_) => {
+                oscctrl.dfllmul.write(|w| unsafe {
+                    w.cstep().bits(0x1).fstep().bits(0x1).mul().bits(MUL_32K)
+                });
+                while oscctrl.dfllsync.read().dfllmul().bit_is_set() {}
+                oscctrl.dfllctrlb.write(|w| w.mode().set_bit());
+            }
+        }
+        while oscctrl.dfllsync.read().dfllctrlb().bit_is_set() {}
+    }
+
+    /// Returns true if the DFLL48M is in USB clock recovery mode
+    pub fn is_started(&self) -> bool {
+        self.oscctrl.dfllctrlb.read().usbcrm().bit_is_set()
+    }
+
+    /// Returns the state of the DFLL48M
+    pub fn status(&self) -> ClockRecoveryStatus {
+        if !self.is_started() {
+            return ClockRecoveryStatus::Fallback;
+        }
+        let status = self.oscctrl.status.read();
+        if status.dflllckf().bit_is_set() && status.dfllrcs().bit_is_clear() {
+            ClockRecoveryStatus::Locked
+        } else {
+            ClockRecoveryStatus::Locking
+        }
+    }
+
+    /// Release the OSCCTRL peripheral, leaving the DFLL48M in its current
+    /// mode
+    pub fn free(self) -> (OSCCTRL, DfllFallback) {
+        (self.oscctrl, self.fallback)
+    }
+}
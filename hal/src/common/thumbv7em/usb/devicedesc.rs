@@ -63,17 +63,19 @@ impl DeviceDescBank {
         self.pcksize.set_auto_zlp(enable);
     }
 
-    /// These bits contains the maximum packet size of the endpoint.
+    /// These bits contains the maximum packet size of the endpoint, rounded
+    /// up to the next size supported by the hardware for isochronous
+    /// endpoints.
     pub fn set_endpoint_size(&mut self, size: u16) {
         let size = match size {
-            8 => 0u32,
-            16 => 1,
-            32 => 2,
-            64 => 3,
-            128 => 4,
-            256 => 5,
-            512 => 6,
-            1023 => 7,
+            0..=8 => 0u32,
+            9..=16 => 1,
+            17..=32 => 2,
+            33..=64 => 3,
+            65..=128 => 4,
+            129..=256 => 5,
+            257..=512 => 6,
+            513..=1023 => 7,
             _ => unreachable!(),
         };
         self.pcksize.set_size(size);
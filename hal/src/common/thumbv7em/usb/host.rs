@@ -0,0 +1,587 @@
+//! USB host mode
+//!
+//! [`UsbHost`] drives the USB peripheral as a full-speed host with a single
+//! port, and implements [`HostBus`] for the enumeration and class drivers
+//! re-exported here.
+//!
+//! The host controller does not switch VBUS: the board must power the port,
+//! usually with a load switch on a GPIO, and call [`UsbHost::set_vbus`].
+//!
+//! ```ignore
+//! let mut host = UsbHost::new(&usb_clock, &mut peripherals.MCLK, dm, dp, peripherals.USB);
+//! host.set_vbus(true);
+//! while host.connected().is_none() {}
+//! let mut config = [0; 256];
+//! let (device, config) = enumerate(&mut host, 1, &mut config).unwrap();
+//! let mut keyboard = hid::Keyboard::new(&mut host, &device, config).unwrap();
+//! ```
+//!
+//! Transfers use packet buffers of 64 bytes, so isochronous endpoints are
+//! not supported.
+
+pub use crate::usb_host::*;
+
+use super::{DmPad, DpPad, UsbPadDm, UsbPadDp};
+use crate::calibration::{usb_transn_cal, usb_transp_cal, usb_trim_cal};
+use crate::clock;
+use crate::target_device;
+use crate::target_device::usb::HOST;
+use crate::target_device::{MCLK, USB};
+use bitfield::bitfield;
+use core::ptr::null_mut;
+use cortex_m::singleton;
+use usb_device::endpoint::EndpointType;
+use usb_device::UsbDirection;
+
+/// Number of pipes. Pipe 0 is the control pipe.
+const PIPES: usize = 8;
+
+/// Size of the packet buffer of each pipe
+const PIPE_BUFFER_SIZE: usize = 64;
+
+/// Time a device may take to complete a control transfer stage, in ms
+const CONTROL_TIMEOUT: u16 = 500;
+
+/// Number of errors before a transaction fails
+const PIPE_ERROR_MAX: u16 = 3;
+
+bitfield! {
+    struct PckSize(u32);
+    impl Debug;
+    pub byte_count, set_byte_count: 13, 0;
+    pub multi_packet_size, set_multi_packet_size: 27, 14;
+    pub size, set_size: 30, 28;
+    pub auto_zlp, set_auto_zlp : 31;
+}
+
+bitfield! {
+    struct CtrlPipe(u16);
+    impl Debug;
+    pub pdaddr, set_pdaddr: 6, 0;
+    pub pepnum, set_pepnum: 11, 8;
+    pub permax, set_permax: 15, 12;
+}
+
+bitfield! {
+    struct StatusPipe(u16);
+    impl Debug;
+    pub dtgler, _: 0;
+    pub dapider, _: 1;
+    pub pider, _: 2;
+    pub touter, _: 3;
+    pub crc16er, _: 4;
+    pub ercnt, _: 7, 5;
+}
+
+/// Pipe descriptor bank, in the layout read by the USB peripheral
+#[repr(C)]
+#[derive(Debug)]
+struct PipeDescBank {
+    /// pipe data buffer, must be 32-bit aligned
+    addr: *mut u8,
+    pcksize: PckSize,
+    extreg: u16,
+    status_bk: u8,
+    _reserved: u8,
+    ctrl_pipe: CtrlPipe,
+    status_pipe: StatusPipe,
+}
+
+impl PipeDescBank {
+    const fn new() -> Self {
+        Self {
+            addr: null_mut(),
+            pcksize: PckSize(0),
+            extreg: 0,
+            status_bk: 0,
+            _reserved: 0,
+            ctrl_pipe: CtrlPipe(0),
+            status_pipe: StatusPipe(0),
+        }
+    }
+}
+
+type PipeDescriptors = [[PipeDescBank; 2]; PIPES];
+
+#[repr(C, align(4))]
+struct PipeBuffers([[u8; PIPE_BUFFER_SIZE]; PIPES]);
+
+/// PTYPE field values of the PCFG registers
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum PipeType {
+    Disabled = 0,
+    Control = 1,
+    Bulk = 3,
+    Interrupt = 4,
+}
+
+/// PTOKEN field values of the PCFG registers
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Token {
+    Setup = 0,
+    In = 1,
+    Out = 2,
+}
+
+/// SIZE field of PCKSIZE able to hold `max_packet_size` bytes
+fn size_bits(max_packet_size: u16) -> u32 {
+    match max_packet_size {
+        0..=8 => 0,
+        9..=16 => 1,
+        17..=32 => 2,
+        _ => 3,
+    }
+}
+
+/// USB host controller
+pub struct UsbHost<Dm: UsbPadDm = DmPad, Dp: UsbPadDp = DpPad> {
+    usb: USB,
+    _dm_pad: Dm,
+    _dp_pad: Dp,
+    desc: &'static mut PipeDescriptors,
+    buffers: &'static mut PipeBuffers,
+    /// Allocated pipes, one bit per pipe
+    allocated: u8,
+    /// Pipes with a transfer in progress, one bit per pipe
+    pending: u8,
+    attached: bool,
+}
+
+/// Generate a method returning the register of a given pipe, see `ep!` in
+/// the device mode driver
+macro_rules! pipe {
+    ($name:ident, $type:ident) => {
+        #[inline]
+        fn $name(&self, pipe: usize) -> &target_device::usb::host::host_pipe::$type {
+            match pipe {
+                0 => &self.host().host_pipe0.$name,
+                1 => &self.host().host_pipe1.$name,
+                2 => &self.host().host_pipe2.$name,
+                3 => &self.host().host_pipe3.$name,
+                4 => &self.host().host_pipe4.$name,
+                5 => &self.host().host_pipe5.$name,
+                6 => &self.host().host_pipe6.$name,
+                7 => &self.host().host_pipe7.$name,
+                _ => unreachable!(),
+            }
+        }
+    };
+}
+
+impl<Dm: UsbPadDm, Dp: UsbPadDp> UsbHost<Dm, Dp> {
+    /// Reset the USB peripheral and enable it in host mode. Can only be
+    /// called once, as the pipe descriptors and buffers are static.
+    pub fn new(
+        _clock: &clock::UsbClock,
+        mclk: &mut MCLK,
+        dm_pad: Dm,
+        dp_pad: Dp,
+        usb: USB,
+    ) -> Self {
+        mclk.ahbmask.modify(|_, w| w.usb_().set_bit());
+        mclk.apbbmask.modify(|_, w| w.usb_().set_bit());
+
+        let desc = singleton!(: PipeDescriptors = [
+            [PipeDescBank::new(), PipeDescBank::new()],
+            [PipeDescBank::new(), PipeDescBank::new()],
+            [PipeDescBank::new(), PipeDescBank::new()],
+            [PipeDescBank::new(), PipeDescBank::new()],
+            [PipeDescBank::new(), PipeDescBank::new()],
+            [PipeDescBank::new(), PipeDescBank::new()],
+            [PipeDescBank::new(), PipeDescBank::new()],
+            [PipeDescBank::new(), PipeDescBank::new()],
+        ])
+        .unwrap();
+        let buffers =
+            singleton!(: PipeBuffers = PipeBuffers([[0; PIPE_BUFFER_SIZE]; PIPES])).unwrap();
+        for (bank, buffer) in desc.iter_mut().zip(buffers.0.iter_mut()) {
+            bank[0].addr = buffer.as_mut_ptr();
+        }
+
+        let host = usb.host();
+        host.ctrla.write(|w| w.swrst().set_bit());
+        while host.syncbusy.read().swrst().bit_is_set() {}
+
+        host.descadd
+            .write(|w| unsafe { w.descadd().bits(desc.as_ptr() as u32) });
+        host.padcal.modify(|_, w| unsafe {
+            w.transn().bits(usb_transn_cal());
+            w.transp().bits(usb_transp_cal());
+            w.trim().bits(usb_trim_cal())
+        });
+        host.ctrla.modify(|_, w| {
+            w.mode().host();
+            w.runstdby().set_bit()
+        });
+        // full or low speed, depending on the device
+        host.ctrlb.modify(|_, w| w.spdconf().normal());
+
+        host.ctrla.modify(|_, w| w.enable().set_bit());
+        while host.syncbusy.read().enable().bit_is_set() {}
+
+        host.intflag
+            .write(|w| unsafe { w.bits(host.intflag.read().bits()) });
+
+        Self {
+            usb,
+            _dm_pad: dm_pad,
+            _dp_pad: dp_pad,
+            desc,
+            buffers,
+            allocated: 1,
+            pending: 0,
+            attached: false,
+        }
+    }
+
+    fn host(&self) -> &HOST {
+        self.usb.host()
+    }
+
+    pipe!(pcfg, PCFG);
+    pipe!(binterval, BINTERVAL);
+    pipe!(pstatusclr, PSTATUSCLR);
+    pipe!(pstatusset, PSTATUSSET);
+    pipe!(pintflag, PINTFLAG);
+
+    /// Notify the host controller whether VBUS is powered. Devices are only
+    /// detected while VBUS is on.
+    pub fn set_vbus(&mut self, powered: bool) {
+        self.host().ctrlb.modify(|_, w| w.vbusok().bit(powered));
+    }
+
+    /// Current frame number
+    fn frame(&self) -> u16 {
+        self.host().fnum.read().fnum().bits()
+    }
+
+    fn speed(&self) -> Speed {
+        match self.host().status.read().speed().bits() {
+            1 => Speed::Low,
+            _ => Speed::Full,
+        }
+    }
+
+    fn freeze(&self, pipe: usize) {
+        self.pstatusset(pipe).write(|w| w.pfreeze().set_bit());
+    }
+
+    /// Configure `pipe` for an endpoint of the device at `address`
+    fn configure(
+        &mut self,
+        pipe: usize,
+        pipe_type: PipeType,
+        address: u8,
+        endpoint: u8,
+        max_packet_size: u16,
+        interval: u8,
+    ) {
+        self.freeze(pipe);
+        self.pcfg(pipe)
+            .write(|w| unsafe { w.ptype().bits(pipe_type as u8) });
+        self.binterval(pipe)
+            .write(|w| unsafe { w.bitinterval().bits(interval) });
+
+        let bank = &mut self.desc[pipe][0];
+        bank.pcksize = PckSize(0);
+        bank.pcksize.set_size(size_bits(max_packet_size));
+        bank.ctrl_pipe.set_pdaddr(address as u16);
+        bank.ctrl_pipe.set_pepnum(endpoint as u16);
+        bank.ctrl_pipe.set_permax(PIPE_ERROR_MAX);
+    }
+
+    /// Start a transaction of `len` bytes from the pipe buffer
+    fn start(&mut self, pipe: usize, token: Token, len: usize) {
+        self.pcfg(pipe)
+            .modify(|_, w| unsafe { w.ptoken().bits(token as u8) });
+        let bank = &mut self.desc[pipe][0];
+        bank.pcksize.set_multi_packet_size(0);
+        bank.pcksize.set_byte_count(len as u32);
+        bank.status_pipe = StatusPipe(0);
+
+        self.pintflag(pipe).write(|w| unsafe { w.bits(0xff) });
+        match token {
+            // The bank holds the data to send
+            Token::Setup | Token::Out => self.pstatusset(pipe).write(|w| w.bk0rdy().set_bit()),
+            // The bank is free for the received data
+            Token::In => self.pstatusclr(pipe).write(|w| w.bk0rdy().set_bit()),
+        }
+        self.pstatusclr(pipe).write(|w| w.pfreeze().set_bit());
+    }
+
+    /// Check the transaction in progress on `pipe`. Returns the number of
+    /// bytes in the pipe buffer once it completes.
+    fn poll(&mut self, pipe: usize, token: Token) -> nb::Result<usize, Error> {
+        let flags = self.pintflag(pipe).read();
+        let result = if self.host().intflag.read().ddisc().bit_is_set() {
+            Err(Error::Disconnected)
+        } else if flags.stall().bit_is_set() {
+            Err(Error::Stall)
+        } else if flags.perr().bit_is_set() {
+            if self.desc[pipe][0].status_pipe.touter() {
+                Err(Error::Timeout)
+            } else {
+                Err(Error::Transaction)
+            }
+        } else if flags.trfail().bit_is_set() {
+            Err(Error::Transaction)
+        } else if match token {
+            Token::Setup => flags.txstp().bit_is_set(),
+            _ => flags.trcpt0().bit_is_set(),
+        } {
+            Ok(self.desc[pipe][0].pcksize.byte_count() as usize)
+        } else {
+            return Err(nb::Error::WouldBlock);
+        };
+        self.freeze(pipe);
+        self.pintflag(pipe).write(|w| unsafe { w.bits(0xff) });
+        result.map_err(nb::Error::Other)
+    }
+
+    /// Run a transaction to completion, giving up after `timeout` frames
+    fn transact(
+        &mut self,
+        pipe: usize,
+        token: Token,
+        len: usize,
+        timeout: u16,
+    ) -> Result<usize, Error> {
+        // The timeout counts frames, which are only sent after a bus reset
+        if self.host().ctrlb.read().sofe().bit_is_clear() {
+            return Err(Error::NotReset);
+        }
+        self.start(pipe, token, len);
+        let start = self.frame();
+        loop {
+            match self.poll(pipe, token) {
+                Err(nb::Error::WouldBlock) => (),
+                Err(nb::Error::Other(error)) => return Err(error),
+                Ok(len) => return Ok(len),
+            }
+            if self.frame().wrapping_sub(start) & 0x7ff > timeout {
+                self.freeze(pipe);
+                return Err(Error::Timeout);
+            }
+        }
+    }
+
+    /// SETUP stage of a control transfer on pipe 0
+    fn setup(
+        &mut self,
+        address: u8,
+        max_packet_size: u8,
+        setup: &SetupPacket,
+    ) -> Result<(), Error> {
+        if !self.attached {
+            return Err(Error::Disconnected);
+        }
+        self.configure(0, PipeType::Control, address, 0, max_packet_size as u16, 0);
+        self.buffers.0[0][..8].copy_from_slice(&setup.to_bytes());
+        self.transact(0, Token::Setup, 8, CONTROL_TIMEOUT)?;
+        // The data and status stages start with DATA1
+        self.pstatusset(0).write(|w| w.dtgl().set_bit());
+        Ok(())
+    }
+
+    /// Status stage of a control transfer on pipe 0
+    fn status(&mut self, token: Token) -> Result<(), Error> {
+        self.pstatusset(0).write(|w| w.dtgl().set_bit());
+        self.transact(0, token, 0, CONTROL_TIMEOUT).map(|_| ())
+    }
+}
+
+impl<Dm: UsbPadDm, Dp: UsbPadDp> HostBus for UsbHost<Dm, Dp> {
+    fn connected(&mut self) -> Option<Speed> {
+        let intflag = self.host().intflag.read();
+        if intflag.ddisc().bit_is_set() {
+            self.host().intflag.write(|w| w.ddisc().set_bit());
+            self.attached = false;
+        }
+        if intflag.dconn().bit_is_set() {
+            self.host().intflag.write(|w| w.dconn().set_bit());
+            self.attached = true;
+        }
+        if self.attached {
+            Some(self.speed())
+        } else {
+            None
+        }
+    }
+
+    fn reset(&mut self) -> Result<Speed, Error> {
+        if self.connected().is_none() {
+            return Err(Error::Disconnected);
+        }
+        for pipe in 0..PIPES {
+            self.freeze(pipe);
+        }
+        self.allocated = 1;
+        self.pending = 0;
+
+        self.host().intflag.write(|w| w.rst().set_bit());
+        self.host().ctrlb.modify(|_, w| w.busreset().set_bit());
+        loop {
+            let intflag = self.host().intflag.read();
+            if intflag.ddisc().bit_is_set() {
+                return Err(Error::Disconnected);
+            }
+            if intflag.rst().bit_is_set() {
+                break;
+            }
+        }
+        self.host().intflag.write(|w| w.rst().set_bit());
+        self.host().ctrlb.modify(|_, w| w.sofe().set_bit());
+        Ok(self.speed())
+    }
+
+    fn wait_frames(&mut self, frames: u16) {
+        for _ in 0..frames {
+            self.host().intflag.write(|w| w.hsof().set_bit());
+            while self.host().intflag.read().hsof().bit_is_clear() {
+                if self.host().ctrlb.read().sofe().bit_is_clear() {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn control_in(
+        &mut self,
+        address: u8,
+        max_packet_size: u8,
+        setup: &SetupPacket,
+        buf: &mut [u8],
+    ) -> Result<usize, Error> {
+        self.setup(address, max_packet_size, setup)?;
+        let expected = buf.len().min(setup.length as usize);
+        let mut received = 0;
+        while received < expected {
+            let len = self.transact(0, Token::In, 0, CONTROL_TIMEOUT)?;
+            if received + len > buf.len() {
+                return Err(Error::BufferOverflow);
+            }
+            buf[received..received + len].copy_from_slice(&self.buffers.0[0][..len]);
+            received += len;
+            if len < max_packet_size as usize {
+                break;
+            }
+        }
+        // The status stage goes in the direction opposite to the data stage,
+        // and is IN without a data stage
+        let status = if setup.length == 0 {
+            Token::In
+        } else {
+            Token::Out
+        };
+        self.status(status)?;
+        Ok(received)
+    }
+
+    fn control_out(
+        &mut self,
+        address: u8,
+        max_packet_size: u8,
+        setup: &SetupPacket,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        self.setup(address, max_packet_size, setup)?;
+        for packet in data.chunks(max_packet_size as usize) {
+            self.buffers.0[0][..packet.len()].copy_from_slice(packet);
+            self.transact(0, Token::Out, packet.len(), CONTROL_TIMEOUT)?;
+        }
+        // IN status stage, after an OUT data stage or without a data stage
+        self.status(Token::In)
+    }
+
+    fn alloc_pipe(&mut self, address: u8, endpoint: &EndpointDescriptor) -> Result<Pipe, Error> {
+        let pipe_type = match endpoint.transfer_type() {
+            EndpointType::Bulk => PipeType::Bulk,
+            EndpointType::Interrupt => PipeType::Interrupt,
+            _ => return Err(Error::Unsupported),
+        };
+        if endpoint.max_packet_size as usize > PIPE_BUFFER_SIZE {
+            return Err(Error::Unsupported);
+        }
+        let index = (1..PIPES)
+            .find(|&pipe| self.allocated & 1 << pipe == 0)
+            .ok_or(Error::NoPipe)?;
+        self.allocated |= 1 << index;
+
+        let interval = match pipe_type {
+            PipeType::Interrupt => endpoint.interval,
+            _ => 0,
+        };
+        self.configure(
+            index,
+            pipe_type,
+            address,
+            endpoint.number(),
+            endpoint.max_packet_size,
+            interval,
+        );
+        let token = match endpoint.direction() {
+            UsbDirection::In => Token::In,
+            UsbDirection::Out => Token::Out,
+        };
+        self.pcfg(index)
+            .modify(|_, w| unsafe { w.ptoken().bits(token as u8) });
+        self.reset_toggle(Pipe {
+            index: index as u8,
+            max_packet_size: endpoint.max_packet_size,
+        });
+
+        Ok(Pipe {
+            index: index as u8,
+            max_packet_size: endpoint.max_packet_size,
+        })
+    }
+
+    fn free_pipe(&mut self, pipe: Pipe) {
+        let index = pipe.index as usize;
+        self.freeze(index);
+        self.pcfg(index)
+            .write(|w| unsafe { w.ptype().bits(PipeType::Disabled as u8) });
+        self.allocated &= !(1 << index);
+        self.pending &= !(1 << index);
+    }
+
+    fn read(&mut self, pipe: Pipe, buf: &mut [u8]) -> nb::Result<usize, Error> {
+        let index = pipe.index as usize;
+        if self.pending & 1 << index == 0 {
+            self.start(index, Token::In, 0);
+            self.pending |= 1 << index;
+        }
+        let result = self.poll(index, Token::In);
+        if !matches!(result, Err(nb::Error::WouldBlock)) {
+            self.pending &= !(1 << index);
+        }
+        let len = result?;
+        if len > buf.len() {
+            return Err(nb::Error::Other(Error::BufferOverflow));
+        }
+        buf[..len].copy_from_slice(&self.buffers.0[index][..len]);
+        Ok(len)
+    }
+
+    fn write(&mut self, pipe: Pipe, data: &[u8]) -> nb::Result<(), Error> {
+        let index = pipe.index as usize;
+        if self.pending & 1 << index == 0 {
+            if data.len() > pipe.max_packet_size as usize {
+                return Err(nb::Error::Other(Error::BufferOverflow));
+            }
+            self.buffers.0[index][..data.len()].copy_from_slice(data);
+            self.start(index, Token::Out, data.len());
+            self.pending |= 1 << index;
+        }
+        let result = self.poll(index, Token::Out);
+        if !matches!(result, Err(nb::Error::WouldBlock)) {
+            self.pending &= !(1 << index);
+        }
+        result.map(|_| ())
+    }
+
+    fn reset_toggle(&mut self, pipe: Pipe) {
+        self.pstatusclr(pipe.index as usize)
+            .write(|w| w.dtgl().set_bit());
+    }
+}
@@ -1,4 +1,4 @@
-//! USB Device support
+//! USB Device and host support
 
 use crate::gpio;
 
@@ -6,11 +6,16 @@ use private::UsbSealed;
 pub use usb_device;
 
 mod bus;
-pub use self::bus::UsbBus;
+pub use self::bus::{LpmHandshake, UsbBus};
 
 mod devicedesc;
 use self::devicedesc::Descriptors;
 
+pub mod host;
+
+mod clock_recovery;
+pub use self::clock_recovery::{ClockRecovery, ClockRecoveryStatus, DfllFallback};
+
 /// Default SOF pad
 pub type SofPad = gpio::v1::Pa23<gpio::v1::PfH>;
 /// Default USB D- pad
@@ -1,3 +1,19 @@
+//! Watchdog timer
+//!
+//! Besides the basic time-out operation of the `embedded-hal` traits, the
+//! watchdog supports:
+//!
+//! * Window mode: feeding the watchdog during the closed period that
+//!   follows each feed also resets the device, which catches code running
+//!   too fast as well as code hanging.
+//! * An early warning interrupt, raised a configurable number of cycles
+//!   after each feed, before the time-out.
+//! * Always-on mode: the watchdog cannot be disabled or reconfigured until
+//!   the next reset.
+//!
+//! At reset, the watchdog is configured from the NVM user row, which
+//! [`Watchdog::config`] returns.
+
 use crate::target_device::WDT;
 use hal::watchdog;
 
@@ -20,6 +36,72 @@ pub enum WatchdogTimeout {
     Cycles16K,
 }
 
+impl WatchdogTimeout {
+    /// Decode the PER, WINDOW and EWOFFSET register fields. The reserved
+    /// values behave as the longest period.
+    fn from_bits(bits: u8) -> Self {
+        match bits {
+            0 => WatchdogTimeout::Cycles8,
+            1 => WatchdogTimeout::Cycles16,
+            2 => WatchdogTimeout::Cycles32,
+            3 => WatchdogTimeout::Cycles64,
+            4 => WatchdogTimeout::Cycles128,
+            5 => WatchdogTimeout::Cycles256,
+            6 => WatchdogTimeout::Cycles512,
+            7 => WatchdogTimeout::Cycles1K,
+            8 => WatchdogTimeout::Cycles2K,
+            9 => WatchdogTimeout::Cycles4K,
+            10 => WatchdogTimeout::Cycles8K,
+            _ => WatchdogTimeout::Cycles16K,
+        }
+    }
+}
+
+/// Watchdog configuration errors
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// The early warning offset is not shorter than the time-out period
+    InvalidEarlyWarning,
+    /// The watchdog is always-on and cannot be reconfigured
+    AlwaysOn,
+}
+
+/// Watchdog configuration
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct WatchdogConfig {
+    /// Time-out period, or open period in window mode
+    pub period: WatchdogTimeout,
+    /// Closed period, during which feeding the watchdog resets the device.
+    /// `None` disables window mode.
+    pub window: Option<WatchdogTimeout>,
+    /// Enable the early warning interrupt. Outside of window mode, it is
+    /// raised this number of cycles after the watchdog is fed.
+    pub early_warning: Option<WatchdogTimeout>,
+    /// Lock the watchdog enabled until the next reset
+    pub always_on: bool,
+}
+
+impl WatchdogConfig {
+    /// Watchdog in normal mode, with the given time-out `period`
+    pub fn new(period: WatchdogTimeout) -> Self {
+        Self {
+            period,
+            window: None,
+            early_warning: None,
+            always_on: false,
+        }
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        match (self.window, self.early_warning) {
+            (None, Some(offset)) if offset as u8 >= self.period as u8 => {
+                Err(Error::InvalidEarlyWarning)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 pub struct Watchdog {
     wdt: WDT,
 }
@@ -28,6 +110,95 @@ impl Watchdog {
     pub fn new(wdt: WDT) -> Self {
         Self { wdt }
     }
+
+    fn sync(&self) {
+        while self.wdt.syncbusy.read().bits() != 0 {}
+    }
+
+    /// Returns `true` if the watchdog is running
+    pub fn is_enabled(&self) -> bool {
+        let ctrla = self.wdt.ctrla.read();
+        ctrla.enable().bit_is_set() || ctrla.alwayson().bit_is_set()
+    }
+
+    /// Returns `true` if the watchdog is locked in always-on mode
+    pub fn is_always_on(&self) -> bool {
+        self.wdt.ctrla.read().alwayson().bit_is_set()
+    }
+
+    /// Read the current configuration, such as the one loaded from the NVM
+    /// user row at reset
+    pub fn config(&self) -> WatchdogConfig {
+        self.sync();
+        let ctrla = self.wdt.ctrla.read();
+        let config = self.wdt.config.read();
+        let window = if ctrla.wen().bit_is_set() {
+            Some(WatchdogTimeout::from_bits(config.window().bits()))
+        } else {
+            None
+        };
+        let early_warning = if self.wdt.intenset.read().ew().bit_is_set() {
+            Some(WatchdogTimeout::from_bits(
+                self.wdt.ewctrl.read().ewoffset().bits(),
+            ))
+        } else {
+            None
+        };
+        WatchdogConfig {
+            period: WatchdogTimeout::from_bits(config.per().bits()),
+            window,
+            early_warning,
+            always_on: ctrla.alwayson().bit_is_set(),
+        }
+    }
+
+    /// Configure and enable the watchdog.
+    ///
+    /// The watchdog is disabled while it is reconfigured, which restarts
+    /// the time-out period.
+    pub fn enable(&mut self, config: &WatchdogConfig) -> Result<(), Error> {
+        if self.is_always_on() {
+            return Err(Error::AlwaysOn);
+        }
+        config.check()?;
+
+        self.wdt.ctrla.write(|w| w.enable().clear_bit());
+        self.sync();
+
+        self.wdt.config.write(|w| unsafe {
+            w.per().bits(config.period as u8);
+            w.window()
+                .bits(config.window.unwrap_or(config.period) as u8)
+        });
+        match config.early_warning {
+            Some(offset) => {
+                self.wdt
+                    .ewctrl
+                    .write(|w| unsafe { w.ewoffset().bits(offset as u8) });
+                self.wdt.intflag.write(|w| w.ew().set_bit());
+                self.wdt.intenset.write(|w| w.ew().set_bit());
+            }
+            None => self.wdt.intenclr.write(|w| w.ew().set_bit()),
+        }
+
+        self.wdt.ctrla.write(|w| {
+            w.wen().bit(config.window.is_some());
+            w.alwayson().bit(config.always_on);
+            w.enable().set_bit()
+        });
+        self.sync();
+        Ok(())
+    }
+
+    /// Returns `true` if the early warning interrupt is pending
+    pub fn early_warning_triggered(&self) -> bool {
+        self.wdt.intflag.read().ew().bit_is_set()
+    }
+
+    /// Clear the early warning interrupt flag
+    pub fn clear_early_warning(&mut self) {
+        self.wdt.intflag.write(|w| w.ew().set_bit());
+    }
 }
 
 impl watchdog::Watchdog for Watchdog {
@@ -39,6 +210,8 @@ impl watchdog::Watchdog for Watchdog {
 }
 
 /// Disables a running watchdog timer so the processor won't be reset.
+///
+/// This has no effect on an always-on watchdog.
 impl watchdog::WatchdogDisable for Watchdog {
     fn disable(&mut self) {
         // Disable the watchdog timer.
@@ -67,3 +240,26 @@ impl watchdog::WatchdogEnable for Watchdog {
         while self.wdt.syncbusy.read().enable().bit_is_set() {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn early_warning_must_precede_time_out() {
+        let mut config = WatchdogConfig::new(WatchdogTimeout::Cycles1K);
+        config.early_warning = Some(WatchdogTimeout::Cycles512);
+        assert_eq!(config.check(), Ok(()));
+        config.early_warning = Some(WatchdogTimeout::Cycles1K);
+        assert_eq!(config.check(), Err(Error::InvalidEarlyWarning));
+        config.window = Some(WatchdogTimeout::Cycles1K);
+        assert_eq!(config.check(), Ok(()));
+    }
+
+    #[test]
+    fn reserved_periods_are_longest() {
+        assert_eq!(WatchdogTimeout::from_bits(0), WatchdogTimeout::Cycles8);
+        assert_eq!(WatchdogTimeout::from_bits(11), WatchdogTimeout::Cycles16K);
+        assert_eq!(WatchdogTimeout::from_bits(15), WatchdogTimeout::Cycles16K);
+    }
+}
@@ -0,0 +1,322 @@
+//! Standard descriptor parsing
+
+use super::Error;
+use usb_device::endpoint::EndpointType;
+use usb_device::UsbDirection;
+
+/// Standard descriptor types
+pub mod descriptor_type {
+    pub const DEVICE: u8 = 1;
+    pub const CONFIGURATION: u8 = 2;
+    pub const STRING: u8 = 3;
+    pub const INTERFACE: u8 = 4;
+    pub const ENDPOINT: u8 = 5;
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+/// Check the length and type of the descriptor at the start of `bytes`
+fn check(bytes: &[u8], kind: u8, min_length: usize) -> Result<(), Error> {
+    if bytes.len() < min_length || (bytes[0] as usize) < min_length || bytes[1] != kind {
+        Err(Error::InvalidDescriptor)
+    } else {
+        Ok(())
+    }
+}
+
+/// Device descriptor
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct DeviceDescriptor {
+    pub usb_version: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub max_packet_size0: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub device_version: u16,
+    pub manufacturer: u8,
+    pub product: u8,
+    pub serial_number: u8,
+    pub num_configurations: u8,
+}
+
+impl DeviceDescriptor {
+    /// Size of the descriptor
+    pub const LENGTH: usize = 18;
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        check(bytes, descriptor_type::DEVICE, Self::LENGTH)?;
+        Ok(Self {
+            usb_version: u16_at(bytes, 2),
+            class: bytes[4],
+            subclass: bytes[5],
+            protocol: bytes[6],
+            max_packet_size0: bytes[7],
+            vendor_id: u16_at(bytes, 8),
+            product_id: u16_at(bytes, 10),
+            device_version: u16_at(bytes, 12),
+            manufacturer: bytes[14],
+            product: bytes[15],
+            serial_number: bytes[16],
+            num_configurations: bytes[17],
+        })
+    }
+}
+
+/// Configuration descriptor header
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ConfigurationDescriptor {
+    /// Length of the configuration, including the interface and endpoint
+    /// descriptors
+    pub total_length: u16,
+    pub num_interfaces: u8,
+    pub configuration_value: u8,
+    pub attributes: u8,
+    /// Maximum current, in units of 2 mA
+    pub max_power: u8,
+}
+
+impl ConfigurationDescriptor {
+    /// Size of the descriptor
+    pub const LENGTH: usize = 9;
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        check(bytes, descriptor_type::CONFIGURATION, Self::LENGTH)?;
+        Ok(Self {
+            total_length: u16_at(bytes, 2),
+            num_interfaces: bytes[4],
+            configuration_value: bytes[5],
+            attributes: bytes[7],
+            max_power: bytes[8],
+        })
+    }
+}
+
+/// Interface descriptor
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct InterfaceDescriptor {
+    pub interface_number: u8,
+    pub alternate_setting: u8,
+    pub num_endpoints: u8,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+}
+
+impl InterfaceDescriptor {
+    /// Size of the descriptor
+    pub const LENGTH: usize = 9;
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        check(bytes, descriptor_type::INTERFACE, Self::LENGTH)?;
+        Ok(Self {
+            interface_number: bytes[2],
+            alternate_setting: bytes[3],
+            num_endpoints: bytes[4],
+            class: bytes[5],
+            subclass: bytes[6],
+            protocol: bytes[7],
+        })
+    }
+}
+
+/// Endpoint descriptor
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct EndpointDescriptor {
+    /// Endpoint number, with the direction in bit 7
+    pub address: u8,
+    pub attributes: u8,
+    pub max_packet_size: u16,
+    /// Polling interval, in frames
+    pub interval: u8,
+}
+
+impl EndpointDescriptor {
+    /// Size of the descriptor
+    pub const LENGTH: usize = 7;
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        check(bytes, descriptor_type::ENDPOINT, Self::LENGTH)?;
+        Ok(Self {
+            address: bytes[2],
+            attributes: bytes[3],
+            max_packet_size: u16_at(bytes, 4) & 0x7ff,
+            interval: bytes[6],
+        })
+    }
+
+    /// Endpoint number
+    pub fn number(&self) -> u8 {
+        self.address & 0x0f
+    }
+
+    /// Direction of the endpoint
+    pub fn direction(&self) -> UsbDirection {
+        UsbDirection::from(self.address)
+    }
+
+    /// Transfer type of the endpoint
+    pub fn transfer_type(&self) -> EndpointType {
+        match self.attributes & 0x03 {
+            0 => EndpointType::Control,
+            1 => EndpointType::Isochronous,
+            2 => EndpointType::Bulk,
+            _ => EndpointType::Interrupt,
+        }
+    }
+}
+
+/// Descriptor of a configuration
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Descriptor<'a> {
+    Configuration(ConfigurationDescriptor),
+    Interface(InterfaceDescriptor),
+    Endpoint(EndpointDescriptor),
+    /// Class or vendor specific descriptor, with its type and raw bytes
+    Other(u8, &'a [u8]),
+}
+
+/// Iterator over the descriptors of a configuration, as returned by
+/// GET_DESCRIPTOR(CONFIGURATION). Stops at the first malformed descriptor.
+#[derive(Debug, Clone)]
+pub struct Descriptors<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Descriptors<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+}
+
+impl<'a> Iterator for Descriptors<'a> {
+    type Item = Descriptor<'a>;
+
+    fn next(&mut self) -> Option<Descriptor<'a>> {
+        let length = *self.bytes.first()? as usize;
+        if length < 2 || length > self.bytes.len() {
+            self.bytes = &[];
+            return None;
+        }
+        let (bytes, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        let descriptor = match bytes[1] {
+            descriptor_type::CONFIGURATION => {
+                ConfigurationDescriptor::parse(bytes).map(Descriptor::Configuration)
+            }
+            descriptor_type::INTERFACE => {
+                InterfaceDescriptor::parse(bytes).map(Descriptor::Interface)
+            }
+            descriptor_type::ENDPOINT => EndpointDescriptor::parse(bytes).map(Descriptor::Endpoint),
+            kind => Ok(Descriptor::Other(kind, bytes)),
+        };
+        match descriptor {
+            Ok(descriptor) => Some(descriptor),
+            Err(_) => {
+                self.bytes = &[];
+                None
+            }
+        }
+    }
+}
+
+/// Find the first interface of a configuration matching `predicate`, and
+/// return it with its endpoints, in the order of the configuration
+pub fn find_interface<F>(
+    config: &[u8],
+    predicate: F,
+) -> Option<(
+    InterfaceDescriptor,
+    impl Iterator<Item = EndpointDescriptor> + '_,
+)>
+where
+    F: Fn(&InterfaceDescriptor) -> bool,
+{
+    let mut descriptors = Descriptors::new(config);
+    let interface = descriptors.find_map(|descriptor| match descriptor {
+        Descriptor::Interface(interface) if predicate(&interface) => Some(interface),
+        _ => None,
+    })?;
+    let endpoints = descriptors
+        .take_while(|descriptor| !matches!(descriptor, Descriptor::Interface(_)))
+        .filter_map(|descriptor| match descriptor {
+            Descriptor::Endpoint(endpoint) => Some(endpoint),
+            _ => None,
+        });
+    Some((interface, endpoints))
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// Configuration of a boot keyboard, with a HID descriptor
+    pub const KEYBOARD_CONFIG: [u8; 34] = [
+        9, 2, 34, 0, 1, 1, 0, 0xa0, 50, // configuration
+        9, 4, 0, 0, 1, 3, 1, 1, 0, // interface: HID, boot, keyboard
+        9, 0x21, 0x11, 0x01, 0, 1, 0x22, 63, 0, // HID
+        7, 5, 0x81, 3, 8, 0, 10, // endpoint 1 IN, interrupt
+    ];
+
+    #[test]
+    fn device_descriptor() {
+        let bytes = [
+            18, 1, 0x00, 0x02, 0, 0, 0, 64, 0x81, 0x07, 0x81, 0x55, 0x00, 0x01, 1, 2, 3, 1,
+        ];
+        let device = DeviceDescriptor::parse(&bytes).unwrap();
+        assert_eq!(device.usb_version, 0x0200);
+        assert_eq!(device.max_packet_size0, 64);
+        assert_eq!(device.vendor_id, 0x0781);
+        assert_eq!(device.product_id, 0x5581);
+        assert_eq!(device.num_configurations, 1);
+        assert_eq!(
+            DeviceDescriptor::parse(&bytes[..8]),
+            Err(Error::InvalidDescriptor)
+        );
+    }
+
+    #[test]
+    fn configuration_descriptors() {
+        let mut descriptors = Descriptors::new(&KEYBOARD_CONFIG);
+        match descriptors.next() {
+            Some(Descriptor::Configuration(config)) => {
+                assert_eq!(config.total_length, 34);
+                assert_eq!(config.configuration_value, 1);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(descriptors.next(), Some(Descriptor::Interface(_))));
+        assert!(matches!(
+            descriptors.next(),
+            Some(Descriptor::Other(0x21, _))
+        ));
+        match descriptors.next() {
+            Some(Descriptor::Endpoint(endpoint)) => {
+                assert_eq!(endpoint.number(), 1);
+                assert_eq!(endpoint.direction(), UsbDirection::In);
+                assert_eq!(endpoint.transfer_type(), EndpointType::Interrupt);
+                assert_eq!(endpoint.max_packet_size, 8);
+                assert_eq!(endpoint.interval, 10);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(descriptors.next(), None);
+    }
+
+    #[test]
+    fn truncated_configuration() {
+        assert_eq!(Descriptors::new(&KEYBOARD_CONFIG[..30]).count(), 3);
+        assert_eq!(Descriptors::new(&[0, 2, 0]).count(), 0);
+    }
+
+    #[test]
+    fn interface_lookup() {
+        let (interface, endpoints) = find_interface(&KEYBOARD_CONFIG, |i| i.class == 3).unwrap();
+        assert_eq!(interface.protocol, 1);
+        assert_eq!(endpoints.count(), 1);
+        assert!(find_interface(&KEYBOARD_CONFIG, |i| i.class == 8).is_none());
+    }
+}
@@ -0,0 +1,192 @@
+//! Enumeration of the attached device
+
+use super::{
+    descriptor_type, ConfigurationDescriptor, DeviceDescriptor, Error, HostBus, SetupPacket, Speed,
+};
+
+/// Time given to the device to apply SET_ADDRESS, in ms
+const SET_ADDRESS_RECOVERY: u16 = 2;
+
+/// Time given to the device to recover from the bus reset, in ms
+const RESET_RECOVERY: u16 = 10;
+
+/// Enumerated device
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Device {
+    /// Address assigned to the device
+    pub address: u8,
+    pub speed: Speed,
+    pub descriptor: DeviceDescriptor,
+    /// Value of the selected configuration
+    pub configuration: u8,
+}
+
+impl Device {
+    /// Maximum packet size of the default control endpoint
+    pub fn max_packet_size0(&self) -> u8 {
+        self.descriptor.max_packet_size0
+    }
+
+    /// Control transfer with an optional IN data stage
+    pub fn control_in<B: HostBus>(
+        &self,
+        bus: &mut B,
+        setup: &SetupPacket,
+        buf: &mut [u8],
+    ) -> Result<usize, Error> {
+        bus.control_in(self.address, self.max_packet_size0(), setup, buf)
+    }
+
+    /// Control transfer with an optional OUT data stage
+    pub fn control_out<B: HostBus>(
+        &self,
+        bus: &mut B,
+        setup: &SetupPacket,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        bus.control_out(self.address, self.max_packet_size0(), setup, data)
+    }
+
+    /// Read string descriptor `index` in the first supported language, as
+    /// UTF-16LE code units. Returns the number of code units.
+    pub fn string<B: HostBus>(
+        &self,
+        bus: &mut B,
+        index: u8,
+        buf: &mut [u16],
+    ) -> Result<usize, Error> {
+        let mut bytes = [0; 255];
+        let setup = SetupPacket::get_descriptor(descriptor_type::STRING, 0, 255);
+        let len = self.control_in(bus, &setup, &mut bytes)?;
+        if len < 4 {
+            return Err(Error::InvalidDescriptor);
+        }
+        let language = u16::from_le_bytes([bytes[2], bytes[3]]);
+
+        let setup = SetupPacket {
+            index: language,
+            ..SetupPacket::get_descriptor(descriptor_type::STRING, index, 255)
+        };
+        let len = self.control_in(bus, &setup, &mut bytes)?;
+        parse_string(&bytes[..len], buf)
+    }
+}
+
+/// Decode the UTF-16 units of a string descriptor into `buf`
+fn parse_string(bytes: &[u8], buf: &mut [u16]) -> Result<usize, Error> {
+    if bytes.len() < 2 || bytes[1] != descriptor_type::STRING {
+        return Err(Error::InvalidDescriptor);
+    }
+    let units = (bytes.len().min(bytes[0] as usize))
+        .checked_sub(2)
+        .ok_or(Error::InvalidDescriptor)?
+        / 2;
+    if units > buf.len() {
+        return Err(Error::BufferOverflow);
+    }
+    for (unit, pair) in buf.iter_mut().zip(bytes[2..].chunks_exact(2)).take(units) {
+        *unit = u16::from_le_bytes([pair[0], pair[1]]);
+    }
+    Ok(units)
+}
+
+/// Reset the attached device, assign it `address`, and select its first
+/// configuration.
+///
+/// The configuration descriptors are read into `config`, and returned for
+/// the class drivers to look up their interfaces.
+pub fn enumerate<'a, B: HostBus>(
+    bus: &mut B,
+    address: u8,
+    config: &'a mut [u8],
+) -> Result<(Device, &'a [u8]), Error> {
+    let speed = bus.reset()?;
+    bus.wait_frames(RESET_RECOVERY);
+
+    // The maximum packet size of the control endpoint is in the first 8
+    // bytes of the device descriptor, which fit in a packet of any size
+    let mut bytes = [0; DeviceDescriptor::LENGTH];
+    let setup = SetupPacket::get_descriptor(descriptor_type::DEVICE, 0, 8);
+    if bus.control_in(0, 8, &setup, &mut bytes[..8])? < 8 {
+        return Err(Error::InvalidDescriptor);
+    }
+    let max_packet_size0 = bytes[7];
+    if !matches!(max_packet_size0, 8 | 16 | 32 | 64) {
+        return Err(Error::InvalidDescriptor);
+    }
+
+    bus.control_out(0, max_packet_size0, &SetupPacket::set_address(address), &[])?;
+    bus.wait_frames(SET_ADDRESS_RECOVERY);
+
+    let setup =
+        SetupPacket::get_descriptor(descriptor_type::DEVICE, 0, DeviceDescriptor::LENGTH as u16);
+    let len = bus.control_in(address, max_packet_size0, &setup, &mut bytes)?;
+    let descriptor = DeviceDescriptor::parse(&bytes[..len])?;
+
+    if config.len() < ConfigurationDescriptor::LENGTH {
+        return Err(Error::BufferOverflow);
+    }
+    let setup = SetupPacket::get_descriptor(
+        descriptor_type::CONFIGURATION,
+        0,
+        ConfigurationDescriptor::LENGTH as u16,
+    );
+    let len = bus.control_in(address, max_packet_size0, &setup, config)?;
+    let header = ConfigurationDescriptor::parse(&config[..len])?;
+    let total_length = header.total_length as usize;
+    if total_length > config.len() {
+        return Err(Error::BufferOverflow);
+    }
+    let setup = SetupPacket::get_descriptor(descriptor_type::CONFIGURATION, 0, header.total_length);
+    let len = bus.control_in(
+        address,
+        max_packet_size0,
+        &setup,
+        &mut config[..total_length],
+    )?;
+
+    let setup = SetupPacket::set_configuration(header.configuration_value);
+    bus.control_out(address, max_packet_size0, &setup, &[])?;
+
+    let device = Device {
+        address,
+        speed,
+        descriptor,
+        configuration: header.configuration_value,
+    };
+    Ok((device, &config[..len]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_descriptor() {
+        let mut buf = [0; 4];
+        let bytes = [8, 3, b'U', 0, b'S', 0, b'B', 0];
+        assert_eq!(parse_string(&bytes, &mut buf), Ok(3));
+        assert_eq!(buf[..3], [b'U' as u16, b'S' as u16, b'B' as u16]);
+        // The length is limited by the bytes received
+        assert_eq!(parse_string(&bytes[..6], &mut buf), Ok(2));
+        assert_eq!(
+            parse_string(&bytes, &mut buf[..2]),
+            Err(Error::BufferOverflow)
+        );
+    }
+
+    #[test]
+    fn short_string_descriptor() {
+        let mut buf = [0; 4];
+        assert_eq!(parse_string(&[2, 3], &mut buf), Ok(0));
+        assert_eq!(
+            parse_string(&[0, 3, b'U', 0], &mut buf),
+            Err(Error::InvalidDescriptor)
+        );
+        assert_eq!(
+            parse_string(&[1, 3, b'U', 0], &mut buf),
+            Err(Error::InvalidDescriptor)
+        );
+        assert_eq!(parse_string(&[4], &mut buf), Err(Error::InvalidDescriptor));
+    }
+}
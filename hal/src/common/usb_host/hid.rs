@@ -0,0 +1,241 @@
+//! HID boot protocol keyboards and mice
+//!
+//! The boot protocol has fixed report formats, so no report descriptor
+//! parsing is needed. Reports are read from the interrupt IN endpoint of
+//! the interface: [`Keyboard::poll`] and [`Mouse::poll`] return
+//! `WouldBlock` until the device has a new report.
+
+use super::{find_interface, Device, Error, HostBus, Pipe, SetupPacket};
+use usb_device::endpoint::EndpointType;
+use usb_device::UsbDirection;
+
+/// HID interface class
+pub const CLASS: u8 = 3;
+/// Boot interface subclass
+pub const SUBCLASS_BOOT: u8 = 1;
+
+const SET_REPORT: u8 = 0x09;
+const SET_IDLE: u8 = 0x0a;
+const SET_PROTOCOL: u8 = 0x0b;
+
+/// Output report type of SET_REPORT
+const REPORT_OUTPUT: u16 = 2;
+
+/// Boot protocol of an interface
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BootProtocol {
+    Keyboard = 1,
+    Mouse = 2,
+}
+
+/// Report of a boot keyboard
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub struct KeyboardReport {
+    /// Modifier keys, Left Control in bit 0 to Right GUI in bit 7
+    pub modifiers: u8,
+    /// Usage IDs of the pressed keys, 0 for none
+    pub keys: [u8; 6],
+}
+
+impl KeyboardReport {
+    /// Parse an 8 byte boot keyboard report
+    pub fn parse(report: &[u8]) -> Option<Self> {
+        if report.len() < 8 {
+            return None;
+        }
+        let mut keys = [0; 6];
+        keys.copy_from_slice(&report[2..8]);
+        Some(Self {
+            modifiers: report[0],
+            keys,
+        })
+    }
+
+    /// Returns `true` if the keyboard reports too many pressed keys
+    pub fn is_rollover(&self) -> bool {
+        self.keys.iter().all(|&key| key == 0x01)
+    }
+
+    /// Returns `true` if the key with usage ID `key` is pressed
+    pub fn is_pressed(&self, key: u8) -> bool {
+        key != 0 && self.keys.contains(&key)
+    }
+}
+
+/// Report of a boot mouse
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub struct MouseReport {
+    /// Buttons, the primary button in bit 0
+    pub buttons: u8,
+    pub x: i8,
+    pub y: i8,
+    /// Wheel movement, 0 if the mouse does not report it
+    pub wheel: i8,
+}
+
+impl MouseReport {
+    /// Parse a boot mouse report of at least 3 bytes
+    pub fn parse(report: &[u8]) -> Option<Self> {
+        if report.len() < 3 {
+            return None;
+        }
+        Some(Self {
+            buttons: report[0],
+            x: report[1] as i8,
+            y: report[2] as i8,
+            wheel: report.get(3).map_or(0, |&wheel| wheel as i8),
+        })
+    }
+}
+
+/// Boot protocol interface and its interrupt IN pipe
+struct BootInterface {
+    device: Device,
+    interface: u8,
+    pipe: Pipe,
+}
+
+impl BootInterface {
+    fn new<B: HostBus>(
+        bus: &mut B,
+        device: &Device,
+        config: &[u8],
+        protocol: BootProtocol,
+    ) -> Result<Self, Error> {
+        let (interface, mut endpoints) = find_interface(config, |interface| {
+            interface.class == CLASS
+                && interface.subclass == SUBCLASS_BOOT
+                && interface.protocol == protocol as u8
+        })
+        .ok_or(Error::Unsupported)?;
+        let endpoint = endpoints
+            .find(|endpoint| {
+                endpoint.transfer_type() == EndpointType::Interrupt
+                    && endpoint.direction() == UsbDirection::In
+            })
+            .ok_or(Error::Unsupported)?;
+        let interface = interface.interface_number;
+
+        let setup = SetupPacket::class_interface(false, SET_PROTOCOL, 0, interface, 0);
+        device.control_out(bus, &setup, &[])?;
+        // Only report changes. Devices may stall this optional request.
+        let setup = SetupPacket::class_interface(false, SET_IDLE, 0, interface, 0);
+        match device.control_out(bus, &setup, &[]) {
+            Ok(()) | Err(Error::Stall) => (),
+            Err(error) => return Err(error),
+        }
+
+        let pipe = bus.alloc_pipe(device.address, &endpoint)?;
+        Ok(Self {
+            device: *device,
+            interface,
+            pipe,
+        })
+    }
+
+    fn poll<B: HostBus>(&mut self, bus: &mut B, report: &mut [u8]) -> nb::Result<usize, Error> {
+        bus.read(self.pipe, report)
+    }
+
+    fn free<B: HostBus>(self, bus: &mut B) {
+        bus.free_pipe(self.pipe);
+    }
+}
+
+/// Boot protocol keyboard
+pub struct Keyboard {
+    inner: BootInterface,
+}
+
+impl Keyboard {
+    /// Switch the keyboard interface of `config` to the boot protocol
+    pub fn new<B: HostBus>(bus: &mut B, device: &Device, config: &[u8]) -> Result<Self, Error> {
+        let inner = BootInterface::new(bus, device, config, BootProtocol::Keyboard)?;
+        Ok(Self { inner })
+    }
+
+    /// Returns the next report of the keyboard
+    pub fn poll<B: HostBus>(&mut self, bus: &mut B) -> nb::Result<KeyboardReport, Error> {
+        let mut report = [0; 8];
+        let len = self.inner.poll(bus, &mut report)?;
+        KeyboardReport::parse(&report[..len]).ok_or(nb::Error::Other(Error::Protocol))
+    }
+
+    /// Set the keyboard LEDs: Num Lock in bit 0, Caps Lock in bit 1,
+    /// Scroll Lock in bit 2
+    pub fn set_leds<B: HostBus>(&mut self, bus: &mut B, leds: u8) -> Result<(), Error> {
+        let setup = SetupPacket::class_interface(
+            false,
+            SET_REPORT,
+            REPORT_OUTPUT << 8,
+            self.inner.interface,
+            1,
+        );
+        self.inner.device.control_out(bus, &setup, &[leds])
+    }
+
+    /// Release the pipe of the keyboard
+    pub fn free<B: HostBus>(self, bus: &mut B) {
+        self.inner.free(bus)
+    }
+}
+
+/// Boot protocol mouse
+pub struct Mouse {
+    inner: BootInterface,
+}
+
+impl Mouse {
+    /// Switch the mouse interface of `config` to the boot protocol
+    pub fn new<B: HostBus>(bus: &mut B, device: &Device, config: &[u8]) -> Result<Self, Error> {
+        let inner = BootInterface::new(bus, device, config, BootProtocol::Mouse)?;
+        Ok(Self { inner })
+    }
+
+    /// Returns the next report of the mouse
+    pub fn poll<B: HostBus>(&mut self, bus: &mut B) -> nb::Result<MouseReport, Error> {
+        let mut report = [0; 8];
+        let len = self.inner.poll(bus, &mut report)?;
+        MouseReport::parse(&report[..len]).ok_or(nb::Error::Other(Error::Protocol))
+    }
+
+    /// Release the pipe of the mouse
+    pub fn free<B: HostBus>(self, bus: &mut B) {
+        self.inner.free(bus)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyboard_reports() {
+        let report = KeyboardReport::parse(&[0x02, 0, 0x04, 0x05, 0, 0, 0, 0]).unwrap();
+        assert_eq!(report.modifiers, 0x02);
+        assert!(report.is_pressed(0x04));
+        assert!(!report.is_pressed(0x06));
+        assert!(!report.is_pressed(0));
+        assert!(!report.is_rollover());
+        assert!(KeyboardReport::parse(&[0, 0, 1, 1, 1, 1, 1, 1])
+            .unwrap()
+            .is_rollover());
+        assert_eq!(KeyboardReport::parse(&[0; 4]), None);
+    }
+
+    #[test]
+    fn mouse_reports() {
+        let report = MouseReport::parse(&[0x01, 0xff, 0x02]).unwrap();
+        assert_eq!(
+            report,
+            MouseReport {
+                buttons: 1,
+                x: -1,
+                y: 2,
+                wheel: 0
+            }
+        );
+        assert_eq!(MouseReport::parse(&[0, 0, 0, 0x80]).unwrap().wheel, -128);
+        assert_eq!(MouseReport::parse(&[0, 0]), None);
+    }
+}
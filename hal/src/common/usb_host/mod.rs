@@ -0,0 +1,260 @@
+//! Chip independent part of the USB host mode support
+//!
+//! The host controller driver, [`UsbHost`](crate::usb::host::UsbHost),
+//! implements [`HostBus`]: bus reset, control transfers, and single packet
+//! transfers on bulk and interrupt pipes. On top of it, [`enumerate`]
+//! configures the device attached to the port, and the [`hid`] and [`msc`]
+//! class drivers talk to keyboards, mice and flash drives.
+//!
+//! Only a single device, attached directly to the port, is supported: there
+//! is no hub support.
+
+mod descriptor;
+pub use descriptor::*;
+
+mod enumeration;
+pub use enumeration::*;
+
+pub mod hid;
+pub mod msc;
+
+/// Speed of the attached device
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Speed {
+    Low,
+    Full,
+}
+
+/// USB host errors
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// No device is attached, or it was detached during the transfer
+    Disconnected,
+    /// The device answered with a STALL handshake
+    Stall,
+    /// The device did not answer in time
+    Timeout,
+    /// A transaction failed: CRC, PID or data toggle error
+    Transaction,
+    /// The data does not fit in the buffer
+    BufferOverflow,
+    /// All pipes are in use
+    NoPipe,
+    /// A descriptor is malformed
+    InvalidDescriptor,
+    /// The device has no interface or endpoint supported by the driver
+    Unsupported,
+    /// The device reported a failed command
+    CommandFailed,
+    /// The device violated the class protocol
+    Protocol,
+    /// The port was not reset with [`HostBus::reset`], so no frames are
+    /// sent
+    NotReset,
+}
+
+/// Handle of a pipe allocated with [`HostBus::alloc_pipe`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Pipe {
+    pub(crate) index: u8,
+    pub(crate) max_packet_size: u16,
+}
+
+impl Pipe {
+    /// Maximum packet size of the endpoint
+    pub fn max_packet_size(&self) -> u16 {
+        self.max_packet_size
+    }
+}
+
+/// Request type: direction, type and recipient of a control request
+pub mod request_type {
+    pub const HOST_TO_DEVICE: u8 = 0x00;
+    pub const DEVICE_TO_HOST: u8 = 0x80;
+    pub const STANDARD: u8 = 0x00;
+    pub const CLASS: u8 = 0x20;
+    pub const DEVICE: u8 = 0x00;
+    pub const INTERFACE: u8 = 0x01;
+    pub const ENDPOINT: u8 = 0x02;
+}
+
+/// Standard request codes
+pub mod request {
+    pub const CLEAR_FEATURE: u8 = 1;
+    pub const SET_ADDRESS: u8 = 5;
+    pub const GET_DESCRIPTOR: u8 = 6;
+    pub const SET_CONFIGURATION: u8 = 9;
+}
+
+/// SETUP packet of a control transfer
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SetupPacket {
+    pub request_type: u8,
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+    pub length: u16,
+}
+
+impl SetupPacket {
+    /// GET_DESCRIPTOR request for `length` bytes of descriptor `kind`
+    pub fn get_descriptor(kind: u8, index: u8, length: u16) -> Self {
+        Self {
+            request_type: request_type::DEVICE_TO_HOST,
+            request: request::GET_DESCRIPTOR,
+            value: (kind as u16) << 8 | index as u16,
+            index: 0,
+            length,
+        }
+    }
+
+    /// SET_ADDRESS request
+    pub fn set_address(address: u8) -> Self {
+        Self {
+            request_type: request_type::HOST_TO_DEVICE,
+            request: request::SET_ADDRESS,
+            value: address as u16,
+            index: 0,
+            length: 0,
+        }
+    }
+
+    /// SET_CONFIGURATION request
+    pub fn set_configuration(value: u8) -> Self {
+        Self {
+            request_type: request_type::HOST_TO_DEVICE,
+            request: request::SET_CONFIGURATION,
+            value: value as u16,
+            index: 0,
+            length: 0,
+        }
+    }
+
+    /// CLEAR_FEATURE(ENDPOINT_HALT) request for `endpoint`
+    pub fn clear_halt(endpoint: u8) -> Self {
+        Self {
+            request_type: request_type::HOST_TO_DEVICE | request_type::ENDPOINT,
+            request: request::CLEAR_FEATURE,
+            value: 0,
+            index: endpoint as u16,
+            length: 0,
+        }
+    }
+
+    /// Class-specific request to `interface`
+    pub fn class_interface(
+        device_to_host: bool,
+        request: u8,
+        value: u16,
+        interface: u8,
+        length: u16,
+    ) -> Self {
+        let direction = if device_to_host {
+            request_type::DEVICE_TO_HOST
+        } else {
+            request_type::HOST_TO_DEVICE
+        };
+        Self {
+            request_type: direction | request_type::CLASS | request_type::INTERFACE,
+            request,
+            value,
+            index: interface as u16,
+            length,
+        }
+    }
+
+    /// Returns `true` if the data stage goes from the device to the host
+    pub fn is_in(&self) -> bool {
+        self.request_type & request_type::DEVICE_TO_HOST != 0
+    }
+
+    /// The packet as sent on the bus
+    pub fn to_bytes(self) -> [u8; 8] {
+        let value = self.value.to_le_bytes();
+        let index = self.index.to_le_bytes();
+        let length = self.length.to_le_bytes();
+        [
+            self.request_type,
+            self.request,
+            value[0],
+            value[1],
+            index[0],
+            index[1],
+            length[0],
+            length[1],
+        ]
+    }
+}
+
+/// USB host controller
+///
+/// The transfer methods of the pipes handle a single packet of at most the
+/// maximum packet size of the endpoint.
+pub trait HostBus {
+    /// Returns the speed of the attached device, or `None` if the port is
+    /// empty
+    fn connected(&mut self) -> Option<Speed>;
+
+    /// Reset the port and start sending start-of-frame packets. The device
+    /// answers to address 0 afterwards. Control transfers fail with
+    /// [`Error::NotReset`] until the port is reset.
+    fn reset(&mut self) -> Result<Speed, Error>;
+
+    /// Wait for `frames` start-of-frame periods, of 1 ms each
+    fn wait_frames(&mut self, frames: u16);
+
+    /// Control transfer with an optional IN data stage. Returns the number
+    /// of bytes received.
+    fn control_in(
+        &mut self,
+        address: u8,
+        max_packet_size: u8,
+        setup: &SetupPacket,
+        buf: &mut [u8],
+    ) -> Result<usize, Error>;
+
+    /// Control transfer with an optional OUT data stage
+    fn control_out(
+        &mut self,
+        address: u8,
+        max_packet_size: u8,
+        setup: &SetupPacket,
+        data: &[u8],
+    ) -> Result<(), Error>;
+
+    /// Allocate a pipe to the bulk or interrupt `endpoint` of the device at
+    /// `address`
+    fn alloc_pipe(&mut self, address: u8, endpoint: &EndpointDescriptor) -> Result<Pipe, Error>;
+
+    /// Release a pipe
+    fn free_pipe(&mut self, pipe: Pipe);
+
+    /// Receive a packet from an IN pipe. Returns `WouldBlock` until the
+    /// device answers with data.
+    fn read(&mut self, pipe: Pipe, buf: &mut [u8]) -> nb::Result<usize, Error>;
+
+    /// Send a packet on an OUT pipe. Returns `WouldBlock` until the device
+    /// acknowledges it.
+    fn write(&mut self, pipe: Pipe, data: &[u8]) -> nb::Result<(), Error>;
+
+    /// Restart the data toggle sequence of a pipe, after clearing an
+    /// endpoint halt
+    fn reset_toggle(&mut self, pipe: Pipe);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setup_packet_layout() {
+        let setup = SetupPacket::get_descriptor(descriptor_type::CONFIGURATION, 0, 0x0109);
+        assert_eq!(setup.to_bytes(), [0x80, 6, 0, 2, 0, 0, 0x09, 0x01]);
+        assert!(setup.is_in());
+        assert_eq!(
+            SetupPacket::clear_halt(0x81).to_bytes(),
+            [0x02, 1, 0, 0, 0x81, 0, 0, 0]
+        );
+        assert!(!SetupPacket::set_address(5).is_in());
+    }
+}
@@ -0,0 +1,389 @@
+//! Mass storage devices, with the bulk-only transport and the SCSI
+//! transparent command set of USB flash drives
+//!
+//! Only the first logical unit is used. Blocks are read and written with
+//! the READ(10) and WRITE(10) commands, which address up to 2 TiB with
+//! 512 byte blocks.
+
+use super::{find_interface, Device, Error, HostBus, Pipe, SetupPacket};
+use usb_device::endpoint::EndpointType;
+use usb_device::UsbDirection;
+
+/// Mass storage interface class
+pub const CLASS: u8 = 8;
+/// SCSI transparent command set subclass
+pub const SUBCLASS_SCSI: u8 = 6;
+/// Bulk-only transport protocol
+pub const PROTOCOL_BULK_ONLY: u8 = 0x50;
+
+/// Bulk-only mass storage reset request
+const MASS_STORAGE_RESET: u8 = 0xff;
+
+const CBW_SIGNATURE: u32 = 0x4342_5355;
+const CSW_SIGNATURE: u32 = 0x5342_5355;
+const CBW_LENGTH: usize = 31;
+const CSW_LENGTH: usize = 13;
+
+const TEST_UNIT_READY: u8 = 0x00;
+const REQUEST_SENSE: u8 = 0x03;
+const READ_CAPACITY_10: u8 = 0x25;
+const READ_10: u8 = 0x28;
+const WRITE_10: u8 = 0x2a;
+
+/// Number of TEST UNIT READY attempts while the medium spins up
+const READY_ATTEMPTS: usize = 50;
+/// Delay between the TEST UNIT READY attempts, in ms
+const READY_DELAY: u16 = 100;
+
+/// Command block wrapper
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct CommandBlock<'a> {
+    tag: u32,
+    data_length: u32,
+    data_in: bool,
+    command: &'a [u8],
+}
+
+impl CommandBlock<'_> {
+    fn to_bytes(self) -> [u8; CBW_LENGTH] {
+        let mut bytes = [0; CBW_LENGTH];
+        bytes[0..4].copy_from_slice(&CBW_SIGNATURE.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.tag.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.data_length.to_le_bytes());
+        bytes[12] = if self.data_in { 0x80 } else { 0 };
+        // LUN 0
+        bytes[14] = self.command.len() as u8;
+        bytes[15..15 + self.command.len()].copy_from_slice(self.command);
+        bytes
+    }
+}
+
+/// Command status of a command status wrapper
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum CommandStatus {
+    Passed,
+    Failed,
+    PhaseError,
+}
+
+/// Decode a command status wrapper. Returns `None` if it is not valid for
+/// the command with `tag`.
+fn parse_status(bytes: &[u8], tag: u32) -> Option<CommandStatus> {
+    if bytes.len() != CSW_LENGTH
+        || bytes[0..4] != CSW_SIGNATURE.to_le_bytes()
+        || bytes[4..8] != tag.to_le_bytes()
+    {
+        return None;
+    }
+    match bytes[12] {
+        0 => Some(CommandStatus::Passed),
+        1 => Some(CommandStatus::Failed),
+        2 => Some(CommandStatus::PhaseError),
+        _ => None,
+    }
+}
+
+/// Command with a 10 byte block address and length
+fn command_10(opcode: u8, lba: u32, blocks: u16) -> [u8; 10] {
+    let lba = lba.to_be_bytes();
+    let blocks = blocks.to_be_bytes();
+    [
+        opcode, 0, lba[0], lba[1], lba[2], lba[3], 0, blocks[0], blocks[1], 0,
+    ]
+}
+
+/// Data stage of a command
+enum Data<'a> {
+    None,
+    In(&'a mut [u8]),
+    Out(&'a [u8]),
+}
+
+/// Bulk-only mass storage device
+pub struct MassStorage {
+    device: Device,
+    interface: u8,
+    bulk_in: Pipe,
+    bulk_out: Pipe,
+    endpoint_in: u8,
+    endpoint_out: u8,
+    tag: u32,
+    block_size: u32,
+    block_count: u32,
+}
+
+impl MassStorage {
+    /// Open the mass storage interface of `config`, and wait for the medium
+    /// to be ready
+    pub fn new<B: HostBus>(bus: &mut B, device: &Device, config: &[u8]) -> Result<Self, Error> {
+        let (interface, endpoints) = find_interface(config, |interface| {
+            interface.class == CLASS
+                && interface.subclass == SUBCLASS_SCSI
+                && interface.protocol == PROTOCOL_BULK_ONLY
+        })
+        .ok_or(Error::Unsupported)?;
+        let (mut endpoint_in, mut endpoint_out) = (None, None);
+        for endpoint in endpoints.filter(|e| e.transfer_type() == EndpointType::Bulk) {
+            match endpoint.direction() {
+                UsbDirection::In => endpoint_in = endpoint_in.or(Some(endpoint)),
+                UsbDirection::Out => endpoint_out = endpoint_out.or(Some(endpoint)),
+            }
+        }
+        let endpoint_in = endpoint_in.ok_or(Error::Unsupported)?;
+        let endpoint_out = endpoint_out.ok_or(Error::Unsupported)?;
+
+        let bulk_in = bus.alloc_pipe(device.address, &endpoint_in)?;
+        let bulk_out = match bus.alloc_pipe(device.address, &endpoint_out) {
+            Ok(pipe) => pipe,
+            Err(error) => {
+                bus.free_pipe(bulk_in);
+                return Err(error);
+            }
+        };
+
+        let mut storage = Self {
+            device: *device,
+            interface: interface.interface_number,
+            bulk_in,
+            bulk_out,
+            endpoint_in: endpoint_in.address,
+            endpoint_out: endpoint_out.address,
+            tag: 0,
+            block_size: 0,
+            block_count: 0,
+        };
+        match storage.init(bus) {
+            Ok(()) => Ok(storage),
+            Err(error) => {
+                storage.free(bus);
+                Err(error)
+            }
+        }
+    }
+
+    fn init<B: HostBus>(&mut self, bus: &mut B) -> Result<(), Error> {
+        let mut attempts = 0;
+        loop {
+            match self.command(bus, &[TEST_UNIT_READY, 0, 0, 0, 0, 0], Data::None) {
+                Ok(()) => break,
+                Err(Error::CommandFailed) if attempts < READY_ATTEMPTS => {
+                    attempts += 1;
+                    // Clears the unit attention condition
+                    let mut sense = [0; 18];
+                    self.command(bus, &[REQUEST_SENSE, 0, 0, 0, 18, 0], Data::In(&mut sense))?;
+                    bus.wait_frames(READY_DELAY);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        let mut capacity = [0; 8];
+        let command = [READ_CAPACITY_10, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        self.command(bus, &command, Data::In(&mut capacity))?;
+        let last_block = u32::from_be_bytes([capacity[0], capacity[1], capacity[2], capacity[3]]);
+        self.block_size = u32::from_be_bytes([capacity[4], capacity[5], capacity[6], capacity[7]]);
+        self.block_count = last_block.wrapping_add(1);
+        if self.block_size == 0 {
+            return Err(Error::Protocol);
+        }
+        Ok(())
+    }
+
+    /// Size of a block, in bytes
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    /// Number of blocks of the medium
+    pub fn block_count(&self) -> u32 {
+        self.block_count
+    }
+
+    fn check_blocks(&self, len: usize) -> u16 {
+        let block_size = self.block_size as usize;
+        let blocks = len / block_size;
+        assert!(
+            blocks * block_size == len && blocks <= u16::MAX as usize,
+            "Not a whole number of blocks"
+        );
+        blocks as u16
+    }
+
+    /// Read the blocks starting at `lba` into `buf`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `buf` is not a multiple of the block size,
+    /// or is more than 65535 blocks.
+    pub fn read_blocks<B: HostBus>(
+        &mut self,
+        bus: &mut B,
+        lba: u32,
+        buf: &mut [u8],
+    ) -> Result<(), Error> {
+        let blocks = self.check_blocks(buf.len());
+        self.command(bus, &command_10(READ_10, lba, blocks), Data::In(buf))
+    }
+
+    /// Write `data` to the blocks starting at `lba`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `data` is not a multiple of the block size,
+    /// or is more than 65535 blocks.
+    pub fn write_blocks<B: HostBus>(
+        &mut self,
+        bus: &mut B,
+        lba: u32,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let blocks = self.check_blocks(data.len());
+        self.command(bus, &command_10(WRITE_10, lba, blocks), Data::Out(data))
+    }
+
+    /// Release the pipes of the device
+    pub fn free<B: HostBus>(self, bus: &mut B) {
+        bus.free_pipe(self.bulk_in);
+        bus.free_pipe(self.bulk_out);
+    }
+
+    fn command<B: HostBus>(
+        &mut self,
+        bus: &mut B,
+        command: &[u8],
+        data: Data<'_>,
+    ) -> Result<(), Error> {
+        self.tag = self.tag.wrapping_add(1);
+        let (data_length, data_in) = match &data {
+            Data::None => (0, false),
+            Data::In(buf) => (buf.len(), true),
+            Data::Out(buf) => (buf.len(), false),
+        };
+        let block = CommandBlock {
+            tag: self.tag,
+            data_length: data_length as u32,
+            data_in,
+            command,
+        };
+        self.write_all(bus, &block.to_bytes())?;
+
+        let stage = match data {
+            Data::None => Ok(()),
+            Data::In(buf) => self.read_all(bus, buf).map(|_| ()),
+            Data::Out(buf) => self.write_all(bus, buf),
+        };
+        match stage {
+            Ok(()) => (),
+            // The device stalls the data stage to end it early
+            Err(Error::Stall) => {
+                let (pipe, endpoint) = if data_in {
+                    (self.bulk_in, self.endpoint_in)
+                } else {
+                    (self.bulk_out, self.endpoint_out)
+                };
+                self.clear_halt(bus, pipe, endpoint)?;
+            }
+            Err(error) => return Err(error),
+        }
+
+        let mut status = [0; CSW_LENGTH];
+        let len = match self.read_all(bus, &mut status) {
+            Err(Error::Stall) => {
+                self.clear_halt(bus, self.bulk_in, self.endpoint_in)?;
+                self.read_all(bus, &mut status)?
+            }
+            result => result?,
+        };
+        match parse_status(&status[..len], self.tag) {
+            Some(CommandStatus::Passed) => Ok(()),
+            Some(CommandStatus::Failed) => Err(Error::CommandFailed),
+            Some(CommandStatus::PhaseError) | None => {
+                self.reset_recovery(bus)?;
+                Err(Error::Protocol)
+            }
+        }
+    }
+
+    fn write_all<B: HostBus>(&mut self, bus: &mut B, data: &[u8]) -> Result<(), Error> {
+        let max_packet_size = self.bulk_out.max_packet_size() as usize;
+        for packet in data.chunks(max_packet_size) {
+            nb::block!(bus.write(self.bulk_out, packet))?;
+        }
+        Ok(())
+    }
+
+    /// Read until `buf` is full or the device sends a short packet
+    fn read_all<B: HostBus>(&mut self, bus: &mut B, buf: &mut [u8]) -> Result<usize, Error> {
+        let max_packet_size = self.bulk_in.max_packet_size() as usize;
+        let mut received = 0;
+        while received < buf.len() {
+            let len = nb::block!(bus.read(self.bulk_in, &mut buf[received..]))?;
+            received += len;
+            if len < max_packet_size {
+                break;
+            }
+        }
+        Ok(received)
+    }
+
+    fn clear_halt<B: HostBus>(
+        &mut self,
+        bus: &mut B,
+        pipe: Pipe,
+        endpoint: u8,
+    ) -> Result<(), Error> {
+        self.device
+            .control_out(bus, &SetupPacket::clear_halt(endpoint), &[])?;
+        bus.reset_toggle(pipe);
+        Ok(())
+    }
+
+    /// Reset the transport after a protocol error
+    fn reset_recovery<B: HostBus>(&mut self, bus: &mut B) -> Result<(), Error> {
+        let setup = SetupPacket::class_interface(false, MASS_STORAGE_RESET, 0, self.interface, 0);
+        self.device.control_out(bus, &setup, &[])?;
+        self.clear_halt(bus, self.bulk_in, self.endpoint_in)?;
+        self.clear_halt(bus, self.bulk_out, self.endpoint_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_block_layout() {
+        let command = command_10(READ_10, 0x0102_0304, 8);
+        assert_eq!(command, [0x28, 0, 1, 2, 3, 4, 0, 0, 8, 0]);
+        let block = CommandBlock {
+            tag: 7,
+            data_length: 4096,
+            data_in: true,
+            command: &command,
+        };
+        let bytes = block.to_bytes();
+        assert_eq!(&bytes[0..4], b"USBC");
+        assert_eq!(&bytes[4..8], &[7, 0, 0, 0]);
+        assert_eq!(&bytes[8..12], &[0, 0x10, 0, 0]);
+        assert_eq!(bytes[12], 0x80);
+        assert_eq!(bytes[14], 10);
+        assert_eq!(&bytes[15..25], &command);
+        assert!(bytes[25..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn command_status() {
+        let mut bytes = [0; CSW_LENGTH];
+        bytes[0..4].copy_from_slice(b"USBS");
+        bytes[4] = 7;
+        assert_eq!(parse_status(&bytes, 7), Some(CommandStatus::Passed));
+        assert_eq!(parse_status(&bytes, 8), None);
+        bytes[12] = 1;
+        assert_eq!(parse_status(&bytes, 7), Some(CommandStatus::Failed));
+        bytes[12] = 2;
+        assert_eq!(parse_status(&bytes, 7), Some(CommandStatus::PhaseError));
+        assert_eq!(parse_status(&bytes[..12], 7), None);
+        bytes[0] = b'X';
+        assert_eq!(parse_status(&bytes, 7), None);
+    }
+}
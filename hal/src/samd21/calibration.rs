@@ -55,3 +55,9 @@ pub fn usb_transp_cal() -> u8 {
 pub fn usb_trim_cal() -> u8 {
     cal_with_errata(4, 23, 7, 7, 3) as u8
 }
+
+/// Temperature log row, holding the calibration values of the temperature
+/// sensor
+pub fn temperature_log_row() -> [u32; 2] {
+    [cal(0x10, 0, !0), cal(0x14, 0, !0)]
+}
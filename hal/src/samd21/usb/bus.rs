@@ -12,7 +12,7 @@ use crate::target_device;
 use crate::target_device::usb::DEVICE;
 use crate::target_device::{PM, USB};
 use crate::usb::devicedesc::DeviceDescBank;
-use core::cell::{Ref, RefCell, RefMut};
+use core::cell::{Cell, Ref, RefCell, RefMut};
 use core::marker::PhantomData;
 use core::mem;
 use cortex_m::interrupt::{free as disable_interrupts, Mutex};
@@ -30,7 +30,8 @@ pub enum EndpointTypeBits {
     Isochronous = 2,
     Bulk = 3,
     Interrupt = 4,
-    #[allow(unused)]
+    /// Second bank of a ping-pong endpoint, which uses the type of the
+    /// other bank of the pair
     DualBank = 5,
 }
 
@@ -81,6 +82,8 @@ impl EPConfig {
 struct EndpointInfo {
     bank0: EPConfig,
     bank1: EPConfig,
+    /// Bank of a ping-pong endpoint used by the next transfer
+    next_bank: Cell<usize>,
 }
 
 impl EndpointInfo {
@@ -111,14 +114,20 @@ impl AllEndpoints {
         }
     }
 
-    fn find_free_endpoint(&self, dir: UsbDirection) -> UsbResult<usize> {
+    fn find_free_endpoint(&self, dir: UsbDirection, ping_pong: bool) -> UsbResult<usize> {
         // start with 1 because 0 is reserved for Control
         for idx in 1..8 {
-            let ep_type = match dir {
-                UsbDirection::Out => self.endpoints[idx].bank0.ep_type,
-                UsbDirection::In => self.endpoints[idx].bank1.ep_type,
+            let info = &self.endpoints[idx];
+            let free = match dir {
+                // A ping-pong endpoint needs both banks of the pair
+                _ if ping_pong => {
+                    info.bank0.ep_type == EndpointTypeBits::Disabled
+                        && info.bank1.ep_type == EndpointTypeBits::Disabled
+                }
+                UsbDirection::Out => info.bank0.ep_type == EndpointTypeBits::Disabled,
+                UsbDirection::In => info.bank1.ep_type == EndpointTypeBits::Disabled,
             };
-            if ep_type == EndpointTypeBits::Disabled {
+            if free {
                 return Ok(idx);
             }
         }
@@ -135,61 +144,80 @@ impl AllEndpoints {
         max_packet_size: u16,
         _interval: u8,
         buffer_addr: *mut u8,
+        ping_pong_addr: Option<*mut u8>,
     ) -> UsbResult<EndpointAddress> {
-        let bank = match dir {
-            UsbDirection::Out => &mut self.endpoints[idx].bank0,
-            UsbDirection::In => &mut self.endpoints[idx].bank1,
+        let info = &mut self.endpoints[idx];
+        let (bank, other) = match dir {
+            UsbDirection::Out => (&mut info.bank0, &mut info.bank1),
+            UsbDirection::In => (&mut info.bank1, &mut info.bank0),
         };
         if bank.ep_type != EndpointTypeBits::Disabled {
             return Err(UsbError::EndpointOverflow);
         }
+        if ping_pong_addr.is_some() && other.ep_type != EndpointTypeBits::Disabled {
+            return Err(UsbError::EndpointOverflow);
+        }
 
         *bank = EPConfig::new(ep_type, allocated_size, max_packet_size, buffer_addr);
+        if let Some(addr) = ping_pong_addr {
+            *other = EPConfig {
+                ep_type: EndpointTypeBits::DualBank,
+                ..EPConfig::new(ep_type, allocated_size, max_packet_size, addr)
+            };
+        }
 
         Ok(EndpointAddress::from_parts(idx, dir))
     }
 }
 
-// FIXME: replace with more general heap?
+/// Handshake of the device to the LPM (Link Power Management) requests of
+/// the host, which put the bus in the L1 sleep state
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LpmHandshake {
+    /// LPM is not supported: requests are not answered
+    Disabled,
+    /// Accept the requests and suspend
+    Ack,
+    /// Refuse the requests for now
+    Nyet,
+    /// Refuse the requests: the requested state is not supported
+    Stall,
+}
+
+/// Size of the endpoint buffer used by `UsbBus::new`
 const BUFFER_SIZE: usize = 2048;
 fn buffer() -> &'static mut [u8; BUFFER_SIZE] {
     singleton!(: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE] ).unwrap()
 }
 
 struct BufferAllocator {
-    buffers: &'static mut [u8; BUFFER_SIZE],
-    next_buf: u16,
+    buffers: &'static mut [u8],
+    next_buf: usize,
 }
 
 impl BufferAllocator {
-    fn new() -> Self {
+    fn new(buffers: &'static mut [u8]) -> Self {
         Self {
             next_buf: 0,
-            buffers: buffer(),
+            buffers,
         }
     }
 
     fn allocate_buffer(&mut self, size: u16) -> UsbResult<*mut u8> {
         debug_assert!(size & 1 == 0);
 
-        let start_addr = &mut self.buffers[self.next_buf as usize] as *mut u8;
-        let buf_end = unsafe { start_addr.add(BUFFER_SIZE) };
+        let free = &mut self.buffers[self.next_buf..];
 
         // The address must be 32-bit aligned, so allow for that here
         // by offsetting by an appropriate alignment.
-        let offset = start_addr.align_offset(mem::align_of::<u32>());
-        let start_addr = unsafe { start_addr.add(offset) };
-
-        if start_addr >= buf_end {
+        let offset = free.as_ptr().align_offset(mem::align_of::<u32>());
+        let end = offset + size as usize;
+        if end > free.len() {
             return Err(UsbError::EndpointMemoryOverflow);
         }
+        let start_addr = free[offset..].as_mut_ptr();
 
-        let end_addr = unsafe { start_addr.offset(size as isize) };
-        if end_addr > buf_end {
-            return Err(UsbError::EndpointMemoryOverflow);
-        }
-
-        self.next_buf = unsafe { end_addr.sub(self.buffers.as_ptr() as usize) as u16 };
+        self.next_buf += end;
 
         Ok(start_addr)
     }
@@ -201,6 +229,12 @@ struct Inner {
     _dp_pad: DpPad,
     endpoints: RefCell<AllEndpoints>,
     buffers: RefCell<BufferAllocator>,
+    /// Endpoint types allocated as ping-pong endpoints, one bit per
+    /// `EndpointTypeBits` value
+    ping_pong: u8,
+    lpm: LpmHandshake,
+    suspend_hook: Option<fn(bool)>,
+    suspended: Cell<bool>,
 }
 
 pub struct UsbBus {
@@ -254,14 +288,81 @@ impl<'a, T> Bank<'a, T> {
     }
 
     #[inline]
-    fn config(&mut self) -> &EPConfig {
-        let ep = &self.endpoints.endpoints[self.address.index()];
+    fn config(&self) -> &EPConfig {
+        let ep = self.info();
         if self.address.is_out() {
             &ep.bank0
         } else {
             &ep.bank1
         }
     }
+
+    #[inline]
+    fn info(&self) -> &EndpointInfo {
+        &self.endpoints.endpoints[self.address.index()]
+    }
+
+    /// Returns true if the endpoint uses both banks of the pair, the
+    /// hardware alternating between them
+    #[inline]
+    fn is_ping_pong(&self) -> bool {
+        let info = self.info();
+        info.bank0.ep_type == EndpointTypeBits::DualBank
+            || info.bank1.ep_type == EndpointTypeBits::DualBank
+    }
+
+    #[inline]
+    fn is_isochronous(&self) -> bool {
+        self.config().ep_type == EndpointTypeBits::Isochronous
+    }
+
+    /// Bank used by the next transfer. `bank` is the bank of the endpoint
+    /// direction, used unless the endpoint is ping-pong.
+    #[inline]
+    fn current(&self, bank: usize) -> usize {
+        if self.is_ping_pong() {
+            self.info().next_bank.get()
+        } else {
+            bank
+        }
+    }
+
+    /// Moves a ping-pong endpoint to its other bank, after a transfer was
+    /// handed to or taken from the hardware.
+    #[inline]
+    fn advance(&self) {
+        if self.is_ping_pong() {
+            let next_bank = &self.info().next_bank;
+            next_bank.set(next_bank.get() ^ 1);
+        }
+    }
+
+    #[inline]
+    fn is_bank_ready(&self, bank: usize) -> bool {
+        let status = self.epstatus(self.index()).read();
+        match bank {
+            0 => status.bk0rdy().bit(),
+            _ => status.bk1rdy().bit(),
+        }
+    }
+
+    #[inline]
+    fn set_bank_ready(&self, bank: usize, ready: bool) {
+        match (bank, ready) {
+            (0, true) => self
+                .epstatusset(self.index())
+                .write(|w| w.bk0rdy().set_bit()),
+            (0, false) => self
+                .epstatusclr(self.index())
+                .write(|w| w.bk0rdy().set_bit()),
+            (_, true) => self
+                .epstatusset(self.index())
+                .write(|w| w.bk1rdy().set_bit()),
+            (_, false) => self
+                .epstatusclr(self.index())
+                .write(|w| w.bk1rdy().set_bit()),
+        }
+    }
 }
 
 /// InBank represents In direction banks, Bank #1
@@ -273,58 +374,53 @@ struct OutBank;
 impl<'a> Bank<'a, InBank> {
     fn desc_bank(&mut self) -> &mut DeviceDescBank {
         let idx = self.index();
-        self.desc.bank(idx, 1)
+        let bank = self.current(1);
+        self.desc.bank(idx, bank)
     }
 
-    /// Returns true if Bank 1 is Ready and thus has data that can be written
+    /// Returns true if the current bank (Bank 1 unless ping-pong) is Ready
+    /// and thus still holds data to be sent
     #[inline]
     fn is_ready(&self) -> bool {
-        self.epstatus(self.index()).read().bk1rdy().bit()
+        self.is_bank_ready(self.current(1))
     }
 
-    /// Set Bank 1 Ready.
+    /// Set the current bank Ready.
     /// Ready means that the buffer contains data that can be sent.
     #[inline]
     fn set_ready(&self, ready: bool) {
-        if ready {
-            self.epstatusset(self.index())
-                .write(|w| w.bk1rdy().set_bit());
-        } else {
-            self.epstatusclr(self.index())
-                .write(|w| w.bk1rdy().set_bit());
-        }
+        self.set_bank_ready(self.current(1), ready);
     }
 
-    /// Acknowledges the signal that the last packet was sent.
+    /// Acknowledges the signal that the last packets were sent.
     #[inline]
     fn clear_transfer_complete(&self) {
+        let ping_pong = self.is_ping_pong();
         // Clear bits in epintflag by writing them to 1
-        self.epintflag(self.index())
-            .write(|w| w.trcpt1().set_bit().trfail1().set_bit());
+        self.epintflag(self.index()).write(|w| {
+            if ping_pong {
+                w.trcpt0().set_bit().trfail0().set_bit();
+            }
+            w.trcpt1().set_bit().trfail1().set_bit()
+        });
     }
 
     /// Indicates if a transfer is complete or pending.
     #[inline]
     fn is_transfer_complete(&self) -> bool {
-        self.epintflag(self.index()).read().trcpt1().bit()
-    }
-
-    /// Writes out endpoint configuration to its in-memory descriptor.
-    fn flush_config(&mut self) {
-        let config = *self.config();
-        {
-            let desc = self.desc_bank();
-            desc.set_address(config.addr as *mut u8);
-            desc.set_endpoint_size(config.max_packet_size);
-            desc.set_multi_packet_size(0);
-            desc.set_byte_count(0);
-        }
+        let flags = self.epintflag(self.index()).read();
+        flags.trcpt1().bit() || (self.is_ping_pong() && flags.trcpt0().bit())
     }
 
     /// Enables endpoint-specific interrupts.
     fn setup_ep_interrupts(&mut self) {
-        self.epintenset(self.index())
-            .write(|w| w.trcpt1().set_bit());
+        let ping_pong = self.is_ping_pong();
+        self.epintenset(self.index()).write(|w| {
+            if ping_pong {
+                w.trcpt0().set_bit();
+            }
+            w.trcpt1().set_bit()
+        });
     }
 
     /// Prepares to transfer a series of bytes by copying the data into the
@@ -350,6 +446,10 @@ impl<'a> Bank<'a, InBank> {
     }
 
     fn set_stall(&mut self, stall: bool) {
+        // Isochronous endpoints have no handshake, so cannot stall
+        if stall && self.is_isochronous() {
+            return;
+        }
         if stall {
             self.epstatusset(self.index())
                 .write(|w| w.stallrq1().set_bit())
@@ -363,41 +463,51 @@ impl<'a> Bank<'a, InBank> {
 impl<'a> Bank<'a, OutBank> {
     fn desc_bank(&mut self) -> &mut DeviceDescBank {
         let idx = self.index();
-        self.desc.bank(idx, 0)
+        let bank = self.current(0);
+        self.desc.bank(idx, bank)
     }
 
-    /// Returns true if Bank 0 is Ready and thus has data that can be read.
+    /// Returns true if the current bank (Bank 0 unless ping-pong) is Ready
+    /// and thus has data that can be read.
     #[inline]
     fn is_ready(&self) -> bool {
-        self.epstatus(self.index()).read().bk0rdy().bit()
+        self.is_bank_ready(self.current(0))
     }
 
-    /// Set Bank 0 Ready.
+    /// Set the current bank Ready.
     /// Ready means that the buffer contains data that can be read.
     #[inline]
     fn set_ready(&self, ready: bool) {
-        if ready {
-            self.epstatusset(self.index())
-                .write(|w| w.bk0rdy().set_bit());
-        } else {
-            self.epstatusclr(self.index())
-                .write(|w| w.bk0rdy().set_bit());
-        }
+        self.set_bank_ready(self.current(0), ready);
     }
 
-    /// Acknowledges the signal that data has been received.
+    /// Acknowledges the signal that data has been received in the current
+    /// bank. The other bank of a ping-pong endpoint may hold the next packet,
+    /// so its flags are left for the next poll.
     #[inline]
     fn clear_transfer_complete(&self) {
         // Clear bits in epintflag by writing them to 1
-        self.epintflag(self.index())
-            .write(|w| w.trcpt0().set_bit().trfail0().set_bit());
+        match self.current(0) {
+            0 => self
+                .epintflag(self.index())
+                .write(|w| w.trcpt0().set_bit().trfail0().set_bit()),
+            _ => self
+                .epintflag(self.index())
+                .write(|w| w.trcpt1().set_bit().trfail1().set_bit()),
+        }
     }
 
     /// Checks if data has been received. Returns true for failed transfers
     /// as well as successful transfers.
     #[inline]
     fn is_transfer_complete(&self) -> bool {
-        self.epintflag(self.index()).read().trcpt0().bit()
+        let flags = self.epintflag(self.index()).read();
+        // Isochronous packets with a CRC error only set TRFAIL, and are
+        // reported by read()
+        let isochronous = self.is_isochronous();
+        let bank0 = flags.trcpt0().bit() || (isochronous && flags.trfail0().bit());
+        let bank1 = flags.trcpt1().bit() || (isochronous && flags.trfail1().bit());
+        bank0 || (self.is_ping_pong() && bank1)
     }
 
     /// Returns true if a Received Setup interrupt has occurred.
@@ -415,29 +525,32 @@ impl<'a> Bank<'a, OutBank> {
         self.epintflag(self.index()).write(|w| w.rxstp().set_bit());
     }
 
-    /// Writes out endpoint configuration to its in-memory descriptor.
-    fn flush_config(&mut self) {
-        let config = *self.config();
-        {
-            let desc = self.desc_bank();
-            desc.set_address(config.addr as *mut u8);
-            desc.set_endpoint_size(config.max_packet_size);
-            desc.set_multi_packet_size(0);
-            desc.set_byte_count(0);
-        }
-    }
-
     /// Enables endpoint-specific interrupts.
     fn setup_ep_interrupts(&mut self) {
-        self.epintenset(self.index())
-            .write(|w| w.rxstp().set_bit().trcpt0().set_bit());
+        let ping_pong = self.is_ping_pong();
+        let isochronous = self.is_isochronous();
+        self.epintenset(self.index()).write(|w| {
+            if ping_pong {
+                w.trcpt1().set_bit().trfail1().bit(isochronous);
+            }
+            w.trfail0().bit(isochronous);
+            w.rxstp().set_bit().trcpt0().set_bit()
+        });
     }
 
-    /// Copies data from the bank0 buffer to the provided array. The caller
-    /// must call set_ready to indicate the buffer is free for the next
+    /// Copies data from the current bank buffer to the provided array. The
+    /// caller must call set_ready to indicate the buffer is free for the next
     /// transfer.
+    ///
+    /// Isochronous packets received with a CRC error are dropped, and
+    /// reported as `UsbError::ParseError`.
     pub fn read(&mut self, buf: &mut [u8]) -> UsbResult<usize> {
+        let isochronous = self.is_isochronous();
         let desc = self.desc_bank();
+        if isochronous && desc.crc_error() {
+            desc.set_byte_count(0);
+            return Err(UsbError::ParseError);
+        }
         let size = desc.get_byte_count() as usize;
 
         if size > buf.len() {
@@ -459,6 +572,10 @@ impl<'a> Bank<'a, OutBank> {
     }
 
     fn set_stall(&mut self, stall: bool) {
+        // Isochronous endpoints have no handshake, so cannot stall
+        if stall && self.is_isochronous() {
+            return;
+        }
         if stall {
             self.epstatusset(self.index())
                 .write(|w| w.stallrq0().set_bit())
@@ -546,8 +663,12 @@ impl Inner {
         }
         let endpoints = self.endpoints.borrow();
 
-        if endpoints.endpoints[ep.index()].bank0.ep_type == EndpointTypeBits::Disabled {
-            return Err(UsbError::InvalidEndpoint);
+        // The second bank of a ping-pong IN endpoint is not an OUT endpoint
+        match endpoints.endpoints[ep.index()].bank0.ep_type {
+            EndpointTypeBits::Disabled | EndpointTypeBits::DualBank => {
+                return Err(UsbError::InvalidEndpoint)
+            }
+            _ => (),
         }
         Ok(Bank {
             address: ep,
@@ -564,8 +685,12 @@ impl Inner {
         }
         let endpoints = self.endpoints.borrow();
 
-        if endpoints.endpoints[ep.index()].bank1.ep_type == EndpointTypeBits::Disabled {
-            return Err(UsbError::InvalidEndpoint);
+        // The second bank of a ping-pong OUT endpoint is not an IN endpoint
+        match endpoints.endpoints[ep.index()].bank1.ep_type {
+            EndpointTypeBits::Disabled | EndpointTypeBits::DualBank => {
+                return Err(UsbError::InvalidEndpoint)
+            }
+            _ => (),
         }
         Ok(Bank {
             address: ep,
@@ -578,12 +703,29 @@ impl Inner {
 }
 
 impl UsbBus {
+    /// Enables the USB peripheral clocks. Endpoint buffers are allocated
+    /// from a static buffer of 2048 bytes, see [`UsbBus::with_buffer`] to
+    /// provide a different one.
     pub fn new(
+        clock: &clock::UsbClock,
+        pm: &mut PM,
+        dm_pad: DmPad,
+        dp_pad: DpPad,
+        usb: USB,
+    ) -> Self {
+        Self::with_buffer(clock, pm, dm_pad, dp_pad, usb, buffer())
+    }
+
+    /// Like [`UsbBus::new`], with the endpoint buffers allocated from
+    /// `buffer`. Each endpoint takes its maximum packet size, and at least
+    /// 64 bytes, from the buffer, twice for ping-pong endpoints.
+    pub fn with_buffer(
         _clock: &clock::UsbClock,
         pm: &mut PM,
         dm_pad: DmPad,
         dp_pad: DpPad,
         _usb: USB,
+        buffer: &'static mut [u8],
     ) -> Self {
         dbgprint!("******** UsbBus::new\n");
         pm.apbbmask.modify(|_, w| w.usb_().set_bit());
@@ -594,14 +736,79 @@ impl UsbBus {
             _dm_pad: dm_pad,
             _dp_pad: dp_pad,
             desc,
-            buffers: RefCell::new(BufferAllocator::new()),
+            buffers: RefCell::new(BufferAllocator::new(buffer)),
             endpoints: RefCell::new(AllEndpoints::new()),
+            ping_pong: 0,
+            lpm: LpmHandshake::Disabled,
+            suspend_hook: None,
+            suspended: Cell::new(false),
         };
 
         Self {
             inner: Mutex::new(RefCell::new(inner)),
         }
     }
+
+    /// Allocate endpoints of type `ep_type` requested after this call as
+    /// ping-pong endpoints, which use both banks of an endpoint number for a
+    /// single direction: the hardware fills or sends one bank while the
+    /// other is being accessed.
+    ///
+    /// This must be called before the bus is passed to the
+    /// `UsbBusAllocator`. Control endpoints are never ping-pong.
+    pub fn set_ping_pong(&mut self, ep_type: EndpointType, enabled: bool) {
+        disable_interrupts(|cs| {
+            let mut inner = self.inner.borrow(cs).borrow_mut();
+            let mask = 1 << EndpointTypeBits::from(ep_type) as u8;
+            if enabled {
+                inner.ping_pong |= mask;
+            } else {
+                inner.ping_pong &= !mask;
+            }
+        })
+    }
+
+    /// Set the handshake to LPM requests. Hosts only send them to devices
+    /// reporting USB 2.01 or later, with LPM support in their BOS
+    /// descriptor. Accepted requests suspend the bus as for a regular
+    /// suspend, which is then reported by `UsbDevice::poll`.
+    ///
+    /// This must be called before the bus is enabled.
+    pub fn set_lpm(&mut self, handshake: LpmHandshake) {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow_mut().lpm = handshake)
+    }
+
+    /// Call `hook` with `true` when the bus is suspended, and `false` when
+    /// it resumes. This also enables the detection of suspends, which are
+    /// otherwise only reported with LPM enabled.
+    ///
+    /// Suspends are only detected once a host has reset the bus. An
+    /// unplugged bus is idle, so unplugging the device afterwards is seen
+    /// as a suspend.
+    ///
+    /// The hook runs from `UsbDevice::poll`, so it should only record the
+    /// state and power down peripherals: the application can then enter
+    /// standby while [`UsbBus::is_suspended`]. The USB interrupt wakes the
+    /// device on bus activity, provided the USB peripheral and its generic
+    /// clock run in standby.
+    pub fn set_suspend_hook(&mut self, hook: fn(bool)) {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow_mut().suspend_hook = Some(hook))
+    }
+
+    /// Returns true while the host has suspended the bus
+    pub fn is_suspended(&self) -> bool {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow().suspended.get())
+    }
+
+    /// Signal remote wakeup to the host, which then resumes the bus.
+    ///
+    /// The host must have enabled remote wakeup, see
+    /// `UsbDevice::remote_wakeup_enabled`, and the bus must have been
+    /// suspended for at least 5 ms. Returns `InvalidState` if the bus is not
+    /// suspended.
+    pub fn remote_wakeup(&self) -> UsbResult<()> {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow().remote_wakeup())
+    }
 }
 
 impl Inner {
@@ -700,6 +907,12 @@ impl Inner {
         });
         // full speed
         usb.ctrlb.modify(|_, w| w.spdconf().fs());
+        usb.ctrlb.modify(|_, w| match self.lpm {
+            LpmHandshake::Disabled => w.lpmhdsk().no(),
+            LpmHandshake::Ack => w.lpmhdsk().ack(),
+            LpmHandshake::Nyet => w.lpmhdsk().nyet(),
+            LpmHandshake::Stall => w.lpmhdsk().stall(),
+        });
 
         usb.ctrla.modify(|_, w| w.enable().set_bit());
         while usb.syncbusy.read().enable().bit_is_set() {}
@@ -707,6 +920,8 @@ impl Inner {
         // Clear pending.
         usb.intflag
             .write(|w| unsafe { w.bits(usb.intflag.read().bits()) });
+        usb.intenclr
+            .write(|w| w.suspend().set_bit().lpmsusp().set_bit());
         usb.intenset.write(|w| w.eorst().set_bit());
 
         // Configure the endpoints before we attach, as hosts may enumerate
@@ -755,12 +970,20 @@ impl Inner {
         let info = &self.endpoints.borrow().endpoints[idx];
         // Write bank descriptors first. We do this so there is no period in
         // which the endpoint is enabled but has an invalid descriptor.
-        if let Ok(mut bank) = self.bank0(EndpointAddress::from_parts(idx, UsbDirection::Out)) {
-            bank.flush_config();
-        }
-        if let Ok(mut bank) = self.bank1(EndpointAddress::from_parts(idx, UsbDirection::In)) {
-            bank.flush_config();
+        {
+            let mut desc = self.desc.borrow_mut();
+            for (bank, config) in [info.bank0, info.bank1].iter().enumerate() {
+                if config.ep_type != EndpointTypeBits::Disabled {
+                    let desc = desc.bank(idx, bank);
+                    desc.set_address(config.addr as *mut u8);
+                    desc.set_endpoint_size(config.max_packet_size);
+                    desc.set_multi_packet_size(0);
+                    desc.set_byte_count(0);
+                }
+            }
         }
+        // The hardware starts with bank 0 of ping-pong endpoints
+        info.next_bank.set(0);
 
         // Set the endpoint type. At this point, the endpoint is enabled.
         cfg.modify(|_, w| unsafe {
@@ -791,9 +1014,53 @@ impl Inner {
 
     fn suspend(&self) {
         dbgprint!("UsbBus::suspend\n");
+        self.suspended.set(true);
+        // Wake up the device on bus activity
+        self.usb().intenset.write(|w| w.wakeup().set_bit());
+        if let Some(hook) = self.suspend_hook {
+            hook(true);
+        }
     }
     fn resume(&self) {
         dbgprint!("UsbBus::resume\n");
+        self.suspended.set(false);
+        self.usb().intenclr.write(|w| w.wakeup().set_bit());
+        if let Some(hook) = self.suspend_hook {
+            hook(false);
+        }
+    }
+
+    /// As the suspend state cannot be distinguished from an unconnected
+    /// bus, which is idle too, the suspend and LPM suspend interrupts are
+    /// only armed once the host has reset the bus, and only for users of
+    /// [`UsbBus::set_suspend_hook`] or [`UsbBus::set_lpm`]. Before that, an
+    /// unplugged self-powered device would report a suspend.
+    fn arm_suspend(&self) {
+        if self.suspend_hook.is_none() && matches!(self.lpm, LpmHandshake::Disabled) {
+            return;
+        }
+        self.usb()
+            .intflag
+            .write(|w| w.suspend().set_bit().lpmsusp().set_bit());
+        self.usb()
+            .intenset
+            .write(|w| w.suspend().set_bit().lpmsusp().set_bit());
+    }
+
+    /// Returns true once the suspend interrupts are armed, see
+    /// [`arm_suspend`](Self::arm_suspend)
+    fn suspend_armed(&self) -> bool {
+        self.usb().intenset.read().suspend().bit_is_set()
+    }
+
+    fn remote_wakeup(&self) -> UsbResult<()> {
+        if !self.suspended.get() {
+            return Err(UsbError::InvalidState);
+        }
+        dbgprint!("UsbBus::remote_wakeup\n");
+        // Cleared by the hardware once the resume signal is sent
+        self.usb().ctrlb.modify(|_, w| w.uprsm().set_bit());
+        Ok(())
     }
 
     fn alloc_ep(
@@ -804,9 +1071,26 @@ impl Inner {
         max_packet_size: u16,
         interval: u8,
     ) -> UsbResult<EndpointAddress> {
-        let allocated_size = max_packet_size.max(64);
+        // Isochronous packets of up to 1023 bytes, which is also the
+        // largest packet size the hardware supports
+        if max_packet_size > 1023 {
+            return Err(UsbError::Unsupported);
+        }
+        // Keep the buffers of odd isochronous packet sizes 16-bit aligned
+        let allocated_size = (max_packet_size.max(64) + 1) & !1;
 
-        let buffer = self.buffers.borrow_mut().allocate_buffer(allocated_size)?;
+        let ep_type_bits = EndpointTypeBits::from(ep_type);
+        let ping_pong =
+            ep_type != EndpointType::Control && self.ping_pong & (1 << ep_type_bits as u8) != 0;
+
+        let mut buffers = self.buffers.borrow_mut();
+        let buffer = buffers.allocate_buffer(allocated_size)?;
+        let ping_pong_buffer = if ping_pong {
+            Some(buffers.allocate_buffer(allocated_size)?)
+        } else {
+            None
+        };
+        drop(buffers);
 
         dbgprint!(
             "UsbBus::alloc_ep dir={:?} addr={:?} type={:?} max_packet_size={} interval={}\n",
@@ -820,7 +1104,7 @@ impl Inner {
         let mut endpoints = self.endpoints.borrow_mut();
 
         let idx = match addr {
-            None => endpoints.find_free_endpoint(dir)?,
+            None => endpoints.find_free_endpoint(dir, ping_pong)?,
             Some(addr) => addr.index(),
         };
 
@@ -832,6 +1116,7 @@ impl Inner {
             max_packet_size,
             interval,
             buffer,
+            ping_pong_buffer,
         )?;
 
         dbgprint!("alloc_ep -> {:?}\n", addr);
@@ -851,12 +1136,33 @@ impl Inner {
         if intflags.eorst().bit() {
             // end of reset interrupt
             self.usb().intflag.write(|w| w.eorst().set_bit());
+            self.arm_suspend();
+            // A reset also ends a suspend, without a resume
+            if self.suspended.get() {
+                self.resume();
+            }
             dbgprint!("PollResult::Reset\n");
             return PollResult::Reset;
         }
-        // As the suspend & wakup interrupts/states cannot distinguish between
-        // unconnected & unsuspended, we do not handle them to avoid spurious
-        // transitions.
+        if self.suspended.get() {
+            if intflags.wakeup().bit() || intflags.eorsm().bit() {
+                self.usb()
+                    .intflag
+                    .write(|w| w.wakeup().set_bit().eorsm().set_bit().uprsm().set_bit());
+                dbgprint!("PollResult::Resume\n");
+                return PollResult::Resume;
+            }
+        } else if self.suspend_armed() && (intflags.suspend().bit() || intflags.lpmsusp().bit()) {
+            // Also clear the wakeup flag set by the bus activity before the
+            // suspend
+            self.usb().intflag.write(|w| {
+                w.suspend().set_bit();
+                w.lpmsusp().set_bit();
+                w.wakeup().set_bit()
+            });
+            dbgprint!("PollResult::Suspend\n");
+            return PollResult::Suspend;
+        }
 
         let intbits = self.usb().epintsmry.read().bits();
         if intbits == 0 {
@@ -875,28 +1181,29 @@ impl Inner {
 
             let idx = ep as usize;
 
-            let bank1 = self
-                .bank1(EndpointAddress::from_parts(idx, UsbDirection::In))
-                .unwrap();
-            if bank1.is_transfer_complete() {
-                bank1.clear_transfer_complete();
-                dbgprint!("ep {} WRITE DONE\n", ep);
-                ep_in_complete |= mask;
-                // Continuing (and hence not setting masks to indicate complete
-                // OUT transfers) is necessary for operation to proceed beyond
-                // the device-address + descriptor stage. The authors suspect a
-                // deadlock caused by waiting on a write when handling a read
-                // somewhere in an underlying class or control crate, but we
-                // can't be sure. Either way, if a write has finished, we only
-                // set the flag for a completed write on that endpoint index.
-                // Future polls will handle the reads.
-                continue;
+            // Either bank may be missing, or be the second bank of a
+            // ping-pong endpoint of the other direction
+            if let Ok(bank1) = self.bank1(EndpointAddress::from_parts(idx, UsbDirection::In)) {
+                if bank1.is_transfer_complete() {
+                    bank1.clear_transfer_complete();
+                    dbgprint!("ep {} WRITE DONE\n", ep);
+                    ep_in_complete |= mask;
+                    // Continuing (and hence not setting masks to indicate complete
+                    // OUT transfers) is necessary for operation to proceed beyond
+                    // the device-address + descriptor stage. The authors suspect a
+                    // deadlock caused by waiting on a write when handling a read
+                    // somewhere in an underlying class or control crate, but we
+                    // can't be sure. Either way, if a write has finished, we only
+                    // set the flag for a completed write on that endpoint index.
+                    // Future polls will handle the reads.
+                    continue;
+                }
             }
-            drop(bank1);
 
-            let bank0 = self
-                .bank0(EndpointAddress::from_parts(idx, UsbDirection::Out))
-                .unwrap();
+            let bank0 = match self.bank0(EndpointAddress::from_parts(idx, UsbDirection::Out)) {
+                Ok(bank0) => bank0,
+                Err(_) => continue,
+            };
             if bank0.received_setup_interrupt() {
                 dbgprint!("ep {} GOT SETUP\n", ep);
                 ep_setup |= mask;
@@ -936,8 +1243,13 @@ impl Inner {
 
         let size = bank.write(buf);
 
-        bank.clear_transfer_complete();
+        // The completion of the other bank of a ping-pong endpoint may not
+        // have been polled yet
+        if !bank.is_ping_pong() {
+            bank.clear_transfer_complete();
+        }
         bank.set_ready(true); // ready to be sent
+        bank.advance();
 
         dbgprint!(
             "UsbBus::write {} bytes {:?} to ep {:?} -> {:?}\n",
@@ -965,6 +1277,7 @@ impl Inner {
 
             bank.clear_transfer_complete();
             bank.set_ready(false);
+            bank.advance();
 
             drop(bank);
 
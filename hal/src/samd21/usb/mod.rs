@@ -1,4 +1,4 @@
-//! USB Device support
+//! USB Device and host support
 
 #[cfg(not(feature = "unproven"))]
 use crate::gpio;
@@ -9,11 +9,13 @@ use crate::gpio::v2::{AlternateG, Pin, PA23, PA24, PA25};
 pub use usb_device;
 
 mod bus;
-pub use self::bus::UsbBus;
+pub use self::bus::{LpmHandshake, UsbBus};
 
 mod devicedesc;
 use self::devicedesc::Descriptors;
 
+pub mod host;
+
 /// Emit SOF at 1Khz on this pin when configured as function G
 #[cfg(feature = "unproven")]
 pub type SofPad = Pin<PA23, AlternateG>;